@@ -2,28 +2,43 @@
 //!
 //! HTTP API server for querying logs and metrics from ClickHouse.
 
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use std::{collections::BTreeMap, convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::Body,
+    extract::{FromRequestParts, Path, Query, State},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
+use futures::{stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
 use tower_http::{cors::CorsLayer, timeout::TimeoutLayer, trace::TraceLayer};
-use tracing::{error, info};
+use tracing::info;
 
 use archives_common::{
-    clickhouse::{ClickHouseClient, LogSearchParams, MetricDataPoint, MetricQueryParams},
+    cache::CacheStats,
+    cursor,
+    filter::{AttributeFilter, AttributeScope},
+    pattern::{LogPattern, PatternMiner},
+    query::LogQuery,
+    resilience::BreakerStatus,
+    retention::RetentionStatus,
+    store::{
+        self, HistogramSeries, LabelKey, LabelQueryParams, LabelValue, LogSearchParams,
+        LogVolumeBucket, MetricCatalogParams, MetricDataPoint, MetricInfo, MetricQueryParams,
+        MetricSeries, ServiceInfo, SummarySeries,
+    },
+    tenant::{self, Credentials, TenantScope},
     types::{Aggregation, LogSeverity, Pagination, TimeRange},
-    Config,
+    Config, Error, Result, SharedStore,
 };
 
 /// Application state shared across handlers
 struct AppState {
-    clickhouse: ClickHouseClient,
+    store: SharedStore,
     config: Config,
 }
 
@@ -45,22 +60,24 @@ async fn main() -> anyhow::Result<()> {
     let config = Config::load_or_default();
     info!(
         clickhouse_url = %config.clickhouse.url,
+        storage_backend = ?config.storage.backend,
         api_port = config.api.port,
         "Configuration loaded"
     );
 
-    // Create ClickHouse client
-    let clickhouse = ClickHouseClient::new(&config.clickhouse)?;
+    // Create storage backend
+    let store = store::from_config(&config).await?;
 
-    // Check ClickHouse connectivity
-    match clickhouse.health_check().await {
-        Ok(true) => info!("ClickHouse connection established"),
-        Ok(false) => error!("ClickHouse health check returned false"),
-        Err(e) => error!(error = %e, "ClickHouse connection failed - continuing anyway"),
+    // Check storage connectivity; the check is retried, so failing here means
+    // the backend is down rather than slow and serving would only return 503s
+    match store.health_check().await {
+        Ok(true) => info!("Storage connection established"),
+        Ok(false) => anyhow::bail!("storage health check returned false"),
+        Err(e) => return Err(anyhow::anyhow!(e).context("storage connection failed")),
     }
 
     let state = Arc::new(AppState {
-        clickhouse,
+        store,
         config: config.clone(),
     });
 
@@ -68,10 +85,28 @@ async fn main() -> anyhow::Result<()> {
     let app = Router::new()
         .route("/health", get(health_handler))
         .route("/v1/status", get(status_handler))
+        .route("/v1/services", get(list_services_handler))
         .route("/v1/logs/search", post(search_logs_handler))
+        .route("/v1/logs/export", post(export_logs_handler))
+        .route("/v1/logs/histogram", post(log_histogram_handler))
+        .route("/v1/logs/patterns", post(log_patterns_handler))
+        .route("/v1/logs/attributes", get(log_attribute_keys_handler))
+        .route(
+            "/v1/logs/attributes/{key}/values",
+            get(log_attribute_values_handler),
+        )
         .route("/v1/logs/{id}", get(get_log_handler))
+        .route("/v1/traces/{trace_id}/logs", get(trace_logs_handler))
         .route("/v1/metrics/query", post(query_metrics_handler))
+        .route("/v1/metrics/heatmap", post(metric_heatmap_handler))
+        .route("/v1/metrics/summary", post(metric_summary_handler))
         .route("/v1/metrics/names", get(list_metrics_handler))
+        .route("/v1/metrics/catalog", get(metric_catalog_handler))
+        .route("/v1/metrics/labels", get(metric_label_keys_handler))
+        .route(
+            "/v1/metrics/labels/{key}/values",
+            get(metric_label_values_handler),
+        )
         .layer(TimeoutLayer::new(Duration::from_secs(
             config.api.timeout_secs,
         )))
@@ -106,14 +141,77 @@ async fn shutdown_signal() {
 // Handlers
 // ============================================================================
 
+/// HTTP status for a failed query
+const fn error_status(error: &Error) -> StatusCode {
+    match error {
+        Error::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+        Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+        Error::Forbidden(_) => StatusCode::FORBIDDEN,
+        Error::NotFound(_) => StatusCode::NOT_FOUND,
+        Error::ClickHouseConnection(_) => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// The store as seen by the tenant of a request, or the whole store with
+/// tenancy disabled. Requests that do not resolve to a tenant are rejected
+/// before the handler runs.
+struct TenantStore {
+    store: SharedStore,
+    scope: Option<TenantScope>,
+}
+
+impl FromRequestParts<Arc<AppState>> for TenantStore {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        let credentials = Credentials {
+            authorization: header(header::AUTHORIZATION.as_str()),
+            api_key: header("x-api-key"),
+            tenant: header(&state.config.tenancy.header),
+        };
+        let scoped = tenant::resolve(&state.config.tenancy, &credentials).and_then(|scope| {
+            let store = match scope {
+                Some(ref scope) => state.store.for_tenant(scope)?,
+                None => Arc::clone(&state.store),
+            };
+            Ok(Self { store, scope })
+        });
+        scoped.map_err(|e| {
+            let mut response = (
+                error_status(&e),
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+                .into_response();
+            if matches!(e, Error::Unauthorized(_)) {
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    header::HeaderValue::from_static("Bearer"),
+                );
+            }
+            response
+        })
+    }
+}
+
 /// Health check endpoint
 async fn health_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.clickhouse.health_check().await {
+    match state.store.health_check().await {
         Ok(true) => (
             StatusCode::OK,
             Json(HealthResponse {
                 status: "healthy",
                 clickhouse: true,
+                circuit_breaker: state.store.breaker_status(),
             }),
         ),
         _ => (
@@ -121,6 +219,7 @@ async fn health_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse
             Json(HealthResponse {
                 status: "unhealthy",
                 clickhouse: false,
+                circuit_breaker: state.store.breaker_status(),
             }),
         ),
     }
@@ -130,20 +229,33 @@ async fn health_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse
 struct HealthResponse {
     status: &'static str,
     clickhouse: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    circuit_breaker: Option<BreakerStatus>,
 }
 
-/// System status endpoint
-async fn status_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.clickhouse.get_stats().await {
+/// System status endpoint; with tenancy enabled the counts are the tenant's
+async fn status_handler(
+    State(state): State<Arc<AppState>>,
+    tenant: TenantStore,
+) -> impl IntoResponse {
+    // Retention rules and cache counters span every tenant
+    let global = tenant.scope.is_none();
+    let tenant_id = tenant.scope.map(|scope| scope.tenant().to_string());
+    let cache = global.then(|| state.store.cache_stats()).flatten();
+    match tenant.store.get_stats().await {
         Ok(stats) => (
             StatusCode::OK,
             Json(StatusResponse {
                 status: "ok",
                 version: env!("CARGO_PKG_VERSION"),
+                tenant: tenant_id,
                 log_count: stats.log_count,
                 log_bytes: stats.log_bytes,
                 metric_count: stats.metric_count,
                 metric_bytes: stats.metric_bytes,
+                cache,
+                retention: global
+                    .then(|| RetentionStatus::new(&state.config.retention, &stats.table_ttls)),
             }),
         ),
         Err(e) => (
@@ -151,10 +263,14 @@ async fn status_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse
             Json(StatusResponse {
                 status: "error",
                 version: env!("CARGO_PKG_VERSION"),
+                tenant: tenant_id,
                 log_count: 0,
                 log_bytes: 0,
                 metric_count: 0,
                 metric_bytes: 0,
+                cache,
+                retention: global
+                    .then(|| RetentionStatus::new(&state.config.retention, &BTreeMap::new())),
             }),
         ),
     }
@@ -164,103 +280,432 @@ async fn status_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse
 struct StatusResponse {
     status: &'static str,
     version: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
     log_count: u64,
     log_bytes: u64,
     metric_count: u64,
     metric_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache: Option<CacheStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retention: Option<RetentionStatus>,
 }
 
-/// Search logs endpoint
-async fn search_logs_handler(
-    State(state): State<Arc<AppState>>,
-    Json(request): Json<LogSearchRequest>,
+/// Service catalog endpoint
+async fn list_services_handler(
+    tenant: TenantStore,
+    Query(request): Query<ServicesRequest>,
 ) -> impl IntoResponse {
-    let params = LogSearchParams {
-        time_range: TimeRange {
-            start: request.start,
-            end: request.end,
-        },
-        min_severity: request.min_severity,
-        text_query: request.query,
-        service_name: request.service,
-        pagination: Pagination {
-            offset: request.offset.unwrap_or(0),
-            limit: request.limit.unwrap_or(100),
-        },
+    let end = request.end.unwrap_or_else(chrono::Utc::now);
+    let time_range = TimeRange {
+        start: request
+            .start
+            .unwrap_or_else(|| end - chrono::Duration::hours(24)),
+        end,
     };
 
-    match state.clickhouse.search_logs(&params).await {
-        Ok(logs) => (
+    match tenant.store.list_services(&time_range).await {
+        Ok(services) => (
             StatusCode::OK,
-            Json(LogSearchResponse { logs, error: None }),
+            Json(ServicesResponse {
+                services,
+                error: None,
+            }),
         ),
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(LogSearchResponse {
-                logs: vec![],
+            error_status(&e),
+            Json(ServicesResponse {
+                services: vec![],
                 error: Some(e.to_string()),
             }),
         ),
     }
 }
 
+#[derive(Deserialize)]
+struct ServicesRequest {
+    start: Option<chrono::DateTime<chrono::Utc>>,
+    end: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Serialize)]
+struct ServicesResponse {
+    services: Vec<ServiceInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Search logs endpoint
+async fn search_logs_handler(
+    tenant: TenantStore,
+    Json(request): Json<LogSearchRequest>,
+) -> impl IntoResponse {
+    let result = async {
+        let params = request.into_params()?;
+        tenant.store.search_logs_page(&params).await
+    }
+    .await;
+
+    match result {
+        Ok(page) => (
+            StatusCode::OK,
+            Json(LogSearchResponse {
+                logs: page.logs,
+                next_cursor: page.next_cursor,
+                has_more: page.has_more,
+                error: None,
+            }),
+        ),
+        Err(e) => search_error(&e),
+    }
+}
+
+/// Rows buffered between the store and a slow export client
+const EXPORT_BUFFER: usize = 1024;
+
+/// Stream matching logs as newline-delimited JSON. Rows are read from the
+/// store as the client consumes them, so memory use does not depend on the
+/// number of rows. Errors before the first row get a status code; an error
+/// mid-stream ends the body with an `{"error": ...}` line. The stream holds
+/// a database pool permit until it ends or the client disconnects.
+async fn export_logs_handler(
+    State(state): State<Arc<AppState>>,
+    tenant: TenantStore,
+    Json(request): Json<LogSearchRequest>,
+) -> Response {
+    let limit = request.limit;
+    let params = match request.into_params() {
+        Ok(mut params) => {
+            params.pagination.limit =
+                limit.unwrap_or_else(|| state.config.limits.default_export_limit());
+            params
+        }
+        Err(e) => return search_error(&e).into_response(),
+    };
+
+    // The store stream borrows from the store, so it is driven by a task that
+    // owns both and stops once the client disconnects
+    let (tx, mut rx) = mpsc::channel(EXPORT_BUFFER);
+    let producer = tenant.store;
+    tokio::spawn(async move {
+        let mut logs = producer.stream_logs(&params);
+        while let Some(item) = logs.next().await {
+            let failed = item.is_err();
+            if tx.send(item).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let first = rx.recv().await;
+    if let Some(Err(e)) = first {
+        return search_error(&e).into_response();
+    }
+    let rest = stream::unfold(rx, |mut rx| async {
+        rx.recv().await.map(|item| (item, rx))
+    });
+    let body = stream::iter(first)
+        .chain(rest)
+        .ready_chunks(EXPORT_BUFFER)
+        .map(|items| {
+            let mut chunk = Vec::new();
+            for item in items {
+                let line = match item {
+                    Ok(log) => serde_json::to_vec(&log),
+                    Err(e) => serde_json::to_vec(&serde_json::json!({"error": e.to_string()})),
+                };
+                chunk.extend(line.unwrap_or_default());
+                chunk.push(b'\n');
+            }
+            Ok::<_, Infallible>(chunk)
+        });
+
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+fn search_error(e: &Error) -> (StatusCode, Json<LogSearchResponse>) {
+    (
+        error_status(e),
+        Json(LogSearchResponse {
+            logs: vec![],
+            next_cursor: None,
+            has_more: false,
+            error: Some(e.to_string()),
+        }),
+    )
+}
+
 #[derive(Deserialize)]
 struct LogSearchRequest {
     start: chrono::DateTime<chrono::Utc>,
     end: chrono::DateTime<chrono::Utc>,
     query: Option<String>,
+    q: Option<String>,
     min_severity: Option<LogSeverity>,
     service: Option<String>,
+    trace_id: Option<String>,
+    span_id: Option<String>,
+    #[serde(default)]
+    attributes: Vec<AttributeFilter>,
     offset: Option<u64>,
     limit: Option<u64>,
+    cursor: Option<String>,
+}
+
+impl LogSearchRequest {
+    /// Search parameters with the `q` query and the cursor applied
+    fn into_params(self) -> Result<LogSearchParams> {
+        let mut params = LogSearchParams {
+            time_range: TimeRange {
+                start: self.start,
+                end: self.end,
+            },
+            min_severity: self.min_severity,
+            text_query: self.query,
+            service_name: self.service,
+            trace_id: self.trace_id,
+            span_id: self.span_id,
+            body_terms: Vec::new(),
+            excluded_body_terms: Vec::new(),
+            attribute_filters: self.attributes,
+            pagination: Pagination {
+                offset: self.offset.unwrap_or(0),
+                limit: self.limit.unwrap_or(100),
+            },
+            cursor: None,
+        };
+        if let Some(ref q) = self.q {
+            LogQuery::parse(q)?.apply(&mut params);
+        }
+        if let Some(ref cursor) = self.cursor {
+            params.cursor = Some(cursor.parse()?);
+        }
+        Ok(params)
+    }
 }
 
 #[derive(Serialize)]
 struct LogSearchResponse {
     logs: Vec<archives_common::types::LogEntry>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+    has_more: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
 }
 
-/// Get single log by ID (placeholder - logs don't have stable IDs in OTEL schema)
-async fn get_log_handler(
-    State(_state): State<Arc<AppState>>,
-    Path(id): Path<String>,
+/// Log volume per time bucket and severity
+async fn log_histogram_handler(
+    tenant: TenantStore,
+    Json(request): Json<LogHistogramRequest>,
 ) -> impl IntoResponse {
-    // Note: OTEL logs don't have stable IDs, this would need trace_id + timestamp
-    (
-        StatusCode::NOT_IMPLEMENTED,
-        Json(serde_json::json!({
-            "error": "Log retrieval by ID not implemented - use search with trace_id filter"
-        })),
-    )
+    let interval_seconds = request.interval_seconds.unwrap_or(60);
+    let result = async {
+        let params = request.search.into_params()?;
+        tenant
+            .store
+            .count_logs_over_time(&params, interval_seconds)
+            .await
+    }
+    .await;
+
+    match result {
+        Ok(buckets) => (
+            StatusCode::OK,
+            Json(LogHistogramResponse {
+                interval_seconds,
+                buckets,
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            error_status(&e),
+            Json(LogHistogramResponse {
+                interval_seconds,
+                buckets: vec![],
+                error: Some(e.to_string()),
+            }),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct LogHistogramRequest {
+    #[serde(flatten)]
+    search: LogSearchRequest,
+    interval_seconds: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct LogHistogramResponse {
+    interval_seconds: u32,
+    buckets: Vec<LogVolumeBucket>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Cluster matching log messages into templates
+async fn log_patterns_handler(
+    tenant: TenantStore,
+    Json(mut request): Json<LogPatternsRequest>,
+) -> impl IntoResponse {
+    // Mine the most recent logs rather than a single search page
+    request.search.limit = Some(request.search.limit.unwrap_or(1000));
+    let result = async {
+        let params = request.search.into_params()?;
+        let logs = tenant.store.search_logs(&params).await?;
+        let mut miner = PatternMiner::new();
+        if let Some(similarity) = request.similarity {
+            miner = miner.with_similarity(similarity);
+        }
+        for log in &logs {
+            miner.add_log(log);
+        }
+        let mut patterns = miner.patterns();
+        patterns.truncate(request.max_patterns.unwrap_or(20));
+        Ok::<_, Error>((logs.len(), patterns))
+    }
+    .await;
+
+    match result {
+        Ok((scanned, patterns)) => (
+            StatusCode::OK,
+            Json(LogPatternsResponse {
+                scanned,
+                patterns,
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            error_status(&e),
+            Json(LogPatternsResponse {
+                scanned: 0,
+                patterns: vec![],
+                error: Some(e.to_string()),
+            }),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct LogPatternsRequest {
+    #[serde(flatten)]
+    search: LogSearchRequest,
+    max_patterns: Option<usize>,
+    similarity: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct LogPatternsResponse {
+    scanned: usize,
+    patterns: Vec<LogPattern>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Get single log by its stable ID
+async fn get_log_handler(tenant: TenantStore, Path(id): Path<String>) -> impl IntoResponse {
+    let result = async {
+        let id = cursor::parse_log_id(&id)?;
+        tenant
+            .store
+            .get_log(id)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("log {id}")))
+    }
+    .await;
+
+    match result {
+        Ok(log) => (StatusCode::OK, Json(serde_json::json!({ "log": log }))),
+        Err(e) => (
+            error_status(&e),
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// All logs of a trace, oldest first
+async fn trace_logs_handler(
+    tenant: TenantStore,
+    Path(trace_id): Path<String>,
+    Query(request): Query<TraceLogsRequest>,
+) -> impl IntoResponse {
+    let end = request.end.unwrap_or_else(chrono::Utc::now);
+    let time_range = TimeRange {
+        start: request
+            .start
+            .unwrap_or_else(|| end - chrono::Duration::hours(24)),
+        end,
+    };
+
+    match tenant
+        .store
+        .get_trace_logs(&trace_id, &time_range, request.limit.unwrap_or(1000))
+        .await
+    {
+        Ok(logs) => (
+            StatusCode::OK,
+            Json(TraceLogsResponse {
+                trace_id,
+                logs,
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            error_status(&e),
+            Json(TraceLogsResponse {
+                trace_id,
+                logs: vec![],
+                error: Some(e.to_string()),
+            }),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct TraceLogsRequest {
+    start: Option<chrono::DateTime<chrono::Utc>>,
+    end: Option<chrono::DateTime<chrono::Utc>>,
+    limit: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct TraceLogsResponse {
+    trace_id: String,
+    logs: Vec<archives_common::types::LogEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
 }
 
 /// Query metrics endpoint
 async fn query_metrics_handler(
-    State(state): State<Arc<AppState>>,
+    tenant: TenantStore,
     Json(request): Json<MetricQueryRequest>,
 ) -> impl IntoResponse {
-    let params = MetricQueryParams {
-        metric_name: request.metric_name,
-        time_range: TimeRange {
-            start: request.start,
-            end: request.end,
-        },
-        aggregation: request.aggregation.unwrap_or(Aggregation::Avg),
-        interval_seconds: request.interval_seconds,
-        labels: request.labels,
-    };
+    let grouped = !request.group_by.is_empty();
+    let params = request.into_params();
 
-    match state.clickhouse.query_metrics(&params).await {
-        Ok(data) => (
+    match tenant.store.query_metrics(&params).await {
+        Ok(series) => (
             StatusCode::OK,
-            Json(MetricQueryResponse { data, error: None }),
+            Json(MetricQueryResponse {
+                // Ungrouped queries also return the single series as `data`
+                data: (!grouped)
+                    .then(|| series.first().map(|s| s.points.clone()).unwrap_or_default()),
+                series,
+                error: None,
+            }),
         ),
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            error_status(&e),
             Json(MetricQueryResponse {
-                data: vec![],
+                data: None,
+                series: vec![],
                 error: Some(e.to_string()),
             }),
         ),
@@ -275,18 +720,103 @@ struct MetricQueryRequest {
     aggregation: Option<Aggregation>,
     interval_seconds: Option<u32>,
     labels: Option<std::collections::HashMap<String, String>>,
+    #[serde(default)]
+    label_filters: Vec<AttributeFilter>,
+    #[serde(default)]
+    group_by: Vec<String>,
+}
+
+impl MetricQueryRequest {
+    fn into_params(self) -> MetricQueryParams {
+        let mut labels = self.labels.map(store::label_matchers).unwrap_or_default();
+        labels.extend(self.label_filters);
+
+        MetricQueryParams {
+            metric_name: self.metric_name,
+            time_range: TimeRange {
+                start: self.start,
+                end: self.end,
+            },
+            aggregation: self.aggregation.unwrap_or(Aggregation::Avg),
+            interval_seconds: self.interval_seconds,
+            labels,
+            group_by: self.group_by,
+        }
+    }
 }
 
 #[derive(Serialize)]
 struct MetricQueryResponse {
-    data: Vec<MetricDataPoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Vec<MetricDataPoint>>,
+    series: Vec<MetricSeries>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Histogram heatmap endpoint
+async fn metric_heatmap_handler(
+    tenant: TenantStore,
+    Json(request): Json<MetricQueryRequest>,
+) -> impl IntoResponse {
+    match tenant.store.query_histogram(&request.into_params()).await {
+        Ok(series) => (
+            StatusCode::OK,
+            Json(MetricHeatmapResponse {
+                series,
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            error_status(&e),
+            Json(MetricHeatmapResponse {
+                series: vec![],
+                error: Some(e.to_string()),
+            }),
+        ),
+    }
+}
+
+#[derive(Serialize)]
+struct MetricHeatmapResponse {
+    series: Vec<HistogramSeries>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Summary metric endpoint
+async fn metric_summary_handler(
+    tenant: TenantStore,
+    Json(request): Json<MetricQueryRequest>,
+) -> impl IntoResponse {
+    match tenant.store.query_summary(&request.into_params()).await {
+        Ok(series) => (
+            StatusCode::OK,
+            Json(MetricSummaryResponse {
+                series,
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            error_status(&e),
+            Json(MetricSummaryResponse {
+                series: vec![],
+                error: Some(e.to_string()),
+            }),
+        ),
+    }
+}
+
+#[derive(Serialize)]
+struct MetricSummaryResponse {
+    series: Vec<SummarySeries>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
 }
 
 /// List metric names endpoint
-async fn list_metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.clickhouse.list_metric_names().await {
+async fn list_metrics_handler(tenant: TenantStore) -> impl IntoResponse {
+    match tenant.store.list_metric_names().await {
         Ok(names) => (
             StatusCode::OK,
             Json(MetricNamesResponse { names, error: None }),
@@ -307,3 +837,203 @@ struct MetricNamesResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
 }
+
+/// Metric catalog endpoint
+async fn metric_catalog_handler(
+    tenant: TenantStore,
+    Query(request): Query<MetricCatalogRequest>,
+) -> impl IntoResponse {
+    let end = request.end.unwrap_or_else(chrono::Utc::now);
+    let params = MetricCatalogParams {
+        time_range: TimeRange {
+            start: request
+                .start
+                .unwrap_or_else(|| end - chrono::Duration::hours(24)),
+            end,
+        },
+        prefix: request.prefix,
+        service_name: request.service,
+    };
+
+    match tenant.store.metric_catalog(&params).await {
+        Ok(metrics) => (
+            StatusCode::OK,
+            Json(MetricCatalogResponse {
+                metrics,
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            error_status(&e),
+            Json(MetricCatalogResponse {
+                metrics: vec![],
+                error: Some(e.to_string()),
+            }),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct MetricCatalogRequest {
+    start: Option<chrono::DateTime<chrono::Utc>>,
+    end: Option<chrono::DateTime<chrono::Utc>>,
+    prefix: Option<String>,
+    service: Option<String>,
+}
+
+#[derive(Serialize)]
+struct MetricCatalogResponse {
+    metrics: Vec<MetricInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Label keys of a metric with their cardinality
+async fn metric_label_keys_handler(
+    tenant: TenantStore,
+    Query(request): Query<LabelRequest>,
+) -> impl IntoResponse {
+    let result = async {
+        let name = request.metric_name()?;
+        tenant
+            .store
+            .metric_label_keys(&name, &request.into_params())
+            .await
+    }
+    .await;
+
+    label_keys_response(result)
+}
+
+/// Top values of one label key of a metric
+async fn metric_label_values_handler(
+    tenant: TenantStore,
+    Path(key): Path<String>,
+    Query(request): Query<LabelRequest>,
+) -> impl IntoResponse {
+    let result = async {
+        let name = request.metric_name()?;
+        tenant
+            .store
+            .metric_label_values(&name, &key, &request.into_params())
+            .await
+    }
+    .await;
+
+    label_values_response(key, result)
+}
+
+/// Log attribute keys with their cardinality
+async fn log_attribute_keys_handler(
+    tenant: TenantStore,
+    Query(request): Query<LabelRequest>,
+) -> impl IntoResponse {
+    let result = tenant
+        .store
+        .log_attribute_keys(&request.into_params())
+        .await;
+    label_keys_response(result)
+}
+
+/// Top values of one log attribute key
+async fn log_attribute_values_handler(
+    tenant: TenantStore,
+    Path(key): Path<String>,
+    Query(request): Query<LabelRequest>,
+) -> impl IntoResponse {
+    let result = tenant
+        .store
+        .log_attribute_values(&key, &request.into_params())
+        .await;
+    label_values_response(key, result)
+}
+
+fn label_keys_response(result: Result<Vec<LabelKey>>) -> (StatusCode, Json<LabelKeysResponse>) {
+    match result {
+        Ok(keys) => (
+            StatusCode::OK,
+            Json(LabelKeysResponse { keys, error: None }),
+        ),
+        Err(e) => (
+            error_status(&e),
+            Json(LabelKeysResponse {
+                keys: vec![],
+                error: Some(e.to_string()),
+            }),
+        ),
+    }
+}
+
+fn label_values_response(
+    key: String,
+    result: Result<Vec<LabelValue>>,
+) -> (StatusCode, Json<LabelValuesResponse>) {
+    match result {
+        Ok(values) => (
+            StatusCode::OK,
+            Json(LabelValuesResponse {
+                key,
+                values,
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            error_status(&e),
+            Json(LabelValuesResponse {
+                key,
+                values: vec![],
+                error: Some(e.to_string()),
+            }),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct LabelRequest {
+    /// Required for metric labels, ignored for log attributes
+    metric_name: Option<String>,
+    start: Option<chrono::DateTime<chrono::Utc>>,
+    end: Option<chrono::DateTime<chrono::Utc>>,
+    service: Option<String>,
+    #[serde(default)]
+    scope: AttributeScope,
+    limit: Option<u64>,
+}
+
+impl LabelRequest {
+    fn metric_name(&self) -> Result<String> {
+        self.metric_name
+            .clone()
+            .ok_or_else(|| Error::InvalidParameter("metric_name is required".to_string()))
+    }
+
+    fn into_params(self) -> LabelQueryParams {
+        let end = self.end.unwrap_or_else(chrono::Utc::now);
+        LabelQueryParams {
+            time_range: TimeRange {
+                start: self
+                    .start
+                    .unwrap_or_else(|| end - chrono::Duration::hours(24)),
+                end,
+            },
+            service_name: self.service,
+            scope: self.scope,
+            limit: self.limit.unwrap_or(20),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LabelKeysResponse {
+    keys: Vec<LabelKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LabelValuesResponse {
+    key: String,
+    values: Vec<LabelValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
@@ -0,0 +1,152 @@
+//! Admin commands
+//!
+//! Unlike the other commands these connect to ClickHouse directly, with the
+//! `[clickhouse]` settings of the Archives configuration, since they change
+//! the database the API only reads from.
+
+use crate::{AdminCommands, OutputFormat};
+use archives_common::{
+    clickhouse::ClickHouseClient,
+    migrations::{self, MigrationPlan, SCHEMA_VERSION},
+    retention::{self, TableRetention},
+    rollup, Config,
+};
+
+pub async fn handle(command: AdminCommands, format: OutputFormat) -> anyhow::Result<()> {
+    match command {
+        AdminCommands::Migrate { dry_run } => migrate(dry_run, format).await,
+        AdminCommands::Retention { dry_run } => retention(dry_run, format).await,
+        AdminCommands::BackfillRollups { dry_run } => backfill_rollups(dry_run, format).await,
+    }
+}
+
+async fn migrate(dry_run: bool, format: OutputFormat) -> anyhow::Result<()> {
+    let config = Config::load_or_default();
+    let client = ClickHouseClient::new(&config.clickhouse)?;
+    let plan = migrations::migrate(&client, dry_run).await?;
+    // Tables created by migrations have no TTL until it is set from config
+    let tables = retention::reconcile(&client, &config.retention, dry_run).await?;
+
+    match format {
+        OutputFormat::Json => {
+            let output = serde_json::json!({ "migrations": plan, "retention": tables });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        _ => print_plan(&plan, &tables, dry_run),
+    }
+    Ok(())
+}
+
+fn print_plan(plan: &MigrationPlan, tables: &[TableRetention], dry_run: bool) {
+    println!(
+        "Schema version: {} (this build: {})",
+        plan.current_version, plan.target_version
+    );
+    for version in &plan.changed {
+        println!("Warning: migration {version} has changed since it was applied");
+    }
+    if plan.is_up_to_date() {
+        println!("Up to date");
+    } else {
+        print_migrations(plan, dry_run);
+    }
+    if tables.iter().any(|t| !t.in_sync) {
+        println!("\nRetention:");
+        print_retention(tables, dry_run);
+    }
+}
+
+fn print_migrations(plan: &MigrationPlan, dry_run: bool) {
+    println!(
+        "\n{} migration(s):",
+        if dry_run { "Pending" } else { "Applied" }
+    );
+    for migration in plan.pending_migrations() {
+        println!("  {:04}_{}", migration.version, migration.name);
+        if dry_run {
+            for statement in migration.statements() {
+                println!("\n{statement};");
+            }
+            println!();
+        }
+    }
+    if dry_run {
+        println!("Run without --dry-run to apply.");
+    } else {
+        println!("\nNow at version {SCHEMA_VERSION}");
+    }
+}
+
+async fn retention(dry_run: bool, format: OutputFormat) -> anyhow::Result<()> {
+    let config = Config::load_or_default();
+    let client = ClickHouseClient::new(&config.clickhouse)?;
+    let tables = retention::reconcile(&client, &config.retention, dry_run).await?;
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&tables)?);
+        }
+        _ => print_retention(&tables, dry_run),
+    }
+    Ok(())
+}
+
+fn print_retention(tables: &[TableRetention], dry_run: bool) {
+    if tables.is_empty() {
+        println!("No exporter tables yet; start the collector and retry");
+        return;
+    }
+    for table in tables {
+        if table.in_sync {
+            println!("  {:<36} up to date", table.table);
+            continue;
+        }
+        println!(
+            "  {:<36} {}",
+            table.table,
+            if dry_run { "would change" } else { "changed" }
+        );
+        println!(
+            "    from: {}",
+            table.applied.as_deref().unwrap_or("(no TTL)")
+        );
+        println!("    to:   {}", table.expected);
+    }
+    if dry_run && tables.iter().any(|t| !t.in_sync) {
+        println!("\nRun without --dry-run to apply.");
+    }
+}
+
+async fn backfill_rollups(dry_run: bool, format: OutputFormat) -> anyhow::Result<()> {
+    let config = Config::load_or_default();
+    let client = ClickHouseClient::new(&config.clickhouse)?;
+    let report = rollup::backfill(&client, dry_run).await?;
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        _ if report.up_to_date => {
+            println!("Rollups hold every point before {}", report.until);
+        }
+        _ => {
+            let verb = if dry_run {
+                "Would aggregate"
+            } else {
+                "Aggregated"
+            };
+            for step in report.steps.iter().filter(|s| !s.done) {
+                println!(
+                    "{verb} {} points before {} into {}",
+                    step.source,
+                    step.until,
+                    step.rollup.table_name()
+                );
+            }
+            if dry_run {
+                println!("Run without --dry-run to apply.");
+            }
+        }
+    }
+    Ok(())
+}
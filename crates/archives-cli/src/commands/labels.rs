@@ -0,0 +1,106 @@
+//! Label key and value listings shared by metrics and logs commands
+
+use crate::OutputFormat;
+use chrono::{Duration, Utc};
+use serde_json::Value;
+
+/// Fetch label keys from `path`, or the values of `key` from `path/{key}/values`,
+/// and print them. `filter_hint` turns a `key=value` pair into the flag that
+/// filters on it, shown as a hint under table output.
+pub async fn handle(
+    client: &reqwest::Client,
+    url: &str,
+    mut query: Vec<(&str, String)>,
+    key: Option<String>,
+    hours: u32,
+    format: OutputFormat,
+    filter_hint: impl Fn(&str) -> String,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let start = now - Duration::hours(hours as i64);
+    query.extend([("start", start.to_rfc3339()), ("end", now.to_rfc3339())]);
+
+    let url = match key {
+        Some(ref key) => format!("{}/{}/values", url, key),
+        None => url.to_string(),
+    };
+    let resp = client
+        .get(url)
+        .query(&query)
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
+
+    if matches!(format, OutputFormat::Json) {
+        println!("{}", serde_json::to_string_pretty(&resp)?);
+        return Ok(());
+    }
+    if let Some(error) = resp.get("error").and_then(|e| e.as_str()) {
+        eprintln!("Error: {}", error);
+        return Ok(());
+    }
+
+    let rows = |field: &str| {
+        resp.get(field)
+            .and_then(|r| r.as_array())
+            .cloned()
+            .unwrap_or_default()
+    };
+    let text = |v: &Value, field: &str| {
+        v.get(field)
+            .and_then(|t| t.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    let number = |v: &Value, field: &str| v.get(field).and_then(Value::as_u64).unwrap_or(0);
+
+    match key {
+        None => {
+            let keys = rows("keys");
+            if matches!(format, OutputFormat::Compact) {
+                for k in &keys {
+                    println!("{} {}", text(k, "key"), number(k, "cardinality"));
+                }
+                return Ok(());
+            }
+            println!(
+                "{:<40} {:<9} {:>11} {:>10}",
+                "KEY", "SCOPE", "CARDINALITY", "COUNT"
+            );
+            println!("{}", "-".repeat(73));
+            for k in &keys {
+                println!(
+                    "{:<40} {:<9} {:>11} {:>10}",
+                    text(k, "key"),
+                    text(k, "scope"),
+                    number(k, "cardinality"),
+                    number(k, "count")
+                );
+            }
+            println!("\nUse --key <KEY> to list the most frequent values of a key");
+        }
+        Some(key) => {
+            let values = rows("values");
+            if matches!(format, OutputFormat::Compact) {
+                for v in &values {
+                    println!("{} {}", text(v, "value"), number(v, "count"));
+                }
+                return Ok(());
+            }
+            println!("{:<50} {:>10}", "VALUE", "COUNT");
+            println!("{}", "-".repeat(61));
+            for v in &values {
+                println!("{:<50} {:>10}", text(v, "value"), number(v, "count"));
+            }
+            if let Some(top) = values.first() {
+                println!(
+                    "\nFilter on a value with {}",
+                    filter_hint(&format!("{}={}", key, text(top, "value")))
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
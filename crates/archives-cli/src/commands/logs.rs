@@ -1,52 +1,44 @@
 //! Logs commands
 
+use super::labels;
 use crate::{LogsCommands, OutputFormat};
-use chrono::{Duration, Utc};
+use archives_common::filter::AttributeFilter;
+use chrono::{DateTime, Duration, Utc};
 use serde_json::Value;
 
 pub async fn handle(
+    client: &reqwest::Client,
     api_url: &str,
     command: LogsCommands,
     format: OutputFormat,
 ) -> anyhow::Result<()> {
-    let client = reqwest::Client::new();
-
     match command {
         LogsCommands::Search {
             query,
             hours,
             severity,
             service,
+            attributes,
             limit,
+            pages,
+            all,
         } => {
-            let now = Utc::now();
-            let start = now - Duration::hours(hours as i64);
-
-            let mut body = serde_json::json!({
-                "start": start.to_rfc3339(),
-                "end": now.to_rfc3339(),
-                "limit": limit
-            });
-
-            if let Some(q) = query {
-                body["query"] = Value::String(q);
-            }
-            if let Some(s) = severity {
-                body["min_severity"] = Value::String(s.to_uppercase());
-            }
-            if let Some(s) = service {
-                body["service"] = Value::String(s);
-            }
-
-            let resp = client
-                .post(format!("{}/v1/logs/search", api_url))
-                .json(&body)
-                .send()
-                .await?
-                .json::<Value>()
-                .await?;
+            let mut body = filter_body(hours_ago(hours), query, severity, service, attributes)?;
+            body["limit"] = limit.into();
+            search(client, api_url, body, pages, all, format).await
+        }
 
-            print_logs(&resp, format);
+        LogsCommands::Histogram {
+            query,
+            hours,
+            interval,
+            severity,
+            service,
+            attributes,
+        } => {
+            let mut body = filter_body(hours_ago(hours), query, severity, service, attributes)?;
+            body["interval_seconds"] = interval.unwrap_or_else(|| hours.max(1) * 60).into();
+            histogram(client, api_url, body, format).await
         }
 
         LogsCommands::Tail {
@@ -54,81 +46,328 @@ pub async fn handle(
             severity,
             service,
         } => {
-            let now = Utc::now();
-            let start = now - Duration::minutes(10);
+            let start = Utc::now() - Duration::minutes(10);
+            let mut body = filter_body(start, None, severity, service, Vec::new())?;
+            body["limit"] = count.into();
+            tail(client, api_url, body, format).await
+        }
 
-            let mut body = serde_json::json!({
-                "start": start.to_rfc3339(),
-                "end": now.to_rfc3339(),
-                "limit": count
-            });
+        LogsCommands::Trace {
+            trace_id,
+            hours,
+            limit,
+        } => trace(client, api_url, &trace_id, hours, limit, format).await,
 
-            if let Some(s) = severity {
-                body["min_severity"] = Value::String(s.to_uppercase());
-            }
-            if let Some(s) = service {
-                body["service"] = Value::String(s);
+        LogsCommands::Attributes {
+            key,
+            service,
+            hours,
+            limit,
+        } => attributes(client, api_url, key, service, hours, limit, format).await,
+
+        LogsCommands::Errors { hours, limit } => {
+            errors(client, api_url, hours, limit, format).await
+        }
+    }
+}
+
+fn hours_ago(hours: u32) -> DateTime<Utc> {
+    Utc::now() - Duration::hours(hours as i64)
+}
+
+/// Search body for the logs between `start` and now matching the filters
+fn filter_body(
+    start: DateTime<Utc>,
+    query: Option<String>,
+    severity: Option<String>,
+    service: Option<String>,
+    attributes: Vec<AttributeFilter>,
+) -> anyhow::Result<Value> {
+    let mut body = serde_json::json!({
+        "start": start.to_rfc3339(),
+        "end": Utc::now().to_rfc3339()
+    });
+
+    if let Some(q) = query {
+        body["q"] = Value::String(q);
+    }
+    if let Some(s) = severity {
+        body["min_severity"] = Value::String(s.to_uppercase());
+    }
+    if let Some(s) = service {
+        body["service"] = Value::String(s);
+    }
+    if !attributes.is_empty() {
+        body["attributes"] = serde_json::to_value(attributes)?;
+    }
+
+    Ok(body)
+}
+
+async fn search(
+    client: &reqwest::Client,
+    api_url: &str,
+    mut body: Value,
+    pages: u32,
+    all: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut logs = Vec::new();
+    let mut fetched = 0;
+    let mut resp = loop {
+        let resp = client
+            .post(format!("{}/v1/logs/search", api_url))
+            .json(&body)
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+        fetched += 1;
+
+        if let Some(page) = resp.get("logs").and_then(|l| l.as_array()) {
+            logs.extend(page.iter().cloned());
+        }
+        match resp.get("next_cursor").and_then(|c| c.as_str()) {
+            Some(cursor) if all || fetched < pages => {
+                body["cursor"] = Value::String(cursor.to_string());
             }
+            _ => break resp,
+        }
+    };
+
+    resp["logs"] = Value::Array(logs);
+    print_logs(&resp, format);
+    Ok(())
+}
+
+async fn histogram(
+    client: &reqwest::Client,
+    api_url: &str,
+    body: Value,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let resp = client
+        .post(format!("{}/v1/logs/histogram", api_url))
+        .json(&body)
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
+        _ => print_histogram(&resp),
+    }
+    Ok(())
+}
+
+async fn tail(
+    client: &reqwest::Client,
+    api_url: &str,
+    body: Value,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let resp = client
+        .post(format!("{}/v1/logs/search", api_url))
+        .json(&body)
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
+
+    print_logs(&resp, format);
+    Ok(())
+}
+
+async fn trace(
+    client: &reqwest::Client,
+    api_url: &str,
+    trace_id: &str,
+    hours: u32,
+    limit: u64,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let start = now - Duration::hours(hours as i64);
 
-            let resp = client
-                .post(format!("{}/v1/logs/search", api_url))
-                .json(&body)
-                .send()
-                .await?
-                .json::<Value>()
-                .await?;
+    let resp = client
+        .get(format!("{}/v1/traces/{}/logs", api_url, trace_id))
+        .query(&[
+            ("start", start.to_rfc3339()),
+            ("end", now.to_rfc3339()),
+            ("limit", limit.to_string()),
+        ])
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
 
-            print_logs(&resp, format);
+    print_logs(&resp, format);
+    Ok(())
+}
+
+async fn attributes(
+    client: &reqwest::Client,
+    api_url: &str,
+    key: Option<String>,
+    service: Option<String>,
+    hours: u32,
+    limit: u64,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut query = vec![("limit", limit.to_string())];
+    query.extend(service.map(|s| ("service", s)));
+
+    labels::handle(
+        client,
+        &format!("{}/v1/logs/attributes", api_url),
+        query,
+        key,
+        hours,
+        format,
+        |filter| format!("archives logs search --attr '{}'", filter),
+    )
+    .await
+}
+
+async fn errors(
+    client: &reqwest::Client,
+    api_url: &str,
+    hours: u32,
+    limit: u64,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let start = now - Duration::hours(hours as i64);
+
+    let body = serde_json::json!({
+        "start": start.to_rfc3339(),
+        "end": now.to_rfc3339(),
+        "min_severity": "ERROR",
+        "max_patterns": limit
+    });
+
+    let resp = client
+        .post(format!("{}/v1/logs/patterns", api_url))
+        .json(&body)
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&resp)?);
         }
+        _ => print_patterns(&resp, hours, format),
+    }
+    Ok(())
+}
 
-        LogsCommands::Errors { hours, limit } => {
-            // Use the MCP endpoint for error summary
-            let body = serde_json::json!({
-                "tool": "get_error_summary",
-                "params": {
-                    "hours": hours,
-                    "limit": limit
-                }
-            });
-
-            let resp = client
-                .post(format!("{}/../:8081/mcp", api_url).replace(":8080/../:8081", ":8081"))
-                .json(&body)
-                .send()
-                .await?
-                .json::<Value>()
-                .await?;
-
-            match format {
-                OutputFormat::Json => {
-                    println!("{}", serde_json::to_string_pretty(&resp)?);
-                }
-                _ => {
-                    if let Some(data) = resp.get("data") {
-                        if let Some(patterns) = data.get("top_patterns").and_then(|p| p.as_array())
-                        {
-                            println!(
-                                "Top {} error patterns (last {} hours):\n",
-                                patterns.len(),
-                                hours
-                            );
-                            for (i, pattern) in patterns.iter().enumerate() {
-                                let count =
-                                    pattern.get("count").and_then(|c| c.as_u64()).unwrap_or(0);
-                                let msg = pattern
-                                    .get("pattern")
-                                    .and_then(|p| p.as_str())
-                                    .unwrap_or("");
-                                println!("{}. [{}x] {}", i + 1, count, msg);
-                            }
-                        }
-                    }
-                }
+fn print_patterns(resp: &Value, hours: u32, format: OutputFormat) {
+    if let Some(error) = resp.get("error").and_then(|e| e.as_str()) {
+        eprintln!("Error: {}", error);
+        return;
+    }
+
+    let patterns = resp
+        .get("patterns")
+        .and_then(|p| p.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let scanned = resp.get("scanned").and_then(|s| s.as_u64()).unwrap_or(0);
+    println!(
+        "Top {} error patterns in {} errors (last {} hours):\n",
+        patterns.len(),
+        scanned,
+        hours
+    );
+    for (i, pattern) in patterns.iter().enumerate() {
+        let count = pattern.get("count").and_then(|c| c.as_u64()).unwrap_or(0);
+        let template = pattern
+            .get("template")
+            .and_then(|t| t.as_str())
+            .unwrap_or("");
+        let last_seen = pattern
+            .get("last_seen")
+            .and_then(|t| t.as_str())
+            .unwrap_or("");
+        println!("{}. [{}x] {}", i + 1, count, template);
+        println!("   last seen {}", last_seen.get(..19).unwrap_or(last_seen));
+        if matches!(format, OutputFormat::Table) {
+            if let Some(sample) = pattern
+                .get("samples")
+                .and_then(|s| s.as_array())
+                .and_then(|s| s.first())
+                .and_then(|s| s.as_str())
+            {
+                println!("   e.g. {}", sample);
             }
         }
     }
+}
 
-    Ok(())
+/// Bar characters per severity, most severe first
+const SEVERITY_BARS: [(&str, char); 6] = [
+    ("FATAL", 'F'),
+    ("ERROR", 'E'),
+    ("WARN", 'W'),
+    ("INFO", '='),
+    ("DEBUG", '-'),
+    ("TRACE", '.'),
+];
+
+/// Widest bar, for the busiest bucket
+const HISTOGRAM_WIDTH: u64 = 60;
+
+/// One bar per time bucket, stacked by severity with the most severe on the left
+fn print_histogram(resp: &Value) {
+    if let Some(error) = resp.get("error").and_then(|e| e.as_str()) {
+        eprintln!("Error: {}", error);
+        return;
+    }
+
+    let buckets = resp
+        .get("buckets")
+        .and_then(|b| b.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let total = |b: &Value| b.get("total").and_then(Value::as_u64).unwrap_or(0);
+    let max = buckets.iter().map(total).max().unwrap_or(0).max(1);
+
+    let legend: Vec<String> = SEVERITY_BARS
+        .iter()
+        .map(|(severity, bar)| format!("{} {}", bar, severity))
+        .collect();
+    println!("Log volume ({})\n", legend.join("  "));
+
+    for bucket in &buckets {
+        let counts = bucket.get("counts");
+        let bar: String = SEVERITY_BARS
+            .iter()
+            .map(|(severity, bar)| {
+                let count = counts
+                    .and_then(|c| c.get(*severity))
+                    .and_then(Value::as_u64)
+                    .unwrap_or(0);
+                // Any log gets at least one character
+                let width = (count * HISTOGRAM_WIDTH).div_ceil(max);
+                bar.to_string().repeat(width as usize)
+            })
+            .collect();
+        let ts = bucket
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .unwrap_or("");
+        println!(
+            "{} {:>8} |{}",
+            ts.get(..16).unwrap_or(ts).replace('T', " "),
+            total(bucket),
+            bar
+        );
+    }
 }
 
 fn print_logs(resp: &Value, format: OutputFormat) {
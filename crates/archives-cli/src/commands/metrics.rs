@@ -1,47 +1,31 @@
 //! Metrics commands
 
+use super::labels;
 use crate::{MetricsCommands, OutputFormat};
 use chrono::{Duration, Utc};
 use serde_json::Value;
 
 pub async fn handle(
+    client: &reqwest::Client,
     api_url: &str,
     command: MetricsCommands,
     format: OutputFormat,
 ) -> anyhow::Result<()> {
-    let client = reqwest::Client::new();
-
     match command {
-        MetricsCommands::List => {
-            let resp = client
-                .get(format!("{}/v1/metrics/names", api_url))
-                .send()
-                .await?
-                .json::<Value>()
-                .await?;
-
-            match format {
-                OutputFormat::Json => {
-                    println!("{}", serde_json::to_string_pretty(&resp)?);
-                }
-                _ => {
-                    if let Some(names) = resp.get("names").and_then(|n| n.as_array()) {
-                        println!("Available metrics ({}):\n", names.len());
-                        for name in names {
-                            if let Some(n) = name.as_str() {
-                                println!("  {}", n);
-                            }
-                        }
-                    }
-                }
-            }
-        }
+        MetricsCommands::List {
+            prefix,
+            service,
+            hours,
+        } => list(client, api_url, prefix, service, hours, format).await,
 
         MetricsCommands::Query {
             name,
             hours,
             aggregation,
             interval,
+            labels,
+            group_by,
+            heatmap,
         } => {
             let now = Utc::now();
             let start = now - Duration::hours(hours as i64);
@@ -51,51 +35,314 @@ pub async fn handle(
                 "start": start.to_rfc3339(),
                 "end": now.to_rfc3339(),
                 "aggregation": aggregation,
-                "interval_seconds": interval
+                "interval_seconds": interval,
+                "label_filters": labels,
+                "group_by": group_by
             });
 
-            let resp = client
-                .post(format!("{}/v1/metrics/query", api_url))
-                .json(&body)
-                .send()
-                .await?
-                .json::<Value>()
-                .await?;
-
-            match format {
-                OutputFormat::Json => {
-                    println!("{}", serde_json::to_string_pretty(&resp)?);
-                }
-                OutputFormat::Compact => {
-                    if let Some(data) = resp.get("data").and_then(|d| d.as_array()) {
-                        for point in data {
-                            let ts = point
-                                .get("timestamp")
-                                .and_then(|t| t.as_str())
-                                .unwrap_or("");
-                            let val = point.get("value").and_then(|v| v.as_f64()).unwrap_or(0.0);
-                            println!("{} {:.4}", &ts[11..19], val);
-                        }
-                    }
-                }
-                OutputFormat::Table => {
-                    println!("Metric: {} ({})", name, aggregation);
-                    println!("{:<25} {:>15}", "TIMESTAMP", "VALUE");
-                    println!("{}", "-".repeat(42));
-                    if let Some(data) = resp.get("data").and_then(|d| d.as_array()) {
-                        for point in data {
-                            let ts = point
-                                .get("timestamp")
-                                .and_then(|t| t.as_str())
-                                .unwrap_or("");
-                            let val = point.get("value").and_then(|v| v.as_f64()).unwrap_or(0.0);
-                            println!("{:<25} {:>15.4}", ts, val);
-                        }
-                    }
-                }
+            if heatmap {
+                query_heatmap(client, api_url, &name, &body, format).await
+            } else {
+                query(client, api_url, &name, &aggregation, &body, format).await
             }
         }
+
+        MetricsCommands::Labels {
+            name,
+            key,
+            service,
+            hours,
+            limit,
+        } => {
+            let mut query = vec![("metric_name", name.clone()), ("limit", limit.to_string())];
+            query.extend(service.map(|s| ("service", s)));
+
+            labels::handle(
+                client,
+                &format!("{}/v1/metrics/labels", api_url),
+                query,
+                key,
+                hours,
+                format,
+                |matcher| format!("archives metrics query {} -l '{}'", name, matcher),
+            )
+            .await
+        }
+    }
+}
+
+async fn list(
+    client: &reqwest::Client,
+    api_url: &str,
+    prefix: Option<String>,
+    service: Option<String>,
+    hours: u32,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let start = now - Duration::hours(hours as i64);
+
+    let mut query = vec![("start", start.to_rfc3339()), ("end", now.to_rfc3339())];
+    query.extend(prefix.map(|p| ("prefix", p)));
+    query.extend(service.map(|s| ("service", s)));
+
+    let resp = client
+        .get(format!("{}/v1/metrics/catalog", api_url))
+        .query(&query)
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
+        _ => print_catalog(&resp, format),
+    }
+    Ok(())
+}
+
+fn print_catalog(resp: &Value, format: OutputFormat) {
+    let metrics = resp
+        .get("metrics")
+        .and_then(|m| m.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let text = |m: &Value, key: &str| {
+        m.get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+
+    if matches!(format, OutputFormat::Compact) {
+        for m in &metrics {
+            println!("{} {}", text(m, "name"), text(m, "metric_type"));
+        }
+        return;
+    }
+
+    println!("Available metrics ({}):\n", metrics.len());
+    println!(
+        "{:<40} {:<22} {:<8} {:>7}  {:<20} SERVICES",
+        "NAME", "TYPE", "UNIT", "SERIES", "LAST SEEN"
+    );
+    println!("{}", "-".repeat(110));
+    for m in &metrics {
+        let services: Vec<&str> = m
+            .get("services")
+            .and_then(|s| s.as_array())
+            .map(|s| s.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let last_seen = text(m, "last_seen");
+        println!(
+            "{:<40} {:<22} {:<8} {:>7}  {:<20} {}",
+            text(m, "name"),
+            text(m, "metric_type"),
+            text(m, "unit"),
+            m.get("series_count").and_then(Value::as_u64).unwrap_or(0),
+            last_seen.get(..19).unwrap_or(&last_seen),
+            services.join(",")
+        );
+        if let Some(description) = m.get("description").and_then(|d| d.as_str()) {
+            println!("    {}", description);
+        }
+    }
+}
+
+async fn query_heatmap(
+    client: &reqwest::Client,
+    api_url: &str,
+    name: &str,
+    body: &Value,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let resp = client
+        .post(format!("{}/v1/metrics/heatmap", api_url))
+        .json(body)
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
+        _ => print_heatmap(name, &resp),
     }
+    Ok(())
+}
+
+async fn query(
+    client: &reqwest::Client,
+    api_url: &str,
+    name: &str,
+    aggregation: &str,
+    body: &Value,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let resp = client
+        .post(format!("{}/v1/metrics/query", api_url))
+        .json(body)
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
 
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
+        _ => print_series(name, aggregation, &resp, format),
+    }
     Ok(())
 }
+
+fn print_series(name: &str, aggregation: &str, resp: &Value, format: OutputFormat) {
+    let series = resp
+        .get("series")
+        .and_then(|s| s.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let point_fields = |point: &Value| {
+        let ts = point
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .unwrap_or("")
+            .to_string();
+        let val = point.get("value").and_then(|v| v.as_f64()).unwrap_or(0.0);
+        (ts, val)
+    };
+
+    if matches!(format, OutputFormat::Compact) {
+        for s in &series {
+            let prefix = series_name(s);
+            for (ts, val) in points(s).iter().map(point_fields) {
+                if prefix.is_empty() {
+                    println!("{} {:.4}", &ts[11..19], val);
+                } else {
+                    println!("{} {} {:.4}", prefix, &ts[11..19], val);
+                }
+            }
+        }
+        return;
+    }
+
+    println!("Metric: {} ({})", name, aggregation);
+    for s in &series {
+        let label = series_name(s);
+        if !label.is_empty() {
+            println!("\n{}", label);
+        }
+        println!("{:<25} {:>15}", "TIMESTAMP", "VALUE");
+        println!("{}", "-".repeat(42));
+        for (ts, val) in points(s).iter().map(point_fields) {
+            println!("{:<25} {:>15.4}", ts, val);
+        }
+    }
+}
+
+/// `{key="value", ...}` for a grouped series, empty when not grouped
+fn series_name(series: &Value) -> String {
+    let labels: Vec<String> = series
+        .get("labels")
+        .and_then(|l| l.as_object())
+        .map(|labels| labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect())
+        .unwrap_or_default();
+
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(", "))
+    }
+}
+
+fn points(series: &Value) -> &[Value] {
+    series
+        .get("points")
+        .and_then(|p| p.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+/// Shades from empty to the busiest cell
+const SHADES: &[u8] = b" .:-=+*#%@";
+
+/// Text heatmap per series: one row per histogram bucket (highest on top),
+/// one column per time bucket, shaded by observation count
+fn print_heatmap(name: &str, resp: &Value) {
+    if let Some(error) = resp.get("error").and_then(|e| e.as_str()) {
+        eprintln!("Error: {}", error);
+        return;
+    }
+
+    println!("Metric: {} (heatmap)", name);
+    let series = resp
+        .get("series")
+        .and_then(|s| s.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    for s in &series {
+        let label = series_name(s);
+        if !label.is_empty() {
+            println!("\n{}", label);
+        }
+
+        let bounds: Vec<f64> = s
+            .get("bounds")
+            .and_then(|b| b.as_array())
+            .map(|b| b.iter().filter_map(Value::as_f64).collect())
+            .unwrap_or_default();
+        let columns: Vec<Vec<u64>> = points(s)
+            .iter()
+            .map(|p| {
+                p.get("counts")
+                    .and_then(|c| c.as_array())
+                    .map(|c| c.iter().filter_map(Value::as_u64).collect())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let max = columns.iter().flatten().copied().max().unwrap_or(0).max(1);
+
+        let row_label = |i: usize| match bounds.get(i) {
+            Some(upper) => format!("<= {}", upper),
+            None => format!("> {}", bounds.last().copied().unwrap_or(0.0)),
+        };
+        let width = (0..=bounds.len())
+            .map(|i| row_label(i).len())
+            .max()
+            .unwrap_or(0);
+
+        for i in (0..=bounds.len()).rev() {
+            let cells: String = columns
+                .iter()
+                .map(|counts| {
+                    let count = counts.get(i).copied().unwrap_or(0);
+                    // Any observation gets at least the lightest shade
+                    let shade = (count * (SHADES.len() as u64 - 1)).div_ceil(max);
+                    SHADES[shade as usize] as char
+                })
+                .collect();
+            println!("{:>width$} |{}", row_label(i), cells, width = width);
+        }
+
+        let time = |p: Option<&Value>| {
+            p.and_then(|p| p.get("timestamp"))
+                .and_then(|t| t.as_str())
+                .map(|t| t[11..16].to_string())
+                .unwrap_or_default()
+        };
+        let pts = points(s);
+        println!(
+            "{:>width$}  {} .. {} (max {} per cell)",
+            "",
+            time(pts.first()),
+            time(pts.last()),
+            max,
+            width = width
+        );
+    }
+}
@@ -1,5 +1,22 @@
 //! CLI command implementations
 
+pub mod admin;
+pub mod labels;
 pub mod logs;
 pub mod metrics;
+pub mod services;
 pub mod status;
+
+/// HTTP client for the API, sending `api_key` as a bearer token on every
+/// request when set
+pub fn client(api_key: Option<&str>) -> anyhow::Result<reqwest::Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(key) = api_key {
+        let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", key))?;
+        value.set_sensitive(true);
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+    }
+    Ok(reqwest::Client::builder()
+        .default_headers(headers)
+        .build()?)
+}
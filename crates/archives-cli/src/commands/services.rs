@@ -0,0 +1,84 @@
+//! Services command
+
+use crate::OutputFormat;
+use chrono::{Duration, Utc};
+use serde_json::Value;
+
+pub async fn handle(
+    client: &reqwest::Client,
+    api_url: &str,
+    hours: u32,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let start = now - Duration::hours(hours as i64);
+
+    let resp = client
+        .get(format!("{}/v1/services", api_url))
+        .query(&[("start", start.to_rfc3339()), ("end", now.to_rfc3339())])
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
+
+    let services = resp
+        .get("services")
+        .and_then(|s| s.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let number = |s: &Value, key: &str| s.get(key).and_then(Value::as_u64).unwrap_or(0);
+    let ratio = |s: &Value| s.get("error_ratio").and_then(Value::as_f64).unwrap_or(0.0);
+    let attribute = |s: &Value, keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| {
+                s.get("resource_attributes")
+                    .and_then(|a| a.get(*key))
+                    .and_then(|v| v.as_str())
+            })
+            .unwrap_or("-")
+            .to_string()
+    };
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&resp)?);
+        }
+        OutputFormat::Compact => {
+            for s in &services {
+                println!(
+                    "{} {} {:.4}",
+                    s.get("name").and_then(|n| n.as_str()).unwrap_or(""),
+                    number(s, "log_count"),
+                    ratio(s)
+                );
+            }
+        }
+        OutputFormat::Table => {
+            println!("Services (last {} hours): {}\n", hours, services.len());
+            println!(
+                "{:<30} {:>10} {:>8} {:>7} {:>8}  {:<20} {:<12} ENVIRONMENT",
+                "NAME", "LOGS", "ERRORS", "ERR %", "METRICS", "LAST SEEN", "VERSION"
+            );
+            println!("{}", "-".repeat(120));
+            for s in &services {
+                let last_seen = s.get("last_seen").and_then(|l| l.as_str()).unwrap_or("");
+                println!(
+                    "{:<30} {:>10} {:>8} {:>6.2}% {:>8}  {:<20} {:<12} {}",
+                    s.get("name").and_then(|n| n.as_str()).unwrap_or(""),
+                    number(s, "log_count"),
+                    number(s, "error_count"),
+                    ratio(s) * 100.0,
+                    number(s, "metric_count"),
+                    last_seen.get(..19).unwrap_or(last_seen),
+                    attribute(s, &["service.version"]),
+                    attribute(
+                        s,
+                        &["deployment.environment.name", "deployment.environment"]
+                    )
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
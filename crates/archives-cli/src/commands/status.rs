@@ -3,9 +3,11 @@
 use crate::OutputFormat;
 use serde_json::Value;
 
-pub async fn handle(api_url: &str, format: OutputFormat) -> anyhow::Result<()> {
-    let client = reqwest::Client::new();
-
+pub async fn handle(
+    client: &reqwest::Client,
+    api_url: &str,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
     let resp = client
         .get(format!("{}/v1/status", api_url))
         .send()
@@ -54,8 +56,60 @@ pub async fn handle(api_url: &str, format: OutputFormat) -> anyhow::Result<()> {
                 metric_count,
                 metric_bytes as f64 / 1024.0 / 1024.0
             );
+
+            if let Some(retention) = resp.get("retention") {
+                print_retention(retention);
+            }
         }
     }
 
     Ok(())
 }
+
+fn print_retention(retention: &Value) {
+    let days = |key: &str| retention.get(key).and_then(|d| d.as_u64()).unwrap_or(0);
+    println!("\nRetention:");
+    println!("  Logs:    {} days", days("log_retention_days"));
+    println!("  Metrics: {} days", days("metrics_retention_days"));
+
+    let rules = retention.get("rules").and_then(|r| r.as_array());
+    for rule in rules.into_iter().flatten() {
+        let mut scope = Vec::new();
+        if let Some(service) = rule.get("service").and_then(|s| s.as_str()) {
+            scope.push(format!("service {}", service));
+        }
+        match (
+            rule.get("min_severity").and_then(|s| s.as_str()),
+            rule.get("max_severity").and_then(|s| s.as_str()),
+        ) {
+            (Some(min), Some(max)) => scope.push(format!("{}..{}", min, max)),
+            (Some(min), None) => scope.push(format!("{}+", min)),
+            (None, Some(max)) => scope.push(format!("{} and below", max)),
+            (None, None) => {}
+        }
+        let scope = if scope.is_empty() {
+            "everything else".to_string()
+        } else {
+            scope.join(", ")
+        };
+        println!(
+            "  {}: {} days",
+            scope,
+            rule.get("days").and_then(|d| d.as_u64()).unwrap_or(0)
+        );
+    }
+
+    let tables = retention.get("tables").and_then(|t| t.as_array());
+    let stale: Vec<&str> = tables
+        .into_iter()
+        .flatten()
+        .filter(|t| t.get("in_sync").and_then(|s| s.as_bool()) == Some(false))
+        .filter_map(|t| t.get("table").and_then(|n| n.as_str()))
+        .collect();
+    if !stale.is_empty() {
+        println!(
+            "  TTL differs from configuration on: {} (run `archives admin retention`)",
+            stale.join(", ")
+        );
+    }
+}
@@ -4,6 +4,7 @@
 
 mod commands;
 
+use archives_common::filter::AttributeFilter;
 use clap::{Parser, Subcommand};
 use tracing_subscriber::EnvFilter;
 
@@ -20,6 +21,10 @@ struct Cli {
     )]
     api_url: String,
 
+    /// API key for servers with tenancy enabled
+    #[arg(long, env = "ARCHIVES_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+
     /// Output format
     #[arg(long, short, default_value = "table")]
     format: OutputFormat,
@@ -49,15 +54,52 @@ enum Commands {
         command: MetricsCommands,
     },
 
+    /// List services with log volume, error ratio and metrics
+    Services {
+        /// Time range in hours (default: 24)
+        #[arg(long, short = 't', default_value = "24")]
+        hours: u32,
+    },
+
     /// Show system status
     Status,
+
+    /// Administer the ClickHouse database directly
+    Admin {
+        #[command(subcommand)]
+        command: AdminCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum AdminCommands {
+    /// Apply pending schema migrations
+    Migrate {
+        /// Show pending migrations and their SQL without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Set table TTLs from the [retention] configuration
+    Retention {
+        /// Show the TTL changes without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Aggregate metric points from before the rollups existed into them
+    BackfillRollups {
+        /// Show what would be aggregated without inserting
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 #[derive(Subcommand)]
 enum LogsCommands {
     /// Search logs
     Search {
-        /// Text to search for
+        /// Query, e.g. 'service:checkout severity>=warn "payment timeout" -body:healthcheck'
         query: Option<String>,
 
         /// Time range in hours (default: 1)
@@ -72,9 +114,48 @@ enum LogsCommands {
         #[arg(long)]
         service: Option<String>,
 
-        /// Maximum results
+        /// Attribute filter, repeatable (key=value, key!=value, key^=prefix,
+        /// key=~regex, key>=n, key<n, or a bare key for existence)
+        #[arg(long = "attr", value_name = "FILTER", value_parser = parse_attribute_filter)]
+        attributes: Vec<AttributeFilter>,
+
+        /// Maximum results per page
         #[arg(long, short = 'n', default_value = "50")]
         limit: u64,
+
+        /// Fetch up to this many pages, following the cursor
+        #[arg(long, default_value = "1", conflicts_with = "all")]
+        pages: u32,
+
+        /// Fetch every page
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Chart log volume over time, stacked by severity
+    Histogram {
+        /// Query, with the same syntax as `logs search`
+        query: Option<String>,
+
+        /// Time range in hours (default: 1)
+        #[arg(long, short = 't', default_value = "1")]
+        hours: u32,
+
+        /// Bucket size in seconds (default: one minute per hour of range)
+        #[arg(long, short = 'i')]
+        interval: Option<u32>,
+
+        /// Minimum severity level
+        #[arg(long, short = 's')]
+        severity: Option<String>,
+
+        /// Filter by service name
+        #[arg(long)]
+        service: Option<String>,
+
+        /// Attribute filter, repeatable, as in `logs search`
+        #[arg(long = "attr", value_name = "FILTER", value_parser = parse_attribute_filter)]
+        attributes: Vec<AttributeFilter>,
     },
 
     /// Tail recent logs
@@ -92,6 +173,39 @@ enum LogsCommands {
         service: Option<String>,
     },
 
+    /// Show all logs of a trace, oldest first
+    Trace {
+        /// Trace ID (hex)
+        trace_id: String,
+
+        /// Time range in hours (default: 24)
+        #[arg(long, short = 't', default_value = "24")]
+        hours: u32,
+
+        /// Maximum results
+        #[arg(long, short = 'n', default_value = "1000")]
+        limit: u64,
+    },
+
+    /// List attribute keys with their cardinality, or the top values of one key
+    Attributes {
+        /// Show the most frequent values of this key instead of listing keys
+        #[arg(long, short = 'k')]
+        key: Option<String>,
+
+        /// Filter by service name
+        #[arg(long)]
+        service: Option<String>,
+
+        /// Time range in hours (default: 24)
+        #[arg(long, short = 't', default_value = "24")]
+        hours: u32,
+
+        /// Maximum number of values
+        #[arg(long, short = 'n', default_value = "20")]
+        limit: u64,
+    },
+
     /// Show error summary
     Errors {
         /// Time range in hours (default: 24)
@@ -106,8 +220,20 @@ enum LogsCommands {
 
 #[derive(Subcommand)]
 enum MetricsCommands {
-    /// List available metrics
-    List,
+    /// List available metrics with type, unit and series counts
+    List {
+        /// Only metrics whose name starts with this prefix
+        #[arg(long)]
+        prefix: Option<String>,
+
+        /// Only metrics emitted by this service
+        #[arg(long)]
+        service: Option<String>,
+
+        /// Time range in hours (default: 24)
+        #[arg(long, short = 't', default_value = "24")]
+        hours: u32,
+    },
 
     /// Query a metric
     Query {
@@ -118,14 +244,53 @@ enum MetricsCommands {
         #[arg(long, short = 't', default_value = "1")]
         hours: u32,
 
-        /// Aggregation function
+        /// Aggregation function (avg, min, max, sum, count, p50, p90, p99,
+        /// or rate/increase for counters)
         #[arg(long, short = 'a', default_value = "avg")]
         aggregation: String,
 
         /// Interval in seconds
         #[arg(long, short = 'i', default_value = "60")]
         interval: u32,
+
+        /// Label matcher, repeatable (key=value, key!=value, key=~regex, ...)
+        #[arg(long = "label", short = 'l', value_name = "MATCHER", value_parser = parse_attribute_filter)]
+        labels: Vec<AttributeFilter>,
+
+        /// Label key to split the result by, repeatable
+        #[arg(long = "group-by", short = 'g', value_name = "KEY")]
+        group_by: Vec<String>,
+
+        /// Show histogram buckets over time instead of an aggregation
+        #[arg(long)]
+        heatmap: bool,
     },
+
+    /// List the label keys of a metric, or the top values of one key
+    Labels {
+        /// Metric name
+        name: String,
+
+        /// Show the most frequent values of this key instead of listing keys
+        #[arg(long, short = 'k')]
+        key: Option<String>,
+
+        /// Only data points emitted by this service
+        #[arg(long)]
+        service: Option<String>,
+
+        /// Time range in hours (default: 24)
+        #[arg(long, short = 't', default_value = "24")]
+        hours: u32,
+
+        /// Maximum number of values
+        #[arg(long, short = 'n', default_value = "20")]
+        limit: u64,
+    },
+}
+
+fn parse_attribute_filter(s: &str) -> Result<AttributeFilter, String> {
+    s.parse().map_err(|e: archives_common::Error| e.to_string())
 }
 
 #[tokio::main]
@@ -137,16 +302,23 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let cli = Cli::parse();
+    let client = commands::client(cli.api_key.as_deref())?;
 
     match cli.command {
         Commands::Logs { command } => {
-            commands::logs::handle(&cli.api_url, command, cli.format).await?;
+            commands::logs::handle(&client, &cli.api_url, command, cli.format).await?;
         }
         Commands::Metrics { command } => {
-            commands::metrics::handle(&cli.api_url, command, cli.format).await?;
+            commands::metrics::handle(&client, &cli.api_url, command, cli.format).await?;
+        }
+        Commands::Services { hours } => {
+            commands::services::handle(&client, &cli.api_url, hours, cli.format).await?;
         }
         Commands::Status => {
-            commands::status::handle(&cli.api_url, cli.format).await?;
+            commands::status::handle(&client, &cli.api_url, cli.format).await?;
+        }
+        Commands::Admin { command } => {
+            commands::admin::handle(command, cli.format).await?;
         }
     }
 
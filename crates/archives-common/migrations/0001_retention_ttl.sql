@@ -0,0 +1,18 @@
+-- Retention TTLs on the tables created by the OTEL exporter:
+-- 30 days for logs, 90 days for metrics
+
+ALTER TABLE otel_logs
+    MODIFY TTL Timestamp + INTERVAL 30 DAY
+    SETTINGS allow_nullable_key = 1;
+
+ALTER TABLE otel_metrics_gauge
+    MODIFY TTL TimeUnix + INTERVAL 90 DAY
+    SETTINGS allow_nullable_key = 1;
+
+ALTER TABLE otel_metrics_sum
+    MODIFY TTL TimeUnix + INTERVAL 90 DAY
+    SETTINGS allow_nullable_key = 1;
+
+ALTER TABLE otel_metrics_histogram
+    MODIFY TTL TimeUnix + INTERVAL 90 DAY
+    SETTINGS allow_nullable_key = 1;
@@ -0,0 +1,113 @@
+-- 1-minute and 1-hour rollups of gauge and sum data points. query_metrics
+-- reads them instead of the raw tables when the interval and time range line
+-- up with their buckets. The views only see new inserts; older data is added
+-- by `archives admin backfill-rollups`, which records its progress per rollup
+-- and source table in metrics_rollup_backfills. The rollups follow the metric
+-- retention of `[retention]`, so `archives admin migrate` sets their TTL.
+
+CREATE TABLE IF NOT EXISTS metrics_rollup_1m (
+    MetricName LowCardinality(String),
+    ServiceName LowCardinality(String),
+    Attributes Map(LowCardinality(String), String),
+    ResourceAttributes Map(LowCardinality(String), String),
+    Bucket DateTime,
+    ValueAvg AggregateFunction(avg, Float64),
+    ValueMin AggregateFunction(min, Float64),
+    ValueMax AggregateFunction(max, Float64),
+    ValueSum AggregateFunction(sum, Float64),
+    ValueCount AggregateFunction(count),
+    ValueQuantiles AggregateFunction(quantiles(0.5, 0.9, 0.99), Float64)
+)
+ENGINE = AggregatingMergeTree()
+PARTITION BY toYYYYMMDD(Bucket)
+ORDER BY (MetricName, ServiceName, Bucket, cityHash64(toString(Attributes), toString(ResourceAttributes)));
+
+CREATE TABLE IF NOT EXISTS metrics_rollup_1h (
+    MetricName LowCardinality(String),
+    ServiceName LowCardinality(String),
+    Attributes Map(LowCardinality(String), String),
+    ResourceAttributes Map(LowCardinality(String), String),
+    Bucket DateTime,
+    ValueAvg AggregateFunction(avg, Float64),
+    ValueMin AggregateFunction(min, Float64),
+    ValueMax AggregateFunction(max, Float64),
+    ValueSum AggregateFunction(sum, Float64),
+    ValueCount AggregateFunction(count),
+    ValueQuantiles AggregateFunction(quantiles(0.5, 0.9, 0.99), Float64)
+)
+ENGINE = AggregatingMergeTree()
+PARTITION BY toYYYYMM(Bucket)
+ORDER BY (MetricName, ServiceName, Bucket, cityHash64(toString(Attributes), toString(ResourceAttributes)));
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS metrics_rollup_1m_gauge_mv TO metrics_rollup_1m AS
+SELECT
+    MetricName,
+    ServiceName,
+    Attributes,
+    ResourceAttributes,
+    toStartOfMinute(TimeUnix) as Bucket,
+    avgState(Value) as ValueAvg,
+    minState(Value) as ValueMin,
+    maxState(Value) as ValueMax,
+    sumState(Value) as ValueSum,
+    countState() as ValueCount,
+    quantilesState(0.5, 0.9, 0.99)(Value) as ValueQuantiles
+FROM otel_metrics_gauge
+GROUP BY MetricName, ServiceName, Attributes, ResourceAttributes, Bucket;
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS metrics_rollup_1m_sum_mv TO metrics_rollup_1m AS
+SELECT
+    MetricName,
+    ServiceName,
+    Attributes,
+    ResourceAttributes,
+    toStartOfMinute(TimeUnix) as Bucket,
+    avgState(Value) as ValueAvg,
+    minState(Value) as ValueMin,
+    maxState(Value) as ValueMax,
+    sumState(Value) as ValueSum,
+    countState() as ValueCount,
+    quantilesState(0.5, 0.9, 0.99)(Value) as ValueQuantiles
+FROM otel_metrics_sum
+GROUP BY MetricName, ServiceName, Attributes, ResourceAttributes, Bucket;
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS metrics_rollup_1h_gauge_mv TO metrics_rollup_1h AS
+SELECT
+    MetricName,
+    ServiceName,
+    Attributes,
+    ResourceAttributes,
+    toStartOfHour(TimeUnix) as Bucket,
+    avgState(Value) as ValueAvg,
+    minState(Value) as ValueMin,
+    maxState(Value) as ValueMax,
+    sumState(Value) as ValueSum,
+    countState() as ValueCount,
+    quantilesState(0.5, 0.9, 0.99)(Value) as ValueQuantiles
+FROM otel_metrics_gauge
+GROUP BY MetricName, ServiceName, Attributes, ResourceAttributes, Bucket;
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS metrics_rollup_1h_sum_mv TO metrics_rollup_1h AS
+SELECT
+    MetricName,
+    ServiceName,
+    Attributes,
+    ResourceAttributes,
+    toStartOfHour(TimeUnix) as Bucket,
+    avgState(Value) as ValueAvg,
+    minState(Value) as ValueMin,
+    maxState(Value) as ValueMax,
+    sumState(Value) as ValueSum,
+    countState() as ValueCount,
+    quantilesState(0.5, 0.9, 0.99)(Value) as ValueQuantiles
+FROM otel_metrics_sum
+GROUP BY MetricName, ServiceName, Attributes, ResourceAttributes, Bucket;
+
+CREATE TABLE IF NOT EXISTS metrics_rollup_backfills (
+    Rollup LowCardinality(String),
+    Source LowCardinality(String),
+    Until DateTime,
+    Done UInt8
+)
+ENGINE = ReplacingMergeTree(Done)
+ORDER BY (Rollup, Source);
@@ -0,0 +1,435 @@
+//! Query result cache
+//!
+//! [`CachedStore`] wraps a storage backend and keeps recent query results in
+//! memory, so agents repeating the same MCP tool calls in a loop do not hit
+//! ClickHouse every time. Keys are the normalized query parameters with the
+//! time range widened to multiples of [`CacheConfig::snap_secs`], so requests
+//! a few seconds apart share an entry, but the backend is queried with the
+//! caller's range and log rows from a shared entry are trimmed back to it.
+//! Metric queries and log histograms, whose buckets are aligned to their
+//! interval anyway, are widened to the interval and queried that way.
+//!
+//! Ranges ending within the last [`LIVE_WINDOW_SECS`] seconds still receive
+//! late data and expire after [`CacheConfig::live_ttl_secs`]; closed ranges
+//! are kept for [`CacheConfig::historical_ttl_secs`]. Tenant views of the
+//! store share the cache, with keys prefixed by the tenant.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    config::CacheConfig,
+    error::Result,
+    resilience::BreakerStatus,
+    store::{
+        DatabaseStats, ErrorSummary, HistogramSeries, LabelKey, LabelQueryParams, LabelValue,
+        LogSearchParams, LogStore, LogStream, LogVolumeBucket, MetricCatalogParams, MetricInfo,
+        MetricQueryParams, MetricSeries, MetricStore, ServiceInfo, SharedStore, Store,
+        SummarySeries,
+    },
+    tenant::TenantScope,
+    types::{LogEntry, TimeRange},
+};
+
+/// Ranges ending less than this many seconds ago count as live
+pub const LIVE_WINDOW_SECS: i64 = 300;
+
+/// Cache counters reported in `/v1/status`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    /// Queries answered from the cache
+    pub hits: u64,
+    /// Queries sent to the backend
+    pub misses: u64,
+    /// Entries dropped to stay within `max_entries`
+    pub evictions: u64,
+    /// Entries currently held, including expired ones not yet dropped
+    pub entries: usize,
+}
+
+/// Number of rows in a result, checked against [`CacheConfig::max_rows`]
+trait CacheRows {
+    fn rows(&self) -> usize;
+}
+
+impl<T> CacheRows for Vec<T> {
+    fn rows(&self) -> usize {
+        self.len()
+    }
+}
+
+impl CacheRows for u64 {
+    fn rows(&self) -> usize {
+        1
+    }
+}
+
+impl CacheRows for ErrorSummary {
+    fn rows(&self) -> usize {
+        self.patterns.len()
+    }
+}
+
+struct Entry {
+    expires: Instant,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+/// Store wrapper caching query results
+pub struct CachedStore {
+    inner: SharedStore,
+    config: CacheConfig,
+    /// Shared with the tenant views of the store, as are the counters
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+    evictions: Arc<AtomicU64>,
+    /// Tenant of this view, whose keys are kept apart from other tenants'
+    tenant: Option<TenantScope>,
+}
+
+impl CachedStore {
+    /// Wrap `inner` with a cache sized by `config`
+    pub fn new(inner: SharedStore, config: CacheConfig) -> Self {
+        Self {
+            inner,
+            config,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+            evictions: Arc::new(AtomicU64::new(0)),
+            tenant: None,
+        }
+    }
+
+    /// Current counters
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: self.entries.lock().expect("cache lock poisoned").len(),
+        }
+    }
+
+    /// Return the entry for `key`, or run `fetch` and keep its result
+    async fn cached<T>(
+        &self,
+        key: String,
+        time_range: &TimeRange,
+        fetch: impl Future<Output = Result<T>> + Send,
+    ) -> Result<T>
+    where
+        T: CacheRows + Clone + Send + Sync + 'static,
+    {
+        let key = self.scoped_key(key);
+        if let Some(value) = self.lookup::<T>(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(value);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let value = fetch.await?;
+        if value.rows() <= self.config.max_rows {
+            self.insert(key, self.ttl(time_range), Arc::new(value.clone()));
+        }
+        Ok(value)
+    }
+
+    /// `key` prefixed with the tenant of this view, so tenants never share
+    /// an entry
+    fn scoped_key(&self, key: String) -> String {
+        match self.tenant {
+            Some(ref scope) => format!("tenant {:?} {key}", scope.tenant()),
+            None => key,
+        }
+    }
+
+    fn lookup<T: Clone + 'static>(&self, key: &str) -> Option<T> {
+        let entries = self.entries.lock().expect("cache lock poisoned");
+        entries
+            .get(key)
+            .filter(|entry| entry.expires > Instant::now())
+            .and_then(|entry| entry.value.downcast_ref::<T>())
+            .cloned()
+    }
+
+    fn insert(&self, key: String, ttl: Duration, value: Arc<dyn Any + Send + Sync>) {
+        if self.config.max_entries == 0 || ttl.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().expect("cache lock poisoned");
+        if entries.len() >= self.config.max_entries && !entries.contains_key(&key) {
+            entries.retain(|_, entry| entry.expires > now);
+        }
+        while entries.len() >= self.config.max_entries && !entries.contains_key(&key) {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+        entries.insert(
+            key,
+            Entry {
+                expires: now + ttl,
+                value,
+            },
+        );
+    }
+
+    fn ttl(&self, time_range: &TimeRange) -> Duration {
+        let live_since = Utc::now() - chrono::Duration::seconds(LIVE_WINDOW_SECS);
+        if time_range.end > live_since {
+            Duration::from_secs(self.config.live_ttl_secs)
+        } else {
+            Duration::from_secs(self.config.historical_ttl_secs)
+        }
+    }
+
+    /// Cache key of a log query, with the time range snapped
+    fn log_key(&self, query: &str, params: &LogSearchParams) -> String {
+        let mut params = normalized(params);
+        params.time_range = snap(&params.time_range, self.config.snap_secs);
+        format!("{query} {params:?}")
+    }
+
+    /// Metric queries snap to their own interval
+    fn metric_key(query: &str, params: &MetricQueryParams) -> (String, MetricQueryParams) {
+        let mut params = params.clone();
+        params.time_range = snap(&params.time_range, params.interval_seconds.unwrap_or(60));
+        params
+            .labels
+            .sort_by_cached_key(|filter| format!("{filter:?}"));
+        (format!("{query} {params:?}"), params)
+    }
+
+    fn label_key(&self, query: &str, params: &LabelQueryParams) -> String {
+        let mut params = params.clone();
+        params.time_range = snap(&params.time_range, self.config.snap_secs);
+        format!("{query} {params:?}")
+    }
+}
+
+/// `params` with filters in a stable order
+fn normalized(params: &LogSearchParams) -> LogSearchParams {
+    let mut params = params.clone();
+    params.body_terms.sort();
+    params.excluded_body_terms.sort();
+    params
+        .attribute_filters
+        .sort_by_cached_key(|filter| format!("{filter:?}"));
+    params
+}
+
+/// Widen `time_range` to multiples of `seconds` since the epoch
+fn snap(time_range: &TimeRange, seconds: u32) -> TimeRange {
+    let step = i64::from(seconds.max(1));
+    let start = time_range.start.timestamp().div_euclid(step) * step;
+    // Round a fractional end up, then up to the next boundary
+    let end = time_range.end.timestamp() + i64::from(time_range.end.timestamp_subsec_nanos() > 0);
+    let end = end + (-end).rem_euclid(step);
+    TimeRange {
+        start: DateTime::from_timestamp(start, 0).unwrap_or(time_range.start),
+        end: DateTime::from_timestamp(end, 0).unwrap_or(time_range.end),
+    }
+}
+
+#[async_trait]
+impl LogStore for CachedStore {
+    async fn search_logs(&self, params: &LogSearchParams) -> Result<Vec<LogEntry>> {
+        let key = self.log_key("search_logs", params);
+        let logs = self
+            .cached(key, &params.time_range, self.inner.search_logs(params))
+            .await?;
+        // An entry shared with a range a few seconds apart
+        Ok(logs
+            .into_iter()
+            .filter(|log| params.time_range.contains(log.timestamp))
+            .collect())
+    }
+
+    async fn count_logs(&self, time_range: &TimeRange) -> Result<u64> {
+        let key = format!("count_logs {:?}", snap(time_range, self.config.snap_secs));
+        self.cached(key, time_range, self.inner.count_logs(time_range))
+            .await
+    }
+
+    async fn get_log(&self, id: Uuid) -> Result<Option<LogEntry>> {
+        self.inner.get_log(id).await
+    }
+
+    async fn count_logs_over_time(
+        &self,
+        params: &LogSearchParams,
+        interval_seconds: u32,
+    ) -> Result<Vec<LogVolumeBucket>> {
+        let mut params = normalized(params);
+        params.time_range = snap(&params.time_range, interval_seconds);
+        let key = format!("count_logs_over_time {interval_seconds} {params:?}");
+        self.cached(
+            key,
+            &params.time_range,
+            self.inner.count_logs_over_time(&params, interval_seconds),
+        )
+        .await
+    }
+
+    async fn error_summary(&self, params: &LogSearchParams) -> Result<ErrorSummary> {
+        let key = self.log_key("error_summary", params);
+        self.cached(key, &params.time_range, self.inner.error_summary(params))
+            .await
+    }
+
+    async fn log_attribute_keys(&self, params: &LabelQueryParams) -> Result<Vec<LabelKey>> {
+        let key = self.label_key("log_attribute_keys", params);
+        self.cached(
+            key,
+            &params.time_range,
+            self.inner.log_attribute_keys(params),
+        )
+        .await
+    }
+
+    async fn log_attribute_values(
+        &self,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>> {
+        let cache_key = self.label_key(&format!("log_attribute_values {key:?}"), params);
+        self.cached(
+            cache_key,
+            &params.time_range,
+            self.inner.log_attribute_values(key, params),
+        )
+        .await
+    }
+
+    fn stream_logs<'a>(&'a self, params: &'a LogSearchParams) -> LogStream<'a> {
+        // Streams exist to avoid holding results in memory, so bypass the cache
+        self.inner.stream_logs(params)
+    }
+}
+
+#[async_trait]
+impl MetricStore for CachedStore {
+    async fn list_metric_names(&self) -> Result<Vec<String>> {
+        self.inner.list_metric_names().await
+    }
+
+    async fn query_metrics(&self, params: &MetricQueryParams) -> Result<Vec<MetricSeries>> {
+        let (key, params) = Self::metric_key("query_metrics", params);
+        self.cached(key, &params.time_range, self.inner.query_metrics(&params))
+            .await
+    }
+
+    async fn query_histogram(&self, params: &MetricQueryParams) -> Result<Vec<HistogramSeries>> {
+        let (key, params) = Self::metric_key("query_histogram", params);
+        self.cached(key, &params.time_range, self.inner.query_histogram(&params))
+            .await
+    }
+
+    async fn metric_catalog(&self, params: &MetricCatalogParams) -> Result<Vec<MetricInfo>> {
+        let mut snapped = params.clone();
+        snapped.time_range = snap(&params.time_range, self.config.snap_secs);
+        let key = format!("metric_catalog {snapped:?}");
+        self.cached(key, &params.time_range, self.inner.metric_catalog(params))
+            .await
+    }
+
+    async fn query_summary(&self, params: &MetricQueryParams) -> Result<Vec<SummarySeries>> {
+        let (key, params) = Self::metric_key("query_summary", params);
+        self.cached(key, &params.time_range, self.inner.query_summary(&params))
+            .await
+    }
+
+    async fn metric_label_keys(
+        &self,
+        metric_name: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelKey>> {
+        let key = self.label_key(&format!("metric_label_keys {metric_name:?}"), params);
+        self.cached(
+            key,
+            &params.time_range,
+            self.inner.metric_label_keys(metric_name, params),
+        )
+        .await
+    }
+
+    async fn metric_label_values(
+        &self,
+        metric_name: &str,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>> {
+        let cache_key = self.label_key(
+            &format!("metric_label_values {metric_name:?} {key:?}"),
+            params,
+        );
+        self.cached(
+            cache_key,
+            &params.time_range,
+            self.inner.metric_label_values(metric_name, key, params),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl Store for CachedStore {
+    async fn health_check(&self) -> Result<bool> {
+        self.inner.health_check().await
+    }
+
+    async fn get_stats(&self) -> Result<DatabaseStats> {
+        self.inner.get_stats().await
+    }
+
+    async fn list_services(&self, time_range: &TimeRange) -> Result<Vec<ServiceInfo>> {
+        let key = format!(
+            "list_services {:?}",
+            snap(time_range, self.config.snap_secs)
+        );
+        self.cached(key, time_range, self.inner.list_services(time_range))
+            .await
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        Some(self.stats())
+    }
+
+    fn breaker_status(&self) -> Option<BreakerStatus> {
+        self.inner.breaker_status()
+    }
+
+    fn for_tenant(&self, scope: &TenantScope) -> Result<SharedStore> {
+        Ok(Arc::new(Self {
+            inner: self.inner.for_tenant(scope)?,
+            config: self.config.clone(),
+            entries: Arc::clone(&self.entries),
+            hits: Arc::clone(&self.hits),
+            misses: Arc::clone(&self.misses),
+            evictions: Arc::clone(&self.evictions),
+            tenant: Some(scope.clone()),
+        }))
+    }
+}
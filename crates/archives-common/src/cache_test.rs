@@ -0,0 +1,94 @@
+//! Tests for cache module
+
+use std::sync::Arc;
+
+use crate::{
+    cache::CachedStore,
+    config::CacheConfig,
+    memory::MemoryStore,
+    store::{LogSearchParams, LogStore, Store},
+    test_support::{self, ts},
+    types::{LogEntry, LogSeverity, TimeRange},
+};
+
+fn log(secs: i64, body: &str) -> LogEntry {
+    test_support::log(secs, LogSeverity::Info, "api", body)
+}
+
+fn search(start: i64, end: i64) -> LogSearchParams {
+    LogSearchParams {
+        time_range: TimeRange {
+            start: ts(start),
+            end: ts(end),
+        },
+        ..LogSearchParams::default()
+    }
+}
+
+#[tokio::test]
+async fn test_cache_hits_within_snapped_range() {
+    let memory = Arc::new(MemoryStore::new());
+    memory.insert_logs([log(5, "first")]);
+    let store = CachedStore::new(memory.clone(), CacheConfig::default());
+
+    assert_eq!(store.search_logs(&search(0, 60)).await.unwrap().len(), 1);
+    // ts(0) is 20s into a minute; both ranges snap to the same 10s buckets
+    memory.insert_logs([log(6, "second")]);
+    assert_eq!(store.search_logs(&search(1, 59)).await.unwrap().len(), 1);
+
+    let stats = store.cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+
+    // Other filters are a different entry
+    let mut params = search(0, 60);
+    params.text_query = Some("second".to_string());
+    assert_eq!(store.search_logs(&params).await.unwrap().len(), 1);
+    assert_eq!(store.cache_stats().unwrap().misses, 2);
+}
+
+#[tokio::test]
+async fn test_cache_keeps_requested_range() {
+    let memory = Arc::new(MemoryStore::new());
+    memory.insert_logs([log(2, "early"), log(5, "inside")]);
+    let store = CachedStore::new(memory, CacheConfig::default());
+
+    // The backend is asked for the requested range, not the snapped one
+    assert_eq!(
+        store.count_logs(&search(3, 60).time_range).await.unwrap(),
+        1
+    );
+    let bodies = |logs: Vec<LogEntry>| logs.into_iter().map(|l| l.body).collect::<Vec<_>>();
+    assert_eq!(
+        bodies(store.search_logs(&search(0, 60)).await.unwrap()),
+        ["inside", "early"]
+    );
+
+    // A shared entry is trimmed to the range of the hit
+    assert_eq!(
+        bodies(store.search_logs(&search(3, 60)).await.unwrap()),
+        ["inside"]
+    );
+    assert_eq!(store.cache_stats().unwrap().hits, 1);
+}
+
+#[tokio::test]
+async fn test_cache_size_limits() {
+    let memory = Arc::new(MemoryStore::new());
+    memory.insert_logs([log(5, "a"), log(6, "b")]);
+    let config = CacheConfig {
+        max_entries: 1,
+        max_rows: 1,
+        ..CacheConfig::default()
+    };
+    let store = CachedStore::new(memory, config);
+
+    // Too many rows to keep
+    store.search_logs(&search(0, 60)).await.unwrap();
+    assert_eq!(store.cache_stats().unwrap().entries, 0);
+
+    // The second entry evicts the first
+    store.count_logs(&search(0, 60).time_range).await.unwrap();
+    store.count_logs(&search(0, 600).time_range).await.unwrap();
+    let stats = store.cache_stats().unwrap();
+    assert_eq!((stats.entries, stats.evictions), (1, 1));
+}
@@ -1,25 +1,77 @@
 //! ClickHouse client wrapper for Archives
 
 use crate::{
-    config::ClickHouseConfig,
+    config::{ClickHouseConfig, LimitsConfig},
+    cursor::{log_id, LogCursor},
     error::{Error, Result},
-    types::{LogEntry, LogSeverity, Pagination, TimeRange},
+    filter::{AttributeFilter, AttributeOp, AttributeScope},
+    histogram::{self, ExponentialHistogram},
+    migrations::{AppliedMigration, Migration, MIGRATIONS_TABLE},
+    pattern, retention,
+    rollup::{self, Rollup},
+    schema::{Column, LogSchema},
+    store::{
+        DatabaseStats, ErrorPattern, ErrorSummary, HistogramSeries, LabelKey, LabelQueryParams,
+        LabelValue, LogSearchParams, LogStore, LogStream, LogVolumeBucket, MetricCatalogParams,
+        MetricDataPoint, MetricInfo, MetricQueryParams, MetricSeries, MetricStore, ServiceInfo,
+        SharedStore, Store, SummarySeries, SERVICE_RESOURCE_KEYS,
+    },
+    tenant::{TenantScope, TENANT_TABLES},
+    types::{
+        Aggregation, LogEntry, LogSeverity, MetricType, QuantileValue, Temporality, TimeRange,
+    },
+};
+use async_trait::async_trait;
+use clickhouse::{query::Query, Client, Row};
+use futures::{stream, StreamExt};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client as HyperClient},
+    rt::TokioExecutor,
 };
-use clickhouse::{Client, Row};
 use serde::Deserialize;
-use tracing::{debug, instrument};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, instrument};
+use uuid::Uuid;
+
+/// How long the rollup coverage looked up for `query_metrics` is reused
+const ROLLUP_COVERAGE_REFRESH: Duration = Duration::from_secs(300);
+
+/// When the rollup coverage was looked up, and the first bucket found
+type CoverageLookup = (Instant, Option<chrono::DateTime<chrono::Utc>>);
 
 /// ClickHouse client wrapper with connection pooling
 #[derive(Clone)]
 pub struct ClickHouseClient {
     client: Client,
     database: String,
+    /// First whole hour `error_summary` counts, once looked up; `None`
+    /// without the view
+    error_summary_start: OnceLock<Option<chrono::DateTime<chrono::Utc>>>,
+    /// See [`Self::rollup_coverage`]
+    rollup_coverage: Arc<Mutex<Option<CoverageLookup>>>,
+    /// Layout of `otel_logs`, see [`Self::detect_schema`]
+    schema: LogSchema,
+    /// Tenant every query is restricted to, see [`Self::with_tenant`]
+    tenant: Option<TenantScope>,
 }
 
 impl ClickHouseClient {
     /// Create a new ClickHouse client from configuration
     pub fn new(config: &ClickHouseConfig) -> Result<Self> {
-        let mut client = Client::default().with_url(&config.url);
+        let mut connector = HttpConnector::new();
+        connector.set_keepalive(Some(Duration::from_secs(60)));
+        // Keep up to `pool_size` idle connections; ClickHouse closes idle
+        // HTTP connections after 3s, so drop them a little earlier
+        let http = HyperClient::builder(TokioExecutor::new())
+            .pool_max_idle_per_host(config.pool_size as usize)
+            .pool_idle_timeout(Duration::from_secs(2))
+            .build(connector);
+        let mut client = Client::with_http_client(http).with_url(&config.url);
 
         if let Some(ref username) = config.username {
             client = client.with_user(username);
@@ -34,333 +86,2291 @@ impl ClickHouseClient {
         Ok(Self {
             client,
             database: config.database.clone(),
+            error_summary_start: OnceLock::new(),
+            rollup_coverage: Arc::new(Mutex::new(None)),
+            schema: LogSchema::default(),
+            tenant: None,
         })
     }
 
-    /// Check if ClickHouse is reachable
-    #[instrument(skip(self))]
-    pub async fn health_check(&self) -> Result<bool> {
-        self.client
-            .query("SELECT 1")
-            .fetch_one::<u8>()
-            .await
-            .map(|_| true)
-            .map_err(|e| Error::ClickHouseConnection(e.to_string()))
-    }
-
-    /// Get database statistics
-    #[instrument(skip(self))]
-    pub async fn get_stats(&self) -> Result<DatabaseStats> {
+    /// Read the columns of the exporter tables from `system.columns` and
+    /// build queries for the detected layout. Fails on layouts that cannot be
+    /// read; until called, the default layout is assumed.
+    pub async fn detect_schema(mut self) -> Result<Self> {
         #[derive(Row, Deserialize)]
-        struct TableStats {
+        struct ColumnRow {
             table: String,
-            rows: u64,
-            bytes: u64,
+            name: String,
+            type_name: String,
         }
 
-        let stats: Vec<TableStats> = self
+        let rows: Vec<ColumnRow> = self
             .client
             .query(
-                r#"
-                SELECT
-                    table,
-                    sum(rows) as rows,
-                    sum(bytes) as bytes
-                FROM system.parts
-                WHERE database = ? AND active = 1
-                GROUP BY table
-                "#,
+                "SELECT table, name, type as type_name FROM system.columns \
+                 WHERE database = ? AND (table = 'otel_logs' OR startsWith(table, 'otel_metrics_'))",
             )
             .bind(&self.database)
             .fetch_all()
             .await
-            .map_err(|e| Error::ClickHouseQuery(e.to_string()))?;
+            .map_err(query_error)?;
+        let columns: Vec<Column> = rows
+            .into_iter()
+            .map(|row| Column {
+                table: row.table,
+                name: row.name,
+                type_name: row.type_name,
+            })
+            .collect();
 
-        let mut db_stats = DatabaseStats::default();
-        for stat in stats {
-            match stat.table.as_str() {
-                "otel_logs" => {
-                    db_stats.log_count = stat.rows;
-                    db_stats.log_bytes = stat.bytes;
-                }
-                t if t.starts_with("otel_metrics") => {
-                    db_stats.metric_count += stat.rows;
-                    db_stats.metric_bytes += stat.bytes;
-                }
-                _ => {}
-            }
-        }
+        self.schema = LogSchema::detect(&columns)?;
+        info!(schema = ?self.schema, "Detected otel_logs layout");
+        Ok(self)
+    }
 
-        Ok(db_stats)
+    /// Build queries for `schema` instead of detecting it
+    #[must_use]
+    pub const fn with_schema(mut self, schema: LogSchema) -> Self {
+        self.schema = schema;
+        self
     }
 
-    /// Search logs with filters
-    #[instrument(skip(self))]
-    pub async fn search_logs(&self, params: &LogSearchParams) -> Result<Vec<LogEntry>> {
-        let mut query = String::from(
-            r#"
-            SELECT
-                generateUUIDv4() as id,
-                Timestamp as timestamp,
-                ObservedTimestamp as observed_timestamp,
-                TraceId as trace_id,
-                SpanId as span_id,
-                SeverityNumber as severity_number,
-                SeverityText as severity_text,
-                Body as body,
-                ResourceAttributes as resource_attributes,
-                LogAttributes as log_attributes,
-                ServiceName as service_name
-            FROM otel_logs
-            WHERE Timestamp >= ? AND Timestamp < ?
-            "#,
-        );
+    /// Detected layout of `otel_logs`
+    pub const fn schema(&self) -> &LogSchema {
+        &self.schema
+    }
 
-        // Add severity filter
-        if let Some(min_severity) = &params.min_severity {
-            query.push_str(&format!(
-                " AND SeverityNumber >= {}",
-                min_severity.to_severity_number()
-            ));
-        }
+    /// Names of the tables and views in the configured database
+    pub async fn table_names(&self) -> Result<Vec<String>> {
+        self.client
+            .query("SELECT name FROM system.tables WHERE database = ?")
+            .bind(&self.database)
+            .fetch_all()
+            .await
+            .map_err(query_error)
+    }
 
-        // Add text search
-        if let Some(ref text) = params.text_query {
-            query.push_str(" AND Body ILIKE ?");
+    /// TTL expression of each exporter table, empty for tables without one
+    pub async fn table_ttls(&self) -> Result<BTreeMap<String, String>> {
+        #[derive(Row, Deserialize)]
+        struct TableRow {
+            name: String,
+            engine_full: String,
         }
 
-        // Add service filter
-        if let Some(ref service) = params.service_name {
-            query.push_str(" AND ServiceName = ?");
-        }
+        let rows: Vec<TableRow> = self
+            .client
+            .query(
+                "SELECT name, engine_full FROM system.tables \
+                 WHERE database = ? AND (name = 'otel_logs' OR startsWith(name, 'otel_metrics_') \
+                 OR startsWith(name, 'metrics_rollup_')) AND engine != 'MaterializedView'",
+            )
+            .bind(&self.database)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let ttl = retention::ttl_clause(&row.engine_full).unwrap_or_default();
+                (row.name, ttl)
+            })
+            .collect())
+    }
 
-        query.push_str(" ORDER BY Timestamp DESC");
-        query.push_str(&format!(
-            " LIMIT {} OFFSET {}",
-            params.pagination.limit, params.pagination.offset
-        ));
+    /// Replace the TTL of `table`; existing parts are rewritten in the
+    /// background
+    pub async fn set_ttl(&self, table: &str, ttl: &str) -> Result<()> {
+        // The expression holds no placeholders; escape literal `?`
+        self.client
+            .query(&format!(
+                "ALTER TABLE {table} MODIFY TTL {}",
+                ttl.replace('?', "??")
+            ))
+            .execute()
+            .await
+            .map_err(query_error)
+    }
 
-        let mut q = self
+    /// First bucket of the metric rollups, or `None` while they are empty
+    pub async fn rollup_start(&self) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let start: u32 = self
             .client
-            .query(&query)
-            .bind(params.time_range.start)
-            .bind(params.time_range.end);
-
-        if let Some(ref text) = params.text_query {
-            q = q.bind(format!("%{}%", text));
-        }
+            .query(&format!(
+                "SELECT toUnixTimestamp(min(Bucket)) FROM {}",
+                Rollup::Minute.table_name()
+            ))
+            .fetch_one()
+            .await
+            .map_err(query_error)?;
+        Ok((start > 0)
+            .then(|| chrono::DateTime::from_timestamp(i64::from(start), 0))
+            .flatten())
+    }
 
-        if let Some(ref service) = params.service_name {
-            q = q.bind(service);
+    /// Steps recorded by [`rollup::backfill`], see [`rollup::BACKFILLS_TABLE`]
+    pub async fn rollup_backfills(&self) -> Result<Vec<rollup::BackfillStep>> {
+        #[derive(Row, Deserialize)]
+        struct BackfillRow {
+            rollup: String,
+            source: String,
+            until: u32,
+            done: u8,
         }
 
-        #[derive(Row, Deserialize)]
-        struct LogRow {
-            id: uuid::Uuid,
-            timestamp: time::OffsetDateTime,
-            observed_timestamp: time::OffsetDateTime,
-            trace_id: String,
-            span_id: String,
-            severity_number: i32,
-            severity_text: String,
-            body: String,
-            resource_attributes: String,
-            log_attributes: String,
-            service_name: String,
-        }
-
-        let rows: Vec<LogRow> = q
+        let rows: Vec<BackfillRow> = self
+            .client
+            .query(&format!(
+                "SELECT Rollup as rollup, Source as source, \
+                 toUnixTimestamp(Until) as until, Done as done \
+                 FROM {} FINAL ORDER BY rollup, source",
+                rollup::BACKFILLS_TABLE
+            ))
             .fetch_all()
             .await
-            .map_err(|e| Error::ClickHouseQuery(e.to_string()))?;
-
-        let entries: Vec<LogEntry> = rows
+            .map_err(query_error)?;
+        Ok(rows
             .into_iter()
-            .map(|row| LogEntry {
-                id: row.id,
-                timestamp: chrono::DateTime::from_timestamp(
-                    row.timestamp.unix_timestamp(),
-                    row.timestamp.nanosecond(),
-                )
-                .unwrap_or_default(),
-                observed_timestamp: chrono::DateTime::from_timestamp(
-                    row.observed_timestamp.unix_timestamp(),
-                    row.observed_timestamp.nanosecond(),
-                )
-                .unwrap_or_default(),
-                trace_id: if row.trace_id.is_empty() {
-                    None
-                } else {
-                    Some(row.trace_id)
-                },
-                span_id: if row.span_id.is_empty() {
-                    None
-                } else {
-                    Some(row.span_id)
-                },
-                severity: LogSeverity::from_severity_number(row.severity_number),
-                severity_text: row.severity_text,
-                body: row.body,
-                resource_attributes: serde_json::from_str(&row.resource_attributes)
-                    .unwrap_or_default(),
-                log_attributes: serde_json::from_str(&row.log_attributes).unwrap_or_default(),
-                service_name: if row.service_name.is_empty() {
-                    None
-                } else {
-                    Some(row.service_name)
-                },
+            .filter_map(|row| {
+                Some(rollup::BackfillStep {
+                    rollup: Rollup::from_table_name(&row.rollup)?,
+                    source: row.source,
+                    until: chrono::DateTime::from_timestamp(i64::from(row.until), 0)?,
+                    done: row.done > 0,
+                })
             })
-            .collect();
-
-        debug!(count = entries.len(), "Found log entries");
-        Ok(entries)
+            .collect())
     }
 
-    /// Get log count for time range
-    #[instrument(skip(self))]
-    pub async fn count_logs(&self, time_range: &TimeRange) -> Result<u64> {
-        #[derive(Row, Deserialize)]
-        struct CountRow {
-            count: u64,
+    /// Record backfill steps, in one insert
+    pub async fn record_backfills(&self, steps: &[rollup::BackfillStep]) -> Result<()> {
+        if steps.is_empty() {
+            return Ok(());
         }
-
-        let row: CountRow = self
-            .client
-            .query("SELECT count() as count FROM otel_logs WHERE Timestamp >= ? AND Timestamp < ?")
-            .bind(time_range.start)
-            .bind(time_range.end)
-            .fetch_one()
+        let mut binds = Vec::new();
+        for step in steps {
+            binds.push(Param::Str(step.rollup.table_name().to_string()));
+            binds.push(Param::Str(step.source.clone()));
+            binds.push(Param::Time(step.until));
+            binds.push(Param::U64(u64::from(step.done)));
+        }
+        let query = format!(
+            "INSERT INTO {} (Rollup, Source, Until, Done) VALUES {}",
+            rollup::BACKFILLS_TABLE,
+            vec!["(?, ?, ?, ?)"; steps.len()].join(", ")
+        );
+        bind_all(self.client.query(&query), binds)
+            .execute()
             .await
-            .map_err(|e| Error::ClickHouseQuery(e.to_string()))?;
+            .map_err(query_error)
+    }
 
-        Ok(row.count)
+    /// Aggregate the points of one backfill step into its rollup, then
+    /// record the step as done
+    pub async fn backfill_rollup(&self, step: &rollup::BackfillStep) -> Result<()> {
+        info!(
+            table = step.rollup.table_name(),
+            source = %step.source,
+            until = %step.until,
+            "Backfilling rollup"
+        );
+        bind_all(
+            self.client.query(&step.rollup.backfill_query(&step.source)),
+            vec![Param::Time(step.until)],
+        )
+        .execute()
+        .await
+        .map_err(query_error)?;
+        self.record_backfills(&[rollup::BackfillStep {
+            done: true,
+            ..step.clone()
+        }])
+        .await
     }
 
-    /// List available metric names
-    #[instrument(skip(self))]
-    pub async fn list_metric_names(&self) -> Result<Vec<String>> {
+    /// Rows of `archives_migrations`, or none before the first migration
+    pub async fn applied_migrations(&self) -> Result<Vec<AppliedMigration>> {
         #[derive(Row, Deserialize)]
-        struct NameRow {
+        struct MigrationRow {
+            version: u32,
             name: String,
+            checksum: u64,
+            applied_at_ms: i64,
         }
 
-        let rows: Vec<NameRow> = self
+        if !self
+            .table_names()
+            .await?
+            .iter()
+            .any(|t| t == MIGRATIONS_TABLE)
+        {
+            return Ok(Vec::new());
+        }
+        let rows: Vec<MigrationRow> = self
             .client
-            .query("SELECT DISTINCT MetricName as name FROM otel_metrics_gauge ORDER BY name")
+            .query(&format!(
+                "SELECT version, name, checksum, toUnixTimestamp64Milli(applied_at) as applied_at_ms \
+                 FROM {MIGRATIONS_TABLE} FINAL ORDER BY version"
+            ))
             .fetch_all()
             .await
-            .map_err(|e| Error::ClickHouseQuery(e.to_string()))?;
+            .map_err(query_error)?;
+        Ok(rows
+            .into_iter()
+            .map(|row| AppliedMigration {
+                version: row.version,
+                name: row.name,
+                checksum: row.checksum,
+                applied_at: chrono::DateTime::from_timestamp_millis(row.applied_at_ms)
+                    .unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// Run the statements of `migration` and record it as applied
+    pub async fn apply_migration(&self, migration: &Migration) -> Result<()> {
+        self.client
+            .query(&format!(
+                "CREATE TABLE IF NOT EXISTS {MIGRATIONS_TABLE} (\
+                 version UInt32, name String, checksum UInt64, \
+                 applied_at DateTime64(3, 'UTC') DEFAULT now64(3)\
+                 ) ENGINE = ReplacingMergeTree ORDER BY version"
+            ))
+            .execute()
+            .await
+            .map_err(query_error)?;
 
-        Ok(rows.into_iter().map(|r| r.name).collect())
+        info!(
+            version = migration.version,
+            name = migration.name,
+            "Applying migration"
+        );
+        for statement in migration.statements() {
+            // Statements are DDL without placeholders; escape literal `?`
+            self.client
+                .query(&statement.replace('?', "??"))
+                .execute()
+                .await
+                .map_err(query_error)?;
+        }
+
+        self.client
+            .query(&format!(
+                "INSERT INTO {MIGRATIONS_TABLE} (version, name, checksum) VALUES (?, ?, ?)"
+            ))
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(migration.checksum())
+            .execute()
+            .await
+            .map_err(query_error)
     }
 
-    /// Query metrics with aggregation
-    #[instrument(skip(self))]
-    pub async fn query_metrics(&self, params: &MetricQueryParams) -> Result<Vec<MetricDataPoint>> {
-        let agg_fn = match params.aggregation {
-            crate::types::Aggregation::Avg => "avg(Value)",
-            crate::types::Aggregation::Min => "min(Value)",
-            crate::types::Aggregation::Max => "max(Value)",
-            crate::types::Aggregation::Sum => "sum(Value)",
-            crate::types::Aggregation::Count => "count()",
-            crate::types::Aggregation::P50 => "quantile(0.5)(Value)",
-            crate::types::Aggregation::P90 => "quantile(0.9)(Value)",
-            crate::types::Aggregation::P99 => "quantile(0.99)(Value)",
+    /// Columns selected for a [`LogRow`]
+    fn log_columns(&self) -> String {
+        let observed = if self.schema.observed_timestamp {
+            "ObservedTimestamp"
+        } else {
+            "Timestamp"
         };
+        format!(
+            "Timestamp as timestamp, \
+             {observed} as observed_timestamp, \
+             TraceId as trace_id, \
+             SpanId as span_id, \
+             toInt32(SeverityNumber) as severity_number, \
+             SeverityText as severity_text, \
+             Body as body, \
+             {} as resource_attributes, \
+             {} as log_attributes, \
+             ServiceName as service_name",
+            self.schema.resource_map(),
+            self.schema.log_map()
+        )
+    }
 
-        let interval_seconds = params.interval_seconds.unwrap_or(60);
+    /// Apply the ClickHouse settings of `limits` to every query
+    #[must_use]
+    pub fn with_limits(mut self, limits: &LimitsConfig) -> Self {
+        if limits.max_execution_time_secs > 0 {
+            self.client = self.client.with_option(
+                "max_execution_time",
+                limits.max_execution_time_secs.to_string(),
+            );
+        }
+        if limits.max_rows_to_read > 0 {
+            self.client = self
+                .client
+                .with_option("max_rows_to_read", limits.max_rows_to_read.to_string());
+        }
+        if limits.readonly {
+            self.client = self.client.with_option("readonly", "1");
+        }
+        self
+    }
 
-        let query = format!(
-            r#"
-            SELECT
-                toStartOfInterval(TimeUnix, INTERVAL {} SECOND) as bucket,
-                {} as value
-            FROM otel_metrics_gauge
-            WHERE MetricName = ?
-              AND TimeUnix >= ?
-              AND TimeUnix < ?
-            GROUP BY bucket
-            ORDER BY bucket
-            "#,
-            interval_seconds, agg_fn
-        );
+    /// Restrict every query to the rows of one tenant. The predicate is set
+    /// through `additional_table_filters`, so ClickHouse applies it to each
+    /// read of the exporter and rollup tables, including subqueries, and
+    /// `error_summary`, which counts all tenants together, reads as
+    /// empty. Call after [`Self::detect_schema`], since the log predicate
+    /// depends on the layout. Fails if the client is scoped to another
+    /// tenant already.
+    pub fn with_tenant(mut self, scope: &TenantScope) -> Result<Self> {
+        if let Some(ref current) = self.tenant {
+            if current != scope {
+                return Err(Error::Forbidden(format!(
+                    "store is restricted to tenant {:?}",
+                    current.tenant()
+                )));
+            }
+            return Ok(self);
+        }
+        let filters = scope.table_filters(&self.schema.resource_map());
+        self.client = self.client.with_option("additional_table_filters", filters);
+        self.tenant = Some(scope.clone());
+        Ok(self)
+    }
 
-        #[derive(Row, Deserialize)]
-        struct MetricRow {
-            bucket: time::OffsetDateTime,
-            value: f64,
+    /// Tenant the client is restricted to
+    pub const fn tenant(&self) -> Option<&TenantScope> {
+        self.tenant.as_ref()
+    }
+
+    /// Row and byte counts of one tenant. `system.parts` counts every
+    /// tenant, so rows are counted through the tenant filter and bytes
+    /// estimated from the tenant's share of each table's rows.
+    async fn tenant_stats(&self, tables: &[(String, u64, u64)]) -> Result<DatabaseStats> {
+        let mut stats = DatabaseStats::default();
+        for (table, rows, bytes) in tables {
+            // Only tables under the tenant filter, and not the rollups
+            let is_logs = table == "otel_logs";
+            if !TENANT_TABLES.contains(&table.as_str())
+                || !(is_logs || table.starts_with("otel_metrics"))
+            {
+                continue;
+            }
+            let count: u64 = self
+                .client
+                .query(&format!("SELECT count() FROM {table}"))
+                .fetch_one()
+                .await
+                .map_err(query_error)?;
+            let share = if *rows == 0 {
+                0
+            } else {
+                let estimate = u128::from(*bytes) * u128::from(count) / u128::from(*rows);
+                u64::try_from(estimate).unwrap_or(*bytes)
+            };
+            if is_logs {
+                stats.log_count = count;
+                stats.log_bytes = share;
+            } else {
+                stats.metric_count += count;
+                stats.metric_bytes += share;
+            }
         }
+        Ok(stats)
+    }
+}
 
-        let rows: Vec<MetricRow> = self
-            .client
-            .query(&query)
-            .bind(&params.metric_name)
-            .bind(params.time_range.start)
-            .bind(params.time_range.end)
-            .fetch_all()
-            .await
-            .map_err(|e| Error::ClickHouseQuery(e.to_string()))?;
+/// Tiebreaker among rows with equal timestamps, see [`crate::cursor::log_row_hash`]
+pub(crate) const ROW_HASH: &str =
+    "xxHash64(concat(ServiceName, char(0), TraceId, char(0), SpanId, char(0), Body))";
+
+/// Per-point increases of `otel_metrics_sum` rows matching `filter`.
+///
+/// Each series (distinct attribute set) is differenced against its previous
+/// point. Delta temporality (1) points are increases already. For monotonic
+/// cumulative sums, a new start time or a drop in value is a counter reset, so
+/// the new value is the increase; non-monotonic sums keep signed differences.
+/// The first point of a series has no predecessor and contributes nothing.
+pub(crate) fn counter_increases(filter: &str) -> String {
+    format!(
+        r#"
+        SELECT
+            TimeUnix,
+            Attributes,
+            ResourceAttributes,
+            multiIf(
+                AggregationTemporality = 1, Value,
+                NOT IsMonotonic, Value - PrevValue,
+                StartTimeUnix != PrevStart OR Value < PrevValue, Value,
+                Value - PrevValue
+            ) as Increase
+        FROM (
+            SELECT
+                TimeUnix,
+                StartTimeUnix,
+                Value,
+                AggregationTemporality,
+                IsMonotonic,
+                Attributes,
+                ResourceAttributes,
+                lagInFrame(Value, 1, Value) OVER series as PrevValue,
+                lagInFrame(StartTimeUnix, 1, StartTimeUnix) OVER series as PrevStart
+            FROM otel_metrics_sum
+            WHERE {filter}
+            WINDOW series AS (
+                PARTITION BY toString(ResourceAttributes), toString(Attributes)
+                ORDER BY TimeUnix
+                ROWS BETWEEN 1 PRECEDING AND CURRENT ROW
+            )
+        )
+        "#
+    )
+}
+
+/// Per-point bucket increases of `otel_metrics_histogram` rows matching
+/// `filter`, mirroring [`crate::histogram::bucket_increase`].
+///
+/// Delta temporality (1) points are increases already. Cumulative points are
+/// differenced per series; a new start time, a lower total count or a changed
+/// bucket layout is a reset, so the new counts are the increase.
+pub(crate) fn histogram_increases(filter: &str) -> String {
+    format!(
+        r#"
+        SELECT
+            TimeUnix,
+            Attributes,
+            ResourceAttributes,
+            ExplicitBounds,
+            multiIf(
+                AggregationTemporality = 1, BucketCounts,
+                StartTimeUnix != PrevStart OR Count < PrevCount
+                    OR ExplicitBounds != PrevBounds
+                    OR length(BucketCounts) != length(PrevCounts), BucketCounts,
+                arrayMap((c, p) -> if(c > p, c - p, 0), BucketCounts, PrevCounts)
+            ) as Increase
+        FROM (
+            SELECT
+                TimeUnix,
+                StartTimeUnix,
+                Count,
+                BucketCounts,
+                ExplicitBounds,
+                AggregationTemporality,
+                Attributes,
+                ResourceAttributes,
+                lagInFrame(BucketCounts, 1, BucketCounts) OVER series as PrevCounts,
+                lagInFrame(ExplicitBounds, 1, ExplicitBounds) OVER series as PrevBounds,
+                lagInFrame(Count, 1, Count) OVER series as PrevCount,
+                lagInFrame(StartTimeUnix, 1, StartTimeUnix) OVER series as PrevStart
+            FROM otel_metrics_histogram
+            WHERE {filter}
+            WINDOW series AS (
+                PARTITION BY toString(ResourceAttributes), toString(Attributes)
+                ORDER BY TimeUnix
+                ROWS BETWEEN 1 PRECEDING AND CURRENT ROW
+            )
+        )
+        "#
+    )
+}
+
+#[derive(Row, Deserialize)]
+struct LogRow {
+    timestamp: time::OffsetDateTime,
+    observed_timestamp: time::OffsetDateTime,
+    trace_id: String,
+    span_id: String,
+    severity_number: i32,
+    severity_text: String,
+    body: String,
+    resource_attributes: Vec<(String, String)>,
+    log_attributes: Vec<(String, String)>,
+    service_name: String,
+}
+
+/// Log count of one bucket and severity, see `count_logs_over_time`
+#[derive(Row, Deserialize)]
+struct VolumeRow {
+    bucket: time::OffsetDateTime,
+    severity_number: i32,
+    count: u64,
+}
+
+/// Error template with its counts, see `error_summary`
+#[derive(Row, Deserialize)]
+struct PatternRow {
+    pattern: String,
+    count: u64,
+    samples: Vec<String>,
+    first_seen: time::OffsetDateTime,
+    last_seen: time::OffsetDateTime,
+    services: Vec<(String, u64)>,
+}
+
+/// Error count of one service, see `error_summary`
+#[derive(Row, Deserialize)]
+struct ServiceErrorsRow {
+    service: String,
+    count: u64,
+}
+
+/// Aggregated value of one group and bucket, see `query_metrics`
+#[derive(Row, Deserialize)]
+struct MetricRow {
+    group_values: Vec<String>,
+    bucket: time::OffsetDateTime,
+    value: f64,
+}
+
+/// Merged bucket counts of one group and bucket, see `query_histogram`
+#[derive(Row, Deserialize)]
+struct HistogramRow {
+    group_values: Vec<String>,
+    bucket: time::OffsetDateTime,
+    bounds: Vec<f64>,
+    counts: Vec<u64>,
+}
+
+/// Catalog entry of one metric name and type, see `metric_catalog`
+#[derive(Row, Deserialize)]
+struct CatalogRow {
+    name: String,
+    kind: u8,
+    unit: String,
+    description: String,
+    services: Vec<String>,
+    series_count: u64,
+    last_seen: time::OffsetDateTime,
+}
+
+/// Summary data point, see `query_summary`
+#[derive(Row, Deserialize)]
+struct SummaryRow {
+    group_values: Vec<String>,
+    series: u64,
+    reset: u8,
+    bucket: time::OffsetDateTime,
+    in_range: u8,
+    count: u64,
+    sum: f64,
+    levels: Vec<f64>,
+    values: Vec<f64>,
+}
+
+/// Attribute key with its counts, see `fetch_label_keys`
+#[derive(Row, Deserialize)]
+struct KeyRow {
+    key: String,
+    resource: u8,
+    cardinality: u64,
+    count: u64,
+}
+
+/// Attribute value with its count, see `fetch_label_values`
+#[derive(Row, Deserialize)]
+struct ValueRow {
+    value: String,
+    count: u64,
+}
+
+/// Exponential histogram data point, see `query_exponential_histogram`
+#[derive(Row, Deserialize)]
+struct ExponentialRow {
+    group_values: Vec<String>,
+    series: u64,
+    reset: u8,
+    bucket: time::OffsetDateTime,
+    in_range: u8,
+    temporality: i32,
+    scale: i32,
+    zero_count: u64,
+    positive_offset: i32,
+    positive_counts: Vec<u64>,
+    negative_offset: i32,
+    negative_counts: Vec<u64>,
+}
+
+/// Bucket, count and sum increases and quantiles of a summary group
+type SummarySamples = Vec<(chrono::DateTime<chrono::Utc>, u64, f64, Vec<QuantileValue>)>;
 
-        let points = rows
+/// Bucket and increase of each point of an exponential histogram group
+type ExponentialSamples = Vec<(chrono::DateTime<chrono::Utc>, ExponentialHistogram)>;
+
+/// JSON object of the entries of a `Map(String, String)` column
+fn attribute_object(entries: Vec<(String, String)>) -> serde_json::Value {
+    serde_json::Value::Object(
+        entries
             .into_iter()
-            .map(|row| MetricDataPoint {
-                timestamp: chrono::DateTime::from_timestamp(
-                    row.bucket.unix_timestamp(),
-                    row.bucket.nanosecond(),
-                )
-                .unwrap_or_default(),
-                value: row.value,
-            })
-            .collect();
+            .map(|(key, value)| (key, serde_json::Value::String(value)))
+            .collect(),
+    )
+}
+
+impl From<LogRow> for LogEntry {
+    fn from(row: LogRow) -> Self {
+        let mut entry = Self {
+            id: Uuid::nil(),
+            timestamp: chrono::DateTime::from_timestamp(
+                row.timestamp.unix_timestamp(),
+                row.timestamp.nanosecond(),
+            )
+            .unwrap_or_default(),
+            observed_timestamp: chrono::DateTime::from_timestamp(
+                row.observed_timestamp.unix_timestamp(),
+                row.observed_timestamp.nanosecond(),
+            )
+            .unwrap_or_default(),
+            trace_id: if row.trace_id.is_empty() {
+                None
+            } else {
+                Some(row.trace_id)
+            },
+            span_id: if row.span_id.is_empty() {
+                None
+            } else {
+                Some(row.span_id)
+            },
+            severity: LogSeverity::from_severity_number(row.severity_number),
+            severity_text: row.severity_text,
+            body: row.body,
+            resource_attributes: attribute_object(row.resource_attributes),
+            log_attributes: attribute_object(row.log_attributes),
+            service_name: if row.service_name.is_empty() {
+                None
+            } else {
+                Some(row.service_name)
+            },
+        };
+        entry.id = log_id(&entry);
+        entry
+    }
+}
 
-        Ok(points)
+/// ClickHouse error codes worth retrying: too many simultaneous queries,
+/// socket timeout and network error
+const TRANSIENT_CODES: [&str; 3] = ["Code: 202.", "Code: 209.", "Code: 210."];
+
+/// Map a client error to [`Error::ClickHouseConnection`] when retrying may
+/// succeed, and to [`Error::ClickHouseQuery`] otherwise. Takes the error by
+/// value so it can be passed to `map_err` directly.
+#[allow(clippy::needless_pass_by_value)]
+fn query_error(error: clickhouse::error::Error) -> Error {
+    use clickhouse::error::Error as ClientError;
+    match &error {
+        ClientError::Network(_) | ClientError::TimedOut => {
+            Error::ClickHouseConnection(error.to_string())
+        }
+        ClientError::BadResponse(message)
+            if TRANSIENT_CODES.iter().any(|code| message.contains(code)) =>
+        {
+            Error::ClickHouseConnection(error.to_string())
+        }
+        _ => Error::ClickHouseQuery(error.to_string()),
     }
 }
 
-/// Database statistics
-#[derive(Debug, Default, Clone, serde::Serialize)]
-pub struct DatabaseStats {
-    pub log_count: u64,
-    pub log_bytes: u64,
-    pub metric_count: u64,
-    pub metric_bytes: u64,
+/// A bound query parameter, applied in placeholder order
+#[derive(Debug, PartialEq)]
+pub(crate) enum Param {
+    Str(String),
+    F64(f64),
+    I64(i64),
+    U64(u64),
+    Time(chrono::DateTime<chrono::Utc>),
 }
 
-/// Parameters for log search
-#[derive(Debug, Clone)]
-pub struct LogSearchParams {
-    pub time_range: TimeRange,
-    pub min_severity: Option<LogSeverity>,
-    pub text_query: Option<String>,
-    pub service_name: Option<String>,
-    pub pagination: Pagination,
+fn bind_all(mut query: Query, binds: Vec<Param>) -> Query {
+    for bind in binds {
+        query = match bind {
+            Param::Str(s) => query.bind(s),
+            Param::F64(f) => query.bind(f),
+            Param::I64(i) => query.bind(i),
+            Param::U64(u) => query.bind(u),
+            Param::Time(t) => query.bind(t),
+        };
+    }
+    query
 }
 
-impl Default for LogSearchParams {
-    fn default() -> Self {
-        Self {
-            time_range: TimeRange::last_hours(1),
-            min_severity: None,
-            text_query: None,
-            service_name: None,
-            pagination: Pagination::default(),
+/// Build the full query for a log search, newest first
+pub(crate) fn log_search(
+    client: &ClickHouseClient,
+    params: &LogSearchParams,
+) -> Result<(String, Vec<Param>)> {
+    let mut query = format!("SELECT {} FROM otel_logs", client.log_columns());
+
+    let (where_clause, binds) = log_filter(params, &client.schema)?;
+    query.push_str(&where_clause);
+    let _ = write!(
+        query,
+        " ORDER BY Timestamp DESC, {ROW_HASH} DESC LIMIT {} OFFSET {}",
+        params.pagination.limit, params.pagination.offset
+    );
+    Ok((query, binds))
+}
+
+/// Build the `WHERE` clause for a log search
+pub(crate) fn log_filter(
+    params: &LogSearchParams,
+    schema: &LogSchema,
+) -> Result<(String, Vec<Param>)> {
+    params.validate()?;
+
+    let mut clause = String::from(" WHERE Timestamp >= ? AND Timestamp < ?");
+    let mut binds = vec![
+        Param::Time(params.time_range.start),
+        Param::Time(params.time_range.end),
+    ];
+
+    // Add severity filter
+    if let Some(min_severity) = &params.min_severity {
+        let _ = write!(
+            clause,
+            " AND SeverityNumber >= {}",
+            min_severity.to_severity_number()
+        );
+    }
+
+    // Add text search
+    if let Some(ref text) = params.text_query {
+        clause.push_str(" AND Body ILIKE ?");
+        binds.push(Param::Str(like_pattern(text)));
+    }
+
+    // Add service filter
+    if let Some(ref service) = params.service_name {
+        clause.push_str(" AND ServiceName = ?");
+        binds.push(Param::Str(service.clone()));
+    }
+
+    // Add trace correlation filters (IDs are stored as lowercase hex)
+    if let Some(ref trace_id) = params.trace_id {
+        clause.push_str(" AND TraceId = ?");
+        binds.push(Param::Str(trace_id.to_ascii_lowercase()));
+    }
+    if let Some(ref span_id) = params.span_id {
+        clause.push_str(" AND SpanId = ?");
+        binds.push(Param::Str(span_id.to_ascii_lowercase()));
+    }
+
+    // Add query language body terms
+    for term in &params.body_terms {
+        clause.push_str(" AND Body ILIKE ?");
+        binds.push(Param::Str(like_pattern(term)));
+    }
+    for term in &params.excluded_body_terms {
+        clause.push_str(" AND NOT (Body ILIKE ?)");
+        binds.push(Param::Str(like_pattern(term)));
+    }
+
+    // Resume after the cursor position
+    if let Some(cursor) = params.cursor {
+        let _ = write!(
+            clause,
+            " AND (Timestamp < fromUnixTimestamp64Nano(?) \
+             OR (Timestamp = fromUnixTimestamp64Nano(?) AND {ROW_HASH} < ?))"
+        );
+        binds.extend([
+            Param::I64(cursor.timestamp_nanos()),
+            Param::I64(cursor.timestamp_nanos()),
+            Param::U64(cursor.row_hash),
+        ]);
+    }
+
+    // Add attribute filters
+    let maps = AttributeMaps::logs(schema);
+    for filter in &params.attribute_filters {
+        let condition = attribute_condition(filter, &maps, &mut binds);
+        if filter.negate {
+            let _ = write!(clause, " AND NOT ({condition})");
+        } else {
+            let _ = write!(clause, " AND {condition}");
+        }
+    }
+
+    Ok((clause, binds))
+}
+
+/// Whether a `CREATE MATERIALIZED VIEW` statement writes to `error_summary`,
+/// as `error_summary_mv` does since migration 0002
+pub(crate) fn feeds_error_summary(create_query: &str) -> bool {
+    let mut tokens = create_query.split_whitespace();
+    while let Some(token) = tokens.next() {
+        if token.eq_ignore_ascii_case("TO") {
+            return tokens.next().is_some_and(|target| {
+                let target = target.split('(').next().unwrap_or_default();
+                let table = target.rsplit('.').next().unwrap_or_default();
+                table.trim_matches('`') == "error_summary"
+            });
+        }
+    }
+    false
+}
+
+/// Subquery of `(ServiceName, c)` log counts for an error summary. When the
+/// logs requested are exactly those `error_summary` counts (ERROR and above,
+/// at most filtered by service), whole hours from `summary_start` on are read
+/// from it and the rest from `otel_logs`.
+pub(crate) fn error_count_query(
+    params: &LogSearchParams,
+    schema: &LogSchema,
+    summary_start: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<(String, Vec<Param>)> {
+    let raw = |time_range: TimeRange| -> Result<(String, Vec<Param>)> {
+        let (where_clause, binds) = log_filter(
+            &LogSearchParams {
+                time_range,
+                ..params.clone()
+            },
+            schema,
+        )?;
+        Ok((
+            format!(
+                "SELECT ServiceName, count() as c FROM otel_logs{where_clause} \
+                 GROUP BY ServiceName"
+            ),
+            binds,
+        ))
+    };
+
+    let summarized = params.min_severity == Some(LogSeverity::Error)
+        && params.text_query.is_none()
+        && params.trace_id.is_none()
+        && params.span_id.is_none()
+        && params.body_terms.is_empty()
+        && params.excluded_body_terms.is_empty()
+        && params.attribute_filters.is_empty();
+    let Some(summary_start) = summary_start.filter(|_| summarized) else {
+        return raw(params.time_range.clone());
+    };
+    let TimeRange { start, end } = params.time_range;
+    let hour = 3600;
+    let first_hour =
+        (start.timestamp() + (-start.timestamp()).rem_euclid(hour)).max(summary_start.timestamp());
+    let last_hour = end.timestamp() - end.timestamp().rem_euclid(hour);
+    if first_hour >= last_hour {
+        return raw(params.time_range.clone());
+    }
+
+    let first_hour = chrono::DateTime::from_timestamp(first_hour, 0).unwrap_or(start);
+    let last_hour = chrono::DateTime::from_timestamp(last_hour, 0).unwrap_or(end);
+    let mut source = String::from(
+        "SELECT ServiceName, sum(Count) as c FROM error_summary \
+         WHERE Hour >= ? AND Hour < ?",
+    );
+    let mut binds = vec![Param::Time(first_hour), Param::Time(last_hour)];
+    if let Some(ref service) = params.service_name {
+        source.push_str(" AND ServiceName = ?");
+        binds.push(Param::Str(service.clone()));
+    }
+    source.push_str(" GROUP BY ServiceName");
+
+    for edge in [
+        TimeRange {
+            start,
+            end: first_hour,
+        },
+        TimeRange {
+            start: last_hour,
+            end,
+        },
+    ] {
+        if edge.start < edge.end {
+            let (edge_source, edge_binds) = raw(edge)?;
+            source = format!("{source} UNION ALL {edge_source}");
+            binds.extend(edge_binds);
         }
     }
+    Ok((source, binds))
+}
+
+/// SQL expression applying [`pattern::MASKS`] to `column`, like
+/// [`pattern::mask`]
+pub(crate) fn masked(column: &str, binds: &mut Vec<Param>) -> String {
+    let mut expression = column.to_string();
+    for (regex, placeholder) in pattern::MASKS {
+        expression = format!("replaceRegexpAll({expression}, ?, ?)");
+        binds.push(Param::Str(pattern::re2_pattern(regex)));
+        binds.push(Param::Str(placeholder.to_string()));
+    }
+    expression
+}
+
+/// `ILIKE` pattern matching `text` anywhere, with wildcards escaped
+pub(crate) fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
 }
 
-/// Parameters for metric query
-#[derive(Debug, Clone)]
-pub struct MetricQueryParams {
-    pub metric_name: String,
-    pub time_range: TimeRange,
-    pub aggregation: crate::types::Aggregation,
-    pub interval_seconds: Option<u32>,
-    pub labels: Option<std::collections::HashMap<String, String>>,
+/// Map expressions read by the `log` and `resource` attribute scopes
+pub(crate) struct AttributeMaps {
+    log: String,
+    resource: String,
 }
 
-/// A single metric data point in a time series
-#[derive(Debug, Clone, serde::Serialize)]
-pub struct MetricDataPoint {
-    pub timestamp: chrono::DateTime<chrono::Utc>,
-    pub value: f64,
+impl AttributeMaps {
+    /// `LogAttributes` and `ResourceAttributes` of `otel_logs`
+    pub(crate) fn logs(schema: &LogSchema) -> Self {
+        Self {
+            log: schema.log_map(),
+            resource: schema.resource_map(),
+        }
+    }
+
+    /// `Attributes` and `ResourceAttributes` of the metric tables
+    pub(crate) fn metrics() -> Self {
+        Self {
+            log: "Attributes".to_string(),
+            resource: "ResourceAttributes".to_string(),
+        }
+    }
+
+    /// Columns of [`log_label_source`] and [`metric_label_source`]
+    pub(crate) fn label_source() -> Self {
+        Self {
+            log: "log_attributes".to_string(),
+            resource: "resource_attributes".to_string(),
+        }
+    }
+}
+
+/// SQL condition for one attribute filter. A missing map key reads as `''`.
+pub(crate) fn attribute_condition(
+    filter: &AttributeFilter,
+    maps: &AttributeMaps,
+    binds: &mut Vec<Param>,
+) -> String {
+    let key = || Param::Str(filter.key.clone());
+    let AttributeMaps { log, resource } = maps;
+
+    if filter.op == AttributeOp::Exists {
+        return match filter.scope {
+            AttributeScope::Log => {
+                binds.push(key());
+                format!("mapContains({log}, ?)")
+            }
+            AttributeScope::Resource => {
+                binds.push(key());
+                format!("mapContains({resource}, ?)")
+            }
+            AttributeScope::Any => {
+                binds.extend([key(), key()]);
+                format!("(mapContains({log}, ?) OR mapContains({resource}, ?))")
+            }
+        };
+    }
+
+    let value = attribute_value(filter.scope, maps, &filter.key, binds);
+    let (condition, operand) = match filter.op {
+        AttributeOp::Eq(ref v) => (format!("{value} = ?"), Param::Str(v.clone())),
+        AttributeOp::Ne(ref v) => (format!("{value} != ?"), Param::Str(v.clone())),
+        AttributeOp::Prefix(ref v) => (format!("startsWith({value}, ?)"), Param::Str(v.clone())),
+        AttributeOp::Regex(ref v) => (format!("match({value}, ?)"), Param::Str(v.clone())),
+        AttributeOp::Gt(n) => (format!("toFloat64OrNull({value}) > ?"), Param::F64(n)),
+        AttributeOp::Gte(n) => (format!("toFloat64OrNull({value}) >= ?"), Param::F64(n)),
+        AttributeOp::Lt(n) => (format!("toFloat64OrNull({value}) < ?"), Param::F64(n)),
+        AttributeOp::Lte(n) => (format!("toFloat64OrNull({value}) <= ?"), Param::F64(n)),
+        AttributeOp::Exists => unreachable!("handled above"),
+    };
+    binds.push(operand);
+    condition
+}
+
+/// SQL expression reading `key` from the map(s) selected by `scope`
+pub(crate) fn attribute_value(
+    scope: AttributeScope,
+    maps: &AttributeMaps,
+    key: &str,
+    binds: &mut Vec<Param>,
+) -> String {
+    let key = || Param::Str(key.to_string());
+    let AttributeMaps { log, resource } = maps;
+    match scope {
+        AttributeScope::Log => {
+            binds.push(key());
+            format!("{log}[?]")
+        }
+        AttributeScope::Resource => {
+            binds.push(key());
+            format!("{resource}[?]")
+        }
+        AttributeScope::Any => {
+            binds.extend([key(), key(), key()]);
+            format!("if(mapContains({log}, ?), {log}[?], {resource}[?])")
+        }
+    }
+}
+
+#[async_trait]
+impl LogStore for ClickHouseClient {
+    /// Search logs with filters
+    #[instrument(skip(self))]
+    async fn search_logs(&self, params: &LogSearchParams) -> Result<Vec<LogEntry>> {
+        let (query, binds) = log_search(self, params)?;
+        let rows: Vec<LogRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        let entries: Vec<LogEntry> = rows.into_iter().map(LogEntry::from).collect();
+
+        debug!(count = entries.len(), "Found log entries");
+        Ok(entries)
+    }
+
+    /// Get log count for time range
+    #[instrument(skip(self))]
+    async fn count_logs(&self, time_range: &TimeRange) -> Result<u64> {
+        #[derive(Row, Deserialize)]
+        struct CountRow {
+            count: u64,
+        }
+
+        let row: CountRow = self
+            .client
+            .query("SELECT count() as count FROM otel_logs WHERE Timestamp >= ? AND Timestamp < ?")
+            .bind(time_range.start)
+            .bind(time_range.end)
+            .fetch_one()
+            .await
+            .map_err(query_error)?;
+
+        Ok(row.count)
+    }
+
+    /// Fetch a single log by decoding its ID into `(Timestamp, row hash)`
+    #[instrument(skip(self))]
+    async fn get_log(&self, id: Uuid) -> Result<Option<LogEntry>> {
+        let key = LogCursor::from_id(id);
+        let query = format!(
+            "SELECT {} FROM otel_logs \
+             WHERE Timestamp = fromUnixTimestamp64Nano(?) AND {ROW_HASH} = ? LIMIT 1",
+            self.log_columns()
+        );
+
+        let row: Option<LogRow> = self
+            .client
+            .query(&query)
+            .bind(key.timestamp_nanos())
+            .bind(key.row_hash)
+            .fetch_optional()
+            .await
+            .map_err(query_error)?;
+
+        Ok(row.map(LogEntry::from))
+    }
+
+    /// Count logs per time bucket and severity number
+    #[instrument(skip(self))]
+    async fn count_logs_over_time(
+        &self,
+        params: &LogSearchParams,
+        interval_seconds: u32,
+    ) -> Result<Vec<LogVolumeBucket>> {
+        LogVolumeBucket::validate_interval(&params.time_range, interval_seconds)?;
+        let params = LogSearchParams {
+            cursor: None,
+            ..params.clone()
+        };
+        let (where_clause, binds) = log_filter(&params, &self.schema)?;
+        let query = format!(
+            "SELECT toStartOfInterval(Timestamp, INTERVAL {interval_seconds} SECOND) as bucket, \
+             toInt32(SeverityNumber) as severity_number, count() as count \
+             FROM otel_logs{where_clause} GROUP BY bucket, severity_number"
+        );
+
+        let rows: Vec<VolumeRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        Ok(LogVolumeBucket::fill(
+            &params.time_range,
+            interval_seconds,
+            rows.into_iter().map(|row| {
+                (
+                    to_chrono(row.bucket),
+                    LogSeverity::from_severity_number(row.severity_number),
+                    row.count,
+                )
+            }),
+        ))
+    }
+
+    /// Count logs per template with the masks applied in SQL, so counts stay
+    /// exact however many logs match
+    #[instrument(skip(self))]
+    async fn error_summary(&self, params: &LogSearchParams) -> Result<ErrorSummary> {
+        let params = LogSearchParams {
+            cursor: None,
+            ..params.clone()
+        };
+        let (where_clause, filter_binds) = log_filter(&params, &self.schema)?;
+        let mut binds = Vec::new();
+        let template = masked("Body", &mut binds);
+        binds.extend(filter_binds);
+
+        let limit = params.pagination.limit;
+        let samples = ErrorPattern::MAX_SAMPLES;
+        let query = format!(
+            "SELECT pattern, sum(c) as count, \
+             arraySlice(arrayDistinct(arrayFlatten(groupArray(s))), 1, {samples}) as samples, \
+             min(first_ts) as first_seen, max(last_ts) as last_seen, \
+             groupArray((ServiceName, c)) as services \
+             FROM (SELECT {template} as pattern, ServiceName, count() as c, \
+             groupUniqArray({samples})(Body) as s, \
+             min(Timestamp) as first_ts, max(Timestamp) as last_ts \
+             FROM otel_logs{where_clause} GROUP BY pattern, ServiceName) \
+             GROUP BY pattern ORDER BY count DESC, pattern LIMIT {limit}"
+        );
+
+        let rows: Vec<PatternRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        let (source, binds) = self.error_count_source(&params).await?;
+        let totals: Vec<ServiceErrorsRow> = bind_all(
+            self.client.query(&format!(
+                "SELECT ServiceName as service, sum(c) as count FROM ({source}) \
+                 GROUP BY service"
+            )),
+            binds,
+        )
+        .fetch_all()
+        .await
+        .map_err(query_error)?;
+
+        let services: BTreeMap<String, u64> =
+            totals.into_iter().map(|r| (r.service, r.count)).collect();
+        Ok(ErrorSummary {
+            total: services.values().sum(),
+            services,
+            patterns: rows
+                .into_iter()
+                .map(|row| ErrorPattern {
+                    pattern: row.pattern,
+                    count: row.count,
+                    samples: row.samples,
+                    first_seen: to_chrono(row.first_seen),
+                    last_seen: to_chrono(row.last_seen),
+                    services: row.services.into_iter().collect(),
+                })
+                .collect(),
+        })
+    }
+
+    /// List log attribute keys with their cardinality
+    #[instrument(skip(self))]
+    async fn log_attribute_keys(&self, params: &LabelQueryParams) -> Result<Vec<LabelKey>> {
+        params.validate(None)?;
+        let mut binds = Vec::new();
+        let source = log_label_source(params, &self.schema, &mut binds);
+        self.fetch_label_keys(&source, params.scope, binds).await
+    }
+
+    /// Top values of one log attribute key
+    #[instrument(skip(self))]
+    async fn log_attribute_values(
+        &self,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>> {
+        params.validate(Some(key))?;
+        let mut binds = Vec::new();
+        let value = attribute_value(
+            params.scope,
+            &AttributeMaps::label_source(),
+            key,
+            &mut binds,
+        );
+        let source = log_label_source(params, &self.schema, &mut binds);
+        self.fetch_label_values(&source, &value, params.limit, binds)
+            .await
+    }
+
+    /// Read rows with the clickhouse cursor as the client consumes them
+    fn stream_logs<'a>(&'a self, params: &'a LogSearchParams) -> LogStream<'a> {
+        let cursor = log_search(self, params).and_then(|(query, binds)| {
+            bind_all(self.client.query(&query), binds)
+                .fetch::<LogRow>()
+                .map_err(query_error)
+        });
+        match cursor {
+            Ok(cursor) => stream::try_unfold(cursor, |mut cursor| async move {
+                let row = cursor.next().await.map_err(query_error)?;
+                Ok(row.map(|row| (LogEntry::from(row), cursor)))
+            })
+            .boxed(),
+            Err(e) => stream::once(async { Err(e) }).boxed(),
+        }
+    }
+}
+
+#[async_trait]
+impl MetricStore for ClickHouseClient {
+    /// List available metric names
+    #[instrument(skip(self))]
+    async fn list_metric_names(&self) -> Result<Vec<String>> {
+        #[derive(Row, Deserialize)]
+        struct NameRow {
+            name: String,
+        }
+
+        let tables: Vec<String> = MetricType::ALL
+            .iter()
+            .map(|t| format!("SELECT MetricName FROM {}", t.table_name()))
+            .collect();
+        let query = format!(
+            "SELECT DISTINCT MetricName as name FROM ({}) ORDER BY name",
+            tables.join(" UNION ALL ")
+        );
+
+        let rows: Vec<NameRow> = self
+            .client
+            .query(&query)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        Ok(rows.into_iter().map(|r| r.name).collect())
+    }
+
+    /// Query metrics with aggregation
+    #[instrument(skip(self))]
+    async fn query_metrics(&self, params: &MetricQueryParams) -> Result<Vec<MetricSeries>> {
+        params.validate()?;
+        match self.distribution_type(&params.metric_name).await? {
+            Some(MetricType::Summary) => {
+                return self
+                    .query_summary(params)
+                    .await?
+                    .into_iter()
+                    .map(|series| series.summarize(params.aggregation))
+                    .collect();
+            }
+            Some(_) => {
+                return self
+                    .query_histogram(params)
+                    .await?
+                    .into_iter()
+                    .map(|series| series.summarize(params.aggregation))
+                    .collect();
+            }
+            None => {}
+        }
+
+        let selected = rollup::select(
+            params.aggregation,
+            params.interval_seconds.unwrap_or(60).max(1),
+            &params.time_range,
+            self.rollup_coverage().await,
+        );
+        if let Some(rollup) = selected {
+            debug!(table = rollup.table_name(), "Reading metric rollup");
+        }
+        let (query, binds) = metric_query(params, selected);
+
+        let rows: Vec<MetricRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        // Rows arrive ordered by group, so each series is a contiguous run
+        let mut series: Vec<MetricSeries> = Vec::new();
+        let mut current: Option<Vec<String>> = None;
+        for row in rows {
+            if current.as_ref() != Some(&row.group_values) {
+                series.push(MetricSeries {
+                    labels: params
+                        .group_by
+                        .iter()
+                        .cloned()
+                        .zip(row.group_values.iter().cloned())
+                        .collect(),
+                    points: Vec::new(),
+                });
+                current = Some(row.group_values);
+            }
+            if let Some(last) = series.last_mut() {
+                last.points.push(MetricDataPoint {
+                    timestamp: to_chrono(row.bucket),
+                    value: row.value,
+                });
+            }
+        }
+
+        Ok(series)
+    }
+
+    /// Query merged histogram buckets per time bucket
+    #[instrument(skip(self))]
+    async fn query_histogram(&self, params: &MetricQueryParams) -> Result<Vec<HistogramSeries>> {
+        params.validate()?;
+        if self.distribution_type(&params.metric_name).await?
+            == Some(MetricType::ExponentialHistogram)
+        {
+            return self.query_exponential_histogram(params).await;
+        }
+
+        let interval_seconds = params.interval_seconds.unwrap_or(60).max(1);
+
+        let mut binds = Vec::new();
+        let group_values = group_values(&params.group_by, &mut binds);
+
+        // Look back one interval so the first bucket has a predecessor
+        let scan_start =
+            params.time_range.start - chrono::Duration::seconds(i64::from(interval_seconds));
+        let filter = metric_filter(params, scan_start, &mut binds);
+        binds.push(Param::Time(params.time_range.start));
+
+        let query = format!(
+            r#"
+            SELECT
+                {group_values} as group_values,
+                toStartOfInterval(TimeUnix, INTERVAL {interval_seconds} SECOND) as bucket,
+                ExplicitBounds as bounds,
+                sumForEach(Increase) as counts
+            FROM ({increases}) WHERE TimeUnix >= ?
+            GROUP BY group_values, bucket, bounds
+            ORDER BY group_values, bucket
+            "#,
+            increases = histogram_increases(&filter)
+        );
+
+        let rows: Vec<HistogramRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        // Rows arrive ordered by group; layouts are reconciled per group
+        let mut groups: Vec<(
+            Vec<String>,
+            Vec<(chrono::DateTime<chrono::Utc>, Vec<f64>, Vec<u64>)>,
+        )> = Vec::new();
+        for row in rows {
+            if groups.last().map(|(group, _)| group) != Some(&row.group_values) {
+                groups.push((row.group_values.clone(), Vec::new()));
+            }
+            if let Some((_, samples)) = groups.last_mut() {
+                samples.push((to_chrono(row.bucket), row.bounds, row.counts));
+            }
+        }
+
+        Ok(groups
+            .into_iter()
+            .map(|(group, samples)| {
+                HistogramSeries::merge(
+                    params.group_by.iter().cloned().zip(group).collect(),
+                    samples,
+                )
+            })
+            .collect())
+    }
+
+    /// Summarize every metric table by metric name
+    #[instrument(skip(self))]
+    async fn metric_catalog(&self, params: &MetricCatalogParams) -> Result<Vec<MetricInfo>> {
+        let mut binds = Vec::new();
+        let mut tables = Vec::new();
+        for (kind, metric_type) in MetricType::ALL.iter().enumerate() {
+            let mut clause = "TimeUnix >= ? AND TimeUnix < ?".to_string();
+            binds.extend([
+                Param::Time(params.time_range.start),
+                Param::Time(params.time_range.end),
+            ]);
+            if let Some(ref prefix) = params.prefix {
+                clause.push_str(" AND startsWith(MetricName, ?)");
+                binds.push(Param::Str(prefix.clone()));
+            }
+            if let Some(ref service) = params.service_name {
+                clause.push_str(" AND ServiceName = ?");
+                binds.push(Param::Str(service.clone()));
+            }
+            tables.push(format!(
+                "SELECT toUInt8({kind}) as kind, MetricName, MetricUnit, MetricDescription, \
+                 ServiceName, TimeUnix, Attributes, ResourceAttributes \
+                 FROM {} WHERE {clause}",
+                metric_type.table_name()
+            ));
+        }
+
+        let query = format!(
+            r#"
+            SELECT
+                MetricName as name,
+                kind,
+                anyIf(MetricUnit, MetricUnit != '') as unit,
+                anyIf(MetricDescription, MetricDescription != '') as description,
+                arraySort(groupUniqArrayIf(ServiceName, ServiceName != '')) as services,
+                uniqExact(toString(ResourceAttributes), toString(Attributes)) as series_count,
+                max(TimeUnix) as last_seen
+            FROM ({})
+            GROUP BY name, kind
+            ORDER BY name, kind
+            "#,
+            tables.join(" UNION ALL ")
+        );
+
+        let rows: Vec<CatalogRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(MetricInfo {
+                    name: row.name,
+                    metric_type: *MetricType::ALL.get(usize::from(row.kind))?,
+                    unit: Some(row.unit).filter(|u| !u.is_empty()),
+                    description: Some(row.description).filter(|d| !d.is_empty()),
+                    services: row.services,
+                    series_count: row.series_count,
+                    last_seen: to_chrono(row.last_seen),
+                })
+            })
+            .collect())
+    }
+
+    /// Query summary counts, sums and reported quantiles per time bucket
+    #[instrument(skip(self))]
+    async fn query_summary(&self, params: &MetricQueryParams) -> Result<Vec<SummarySeries>> {
+        params.validate()?;
+
+        let interval_seconds = params.interval_seconds.unwrap_or(60).max(1);
+
+        let mut binds = Vec::new();
+        let group_values = group_values(&params.group_by, &mut binds);
+        binds.push(Param::Time(params.time_range.start));
+
+        // Look back one interval so the first bucket has a predecessor
+        let scan_start =
+            params.time_range.start - chrono::Duration::seconds(i64::from(interval_seconds));
+        let filter = metric_filter(params, scan_start, &mut binds);
+
+        let query = format!(
+            r#"
+            SELECT
+                {group_values} as group_values,
+                {SERIES_COLUMNS},
+                toStartOfInterval(TimeUnix, INTERVAL {interval_seconds} SECOND) as bucket,
+                TimeUnix >= ? as in_range,
+                Count as count,
+                Sum as sum,
+                ValueAtQuantiles.Quantile as levels,
+                ValueAtQuantiles.Value as values
+            FROM otel_metrics_summary
+            WHERE {filter}
+            {SERIES_WINDOW}
+            ORDER BY series, TimeUnix
+            "#
+        );
+
+        let rows: Vec<SummaryRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        let mut groups: BTreeMap<Vec<String>, SummarySamples> = BTreeMap::new();
+        let mut previous: Option<(u64, (u64, f64))> = None;
+        for row in rows {
+            let prev = previous
+                .filter(|(series, _)| *series == row.series)
+                .map(|(_, totals)| totals);
+            let (count, sum) =
+                histogram::summary_increase(prev, row.count, row.sum, row.reset == 1);
+            previous = Some((row.series, (row.count, row.sum)));
+            if row.in_range == 1 {
+                let quantiles = row
+                    .levels
+                    .into_iter()
+                    .zip(row.values)
+                    .map(|(quantile, value)| QuantileValue { quantile, value })
+                    .collect();
+                groups.entry(row.group_values).or_default().push((
+                    to_chrono(row.bucket),
+                    count,
+                    sum,
+                    quantiles,
+                ));
+            }
+        }
+
+        Ok(groups
+            .into_iter()
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|(group, samples)| {
+                SummarySeries::merge(
+                    params.group_by.iter().cloned().zip(group).collect(),
+                    samples,
+                )
+            })
+            .collect())
+    }
+
+    /// List label keys of one metric with their cardinality
+    #[instrument(skip(self))]
+    async fn metric_label_keys(
+        &self,
+        metric_name: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelKey>> {
+        params.validate(None)?;
+        let mut binds = Vec::new();
+        let source = metric_label_source(metric_name, params, &mut binds);
+        self.fetch_label_keys(&source, params.scope, binds).await
+    }
+
+    /// Top values of one label key of a metric
+    #[instrument(skip(self))]
+    async fn metric_label_values(
+        &self,
+        metric_name: &str,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>> {
+        params.validate(Some(key))?;
+        let mut binds = Vec::new();
+        let value = attribute_value(
+            params.scope,
+            &AttributeMaps::label_source(),
+            key,
+            &mut binds,
+        );
+        let source = metric_label_source(metric_name, params, &mut binds);
+        self.fetch_label_values(&source, &value, params.limit, binds)
+            .await
+    }
+}
+
+impl ClickHouseClient {
+    /// Keys of the `log_attributes` and `resource_attributes` maps selected
+    /// by `scope` over the rows of `source`, with distinct value and row counts
+    async fn fetch_label_keys(
+        &self,
+        source: &str,
+        scope: AttributeScope,
+        binds: Vec<Param>,
+    ) -> Result<Vec<LabelKey>> {
+        // (key, is_resource, value) tuples, so log attributes sort first
+        let log = "arrayMap((k, v) -> (k, toUInt8(0), v), \
+                   mapKeys(log_attributes), mapValues(log_attributes))"
+            .to_string();
+        let resource = "arrayMap((k, v) -> (k, toUInt8(1), v), \
+                        mapKeys(resource_attributes), mapValues(resource_attributes))";
+        let entries = match scope {
+            AttributeScope::Any => format!("arrayConcat({log}, {resource})"),
+            AttributeScope::Log => log,
+            AttributeScope::Resource => resource.to_string(),
+        };
+        let query = format!(
+            r#"
+            SELECT
+                entry.1 as key,
+                entry.2 as resource,
+                uniqExact(entry.3) as cardinality,
+                count() as count
+            FROM ({source})
+            ARRAY JOIN {entries} as entry
+            GROUP BY key, resource
+            ORDER BY key, resource
+            "#
+        );
+
+        let rows: Vec<KeyRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| LabelKey {
+                key: row.key,
+                scope: if row.resource == 1 {
+                    AttributeScope::Resource
+                } else {
+                    AttributeScope::Log
+                },
+                cardinality: row.cardinality,
+                count: row.count,
+            })
+            .collect())
+    }
+
+    /// Most frequent non-empty results of the `value` expression over the
+    /// rows of `source`. `binds` hold the value binds, then the source binds.
+    async fn fetch_label_values(
+        &self,
+        source: &str,
+        value: &str,
+        limit: u64,
+        binds: Vec<Param>,
+    ) -> Result<Vec<LabelValue>> {
+        let query = format!(
+            "SELECT {value} as value, count() as count FROM ({source}) \
+             WHERE value != '' GROUP BY value ORDER BY count DESC, value LIMIT {limit}"
+        );
+
+        let rows: Vec<ValueRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| LabelValue {
+                value: row.value,
+                count: row.count,
+            })
+            .collect())
+    }
+
+    /// Subquery of `(ServiceName, c)` log counts for an error summary, see
+    /// [`error_count_query`]
+    async fn error_count_source(&self, params: &LogSearchParams) -> Result<(String, Vec<Param>)> {
+        // The view counts all tenants together
+        let summary_start = if self.tenant.is_some() {
+            None
+        } else {
+            self.error_summary_start().await
+        };
+        error_count_query(params, &self.schema, summary_start)
+    }
+
+    /// First whole hour `error_summary` counts. Only a successful lookup is
+    /// cached.
+    async fn error_summary_start(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        if let Some(&start) = self.error_summary_start.get() {
+            return start;
+        }
+        match self.lookup_error_summary_start().await {
+            Ok(start) => *self.error_summary_start.get_or_init(|| start),
+            Err(e) => {
+                debug!("error_summary_mv lookup failed: {e}");
+                None
+            }
+        }
+    }
+
+    /// The hour after migration `error_summary_mv` was recorded, since the
+    /// view only sees logs inserted once it exists. `None` unless the view
+    /// found writes to `error_summary`: a view left from before migrations
+    /// keeps its counts elsewhere.
+    async fn lookup_error_summary_start(&self) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let view = self
+            .client
+            .query(
+                "SELECT create_table_query FROM system.tables \
+                 WHERE database = ? AND name = 'error_summary_mv'",
+            )
+            .bind(&self.database)
+            .fetch_optional::<String>()
+            .await
+            .map_err(query_error)?;
+        if !view.as_deref().is_some_and(feeds_error_summary) {
+            debug!("error_summary_mv does not fill error_summary; counting errors from otel_logs");
+            return Ok(None);
+        }
+
+        let applied = self
+            .client
+            .query(&format!(
+                "SELECT toUnixTimestamp(applied_at) FROM {MIGRATIONS_TABLE} FINAL \
+                 WHERE name = 'error_summary_mv'"
+            ))
+            .fetch_optional::<u32>()
+            .await
+            .map_err(query_error)?;
+        Ok(applied.and_then(|applied| {
+            let hour = 3600;
+            let applied = i64::from(applied);
+            chrono::DateTime::from_timestamp(applied - applied.rem_euclid(hour) + hour, 0)
+        }))
+    }
+
+    /// First bucket the rollups cover, for choosing them in `query_metrics`.
+    /// Looked up again every [`ROLLUP_COVERAGE_REFRESH`], so backfills and
+    /// expired buckets are picked up while running; a failed lookup reads
+    /// raw points and is retried by the next query.
+    async fn rollup_coverage(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let cached = *self
+            .rollup_coverage
+            .lock()
+            .expect("rollup coverage lock poisoned");
+        if let Some((looked_up, start)) = cached {
+            if looked_up.elapsed() < ROLLUP_COVERAGE_REFRESH {
+                return start;
+            }
+        }
+        match self.lookup_rollup_coverage().await {
+            Ok(start) => {
+                *self
+                    .rollup_coverage
+                    .lock()
+                    .expect("rollup coverage lock poisoned") = Some((Instant::now(), start));
+                start
+            }
+            Err(e) => {
+                debug!("rollup lookup failed: {e}");
+                None
+            }
+        }
+    }
+
+    /// First bucket both rollups hold every point from. While a backfill is
+    /// unfinished, only the buckets filled by the views are.
+    async fn lookup_rollup_coverage(&self) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let backfills = self.rollup_backfills().await?;
+        let pending = backfills.iter().filter(|step| !step.done);
+        if let Some(until) = pending.map(|step| step.until).max() {
+            return Ok(Some(until));
+        }
+        self.rollup_start().await
+    }
+
+    /// Type of `name` if it has data points in the histogram, exponential
+    /// histogram or summary table
+    async fn distribution_type(&self, name: &str) -> Result<Option<MetricType>> {
+        #[derive(Row, Deserialize)]
+        struct Kind {
+            kind: u8,
+        }
+
+        let row: Option<Kind> = self
+            .client
+            .query(DISTRIBUTION_TYPE_QUERY)
+            .bind(name)
+            .bind(name)
+            .bind(name)
+            .fetch_optional()
+            .await
+            .map_err(query_error)?;
+
+        Ok(row.and_then(|r| distribution_kind(r.kind)))
+    }
+
+    /// Exponential histogram counterpart of `query_histogram`. Scales cannot
+    /// be merged in SQL, so points are differenced and merged here.
+    async fn query_exponential_histogram(
+        &self,
+        params: &MetricQueryParams,
+    ) -> Result<Vec<HistogramSeries>> {
+        let interval_seconds = params.interval_seconds.unwrap_or(60).max(1);
+
+        let mut binds = Vec::new();
+        let group_values = group_values(&params.group_by, &mut binds);
+        binds.push(Param::Time(params.time_range.start));
+
+        // Look back one interval so the first bucket has a predecessor
+        let scan_start =
+            params.time_range.start - chrono::Duration::seconds(i64::from(interval_seconds));
+        let filter = metric_filter(params, scan_start, &mut binds);
+
+        let query = format!(
+            r#"
+            SELECT
+                {group_values} as group_values,
+                {SERIES_COLUMNS},
+                toStartOfInterval(TimeUnix, INTERVAL {interval_seconds} SECOND) as bucket,
+                TimeUnix >= ? as in_range,
+                AggregationTemporality as temporality,
+                Scale as scale,
+                ZeroCount as zero_count,
+                PositiveOffset as positive_offset,
+                PositiveBucketCounts as positive_counts,
+                NegativeOffset as negative_offset,
+                NegativeBucketCounts as negative_counts
+            FROM otel_metrics_exponential_histogram
+            WHERE {filter}
+            {SERIES_WINDOW}
+            ORDER BY series, TimeUnix
+            "#
+        );
+
+        let rows: Vec<ExponentialRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        let mut groups: BTreeMap<Vec<String>, ExponentialSamples> = BTreeMap::new();
+        let mut previous: Option<(u64, ExponentialHistogram)> = None;
+        for row in rows {
+            let current = ExponentialHistogram::from_dense(
+                row.scale,
+                row.zero_count,
+                row.positive_offset,
+                &row.positive_counts,
+                row.negative_offset,
+                &row.negative_counts,
+            );
+            let prev = previous
+                .as_ref()
+                .filter(|(series, _)| *series == row.series)
+                .map(|(_, histogram)| histogram);
+            let increase = histogram::exponential_increase(
+                temporality(row.temporality),
+                prev,
+                &current,
+                row.reset == 1,
+            );
+            if row.in_range == 1 {
+                groups
+                    .entry(row.group_values)
+                    .or_default()
+                    .push((to_chrono(row.bucket), increase));
+            }
+            previous = Some((row.series, current));
+        }
+
+        Ok(groups
+            .into_iter()
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|(group, samples)| {
+                HistogramSeries::merge_exponential(
+                    params.group_by.iter().cloned().zip(group).collect(),
+                    samples,
+                )
+            })
+            .collect())
+    }
+}
+
+/// Kind of the first table holding points of the bound metric name, bound
+/// once per table, see [`distribution_kind`]
+pub(crate) const DISTRIBUTION_TYPE_QUERY: &str = "SELECT kind FROM ( \
+       (SELECT 1 as kind FROM otel_metrics_histogram WHERE MetricName = ? LIMIT 1) \
+       UNION ALL \
+       (SELECT 2 as kind FROM otel_metrics_exponential_histogram \
+        WHERE MetricName = ? LIMIT 1) \
+       UNION ALL \
+       (SELECT 3 as kind FROM otel_metrics_summary WHERE MetricName = ? LIMIT 1) \
+     ) LIMIT 1";
+
+/// Metric type of a kind returned by [`DISTRIBUTION_TYPE_QUERY`]
+pub(crate) const fn distribution_kind(kind: u8) -> Option<MetricType> {
+    match kind {
+        1 => Some(MetricType::Histogram),
+        2 => Some(MetricType::ExponentialHistogram),
+        3 => Some(MetricType::Summary),
+        _ => None,
+    }
+}
+
+/// Series identity and reset flag of a data point, for queries that
+/// difference points in Rust. Requires [`SERIES_WINDOW`].
+pub(crate) const SERIES_COLUMNS: &str = "
+    cityHash64(toString(ResourceAttributes), toString(Attributes)) as series,
+    StartTimeUnix != lagInFrame(StartTimeUnix, 1, StartTimeUnix) OVER series_window as reset";
+
+/// Window over consecutive points of one series
+pub(crate) const SERIES_WINDOW: &str = "
+    WINDOW series_window AS (
+        PARTITION BY toString(ResourceAttributes), toString(Attributes)
+        ORDER BY TimeUnix
+        ROWS BETWEEN 1 PRECEDING AND CURRENT ROW
+    )";
+
+/// OTLP `AggregationTemporality` (1 = delta, 2 = cumulative)
+const fn temporality(value: i32) -> Temporality {
+    if value == 1 {
+        Temporality::Delta
+    } else {
+        Temporality::Cumulative
+    }
+}
+
+/// Convert a ClickHouse timestamp to chrono
+fn to_chrono(timestamp: time::OffsetDateTime) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp(timestamp.unix_timestamp(), timestamp.nanosecond())
+        .unwrap_or_default()
+}
+
+/// Query aggregating the points of a gauge or sum metric per group and
+/// bucket, reading `rollup` instead of the raw tables if given
+pub(crate) fn metric_query(
+    params: &MetricQueryParams,
+    rollup: Option<Rollup>,
+) -> (String, Vec<Param>) {
+    let interval_seconds = params.interval_seconds.unwrap_or(60).max(1);
+
+    // Group key expressions come first in the SELECT, so their binds do too
+    let mut binds = Vec::new();
+    let group_values = group_values(&params.group_by, &mut binds);
+
+    let (agg_fn, time_column, source) = if let Some(rollup) = rollup {
+        let filter = metric_filter_on("Bucket", params, params.time_range.start, &mut binds);
+        (
+            rollup::merge_expression(params.aggregation)
+                .unwrap_or_default()
+                .to_string(),
+            "Bucket",
+            format!("{} WHERE {filter}", rollup.table_name()),
+        )
+    } else {
+        let agg_fn = match params.aggregation {
+            Aggregation::Avg => "avg(Value)".to_string(),
+            Aggregation::Min => "min(Value)".to_string(),
+            Aggregation::Max => "max(Value)".to_string(),
+            Aggregation::Sum => "sum(Value)".to_string(),
+            Aggregation::Count => "count()".to_string(),
+            Aggregation::P50 => "quantile(0.5)(Value)".to_string(),
+            Aggregation::P90 => "quantile(0.9)(Value)".to_string(),
+            Aggregation::P99 => "quantile(0.99)(Value)".to_string(),
+            Aggregation::Increase => "sum(Increase)".to_string(),
+            Aggregation::Rate => format!("sum(Increase) / {interval_seconds}"),
+        };
+
+        // Counters look back one interval so the first bucket has a predecessor
+        let scan_start = if params.aggregation.is_counter() {
+            params.time_range.start - chrono::Duration::seconds(i64::from(interval_seconds))
+        } else {
+            params.time_range.start
+        };
+        let filter = metric_filter(params, scan_start, &mut binds);
+
+        let source = if params.aggregation.is_counter() {
+            binds.push(Param::Time(params.time_range.start));
+            format!("({}) WHERE TimeUnix >= ?", counter_increases(&filter))
+        } else {
+            format!(
+                "(SELECT MetricName, TimeUnix, Value, Attributes, ResourceAttributes \
+                  FROM otel_metrics_gauge \
+                  UNION ALL \
+                  SELECT MetricName, TimeUnix, Value, Attributes, ResourceAttributes \
+                  FROM otel_metrics_sum) \
+                 WHERE {filter}"
+            )
+        };
+        (agg_fn, "TimeUnix", source)
+    };
+
+    let query = format!(
+        r#"
+        SELECT
+            {group_values} as group_values,
+            toStartOfInterval({time_column}, INTERVAL {interval_seconds} SECOND) as bucket,
+            {agg_fn} as value
+        FROM {source}
+        GROUP BY group_values, bucket
+        ORDER BY group_values, bucket
+        "#
+    );
+    (query, binds)
+}
+
+/// Array of the `group_by` label values of a data point, as a SQL expression
+pub(crate) fn group_values(group_by: &[String], binds: &mut Vec<Param>) -> String {
+    if group_by.is_empty() {
+        return "CAST([], 'Array(String)')".to_string();
+    }
+    let keys: Vec<String> = group_by
+        .iter()
+        .map(|key| attribute_value(AttributeScope::Any, &AttributeMaps::metrics(), key, binds))
+        .collect();
+    format!("[{}]", keys.join(", "))
+}
+
+/// WHERE condition selecting the data points of a metric query, scanning from
+/// `scan_start` instead of the range start
+pub(crate) fn metric_filter(
+    params: &MetricQueryParams,
+    scan_start: chrono::DateTime<chrono::Utc>,
+    binds: &mut Vec<Param>,
+) -> String {
+    metric_filter_on("TimeUnix", params, scan_start, binds)
+}
+
+/// [`metric_filter`] for a table whose time column is `time_column`
+pub(crate) fn metric_filter_on(
+    time_column: &str,
+    params: &MetricQueryParams,
+    scan_start: chrono::DateTime<chrono::Utc>,
+    binds: &mut Vec<Param>,
+) -> String {
+    binds.extend([
+        Param::Str(params.metric_name.clone()),
+        Param::Time(scan_start),
+        Param::Time(params.time_range.end),
+    ]);
+    let mut label_clause = String::new();
+    let maps = AttributeMaps::metrics();
+    for filter in &params.labels {
+        let condition = attribute_condition(filter, &maps, binds);
+        if filter.negate {
+            let _ = write!(label_clause, " AND NOT ({condition})");
+        } else {
+            let _ = write!(label_clause, " AND {condition}");
+        }
+    }
+    format!("MetricName = ? AND {time_column} >= ? AND {time_column} < ?{label_clause}")
+}
+
+/// Attribute maps of the log entries matching a label query, as
+/// `log_attributes` and `resource_attributes`
+pub(crate) fn log_label_source(
+    params: &LabelQueryParams,
+    schema: &LogSchema,
+    binds: &mut Vec<Param>,
+) -> String {
+    binds.extend([
+        Param::Time(params.time_range.start),
+        Param::Time(params.time_range.end),
+    ]);
+    let mut source = format!(
+        "SELECT {} as log_attributes, {} as resource_attributes FROM otel_logs \
+         WHERE Timestamp >= ? AND Timestamp < ?",
+        schema.log_map(),
+        schema.resource_map()
+    );
+    if let Some(ref service) = params.service_name {
+        source.push_str(" AND ServiceName = ?");
+        binds.push(Param::Str(service.clone()));
+    }
+    source
+}
+
+/// Attribute maps of the data points of one metric matching a label query,
+/// read from every metric table since the name alone does not tell the type.
+/// Columns are named as in [`log_label_source`].
+pub(crate) fn metric_label_source(
+    metric_name: &str,
+    params: &LabelQueryParams,
+    binds: &mut Vec<Param>,
+) -> String {
+    let mut tables = Vec::new();
+    for metric_type in MetricType::ALL {
+        binds.extend([
+            Param::Str(metric_name.to_string()),
+            Param::Time(params.time_range.start),
+            Param::Time(params.time_range.end),
+        ]);
+        let mut clause = "MetricName = ? AND TimeUnix >= ? AND TimeUnix < ?".to_string();
+        if let Some(ref service) = params.service_name {
+            clause.push_str(" AND ServiceName = ?");
+            binds.push(Param::Str(service.clone()));
+        }
+        tables.push(format!(
+            "SELECT Attributes as log_attributes, ResourceAttributes as resource_attributes \
+             FROM {} WHERE {clause}",
+            metric_type.table_name()
+        ));
+    }
+    tables.join(" UNION ALL ")
+}
+
+#[async_trait]
+impl Store for ClickHouseClient {
+    /// Check if ClickHouse is reachable
+    #[instrument(skip(self))]
+    async fn health_check(&self) -> Result<bool> {
+        self.client
+            .query("SELECT 1")
+            .fetch_one::<u8>()
+            .await
+            .map(|_| true)
+            .map_err(|e| Error::ClickHouseConnection(e.to_string()))
+    }
+
+    /// Get database statistics
+    #[instrument(skip(self))]
+    async fn get_stats(&self) -> Result<DatabaseStats> {
+        #[derive(Row, Deserialize)]
+        struct TableStats {
+            table: String,
+            rows: u64,
+            bytes: u64,
+        }
+
+        let stats: Vec<TableStats> = self
+            .client
+            .query(
+                r#"
+                SELECT
+                    table,
+                    sum(rows) as rows,
+                    sum(bytes) as bytes
+                FROM system.parts
+                WHERE database = ? AND active = 1
+                GROUP BY table
+                "#,
+            )
+            .bind(&self.database)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        // Table TTLs may name other tenants' services, so scoped stats omit them
+        if self.tenant.is_some() {
+            let tables: Vec<(String, u64, u64)> = stats
+                .into_iter()
+                .map(|stat| (stat.table, stat.rows, stat.bytes))
+                .collect();
+            return self.tenant_stats(&tables).await;
+        }
+
+        let mut db_stats = DatabaseStats {
+            table_ttls: self.table_ttls().await?,
+            ..DatabaseStats::default()
+        };
+        for stat in stats {
+            match stat.table.as_str() {
+                "otel_logs" => {
+                    db_stats.log_count = stat.rows;
+                    db_stats.log_bytes = stat.bytes;
+                }
+                t if t.starts_with("otel_metrics") => {
+                    db_stats.metric_count += stat.rows;
+                    db_stats.metric_bytes += stat.bytes;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(db_stats)
+    }
+
+    /// List services from log and metric `ServiceName` columns
+    #[instrument(skip(self))]
+    async fn list_services(&self, time_range: &TimeRange) -> Result<Vec<ServiceInfo>> {
+        #[derive(Row, Deserialize)]
+        struct LogServiceRow {
+            name: String,
+            log_count: u64,
+            error_count: u64,
+            first_seen: time::OffsetDateTime,
+            last_seen: time::OffsetDateTime,
+            attributes: Vec<String>,
+        }
+
+        #[derive(Row, Deserialize)]
+        struct MetricServiceRow {
+            name: String,
+            metric_count: u64,
+            first_seen: time::OffsetDateTime,
+            last_seen: time::OffsetDateTime,
+            attributes: Vec<String>,
+        }
+
+        let mut binds = Vec::new();
+        let attributes = service_attributes(&self.schema.resource_map(), &mut binds);
+        binds.extend([Param::Time(time_range.start), Param::Time(time_range.end)]);
+        let query = format!(
+            r#"
+            SELECT
+                ServiceName as name,
+                count() as log_count,
+                countIf(SeverityNumber >= {}) as error_count,
+                min(Timestamp) as first_seen,
+                max(Timestamp) as last_seen,
+                {attributes} as attributes
+            FROM otel_logs
+            WHERE Timestamp >= ? AND Timestamp < ? AND ServiceName != ''
+            GROUP BY name
+            "#,
+            LogSeverity::Error.to_severity_number()
+        );
+        let log_rows: Vec<LogServiceRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        let mut binds = Vec::new();
+        let attributes = service_attributes("ResourceAttributes", &mut binds);
+        let tables: Vec<String> = MetricType::ALL
+            .iter()
+            .map(|metric_type| {
+                binds.extend([Param::Time(time_range.start), Param::Time(time_range.end)]);
+                format!(
+                    "SELECT ServiceName, MetricName, TimeUnix, ResourceAttributes FROM {} \
+                     WHERE TimeUnix >= ? AND TimeUnix < ? AND ServiceName != ''",
+                    metric_type.table_name()
+                )
+            })
+            .collect();
+        let query = format!(
+            r#"
+            SELECT
+                ServiceName as name,
+                uniqExact(MetricName) as metric_count,
+                min(TimeUnix) as first_seen,
+                max(TimeUnix) as last_seen,
+                {attributes} as attributes
+            FROM ({})
+            GROUP BY name
+            "#,
+            tables.join(" UNION ALL ")
+        );
+        let metric_rows: Vec<MetricServiceRow> = bind_all(self.client.query(&query), binds)
+            .fetch_all()
+            .await
+            .map_err(query_error)?;
+
+        // Resource attribute values from logs win over those from metrics
+        let mut services: BTreeMap<String, ServiceInfo> = BTreeMap::new();
+        for row in log_rows {
+            let info = service_entry(
+                &mut services,
+                row.name,
+                (row.first_seen, row.last_seen),
+                row.attributes,
+            );
+            info.log_count = row.log_count;
+            info.error_count = row.error_count;
+        }
+        for row in metric_rows {
+            let info = service_entry(
+                &mut services,
+                row.name,
+                (row.first_seen, row.last_seen),
+                row.attributes,
+            );
+            info.metric_count = row.metric_count;
+        }
+
+        Ok(services
+            .into_values()
+            .map(ServiceInfo::with_error_ratio)
+            .collect())
+    }
+
+    fn for_tenant(&self, scope: &TenantScope) -> Result<SharedStore> {
+        Ok(Arc::new(self.clone().with_tenant(scope)?))
+    }
+}
+
+/// Catalog entry of a service, widened to the seen range of a result row and
+/// given the row's resource attribute values for keys it does not have yet
+fn service_entry(
+    services: &mut BTreeMap<String, ServiceInfo>,
+    name: String,
+    (first_seen, last_seen): (time::OffsetDateTime, time::OffsetDateTime),
+    attributes: Vec<String>,
+) -> &mut ServiceInfo {
+    let (first_seen, last_seen) = (to_chrono(first_seen), to_chrono(last_seen));
+    let info = services
+        .entry(name.clone())
+        .or_insert_with(|| ServiceInfo::new(name, first_seen));
+    info.seen(first_seen, last_seen);
+    for (key, value) in SERVICE_RESOURCE_KEYS.iter().zip(attributes) {
+        if !value.is_empty() {
+            info.resource_attributes
+                .entry((*key).to_string())
+                .or_insert(value);
+        }
+    }
+    info
+}
+
+/// Most frequent non-empty value of each service catalog resource key, as an
+/// array expression over the `resource` map (`''` when a key is never set)
+fn service_attributes(resource: &str, binds: &mut Vec<Param>) -> String {
+    let values: Vec<String> = SERVICE_RESOURCE_KEYS
+        .iter()
+        .map(|key| {
+            binds.extend([
+                Param::Str((*key).to_string()),
+                Param::Str((*key).to_string()),
+            ]);
+            format!("(topKIf(1)({resource}[?], {resource}[?] != ''))[1]")
+        })
+        .collect();
+    format!("[{}]", values.join(", "))
 }
@@ -0,0 +1,529 @@
+//! Tests for the SQL built by the clickhouse module
+
+use crate::{
+    clickhouse::{
+        attribute_condition, counter_increases, distribution_kind, error_count_query,
+        feeds_error_summary, histogram_increases, like_pattern, log_filter, log_label_source,
+        log_search, masked, metric_filter_on, metric_label_source, metric_query, AttributeMaps,
+        ClickHouseClient, Param, DISTRIBUTION_TYPE_QUERY, ROW_HASH, SERIES_COLUMNS, SERIES_WINDOW,
+    },
+    config::ClickHouseConfig,
+    cursor::LogCursor,
+    filter::{AttributeFilter, AttributeOp, AttributeScope},
+    pattern::{re2_pattern, MASKS},
+    rollup::Rollup,
+    schema::{AttributeLayout, LogSchema},
+    store::{LabelQueryParams, LogSearchParams, MetricQueryParams},
+    test_support::{all_time, ts},
+    types::{Aggregation, LogSeverity, MetricType, Pagination, TimeRange},
+};
+
+/// Number of `?` placeholders, which must match the binds
+fn placeholders(sql: &str) -> usize {
+    sql.matches('?').count()
+}
+
+fn str(value: &str) -> Param {
+    Param::Str(value.to_string())
+}
+
+fn filter(key: &str, scope: AttributeScope, op: AttributeOp, negate: bool) -> AttributeFilter {
+    AttributeFilter {
+        scope,
+        negate,
+        ..AttributeFilter::new(key, op)
+    }
+}
+
+fn metric_params(aggregation: Aggregation) -> MetricQueryParams {
+    MetricQueryParams {
+        metric_name: "queue_depth".to_string(),
+        time_range: all_time(),
+        aggregation,
+        interval_seconds: Some(60),
+        labels: Vec::new(),
+        group_by: Vec::new(),
+    }
+}
+
+#[test]
+fn test_log_filter() {
+    let params = LogSearchParams {
+        time_range: all_time(),
+        min_severity: Some(LogSeverity::Warn),
+        text_query: Some("50%_off".to_string()),
+        service_name: Some("api".to_string()),
+        trace_id: Some("4BF92F35".to_string()),
+        span_id: Some("00F067AA".to_string()),
+        body_terms: vec!["timeout".to_string()],
+        excluded_body_terms: vec!["healthcheck".to_string()],
+        attribute_filters: vec![
+            filter(
+                "http.status_code",
+                AttributeScope::Any,
+                AttributeOp::Gte(500.0),
+                false,
+            ),
+            filter("debug", AttributeScope::Log, AttributeOp::Exists, true),
+        ],
+        ..LogSearchParams::default()
+    };
+    let (clause, binds) = log_filter(&params, &LogSchema::default()).unwrap();
+
+    assert_eq!(
+        clause,
+        " WHERE Timestamp >= ? AND Timestamp < ? AND SeverityNumber >= 13 \
+         AND Body ILIKE ? AND ServiceName = ? AND TraceId = ? AND SpanId = ? \
+         AND Body ILIKE ? AND NOT (Body ILIKE ?) \
+         AND toFloat64OrNull(if(mapContains(LogAttributes, ?), LogAttributes[?], \
+         ResourceAttributes[?])) >= ? \
+         AND NOT (mapContains(LogAttributes, ?))"
+    );
+    assert_eq!(
+        binds,
+        [
+            Param::Time(ts(0)),
+            Param::Time(ts(3600)),
+            str("%50\\%\\_off%"),
+            str("api"),
+            str("4bf92f35"),
+            str("00f067aa"),
+            str("%timeout%"),
+            str("%healthcheck%"),
+            str("http.status_code"),
+            str("http.status_code"),
+            str("http.status_code"),
+            Param::F64(500.0),
+            str("debug"),
+        ]
+    );
+}
+
+#[test]
+fn test_log_filter_cursor_and_validation() {
+    let cursor = LogCursor {
+        timestamp: ts(10),
+        row_hash: 42,
+    };
+    let params = LogSearchParams {
+        time_range: all_time(),
+        cursor: Some(cursor),
+        ..LogSearchParams::default()
+    };
+    let (clause, binds) = log_filter(&params, &LogSchema::default()).unwrap();
+    assert!(clause.ends_with(&format!(
+        " AND (Timestamp < fromUnixTimestamp64Nano(?) \
+         OR (Timestamp = fromUnixTimestamp64Nano(?) AND {ROW_HASH} < ?))"
+    )));
+    assert_eq!(placeholders(&clause), binds.len());
+    assert_eq!(
+        binds[2..],
+        [
+            Param::I64(cursor.timestamp_nanos()),
+            Param::I64(cursor.timestamp_nanos()),
+            Param::U64(42),
+        ]
+    );
+
+    // Invalid parameters never reach SQL
+    let offset = LogSearchParams {
+        pagination: Pagination {
+            limit: 10,
+            offset: 10,
+        },
+        ..params
+    };
+    assert!(log_filter(&offset, &LogSchema::default()).is_err());
+    let regex = LogSearchParams {
+        attribute_filters: vec![AttributeFilter::new("k", AttributeOp::Regex("(".into()))],
+        ..LogSearchParams::default()
+    };
+    assert!(log_filter(&regex, &LogSchema::default()).is_err());
+}
+
+#[test]
+fn test_log_filter_reads_detected_layout() {
+    let schema = LogSchema {
+        resource_attributes: AttributeLayout::String,
+        log_attributes: AttributeLayout::Json,
+        observed_timestamp: false,
+    };
+    let params = LogSearchParams {
+        attribute_filters: vec![filter(
+            "k8s.pod.name",
+            AttributeScope::Any,
+            AttributeOp::Prefix("checkout".to_string()),
+            false,
+        )],
+        ..LogSearchParams::default()
+    };
+    let (clause, binds) = log_filter(&params, &schema).unwrap();
+    let (log, resource) = (schema.log_map(), schema.resource_map());
+    assert!(clause.ends_with(&format!(
+        " AND startsWith(if(mapContains({log}, ?), {log}[?], {resource}[?]), ?)"
+    )));
+    assert_eq!(placeholders(&clause), binds.len());
+
+    // Without ObservedTimestamp, the search reads Timestamp twice
+    let mut client = ClickHouseClient::new(&ClickHouseConfig::default()).unwrap();
+    let default_search = log_search(&client, &params).unwrap().0;
+    assert!(default_search.contains("ObservedTimestamp as observed_timestamp"));
+    client = client.with_schema(schema);
+    let (query, binds) = log_search(&client, &params).unwrap();
+    assert!(query.contains("Timestamp as observed_timestamp"));
+    assert!(!query.contains("ObservedTimestamp"));
+    assert!(query.contains(&format!("{resource} as resource_attributes")));
+    assert!(query.ends_with(&format!(
+        " ORDER BY Timestamp DESC, {ROW_HASH} DESC LIMIT 100 OFFSET 0"
+    )));
+    assert_eq!(placeholders(&query), binds.len());
+}
+
+#[test]
+fn test_attribute_condition() {
+    let maps = AttributeMaps::metrics();
+    let cases = [
+        (
+            AttributeScope::Log,
+            AttributeOp::Eq("a".into()),
+            "Attributes[?] = ?",
+        ),
+        (
+            AttributeScope::Resource,
+            AttributeOp::Ne("a".into()),
+            "ResourceAttributes[?] != ?",
+        ),
+        (
+            AttributeScope::Any,
+            AttributeOp::Regex("^a".into()),
+            "match(if(mapContains(Attributes, ?), Attributes[?], ResourceAttributes[?]), ?)",
+        ),
+        (
+            AttributeScope::Log,
+            AttributeOp::Lt(1.5),
+            "toFloat64OrNull(Attributes[?]) < ?",
+        ),
+        (
+            AttributeScope::Resource,
+            AttributeOp::Lte(1.5),
+            "toFloat64OrNull(ResourceAttributes[?]) <= ?",
+        ),
+        (
+            AttributeScope::Log,
+            AttributeOp::Gt(1.5),
+            "toFloat64OrNull(Attributes[?]) > ?",
+        ),
+        (
+            AttributeScope::Resource,
+            AttributeOp::Exists,
+            "mapContains(ResourceAttributes, ?)",
+        ),
+        (
+            AttributeScope::Any,
+            AttributeOp::Exists,
+            "(mapContains(Attributes, ?) OR mapContains(ResourceAttributes, ?))",
+        ),
+    ];
+    for (scope, op, expected) in cases {
+        let mut binds = vec![str("earlier")];
+        let condition =
+            attribute_condition(&filter("pod", scope, op.clone(), false), &maps, &mut binds);
+        assert_eq!(condition, expected, "{scope:?} {op:?}");
+        assert_eq!(placeholders(&condition), binds.len() - 1);
+
+        // The key comes first for each map read, then the operand
+        let keys = placeholders(&condition) - usize::from(op != AttributeOp::Exists);
+        assert!(binds[1..=keys].iter().all(|bind| *bind == str("pod")));
+        match op {
+            AttributeOp::Eq(v) | AttributeOp::Ne(v) | AttributeOp::Regex(v) => {
+                assert_eq!(binds.last(), Some(&Param::Str(v)));
+            }
+            AttributeOp::Lt(n) | AttributeOp::Lte(n) | AttributeOp::Gt(n) => {
+                assert_eq!(binds.last(), Some(&Param::F64(n)));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_feeds_error_summary() {
+    // As system.tables reports it, and as migration 0002 writes it
+    assert!(feeds_error_summary(
+        "CREATE MATERIALIZED VIEW default.error_summary_mv TO default.error_summary \
+         (`ServiceName` LowCardinality(String), `Count` UInt64) AS SELECT ServiceName"
+    ));
+    assert!(feeds_error_summary(
+        "CREATE MATERIALIZED VIEW IF NOT EXISTS error_summary_mv TO error_summary AS\nSELECT"
+    ));
+    assert!(feeds_error_summary(
+        "CREATE MATERIALIZED VIEW `logs`.error_summary_mv TO `logs`.`error_summary`(`Hour` DateTime)"
+    ));
+
+    // The view of the former init.sql keeps its own table
+    assert!(!feeds_error_summary(
+        "CREATE MATERIALIZED VIEW default.error_summary_mv (`Hour` DateTime, `Count` UInt64) \
+         ENGINE = SummingMergeTree PARTITION BY toYYYYMMDD(Timestamp) AS SELECT \
+         toStartOfHour(Timestamp) AS Hour, count() AS Count FROM default.otel_logs"
+    ));
+    assert!(!feeds_error_summary(
+        "CREATE MATERIALIZED VIEW default.error_summary_mv TO default.error_summary_old AS SELECT 1"
+    ));
+}
+
+#[test]
+fn test_error_count_query() {
+    // Whole hours of ts(0)..ts(10100) run from ts(2800) to ts(10000)
+    let params = LogSearchParams {
+        time_range: TimeRange {
+            start: ts(0),
+            end: ts(10_100),
+        },
+        min_severity: Some(LogSeverity::Error),
+        service_name: Some("api".to_string()),
+        ..LogSearchParams::default()
+    };
+    let raw = "SELECT ServiceName, count() as c FROM otel_logs WHERE ";
+    let schema = LogSchema::default();
+
+    // Without the view, or for other logs, everything is counted from the logs
+    let (source, binds) = error_count_query(&params, &schema, None).unwrap();
+    assert!(source.starts_with(raw));
+    assert_eq!(binds[..2], [Param::Time(ts(0)), Param::Time(ts(10_100))]);
+    let warnings = LogSearchParams {
+        min_severity: Some(LogSeverity::Warn),
+        ..params.clone()
+    };
+    let (source, _) = error_count_query(&warnings, &schema, Some(ts(-7200))).unwrap();
+    assert!(source.starts_with(raw));
+    assert!(!source.contains("error_summary"));
+
+    // Whole hours from the view, the partial ones at either end from the logs
+    let (source, binds) = error_count_query(&params, &schema, Some(ts(-7200))).unwrap();
+    assert_eq!(
+        source.split(" UNION ALL ").next().unwrap(),
+        "SELECT ServiceName, sum(Count) as c FROM error_summary \
+         WHERE Hour >= ? AND Hour < ? AND ServiceName = ? GROUP BY ServiceName"
+    );
+    assert_eq!(source.matches(raw).count(), 2);
+    assert_eq!(placeholders(&source), binds.len());
+    assert_eq!(
+        binds[..3],
+        [Param::Time(ts(2800)), Param::Time(ts(10_000)), str("api")]
+    );
+    assert_eq!(binds[3..5], [Param::Time(ts(0)), Param::Time(ts(2800))]);
+
+    // Hours before the view was created are counted from the logs
+    let (source, binds) = error_count_query(&params, &schema, Some(ts(6400))).unwrap();
+    assert_eq!(placeholders(&source), binds.len());
+    assert_eq!(binds[..2], [Param::Time(ts(6400)), Param::Time(ts(10_000))]);
+    assert_eq!(binds[3..5], [Param::Time(ts(0)), Param::Time(ts(6400))]);
+    let (source, _) = error_count_query(&params, &schema, Some(ts(10_000))).unwrap();
+    assert!(source.starts_with(raw));
+    assert!(!source.contains("error_summary"));
+}
+
+#[test]
+fn test_like_pattern_and_masks() {
+    assert_eq!(like_pattern(r"50%_off\"), r"%50\%\_off\\%");
+
+    // Masks apply in order, so the first one is innermost
+    let mut binds = vec![str("earlier")];
+    let expression = masked("Body", &mut binds);
+    assert!(expression.starts_with(&"replaceRegexpAll(".repeat(MASKS.len())));
+    assert!(expression.contains("(Body, ?, ?)"));
+    assert_eq!(placeholders(&expression), 2 * MASKS.len());
+    let expected: Vec<Param> = MASKS
+        .iter()
+        .flat_map(|(regex, placeholder)| [str(&re2_pattern(regex)), str(placeholder)])
+        .collect();
+    assert_eq!(binds[1..], expected[..]);
+    // RE2 rejects the Unicode flag
+    assert!(binds
+        .iter()
+        .all(|bind| !matches!(bind, Param::Str(s) if s.contains("(?-u"))));
+}
+
+#[test]
+fn test_metric_filter_on() {
+    let params = MetricQueryParams {
+        labels: vec![
+            filter(
+                "pod",
+                AttributeScope::Log,
+                AttributeOp::Eq("a".into()),
+                false,
+            ),
+            filter(
+                "region",
+                AttributeScope::Resource,
+                AttributeOp::Exists,
+                true,
+            ),
+        ],
+        ..metric_params(Aggregation::Avg)
+    };
+    let mut binds = Vec::new();
+    let clause = metric_filter_on("Bucket", &params, ts(-60), &mut binds);
+    assert_eq!(
+        clause,
+        "MetricName = ? AND Bucket >= ? AND Bucket < ? AND Attributes[?] = ? \
+         AND NOT (mapContains(ResourceAttributes, ?))"
+    );
+    assert_eq!(
+        binds,
+        [
+            str("queue_depth"),
+            Param::Time(ts(-60)),
+            Param::Time(ts(3600)),
+            str("pod"),
+            str("a"),
+            str("region"),
+        ]
+    );
+}
+
+#[test]
+fn test_metric_query_raw_points() {
+    let params = MetricQueryParams {
+        group_by: vec!["pod".to_string()],
+        ..metric_params(Aggregation::P90)
+    };
+    let (query, binds) = metric_query(&params, None);
+    assert!(query.contains(
+        "[if(mapContains(Attributes, ?), Attributes[?], ResourceAttributes[?])] as group_values"
+    ));
+    assert!(query.contains("toStartOfInterval(TimeUnix, INTERVAL 60 SECOND) as bucket"));
+    assert!(query.contains("quantile(0.9)(Value) as value"));
+    assert!(query.contains("FROM otel_metrics_gauge UNION ALL"));
+    assert!(query.contains("FROM otel_metrics_sum) WHERE MetricName = ? AND TimeUnix >= ?"));
+    assert_eq!(placeholders(&query), binds.len());
+    // Group keys are selected first, so their binds come first
+    assert_eq!(
+        binds,
+        [
+            str("pod"),
+            str("pod"),
+            str("pod"),
+            str("queue_depth"),
+            Param::Time(ts(0)),
+            Param::Time(ts(3600)),
+        ]
+    );
+}
+
+#[test]
+fn test_metric_query_counters_look_back() {
+    let (query, binds) = metric_query(&metric_params(Aggregation::Rate), None);
+    let mut filter_binds = Vec::new();
+    let filter = metric_filter_on(
+        "TimeUnix",
+        &metric_params(Aggregation::Rate),
+        ts(-60),
+        &mut filter_binds,
+    );
+    assert!(query.contains(&format!(
+        "({}) WHERE TimeUnix >= ?",
+        counter_increases(&filter)
+    )));
+    assert!(query.contains("sum(Increase) / 60 as value"));
+    assert_eq!(placeholders(&query), binds.len());
+    // One interval before the range, then the range start for the result
+    assert_eq!(binds[1], Param::Time(ts(-60)));
+    assert_eq!(binds.last(), Some(&Param::Time(ts(0))));
+}
+
+#[test]
+fn test_metric_query_rollups() {
+    for rollup in Rollup::ALL {
+        let (query, binds) = metric_query(&metric_params(Aggregation::Max), Some(rollup));
+        assert!(query.contains("toStartOfInterval(Bucket, INTERVAL 60 SECOND) as bucket"));
+        assert!(query.contains("maxMerge(ValueMax) as value"));
+        assert!(query.contains(&format!(
+            "FROM {} WHERE MetricName = ? AND Bucket >= ? AND Bucket < ?",
+            rollup.table_name()
+        )));
+        assert!(!query.contains("otel_metrics"));
+        assert_eq!(placeholders(&query), binds.len());
+        assert_eq!(binds[1], Param::Time(ts(0)));
+    }
+}
+
+#[test]
+fn test_increase_queries() {
+    let counters = counter_increases("F");
+    assert!(counters.contains("FROM otel_metrics_sum\n            WHERE F\n"));
+    assert!(counters.contains("AggregationTemporality = 1, Value,"));
+    assert!(counters.contains("NOT IsMonotonic, Value - PrevValue,"));
+    assert!(counters.contains("StartTimeUnix != PrevStart OR Value < PrevValue, Value,"));
+
+    let histograms = histogram_increases("F");
+    assert!(histograms.contains("FROM otel_metrics_histogram\n            WHERE F\n"));
+    assert!(histograms.contains("AggregationTemporality = 1, BucketCounts,"));
+    assert!(histograms.contains("OR ExplicitBounds != PrevBounds"));
+    assert!(
+        histograms.contains("arrayMap((c, p) -> if(c > p, c - p, 0), BucketCounts, PrevCounts)")
+    );
+    assert_eq!(placeholders(&counters) + placeholders(&histograms), 0);
+
+    // Every query differencing points partitions series the same way
+    let partition = "PARTITION BY toString(ResourceAttributes), toString(Attributes)";
+    for query in [&counters, &histograms, SERIES_WINDOW] {
+        assert!(query.contains(partition));
+        assert!(query.contains("ORDER BY TimeUnix"));
+        assert!(query.contains("ROWS BETWEEN 1 PRECEDING AND CURRENT ROW"));
+    }
+    assert!(SERIES_WINDOW.contains("WINDOW series_window AS"));
+    assert!(SERIES_COLUMNS.contains("OVER series_window as reset"));
+    assert!(SERIES_COLUMNS
+        .contains("cityHash64(toString(ResourceAttributes), toString(Attributes)) as series"));
+}
+
+#[test]
+fn test_distribution_type_query() {
+    assert_eq!(placeholders(DISTRIBUTION_TYPE_QUERY), 3);
+    for kind in 1..=3 {
+        let metric_type = distribution_kind(kind).unwrap();
+        assert!(DISTRIBUTION_TYPE_QUERY.contains(&format!(
+            "SELECT {kind} as kind FROM {} ",
+            metric_type.table_name()
+        )));
+    }
+    assert_eq!(distribution_kind(0), None);
+    assert_eq!(distribution_kind(4), None);
+    assert!(!DISTRIBUTION_TYPE_QUERY.contains(MetricType::Gauge.table_name()));
+}
+
+#[test]
+fn test_label_sources() {
+    let params = LabelQueryParams {
+        time_range: all_time(),
+        service_name: Some("api".to_string()),
+        scope: AttributeScope::Any,
+        limit: 10,
+    };
+    let mut binds = Vec::new();
+    let logs = log_label_source(&params, &LogSchema::default(), &mut binds);
+    assert_eq!(
+        logs,
+        "SELECT LogAttributes as log_attributes, ResourceAttributes as resource_attributes \
+         FROM otel_logs WHERE Timestamp >= ? AND Timestamp < ? AND ServiceName = ?"
+    );
+    assert_eq!(
+        binds,
+        [Param::Time(ts(0)), Param::Time(ts(3600)), str("api")]
+    );
+
+    let mut binds = Vec::new();
+    let metrics = metric_label_source("queue_depth", &params, &mut binds);
+    assert_eq!(placeholders(&metrics), binds.len());
+    assert_eq!(binds.len(), 4 * MetricType::ALL.len());
+    for metric_type in MetricType::ALL {
+        assert!(metrics.contains(&format!(
+            "FROM {} WHERE MetricName = ? AND TimeUnix >= ? AND TimeUnix < ? AND ServiceName = ?",
+            metric_type.table_name()
+        )));
+    }
+    assert_eq!(binds[4], str("queue_depth"));
+    assert_eq!(binds[5], Param::Time(ts(0)));
+}
@@ -2,6 +2,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::{store::StorageBackend, types::LogSeverity};
+
 /// Main configuration structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -20,6 +22,22 @@ pub struct Config {
     /// Retention configuration
     #[serde(default)]
     pub retention: RetentionConfig,
+
+    /// Storage backend configuration
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    /// Query guardrails
+    #[serde(default)]
+    pub limits: LimitsConfig,
+
+    /// Query result cache
+    #[serde(default)]
+    pub cache: CacheConfig,
+
+    /// Multi-tenant data isolation
+    #[serde(default)]
+    pub tenancy: TenancyConfig,
 }
 
 impl Default for Config {
@@ -29,6 +47,10 @@ impl Default for Config {
             api: ApiConfig::default(),
             mcp: McpConfig::default(),
             retention: RetentionConfig::default(),
+            storage: StorageConfig::default(),
+            limits: LimitsConfig::default(),
+            cache: CacheConfig::default(),
+            tenancy: TenancyConfig::default(),
         }
     }
 }
@@ -52,9 +74,28 @@ pub struct ClickHouseConfig {
     #[serde(default)]
     pub password: Option<String>,
 
-    /// Connection pool size
+    /// Connection pool size: idle HTTP connections kept open and most
+    /// queries in flight at once
     #[serde(default = "default_pool_size")]
     pub pool_size: u32,
+
+    /// Retries of a read after a transient error
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+
+    /// Backoff before the first retry, in milliseconds; doubled per retry and
+    /// jittered
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+
+    /// Consecutive failed requests that open the circuit breaker
+    #[serde(default = "default_breaker_failure_threshold")]
+    pub breaker_failure_threshold: u32,
+
+    /// Seconds the open breaker fails fast before letting a trial request
+    /// through
+    #[serde(default = "default_breaker_cooldown_secs")]
+    pub breaker_cooldown_secs: u64,
 }
 
 fn default_clickhouse_url() -> String {
@@ -69,6 +110,22 @@ fn default_pool_size() -> u32 {
     10
 }
 
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    100
+}
+
+fn default_breaker_failure_threshold() -> u32 {
+    5
+}
+
+fn default_breaker_cooldown_secs() -> u64 {
+    30
+}
+
 impl Default for ClickHouseConfig {
     fn default() -> Self {
         Self {
@@ -77,6 +134,10 @@ impl Default for ClickHouseConfig {
             username: std::env::var("CLICKHOUSE_USERNAME").ok(),
             password: std::env::var("CLICKHOUSE_PASSWORD").ok(),
             pool_size: default_pool_size(),
+            max_retries: default_max_retries(),
+            retry_backoff_ms: default_retry_backoff_ms(),
+            breaker_failure_threshold: default_breaker_failure_threshold(),
+            breaker_cooldown_secs: default_breaker_cooldown_secs(),
         }
     }
 }
@@ -163,6 +224,34 @@ pub struct RetentionConfig {
     /// Metrics retention in days
     #[serde(default = "default_metrics_retention_days")]
     pub metrics_retention_days: u32,
+
+    /// Overrides for matching logs, the first matching rule wins
+    #[serde(default)]
+    pub rules: Vec<RetentionRule>,
+
+    /// Reconcile ClickHouse TTLs with this configuration when a server starts
+    #[serde(default)]
+    pub enforce_on_startup: bool,
+}
+
+/// Retention override for logs of a service and/or severity range. Rules
+/// without a severity bound also apply to the metrics of the service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionRule {
+    /// Only data of this service
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+
+    /// Only logs at this severity or above
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_severity: Option<LogSeverity>,
+
+    /// Only logs at this severity or below
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_severity: Option<LogSeverity>,
+
+    /// Retention in days
+    pub days: u32,
 }
 
 fn default_log_retention_days() -> u32 {
@@ -178,6 +267,218 @@ impl Default for RetentionConfig {
         Self {
             log_retention_days: default_log_retention_days(),
             metrics_retention_days: default_metrics_retention_days(),
+            rules: Vec::new(),
+            enforce_on_startup: false,
+        }
+    }
+}
+
+/// Storage backend configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageConfig {
+    /// Backend serving queries ("clickhouse" or "memory")
+    #[serde(default)]
+    pub backend: StorageBackend,
+
+    /// JSON file the memory backend is seeded from on startup, see
+    /// [`crate::memory::MemoryStore::from_fixture`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixture: Option<String>,
+}
+
+/// Query guardrails applied to every API and MCP request. A value of 0
+/// disables the respective limit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LimitsConfig {
+    /// Longest time range of a log query, in hours
+    #[serde(default = "default_max_log_range_hours")]
+    pub max_log_range_hours: u32,
+
+    /// Longest time range of a metric or catalog query, in hours
+    #[serde(default = "default_max_metric_range_hours")]
+    pub max_metric_range_hours: u32,
+
+    /// Largest `limit` a request may ask for
+    #[serde(default = "default_max_limit")]
+    pub max_limit: u64,
+
+    /// Most rows a streamed export may return; also its default limit. 0
+    /// lifts the maximum, but exports still default to
+    /// [`crate::limits::DEFAULT_EXPORT_LIMIT`] rows unless they ask for more
+    #[serde(default = "default_max_export_limit")]
+    pub max_export_limit: u64,
+
+    /// Most time buckets per series
+    #[serde(default = "default_max_points")]
+    pub max_points: u64,
+
+    /// ClickHouse `max_execution_time` per query, in seconds
+    #[serde(default = "default_timeout")]
+    pub max_execution_time_secs: u64,
+
+    /// ClickHouse `max_rows_to_read` per query
+    #[serde(default = "default_max_rows_to_read")]
+    pub max_rows_to_read: u64,
+
+    /// Run queries with ClickHouse `readonly=1`
+    #[serde(default = "default_true")]
+    pub readonly: bool,
+}
+
+fn default_max_log_range_hours() -> u32 {
+    7 * 24
+}
+
+fn default_max_metric_range_hours() -> u32 {
+    30 * 24
+}
+
+fn default_max_limit() -> u64 {
+    10_000
+}
+
+fn default_max_export_limit() -> u64 {
+    crate::limits::DEFAULT_EXPORT_LIMIT
+}
+
+fn default_max_points() -> u64 {
+    10_000
+}
+
+fn default_max_rows_to_read() -> u64 {
+    1_000_000_000
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_log_range_hours: default_max_log_range_hours(),
+            max_metric_range_hours: default_max_metric_range_hours(),
+            max_limit: default_max_limit(),
+            max_export_limit: default_max_export_limit(),
+            max_points: default_max_points(),
+            max_execution_time_secs: default_timeout(),
+            max_rows_to_read: default_max_rows_to_read(),
+            readonly: default_true(),
+        }
+    }
+}
+
+/// Query result cache configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// Whether ClickHouse query results are cached
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Most cached results; the entries closest to expiry are evicted first
+    #[serde(default = "default_cache_max_entries")]
+    pub max_entries: usize,
+
+    /// Results with more rows than this are not cached
+    #[serde(default = "default_cache_max_rows")]
+    pub max_rows: usize,
+
+    /// Time to live, in seconds, of results whose time range reaches into the
+    /// last few minutes, where new data still arrives
+    #[serde(default = "default_cache_live_ttl_secs")]
+    pub live_ttl_secs: u64,
+
+    /// Time to live, in seconds, of results over closed historical ranges
+    #[serde(default = "default_cache_historical_ttl_secs")]
+    pub historical_ttl_secs: u64,
+
+    /// Cache keys of log, label and catalog queries have their time range
+    /// widened to multiples of this many seconds, so requests a few seconds
+    /// apart share an entry
+    #[serde(default = "default_cache_snap_secs")]
+    pub snap_secs: u32,
+}
+
+fn default_cache_max_entries() -> usize {
+    1_000
+}
+
+fn default_cache_max_rows() -> usize {
+    10_000
+}
+
+fn default_cache_live_ttl_secs() -> u64 {
+    10
+}
+
+fn default_cache_historical_ttl_secs() -> u64 {
+    600
+}
+
+fn default_cache_snap_secs() -> u32 {
+    10
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_true(),
+            max_entries: default_cache_max_entries(),
+            max_rows: default_cache_max_rows(),
+            live_ttl_secs: default_cache_live_ttl_secs(),
+            historical_ttl_secs: default_cache_historical_ttl_secs(),
+            snap_secs: default_cache_snap_secs(),
+        }
+    }
+}
+
+/// Multi-tenant data isolation, see [`crate::tenant`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenancyConfig {
+    /// Whether every request must resolve to a tenant and only sees its data
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Resource attribute holding the tenant ID of a log or metric row
+    #[serde(default = "default_tenant_attribute")]
+    pub attribute: String,
+
+    /// Header naming the tenant of a request
+    #[serde(default = "default_tenant_header")]
+    pub header: String,
+
+    /// Accept the tenant header without an API key, for deployments behind a
+    /// gateway that authenticates callers and sets it
+    #[serde(default)]
+    pub trust_header: bool,
+
+    /// API keys and the tenant each one is bound to
+    #[serde(default)]
+    pub api_keys: Vec<TenantApiKey>,
+}
+
+/// An API key granting access to one tenant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantApiKey {
+    /// Key sent as `Authorization: Bearer <key>` or `X-API-Key`
+    pub key: String,
+
+    /// Tenant ID the key is bound to
+    pub tenant: String,
+}
+
+fn default_tenant_attribute() -> String {
+    "tenant.id".to_string()
+}
+
+fn default_tenant_header() -> String {
+    "X-Tenant-ID".to_string()
+}
+
+impl Default for TenancyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            attribute: default_tenant_attribute(),
+            header: default_tenant_header(),
+            trust_header: false,
+            api_keys: Vec::new(),
         }
     }
 }
@@ -23,6 +23,15 @@ fn test_default_config() {
     // Check retention defaults
     assert_eq!(config.retention.log_retention_days, 30);
     assert_eq!(config.retention.metrics_retention_days, 90);
+
+    // Check query limits
+    assert_eq!(config.limits.max_log_range_hours, 168);
+    assert_eq!(config.limits.max_limit, 10_000);
+    assert!(config.limits.readonly);
+
+    // Check cache defaults
+    assert!(config.cache.enabled);
+    assert_eq!(config.cache.live_ttl_secs, 10);
 }
 
 #[test]
@@ -31,6 +40,8 @@ fn test_clickhouse_config_default() {
     assert!(config.url.contains("localhost") || config.url.contains("8123"));
     assert_eq!(config.database, "default");
     assert_eq!(config.pool_size, 10);
+    assert_eq!(config.max_retries, 3);
+    assert_eq!(config.breaker_failure_threshold, 5);
 }
 
 #[test]
@@ -54,6 +65,8 @@ fn test_retention_config_default() {
     let config = RetentionConfig::default();
     assert_eq!(config.log_retention_days, 30);
     assert_eq!(config.metrics_retention_days, 90);
+    assert!(config.rules.is_empty());
+    assert!(!config.enforce_on_startup);
 }
 
 #[test]
@@ -0,0 +1,140 @@
+//! Keyset cursors and stable identifiers for log rows
+//!
+//! Logs are ordered by `(Timestamp DESC, row hash DESC)`, where the row hash is
+//! `xxHash64` over service name, trace ID, span ID and body. A cursor holds that
+//! key for the last row of a page; the next page starts strictly after it, so
+//! rows arriving between requests cause neither duplicates nor gaps.
+//!
+//! The same key, packed into a UUID, is the log ID: it is derived from stored
+//! columns only, so it is stable across queries and decodes back into a point
+//! lookup on `(Timestamp, row hash)`.
+
+use std::{cmp::Ordering, str::FromStr};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::{
+    error::{Error, Result},
+    types::LogEntry,
+};
+
+/// Hash identifying a log row among rows with the same timestamp.
+///
+/// Equal to ClickHouse
+/// `xxHash64(concat(ServiceName, '\0', TraceId, '\0', SpanId, '\0', Body))`.
+pub fn log_row_hash(entry: &LogEntry) -> u64 {
+    let mut bytes = Vec::with_capacity(entry.body.len() + 64);
+    for part in [
+        entry.service_name.as_deref().unwrap_or_default(),
+        entry.trace_id.as_deref().unwrap_or_default(),
+        entry.span_id.as_deref().unwrap_or_default(),
+    ] {
+        bytes.extend_from_slice(part.as_bytes());
+        bytes.push(0);
+    }
+    bytes.extend_from_slice(entry.body.as_bytes());
+    xxhash_rust::xxh64::xxh64(&bytes, 0)
+}
+
+/// Stable identifier of a log row, see [`LogCursor::id`]
+pub fn log_id(entry: &LogEntry) -> Uuid {
+    LogCursor::for_entry(entry).id()
+}
+
+/// Parse a log ID as returned in [`LogEntry::id`]
+pub fn parse_log_id(s: &str) -> Result<Uuid> {
+    Uuid::parse_str(s).map_err(|_| Error::InvalidParameter(format!("invalid log id: {s}")))
+}
+
+/// Position of a log row in `(timestamp, row hash)` order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogCursor {
+    /// Timestamp of the last row
+    pub timestamp: DateTime<Utc>,
+    /// Row hash of the last row
+    pub row_hash: u64,
+}
+
+impl LogCursor {
+    /// Cursor positioned at `entry`
+    pub fn for_entry(entry: &LogEntry) -> Self {
+        Self {
+            timestamp: entry.timestamp,
+            row_hash: log_row_hash(entry),
+        }
+    }
+
+    /// Timestamp as Unix nanoseconds
+    pub fn timestamp_nanos(&self) -> i64 {
+        self.timestamp.timestamp_nanos_opt().unwrap_or_default()
+    }
+
+    /// Whether `entry` sorts after the cursor in `(timestamp, row hash)` descending order
+    pub fn precedes(&self, entry: &LogEntry) -> bool {
+        match entry.timestamp.cmp(&self.timestamp) {
+            Ordering::Less => true,
+            Ordering::Equal => log_row_hash(entry) < self.row_hash,
+            Ordering::Greater => false,
+        }
+    }
+
+    /// Encode as an opaque URL-safe string
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.to_bytes())
+    }
+
+    /// Identifier of the row at this position: the timestamp nanoseconds and
+    /// row hash, big-endian, as UUID bytes (no RFC 4122 version bits)
+    pub fn id(&self) -> Uuid {
+        Uuid::from_bytes(self.to_bytes())
+    }
+
+    /// Position of the row with the given ID
+    pub fn from_id(id: Uuid) -> Self {
+        Self::from_bytes(*id.as_bytes())
+    }
+
+    fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&self.timestamp_nanos().to_be_bytes());
+        bytes[8..].copy_from_slice(&self.row_hash.to_be_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: [u8; 16]) -> Self {
+        let (nanos, hash) = bytes.split_at(8);
+        let mut nanos_bytes = [0u8; 8];
+        nanos_bytes.copy_from_slice(nanos);
+        let mut hash_bytes = [0u8; 8];
+        hash_bytes.copy_from_slice(hash);
+
+        Self {
+            timestamp: DateTime::from_timestamp_nanos(i64::from_be_bytes(nanos_bytes)),
+            row_hash: u64::from_be_bytes(hash_bytes),
+        }
+    }
+}
+
+impl FromStr for LogCursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidParameter(format!("invalid cursor: {s}"));
+
+        let bytes: [u8; 16] = URL_SAFE_NO_PAD
+            .decode(s)
+            .map_err(|_| invalid())?
+            .try_into()
+            .map_err(|_| invalid())?;
+        Ok(Self::from_bytes(bytes))
+    }
+}
+
+/// Sort order shared by all backends: newest first, ties broken by row hash
+pub fn compare_desc(a: &LogEntry, b: &LogEntry) -> Ordering {
+    b.timestamp
+        .cmp(&a.timestamp)
+        .then_with(|| log_row_hash(b).cmp(&log_row_hash(a)))
+}
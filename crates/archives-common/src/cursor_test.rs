@@ -0,0 +1,93 @@
+//! Tests for cursor module
+
+use chrono::DateTime;
+
+use crate::{
+    clickhouse::ROW_HASH,
+    cursor::{compare_desc, log_id, log_row_hash, parse_log_id, LogCursor},
+    test_support::log,
+    types::{LogEntry, LogSeverity},
+};
+
+fn entry(nanos: i64, body: &str) -> LogEntry {
+    LogEntry {
+        timestamp: DateTime::from_timestamp_nanos(nanos),
+        observed_timestamp: DateTime::from_timestamp_nanos(nanos),
+        trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+        ..log(0, LogSeverity::Info, "api", body)
+    }
+}
+
+#[test]
+fn test_encode_roundtrip() {
+    let cursor = LogCursor::for_entry(&entry(1_700_000_000_123_456_789, "hello"));
+    let encoded = cursor.encode();
+    assert!(encoded
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+
+    let decoded: LogCursor = encoded.parse().unwrap();
+    assert_eq!(decoded, cursor);
+}
+
+#[test]
+fn test_decode_invalid() {
+    assert!("".parse::<LogCursor>().is_err());
+    assert!("not a cursor!".parse::<LogCursor>().is_err());
+    // Valid base64, wrong length
+    assert!("AAAA".parse::<LogCursor>().is_err());
+}
+
+#[test]
+fn test_row_hash_matches_clickhouse() {
+    // The row hash the keyset cursor compares against in SQL
+    assert_eq!(
+        ROW_HASH,
+        "xxHash64(concat(ServiceName, char(0), TraceId, char(0), SpanId, char(0), Body))"
+    );
+
+    // SELECT xxHash64(concat('api', char(0), '4bf92f3577b34da6a3ce929d0e0e4736',
+    //     char(0), '', char(0), 'hello'))
+    assert_eq!(log_row_hash(&entry(0, "hello")), 8_373_105_837_683_688_710);
+    // SELECT xxHash64(concat('', char(0), '', char(0), '', char(0), ''))
+    let empty = LogEntry {
+        service_name: None,
+        trace_id: None,
+        ..entry(0, "")
+    };
+    assert_eq!(log_row_hash(&empty), 3_569_224_950_158_953_636);
+    assert_ne!(
+        log_row_hash(&entry(0, "hello")),
+        log_row_hash(&entry(0, "world"))
+    );
+}
+
+#[test]
+fn test_precedes_follows_sort_order() {
+    let mut entries = [
+        entry(100, "a"),
+        entry(200, "b"),
+        entry(200, "c"),
+        entry(200, "d"),
+        entry(300, "e"),
+    ];
+    entries.sort_by(compare_desc);
+
+    for (i, at) in entries.iter().enumerate() {
+        let cursor = LogCursor::for_entry(at);
+        for (j, other) in entries.iter().enumerate() {
+            assert_eq!(cursor.precedes(other), j > i, "cursor {i}, entry {j}");
+        }
+    }
+}
+
+#[test]
+fn test_log_id_roundtrip() {
+    let log = entry(1_700_000_000_123_456_789, "hello");
+    let id = log_id(&log);
+    assert_eq!(id, log_id(&entry(1_700_000_000_123_456_789, "hello")));
+
+    let parsed = parse_log_id(&id.to_string()).unwrap();
+    assert_eq!(LogCursor::from_id(parsed), LogCursor::for_entry(&log));
+    assert!(parse_log_id("not-a-uuid").is_err());
+}
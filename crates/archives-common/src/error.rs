@@ -17,9 +17,18 @@ pub enum Error {
     #[error("Configuration error: {0}")]
     Config(String),
 
+    #[error("Unsupported schema: {0}")]
+    Schema(String),
+
     #[error("Invalid query parameter: {0}")]
     InvalidParameter(String),
 
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
     #[error("Resource not found: {0}")]
     NotFound(String),
 
@@ -0,0 +1,183 @@
+//! Fake database server for tests
+//!
+//! Records the queries of a [`ClickHouseClient`] and answers each with the
+//! `RowBinary` rows, or the error, that a test supplies for its SQL.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{clickhouse::ClickHouseClient, config::ClickHouseConfig};
+
+/// Rows for a query, or the exception the server returns instead
+pub type Answer = dyn Fn(&str) -> Result<Vec<u8>, String> + Send + Sync;
+
+/// A query received by [`FakeClickHouse`]
+#[derive(Debug)]
+pub struct Received {
+    /// URL parameters, decoded
+    pub options: Vec<(String, String)>,
+    pub sql: String,
+}
+
+impl Received {
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Tables and views read, without table functions and subqueries
+    pub fn tables(&self) -> Vec<String> {
+        let from = regex::Regex::new(r"\bFROM\s+([A-Za-z_][\w.]*)(\s*\()?").unwrap();
+        from.captures_iter(&self.sql)
+            .filter(|c| c.get(2).is_none())
+            .map(|c| c[1].to_string())
+            .collect()
+    }
+}
+
+/// HTTP server answering the queries of a [`ClickHouseClient`]
+pub struct FakeClickHouse {
+    url: String,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl FakeClickHouse {
+    pub async fn start(
+        answer: impl Fn(&str) -> Result<Vec<u8>, String> + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        let answer: Arc<Answer> = Arc::new(answer);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(Self::serve(stream, log.clone(), answer.clone()));
+            }
+        });
+        Self { url, received }
+    }
+
+    pub fn client(&self) -> ClickHouseClient {
+        ClickHouseClient::new(&ClickHouseConfig {
+            url: self.url.clone(),
+            ..ClickHouseConfig::default()
+        })
+        .unwrap()
+    }
+
+    pub fn take(&self) -> Vec<Received> {
+        std::mem::take(&mut *self.received.lock().unwrap())
+    }
+
+    /// Serve HTTP/1.1 requests of one keep-alive connection
+    async fn serve(
+        mut stream: TcpStream,
+        received: Arc<Mutex<Vec<Received>>>,
+        answer: Arc<Answer>,
+    ) {
+        stream.set_nodelay(true).unwrap();
+        let mut buffer = Vec::new();
+        loop {
+            let head_end = loop {
+                if let Some(i) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break i + 4;
+                }
+                let mut chunk = [0; 4096];
+                match stream.read(&mut chunk).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                }
+            };
+            let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+            let length = head
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then(|| value.trim().parse::<usize>().unwrap())
+                })
+                .unwrap_or(0);
+            while buffer.len() < head_end + length {
+                let mut chunk = [0; 4096];
+                match stream.read(&mut chunk).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                }
+            }
+            let body: Vec<u8> = buffer.drain(..head_end + length).skip(head_end).collect();
+
+            let target = head.split_whitespace().nth(1).unwrap_or("/");
+            let options: Vec<(String, String)> = target
+                .split_once('?')
+                .map_or("", |(_, query)| query)
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(key, value)| (url_decode(key), url_decode(value)))
+                .collect();
+            // Short reads send the query in the URL, everything else in the body
+            let request = Received {
+                sql: options.iter().find(|(key, _)| key == "query").map_or_else(
+                    || String::from_utf8_lossy(&body).to_string(),
+                    |(_, q)| q.clone(),
+                ),
+                options,
+            };
+            let (status, rows) = match answer(&request.sql) {
+                Ok(rows) => ("200 OK", rows),
+                Err(error) => ("500 Internal Server Error", error.into_bytes()),
+            };
+            received.lock().unwrap().push(request);
+
+            let header = format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\n\r\n",
+                rows.len()
+            );
+            if stream.write_all(header.as_bytes()).await.is_err()
+                || stream.write_all(&rows).await.is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+/// Append a `RowBinary` string: its LEB128 length, then its bytes
+pub fn push_string(rows: &mut Vec<u8>, s: &str) {
+    let mut len = s.len();
+    loop {
+        let byte = u8::try_from(len & 0x7f).unwrap();
+        len >>= 7;
+        if len == 0 {
+            rows.push(byte);
+            break;
+        }
+        rows.push(byte | 0x80);
+    }
+    rows.extend_from_slice(s.as_bytes());
+}
+
+/// Decode a `application/x-www-form-urlencoded` component
+fn url_decode(encoded: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' if rest.len() >= 2 => {
+                let hex = std::str::from_utf8(&rest[..2]).unwrap();
+                bytes.push(u8::from_str_radix(hex, 16).unwrap());
+                rest = &rest[2..];
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).unwrap()
+}
@@ -0,0 +1,205 @@
+//! Attribute filters for log search and metric labels
+//!
+//! Predicates on `LogAttributes` / `ResourceAttributes` keys such as
+//! `http.status_code` or `k8s.pod.name`. For metrics the same filters apply to
+//! data point `Attributes` (the `log` scope) and `ResourceAttributes`. A missing
+//! key reads as the empty string, matching ClickHouse map lookup semantics.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Which attribute map a filter reads from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeScope {
+    /// Log attributes, falling back to resource attributes when the key is absent
+    #[default]
+    Any,
+    /// `LogAttributes` only (data point `Attributes` for metrics)
+    Log,
+    /// `ResourceAttributes` only
+    Resource,
+}
+
+impl AttributeScope {
+    /// Look up `key` in the attribute map(s) selected by the scope
+    pub fn lookup<'a>(
+        self,
+        key: &str,
+        resource_attributes: &'a serde_json::Value,
+        attributes: &'a serde_json::Value,
+    ) -> Option<&'a serde_json::Value> {
+        match self {
+            Self::Log => attributes.get(key),
+            Self::Resource => resource_attributes.get(key),
+            Self::Any => attributes.get(key).or_else(|| resource_attributes.get(key)),
+        }
+    }
+}
+
+/// String form of an attribute value as stored in a ClickHouse `Map(String, String)`;
+/// a missing key reads as `""`
+pub fn attribute_text(value: Option<&serde_json::Value>) -> String {
+    match value {
+        None => String::new(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Comparison applied to an attribute value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", content = "value", rename_all = "snake_case")]
+pub enum AttributeOp {
+    /// Value equals the string
+    Eq(#[serde(deserialize_with = "string_or_number")] String),
+    /// Value differs from the string (missing keys match)
+    Ne(#[serde(deserialize_with = "string_or_number")] String),
+    /// Key is present
+    Exists,
+    /// Value starts with the string
+    Prefix(String),
+    /// Value matches the regular expression
+    Regex(String),
+    /// Value parses as a number greater than the operand
+    Gt(f64),
+    /// Value parses as a number greater than or equal to the operand
+    Gte(f64),
+    /// Value parses as a number less than the operand
+    Lt(f64),
+    /// Value parses as a number less than or equal to the operand
+    Lte(f64),
+}
+
+/// A predicate on one attribute key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributeFilter {
+    /// Attribute key, e.g. `http.status_code`
+    pub key: String,
+
+    /// Attribute map to read
+    #[serde(default)]
+    pub scope: AttributeScope,
+
+    /// Comparison to apply
+    #[serde(flatten)]
+    pub op: AttributeOp,
+
+    /// Invert the result of the comparison
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub negate: bool,
+}
+
+fn string_or_number<'de, D>(deserializer: D) -> std::result::Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => Ok(s),
+        other => Ok(other.to_string()),
+    }
+}
+
+impl AttributeFilter {
+    /// Create a filter on any attribute map
+    pub fn new(key: impl Into<String>, op: AttributeOp) -> Self {
+        Self {
+            key: key.into(),
+            scope: AttributeScope::Any,
+            op,
+            negate: false,
+        }
+    }
+
+    /// Check that the filter can be executed
+    pub fn validate(&self) -> Result<()> {
+        if self.key.is_empty() {
+            return Err(Error::InvalidParameter(
+                "attribute filter key must not be empty".to_string(),
+            ));
+        }
+        if let AttributeOp::Regex(ref pattern) = self.op {
+            regex::Regex::new(pattern).map_err(|e| {
+                Error::InvalidParameter(format!("invalid regex for attribute {}: {e}", self.key))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Evaluate the filter against JSON attribute objects
+    pub fn matches(
+        &self,
+        resource_attributes: &serde_json::Value,
+        log_attributes: &serde_json::Value,
+    ) -> bool {
+        let value = self
+            .scope
+            .lookup(&self.key, resource_attributes, log_attributes);
+        let text = attribute_text(value);
+        let number = || text.parse::<f64>().ok();
+
+        let matched = match self.op {
+            AttributeOp::Eq(ref expected) => text == *expected,
+            AttributeOp::Ne(ref expected) => text != *expected,
+            AttributeOp::Exists => value.is_some(),
+            AttributeOp::Prefix(ref prefix) => text.starts_with(prefix.as_str()),
+            AttributeOp::Regex(ref pattern) => {
+                regex::Regex::new(pattern).is_ok_and(|re| re.is_match(&text))
+            }
+            AttributeOp::Gt(operand) => number().is_some_and(|n| n > operand),
+            AttributeOp::Gte(operand) => number().is_some_and(|n| n >= operand),
+            AttributeOp::Lt(operand) => number().is_some_and(|n| n < operand),
+            AttributeOp::Lte(operand) => number().is_some_and(|n| n <= operand),
+        };
+        matched != self.negate
+    }
+}
+
+/// Operators recognised by [`AttributeFilter::from_str`], longest first
+const OPERATORS: [&str; 8] = ["!=", ">=", "<=", "=~", "^=", "=", ">", "<"];
+
+impl FromStr for AttributeFilter {
+    type Err = Error;
+
+    /// Parse `key=value`, `key!=value`, `key^=prefix`, `key=~regex`,
+    /// `key>=n` (also `>`, `<`, `<=`) or a bare `key` for existence
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let Some(pos) = s.find(['=', '!', '<', '>', '^']) else {
+            let filter = Self::new(s, AttributeOp::Exists);
+            filter.validate()?;
+            return Ok(filter);
+        };
+
+        let (key, rest) = s.split_at(pos);
+        let op = OPERATORS
+            .iter()
+            .find(|op| rest.starts_with(*op))
+            .ok_or_else(|| Error::InvalidParameter(format!("invalid attribute filter: {s}")))?;
+        let value = rest[op.len()..].to_string();
+
+        let number = || {
+            value.parse::<f64>().map_err(|_| {
+                Error::InvalidParameter(format!("attribute {key} {op} expects a number: {value}"))
+            })
+        };
+
+        let op = match *op {
+            "=" => AttributeOp::Eq(value.clone()),
+            "!=" => AttributeOp::Ne(value.clone()),
+            "^=" => AttributeOp::Prefix(value.clone()),
+            "=~" => AttributeOp::Regex(value.clone()),
+            ">" => AttributeOp::Gt(number()?),
+            ">=" => AttributeOp::Gte(number()?),
+            "<" => AttributeOp::Lt(number()?),
+            _ => AttributeOp::Lte(number()?),
+        };
+
+        let filter = Self::new(key.trim(), op);
+        filter.validate()?;
+        Ok(filter)
+    }
+}
@@ -0,0 +1,120 @@
+//! Tests for filter module
+
+use crate::filter::{AttributeFilter, AttributeOp, AttributeScope};
+
+#[test]
+fn test_parse_operators() {
+    let cases = [
+        (
+            "k8s.pod.name=checkout-1",
+            AttributeOp::Eq("checkout-1".to_string()),
+        ),
+        ("env!=prod", AttributeOp::Ne("prod".to_string())),
+        (
+            "k8s.pod.name^=checkout",
+            AttributeOp::Prefix("checkout".to_string()),
+        ),
+        (
+            "http.route=~^/api/v[12]",
+            AttributeOp::Regex("^/api/v[12]".to_string()),
+        ),
+        ("http.status_code>=500", AttributeOp::Gte(500.0)),
+        ("http.status_code>499", AttributeOp::Gt(499.0)),
+        ("duration_ms<=10", AttributeOp::Lte(10.0)),
+        ("duration_ms<1.5", AttributeOp::Lt(1.5)),
+        ("user.id", AttributeOp::Exists),
+    ];
+
+    for (input, op) in cases {
+        let filter: AttributeFilter = input.parse().unwrap();
+        assert_eq!(filter.op, op, "parsing {input}");
+        assert_eq!(filter.scope, AttributeScope::Any);
+    }
+
+    let filter: AttributeFilter = "url=https://example.com/?a=b".parse().unwrap();
+    assert_eq!(filter.key, "url");
+    assert_eq!(
+        filter.op,
+        AttributeOp::Eq("https://example.com/?a=b".to_string())
+    );
+}
+
+#[test]
+fn test_parse_errors() {
+    assert!("http.status_code>=abc".parse::<AttributeFilter>().is_err());
+    assert!("=value".parse::<AttributeFilter>().is_err());
+    assert!("path=~([".parse::<AttributeFilter>().is_err());
+    assert!("key!value".parse::<AttributeFilter>().is_err());
+}
+
+#[test]
+fn test_deserialize() {
+    let filter: AttributeFilter = serde_json::from_value(serde_json::json!({
+        "key": "http.status_code",
+        "op": "eq",
+        "value": 500
+    }))
+    .unwrap();
+    assert_eq!(filter.op, AttributeOp::Eq("500".to_string()));
+    assert_eq!(filter.scope, AttributeScope::Any);
+
+    let filter: AttributeFilter = serde_json::from_value(serde_json::json!({
+        "key": "k8s.pod.name",
+        "op": "exists",
+        "scope": "resource"
+    }))
+    .unwrap();
+    assert_eq!(filter.op, AttributeOp::Exists);
+    assert_eq!(filter.scope, AttributeScope::Resource);
+
+    let filter: AttributeFilter = serde_json::from_value(serde_json::json!({
+        "key": "latency",
+        "op": "gt",
+        "value": 2.5
+    }))
+    .unwrap();
+    assert_eq!(filter.op, AttributeOp::Gt(2.5));
+}
+
+#[test]
+fn test_matches_scopes() {
+    let resource = serde_json::json!({"k8s.pod.name": "checkout-7f9", "env": "prod"});
+    let log = serde_json::json!({"http.status_code": "503", "env": "canary"});
+
+    let any: AttributeFilter = "env=canary".parse().unwrap();
+    assert!(any.matches(&resource, &log));
+
+    let mut resource_only = any;
+    resource_only.scope = AttributeScope::Resource;
+    assert!(!resource_only.matches(&resource, &log));
+
+    let pod: AttributeFilter = "k8s.pod.name^=checkout".parse().unwrap();
+    assert!(pod.matches(&resource, &log));
+
+    let mut log_only = pod;
+    log_only.scope = AttributeScope::Log;
+    assert!(!log_only.matches(&resource, &log));
+}
+
+#[test]
+fn test_matches_operators() {
+    let resource = serde_json::json!({});
+    let log = serde_json::json!({"http.status_code": 503, "route": "/api/v2/orders"});
+
+    let check = |expr: &str| {
+        expr.parse::<AttributeFilter>()
+            .unwrap()
+            .matches(&resource, &log)
+    };
+
+    assert!(check("http.status_code=503"));
+    assert!(check("http.status_code>=500"));
+    assert!(!check("http.status_code<500"));
+    assert!(check("route=~^/api/v[12]/"));
+    assert!(check("http.status_code"));
+    assert!(!check("missing"));
+    // Missing keys read as the empty string
+    assert!(check("missing!=x"));
+    assert!(!check("missing>0"));
+    assert!(!check("route>0"));
+}
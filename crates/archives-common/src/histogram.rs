@@ -0,0 +1,296 @@
+//! Histogram and summary arithmetic
+//!
+//! OTEL histograms store `BucketCounts` for the ranges delimited by
+//! `ExplicitBounds`: bucket `i` counts values in `(bounds[i-1], bounds[i]]`,
+//! and the last bucket is unbounded above. Exponential histograms are merged
+//! at a common scale and then laid out as explicit buckets, so both kinds
+//! share quantile interpolation and heatmaps. These helpers difference
+//! cumulative points, merge histograms and interpolate quantiles the same way
+//! for every storage backend.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::Temporality;
+
+/// Observations added since the previous point of the same series.
+///
+/// Delta points are increases already. A cumulative point is differenced
+/// against `previous`; a drop in the total, a change of layout or a new
+/// series start is a reset, so the point itself is the increase. Without a
+/// previous point nothing is known to have been added.
+pub fn bucket_increase(
+    temporality: Temporality,
+    previous: Option<&[u64]>,
+    counts: &[u64],
+    reset: bool,
+) -> Vec<u64> {
+    match (temporality, previous) {
+        (Temporality::Delta, _) => counts.to_vec(),
+        (Temporality::Cumulative, None) => vec![0; counts.len()],
+        (Temporality::Cumulative, Some(prev)) => {
+            let dropped = counts.iter().sum::<u64>() < prev.iter().sum::<u64>();
+            if reset || dropped || prev.len() != counts.len() {
+                counts.to_vec()
+            } else {
+                counts
+                    .iter()
+                    .zip(prev)
+                    .map(|(c, p)| c.saturating_sub(*p))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Add `counts` (laid out by `bounds`) into `target` (laid out by `target_bounds`).
+///
+/// With different layouts, each source bucket is moved to the first target
+/// bucket whose upper bound covers the source upper bound.
+pub fn merge_into(target_bounds: &[f64], target: &mut [u64], bounds: &[f64], counts: &[u64]) {
+    if target.is_empty() {
+        return;
+    }
+    if bounds == target_bounds && counts.len() == target.len() {
+        for (t, c) in target.iter_mut().zip(counts) {
+            *t += c;
+        }
+        return;
+    }
+
+    let last = target.len() - 1;
+    for (i, count) in counts.iter().enumerate() {
+        let index = bounds.get(i).map_or(last, |upper| {
+            target_bounds
+                .iter()
+                .position(|b| b >= upper)
+                .unwrap_or(last)
+        });
+        target[index.min(last)] += count;
+    }
+}
+
+/// Quantile `level` (0..=1) estimated by linear interpolation within the
+/// bucket holding the target rank, like Prometheus `histogram_quantile`.
+///
+/// The first bucket's lower bound is taken as 0 when its upper bound is
+/// positive; a rank in the unbounded last bucket returns the highest finite
+/// bound. Returns `None` for an empty histogram.
+pub fn quantile(bounds: &[f64], counts: &[u64], level: f64) -> Option<f64> {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let rank = level.clamp(0.0, 1.0) * total as f64;
+    let mut below = 0u64;
+    for (i, &count) in counts.iter().enumerate() {
+        #[allow(clippy::cast_precision_loss)]
+        let cumulative = (below + count) as f64;
+        if count > 0 && cumulative >= rank {
+            let Some(&upper) = bounds.get(i) else {
+                return bounds.last().copied().or(Some(0.0));
+            };
+            let lower = match i {
+                0 if upper > 0.0 => 0.0,
+                0 => upper,
+                _ => bounds[i - 1],
+            };
+            #[allow(clippy::cast_precision_loss)]
+            let fraction = (rank - below as f64) / count as f64;
+            return Some((upper - lower).mul_add(fraction, lower));
+        }
+        below += count;
+    }
+    bounds.last().copied()
+}
+
+/// Exponential histogram buckets, keyed by bucket index.
+///
+/// At scale `s` the base is `2^(2^-s)`; positive bucket `i` counts values in
+/// `(base^i, base^(i+1)]` and negative bucket `i` the mirrored range.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExponentialHistogram {
+    /// Resolution; each step down halves the number of buckets per power of 2
+    pub scale: i32,
+    /// Observations of (approximately) zero
+    #[serde(default)]
+    pub zero_count: u64,
+    /// Counts of positive buckets
+    #[serde(default)]
+    pub positive: BTreeMap<i32, u64>,
+    /// Counts of negative buckets, by the index of the absolute value
+    #[serde(default)]
+    pub negative: BTreeMap<i32, u64>,
+}
+
+impl ExponentialHistogram {
+    /// Build from the dense OTLP layout: counts start at bucket `offset`
+    pub fn from_dense(
+        scale: i32,
+        zero_count: u64,
+        positive_offset: i32,
+        positive: &[u64],
+        negative_offset: i32,
+        negative: &[u64],
+    ) -> Self {
+        let sparse = |offset: i32, counts: &[u64]| {
+            (offset..)
+                .zip(counts.iter().copied())
+                .filter(|(_, count)| *count > 0)
+                .collect()
+        };
+        Self {
+            scale,
+            zero_count,
+            positive: sparse(positive_offset, positive),
+            negative: sparse(negative_offset, negative),
+        }
+    }
+
+    /// Number of observations
+    pub fn total(&self) -> u64 {
+        self.zero_count + self.positive.values().sum::<u64>() + self.negative.values().sum::<u64>()
+    }
+
+    /// The same observations at a lower (or equal) scale, merging adjacent buckets
+    #[must_use]
+    pub fn downscale(&self, scale: i32) -> Self {
+        let shift = (self.scale - scale).max(0);
+        let rebucket = |buckets: &BTreeMap<i32, u64>| {
+            let mut merged = BTreeMap::new();
+            for (index, count) in buckets {
+                *merged.entry(index >> shift).or_insert(0) += count;
+            }
+            merged
+        };
+        Self {
+            scale: self.scale - shift,
+            zero_count: self.zero_count,
+            positive: rebucket(&self.positive),
+            negative: rebucket(&self.negative),
+        }
+    }
+
+    /// Add `other` into `self` at the lower of the two scales
+    pub fn merge(&mut self, other: &Self) {
+        let scale = self.scale.min(other.scale);
+        *self = self.downscale(scale);
+        let other = other.downscale(scale);
+        self.zero_count += other.zero_count;
+        for (index, count) in other.positive {
+            *self.positive.entry(index).or_insert(0) += count;
+        }
+        for (index, count) in other.negative {
+            *self.negative.entry(index).or_insert(0) += count;
+        }
+    }
+
+    fn base_power(&self, index: i32) -> f64 {
+        (f64::from(index) * 2f64.powi(-self.scale)).exp2()
+    }
+
+    /// Explicit bucket bounds covering every bucket of `self`: an empty edge
+    /// bucket, the negative buckets, the zero bucket, an empty edge bucket and
+    /// the positive buckets. The trailing unbounded bucket stays empty.
+    pub fn explicit_bounds(&self) -> Vec<f64> {
+        let mut bounds = Vec::new();
+        if let (Some(&low), Some(&high)) = (
+            self.negative.keys().next(),
+            self.negative.keys().next_back(),
+        ) {
+            bounds.push(-self.base_power(high + 1));
+            bounds.extend((low..=high).rev().map(|i| -self.base_power(i)));
+        }
+        bounds.push(0.0);
+        if let (Some(&low), Some(&high)) = (
+            self.positive.keys().next(),
+            self.positive.keys().next_back(),
+        ) {
+            bounds.push(self.base_power(low));
+            bounds.extend((low..=high).map(|i| self.base_power(i + 1)));
+        }
+        bounds
+    }
+
+    /// Counts of `self` laid out by `layout.explicit_bounds()`. `layout` must
+    /// be at a scale no higher than `self` and cover all of its buckets, like
+    /// a merge of every histogram being laid out.
+    pub fn explicit_counts(&self, layout: &Self) -> Vec<u64> {
+        let own = self.downscale(layout.scale);
+        let mut counts = Vec::new();
+        if let (Some(&low), Some(&high)) = (
+            layout.negative.keys().next(),
+            layout.negative.keys().next_back(),
+        ) {
+            counts.push(0);
+            counts.extend(
+                (low..=high)
+                    .rev()
+                    .map(|i| own.negative.get(&i).copied().unwrap_or(0)),
+            );
+        }
+        counts.push(own.zero_count);
+        if let (Some(&low), Some(&high)) = (
+            layout.positive.keys().next(),
+            layout.positive.keys().next_back(),
+        ) {
+            counts.push(0);
+            counts.extend((low..=high).map(|i| own.positive.get(&i).copied().unwrap_or(0)));
+        }
+        counts.push(0);
+        counts
+    }
+}
+
+/// Exponential histogram counterpart of [`bucket_increase`]. Points are
+/// compared at their common scale.
+pub fn exponential_increase(
+    temporality: Temporality,
+    previous: Option<&ExponentialHistogram>,
+    current: &ExponentialHistogram,
+    reset: bool,
+) -> ExponentialHistogram {
+    match (temporality, previous) {
+        (Temporality::Delta, _) => current.clone(),
+        (Temporality::Cumulative, None) => ExponentialHistogram {
+            scale: current.scale,
+            ..ExponentialHistogram::default()
+        },
+        (Temporality::Cumulative, Some(prev)) => {
+            if reset || current.total() < prev.total() {
+                return current.clone();
+            }
+            let scale = current.scale.min(prev.scale);
+            let prev = prev.downscale(scale);
+            let mut increase = current.downscale(scale);
+            increase.zero_count = increase.zero_count.saturating_sub(prev.zero_count);
+            for (index, count) in &mut increase.positive {
+                *count = count.saturating_sub(prev.positive.get(index).copied().unwrap_or(0));
+            }
+            for (index, count) in &mut increase.negative {
+                *count = count.saturating_sub(prev.negative.get(index).copied().unwrap_or(0));
+            }
+            increase.positive.retain(|_, count| *count > 0);
+            increase.negative.retain(|_, count| *count > 0);
+            increase
+        }
+    }
+}
+
+/// Increase of a summary's cumulative count and sum since `previous`. A lower
+/// count or a new series start is a reset; the first point contributes nothing.
+pub fn summary_increase(
+    previous: Option<(u64, f64)>,
+    count: u64,
+    sum: f64,
+    reset: bool,
+) -> (u64, f64) {
+    match previous {
+        None => (0, 0.0),
+        Some((prev_count, _)) if reset || count < prev_count => (count, sum),
+        Some((prev_count, prev_sum)) => (count - prev_count, sum - prev_sum),
+    }
+}
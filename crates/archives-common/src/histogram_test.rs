@@ -0,0 +1,118 @@
+//! Tests for histogram module
+
+use crate::{
+    histogram::{
+        bucket_increase, exponential_increase, merge_into, quantile, summary_increase,
+        ExponentialHistogram,
+    },
+    types::Temporality,
+};
+
+const BOUNDS: [f64; 3] = [0.1, 0.5, 1.0];
+
+#[test]
+fn test_quantile_interpolates_within_bucket() {
+    // 10 observations in (0.1, 0.5]
+    let counts = [0, 10, 0, 0];
+    let p50 = quantile(&BOUNDS, &counts, 0.5).unwrap();
+    assert!((p50 - 0.3).abs() < 1e-9);
+
+    // First bucket starts at zero
+    let counts = [4, 0, 0, 0];
+    let p50 = quantile(&BOUNDS, &counts, 0.5).unwrap();
+    assert!((p50 - 0.05).abs() < 1e-9);
+}
+
+#[test]
+fn test_quantile_overflow_bucket_and_empty() {
+    let counts = [1, 0, 0, 9];
+    assert_eq!(quantile(&BOUNDS, &counts, 0.99), Some(1.0));
+    assert_eq!(quantile(&BOUNDS, &[0, 0, 0, 0], 0.5), None);
+}
+
+#[test]
+fn test_merge_same_and_different_layouts() {
+    let mut target = vec![1, 1, 1, 1];
+    merge_into(&BOUNDS, &mut target, &BOUNDS, &[1, 2, 3, 4]);
+    assert_eq!(target, vec![2, 3, 4, 5]);
+
+    // Coarser source: (0.5, 2.0] lands in the overflow bucket, <= 0.5 in (0.1, 0.5]
+    let mut target = vec![0; 4];
+    merge_into(&BOUNDS, &mut target, &[0.5, 2.0], &[3, 5, 7]);
+    assert_eq!(target, vec![0, 3, 0, 12]);
+}
+
+#[test]
+fn test_bucket_increase() {
+    // Delta points are increases already
+    assert_eq!(
+        bucket_increase(Temporality::Delta, Some(&[5, 5]), &[1, 2], false),
+        vec![1, 2]
+    );
+    // First cumulative point contributes nothing
+    assert_eq!(
+        bucket_increase(Temporality::Cumulative, None, &[3, 4], false),
+        vec![0, 0]
+    );
+    assert_eq!(
+        bucket_increase(Temporality::Cumulative, Some(&[3, 4]), &[5, 9], false),
+        vec![2, 5]
+    );
+    // Lower total is a reset
+    assert_eq!(
+        bucket_increase(Temporality::Cumulative, Some(&[3, 4]), &[1, 1], false),
+        vec![1, 1]
+    );
+    assert_eq!(
+        bucket_increase(Temporality::Cumulative, Some(&[3, 4]), &[5, 9], true),
+        vec![5, 9]
+    );
+}
+
+#[test]
+fn test_exponential_layout_and_quantile() {
+    // Scale 0 has base 2: bucket 0 is (1, 2], bucket 1 is (2, 4]
+    let h = ExponentialHistogram::from_dense(0, 0, 0, &[4, 4], 0, &[]);
+    let bounds = h.explicit_bounds();
+    let counts = h.explicit_counts(&h);
+    assert_eq!(bounds, [0.0, 1.0, 2.0, 4.0]);
+    assert_eq!(counts, [0, 0, 4, 4, 0]);
+    assert_eq!(quantile(&bounds, &counts, 0.5), Some(2.0));
+    assert_eq!(quantile(&bounds, &counts, 0.75), Some(3.0));
+
+    // Negative buckets come first, most negative on top
+    let h = ExponentialHistogram::from_dense(0, 2, 0, &[], 1, &[3]);
+    assert_eq!(h.explicit_bounds(), [-4.0, -2.0, 0.0]);
+    assert_eq!(h.explicit_counts(&h), [0, 3, 2, 0]);
+}
+
+#[test]
+fn test_exponential_merge_downscales() {
+    let mut h = ExponentialHistogram::from_dense(0, 1, 0, &[4, 4], 0, &[]);
+    // Scale 1 bucket 2 is (2, 2.83], which is bucket 1 at scale 0
+    h.merge(&ExponentialHistogram::from_dense(1, 0, 2, &[5], 0, &[]));
+    assert_eq!(h.scale, 0);
+    assert_eq!(h.total(), 14);
+    assert_eq!(h.positive.get(&1), Some(&9));
+
+    let coarse = h.downscale(-1);
+    assert_eq!(coarse.positive.get(&0), Some(&13));
+}
+
+#[test]
+fn test_exponential_and_summary_increase() {
+    let first = ExponentialHistogram::from_dense(1, 0, 0, &[2, 2], 0, &[]);
+    let second = ExponentialHistogram::from_dense(0, 1, 0, &[6], 0, &[]);
+    assert_eq!(
+        exponential_increase(Temporality::Cumulative, None, &first, false).total(),
+        0
+    );
+    // Compared at scale 0: 4 -> 6 in bucket 0, plus one zero
+    let increase = exponential_increase(Temporality::Cumulative, Some(&first), &second, false);
+    assert_eq!(increase.positive.get(&0), Some(&2));
+    assert_eq!(increase.zero_count, 1);
+
+    assert_eq!(summary_increase(None, 10, 5.0, false), (0, 0.0));
+    assert_eq!(summary_increase(Some((10, 5.0)), 15, 8.0, false), (5, 3.0));
+    assert_eq!(summary_increase(Some((10, 5.0)), 3, 1.0, false), (3, 1.0));
+}
@@ -2,18 +2,68 @@
 //!
 //! Shared types, utilities, and ClickHouse client for the Archives observability platform.
 
+pub mod cache;
 pub mod clickhouse;
 pub mod config;
+pub mod cursor;
 pub mod error;
+#[cfg(test)]
+mod fake_clickhouse;
+pub mod filter;
+pub mod histogram;
+pub mod limits;
+pub mod memory;
+pub mod migrations;
+pub mod pattern;
+pub mod query;
+pub mod resilience;
+pub mod retention;
+pub mod rollup;
+pub mod schema;
+pub mod store;
+pub mod tenant;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 pub mod types;
 
+#[cfg(test)]
+mod cache_test;
+#[cfg(test)]
+mod clickhouse_test;
 #[cfg(test)]
 mod config_test;
 #[cfg(test)]
+mod cursor_test;
+#[cfg(test)]
 mod error_test;
 #[cfg(test)]
+mod filter_test;
+#[cfg(test)]
+mod histogram_test;
+#[cfg(test)]
+mod limits_test;
+#[cfg(test)]
+mod memory_test;
+#[cfg(test)]
+mod migrations_test;
+#[cfg(test)]
+mod pattern_test;
+#[cfg(test)]
+mod query_test;
+#[cfg(test)]
+mod resilience_test;
+#[cfg(test)]
+mod retention_test;
+#[cfg(test)]
+mod rollup_test;
+#[cfg(test)]
+mod schema_test;
+#[cfg(test)]
+mod tenant_test;
+#[cfg(test)]
 mod types_test;
 
 pub use config::Config;
 pub use error::{Error, Result};
+pub use store::{LogStore, MetricStore, SharedStore, Store};
 pub use types::{LogEntry, LogSeverity, Metric, MetricType};
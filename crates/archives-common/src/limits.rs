@@ -0,0 +1,290 @@
+//! Query guardrails
+//!
+//! [`LimitedStore`] wraps a storage backend and rejects requests that exceed
+//! the configured [`LimitsConfig`] before they reach the database: time
+//! ranges that are too long, limits that are too large and bucket intervals
+//! that give too many points per series. Both servers get their store from
+//! [`crate::store::from_config`], which applies the wrapper, so every API
+//! endpoint and MCP tool goes through the same checks.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use uuid::Uuid;
+
+use crate::{
+    cache::CacheStats,
+    config::LimitsConfig,
+    error::{Error, Result},
+    resilience::BreakerStatus,
+    store::{
+        DatabaseStats, ErrorSummary, HistogramSeries, LabelKey, LabelQueryParams, LabelValue,
+        LogPage, LogSearchParams, LogStore, LogStream, LogVolumeBucket, MetricCatalogParams,
+        MetricInfo, MetricQueryParams, MetricSeries, MetricStore, ServiceInfo, SharedStore, Store,
+        SummarySeries,
+    },
+    tenant::TenantScope,
+    types::{LogEntry, TimeRange},
+};
+
+/// Default `max_export_limit`, and the limit of exports that do not ask for
+/// one when there is no maximum
+pub const DEFAULT_EXPORT_LIMIT: u64 = 1_000_000;
+
+impl LimitsConfig {
+    /// Check the time range of a log query
+    pub fn check_log_range(&self, time_range: &TimeRange) -> Result<()> {
+        check_range("log", time_range, self.max_log_range_hours)
+    }
+
+    /// Check the time range of a metric or catalog query
+    pub fn check_metric_range(&self, time_range: &TimeRange) -> Result<()> {
+        check_range("metric", time_range, self.max_metric_range_hours)
+    }
+
+    /// Check the number of rows a request asks for
+    pub fn check_limit(&self, limit: u64) -> Result<()> {
+        if self.max_limit > 0 && limit > self.max_limit {
+            return Err(Error::InvalidParameter(format!(
+                "limit {limit} exceeds the maximum of {}",
+                self.max_limit
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check the number of rows a streamed export asks for
+    pub fn check_export_limit(&self, limit: u64) -> Result<()> {
+        if self.max_export_limit > 0 && limit > self.max_export_limit {
+            return Err(Error::InvalidParameter(format!(
+                "limit {limit} exceeds the maximum of {} for exports",
+                self.max_export_limit
+            )));
+        }
+        Ok(())
+    }
+
+    /// Limit of an export that does not ask for one. Without a maximum this
+    /// is still [`DEFAULT_EXPORT_LIMIT`]: an export of every matching row has
+    /// to ask for its limit.
+    pub const fn default_export_limit(&self) -> u64 {
+        if self.max_export_limit == 0 {
+            DEFAULT_EXPORT_LIMIT
+        } else {
+            self.max_export_limit
+        }
+    }
+
+    /// Check the number of `interval_seconds` buckets in `time_range`
+    pub fn check_points(&self, time_range: &TimeRange, interval_seconds: u32) -> Result<()> {
+        if interval_seconds == 0 {
+            return Err(Error::InvalidParameter(
+                "interval_seconds must be at least 1".to_string(),
+            ));
+        }
+        let seconds = (time_range.end - time_range.start).num_seconds().max(0);
+        let points = seconds.unsigned_abs() / u64::from(interval_seconds);
+        if self.max_points > 0 && points > self.max_points {
+            return Err(Error::InvalidParameter(format!(
+                "interval_seconds {interval_seconds} gives {points} points per series, more than \
+                 the maximum of {}; use a larger interval or a shorter time range",
+                self.max_points
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_log_search(&self, params: &LogSearchParams) -> Result<()> {
+        self.check_log_range(&params.time_range)?;
+        self.check_limit(params.pagination.limit)
+    }
+
+    fn check_metric_query(&self, params: &MetricQueryParams) -> Result<()> {
+        self.check_metric_range(&params.time_range)?;
+        self.check_points(&params.time_range, params.interval_seconds.unwrap_or(60))
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn check_range(kind: &str, time_range: &TimeRange, max_hours: u32) -> Result<()> {
+    let seconds = (time_range.end - time_range.start).num_seconds();
+    if max_hours > 0 && seconds > i64::from(max_hours) * 3600 {
+        return Err(Error::InvalidParameter(format!(
+            "time range of {:.1}h exceeds the maximum of {max_hours}h for {kind} queries",
+            seconds as f64 / 3600.0
+        )));
+    }
+    Ok(())
+}
+
+/// Store wrapper enforcing [`LimitsConfig`] on every query
+pub struct LimitedStore {
+    inner: SharedStore,
+    limits: LimitsConfig,
+}
+
+impl LimitedStore {
+    /// Wrap `inner` with the given limits
+    pub fn new(inner: SharedStore, limits: LimitsConfig) -> Self {
+        Self { inner, limits }
+    }
+}
+
+#[async_trait]
+impl LogStore for LimitedStore {
+    async fn search_logs(&self, params: &LogSearchParams) -> Result<Vec<LogEntry>> {
+        self.limits.check_log_search(params)?;
+        self.inner.search_logs(params).await
+    }
+
+    async fn count_logs(&self, time_range: &TimeRange) -> Result<u64> {
+        self.limits.check_log_range(time_range)?;
+        self.inner.count_logs(time_range).await
+    }
+
+    async fn get_log(&self, id: Uuid) -> Result<Option<LogEntry>> {
+        self.inner.get_log(id).await
+    }
+
+    async fn count_logs_over_time(
+        &self,
+        params: &LogSearchParams,
+        interval_seconds: u32,
+    ) -> Result<Vec<LogVolumeBucket>> {
+        self.limits.check_log_range(&params.time_range)?;
+        self.limits
+            .check_points(&params.time_range, interval_seconds)?;
+        self.inner
+            .count_logs_over_time(params, interval_seconds)
+            .await
+    }
+
+    async fn error_summary(&self, params: &LogSearchParams) -> Result<ErrorSummary> {
+        self.limits.check_log_search(params)?;
+        self.inner.error_summary(params).await
+    }
+
+    async fn log_attribute_keys(&self, params: &LabelQueryParams) -> Result<Vec<LabelKey>> {
+        self.limits.check_log_range(&params.time_range)?;
+        self.inner.log_attribute_keys(params).await
+    }
+
+    async fn log_attribute_values(
+        &self,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>> {
+        self.limits.check_log_range(&params.time_range)?;
+        self.limits.check_limit(params.limit)?;
+        self.inner.log_attribute_values(key, params).await
+    }
+
+    async fn search_logs_page(&self, params: &LogSearchParams) -> Result<LogPage> {
+        self.limits.check_log_search(params)?;
+        self.inner.search_logs_page(params).await
+    }
+
+    async fn get_trace_logs(
+        &self,
+        trace_id: &str,
+        time_range: &TimeRange,
+        limit: u64,
+    ) -> Result<Vec<LogEntry>> {
+        self.limits.check_log_range(time_range)?;
+        self.limits.check_limit(limit)?;
+        self.inner.get_trace_logs(trace_id, time_range, limit).await
+    }
+
+    fn stream_logs<'a>(&'a self, params: &'a LogSearchParams) -> LogStream<'a> {
+        let checked = self
+            .limits
+            .check_log_range(&params.time_range)
+            .and_then(|()| self.limits.check_export_limit(params.pagination.limit));
+        match checked {
+            Ok(()) => self.inner.stream_logs(params),
+            Err(e) => stream::once(async { Err(e) }).boxed(),
+        }
+    }
+}
+
+#[async_trait]
+impl MetricStore for LimitedStore {
+    async fn list_metric_names(&self) -> Result<Vec<String>> {
+        self.inner.list_metric_names().await
+    }
+
+    async fn query_metrics(&self, params: &MetricQueryParams) -> Result<Vec<MetricSeries>> {
+        self.limits.check_metric_query(params)?;
+        self.inner.query_metrics(params).await
+    }
+
+    async fn query_histogram(&self, params: &MetricQueryParams) -> Result<Vec<HistogramSeries>> {
+        self.limits.check_metric_query(params)?;
+        self.inner.query_histogram(params).await
+    }
+
+    async fn metric_catalog(&self, params: &MetricCatalogParams) -> Result<Vec<MetricInfo>> {
+        self.limits.check_metric_range(&params.time_range)?;
+        self.inner.metric_catalog(params).await
+    }
+
+    async fn query_summary(&self, params: &MetricQueryParams) -> Result<Vec<SummarySeries>> {
+        self.limits.check_metric_query(params)?;
+        self.inner.query_summary(params).await
+    }
+
+    async fn metric_label_keys(
+        &self,
+        metric_name: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelKey>> {
+        self.limits.check_metric_range(&params.time_range)?;
+        self.inner.metric_label_keys(metric_name, params).await
+    }
+
+    async fn metric_label_values(
+        &self,
+        metric_name: &str,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>> {
+        self.limits.check_metric_range(&params.time_range)?;
+        self.limits.check_limit(params.limit)?;
+        self.inner
+            .metric_label_values(metric_name, key, params)
+            .await
+    }
+}
+
+#[async_trait]
+impl Store for LimitedStore {
+    async fn health_check(&self) -> Result<bool> {
+        self.inner.health_check().await
+    }
+
+    async fn get_stats(&self) -> Result<DatabaseStats> {
+        self.inner.get_stats().await
+    }
+
+    async fn list_services(&self, time_range: &TimeRange) -> Result<Vec<ServiceInfo>> {
+        // The catalog reads logs too, so the shorter log range applies
+        self.limits.check_log_range(time_range)?;
+        self.inner.list_services(time_range).await
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn breaker_status(&self) -> Option<BreakerStatus> {
+        self.inner.breaker_status()
+    }
+
+    fn for_tenant(&self, scope: &TenantScope) -> Result<SharedStore> {
+        Ok(Arc::new(Self::new(
+            self.inner.for_tenant(scope)?,
+            self.limits.clone(),
+        )))
+    }
+}
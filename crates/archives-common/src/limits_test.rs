@@ -0,0 +1,105 @@
+//! Tests for limits module
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    config::LimitsConfig,
+    limits::{LimitedStore, DEFAULT_EXPORT_LIMIT},
+    memory::MemoryStore,
+    store::{LogSearchParams, LogStore, MetricQueryParams, MetricStore},
+    types::{Aggregation, Pagination, TimeRange},
+    Error,
+};
+
+fn hours(hours: i64) -> TimeRange {
+    let end = DateTime::<Utc>::from_timestamp(1_700_000_000, 0).unwrap();
+    TimeRange {
+        start: end - Duration::hours(hours),
+        end,
+    }
+}
+
+#[test]
+fn test_check_ranges() {
+    let limits = LimitsConfig::default();
+    assert!(limits.check_log_range(&hours(168)).is_ok());
+    assert!(matches!(
+        limits.check_log_range(&hours(169)),
+        Err(Error::InvalidParameter(_))
+    ));
+    assert!(limits.check_metric_range(&hours(720)).is_ok());
+    assert!(limits.check_metric_range(&hours(721)).is_err());
+
+    // Zero disables a limit
+    let unlimited = LimitsConfig {
+        max_log_range_hours: 0,
+        ..LimitsConfig::default()
+    };
+    assert!(unlimited.check_log_range(&hours(24 * 365)).is_ok());
+}
+
+#[test]
+fn test_check_limit_and_points() {
+    let limits = LimitsConfig::default();
+    assert!(limits.check_limit(10_000).is_ok());
+    assert!(limits.check_limit(10_000_000).is_err());
+
+    assert!(limits.check_export_limit(1_000_000).is_ok());
+    assert!(limits.check_export_limit(1_000_001).is_err());
+    assert_eq!(limits.default_export_limit(), 1_000_000);
+
+    // Without a maximum, only an export asking for it is unbounded
+    let unlimited = LimitsConfig {
+        max_export_limit: 0,
+        ..LimitsConfig::default()
+    };
+    assert!(unlimited.check_export_limit(u64::MAX).is_ok());
+    assert_eq!(unlimited.default_export_limit(), DEFAULT_EXPORT_LIMIT);
+
+    // 1-second buckets over 30 days
+    assert!(limits.check_points(&hours(720), 1).is_err());
+    assert!(limits.check_points(&hours(720), 300).is_ok());
+    assert!(limits.check_points(&hours(1), 0).is_err());
+}
+
+#[tokio::test]
+async fn test_limited_store_rejects_before_querying() {
+    let store = LimitedStore::new(Arc::new(MemoryStore::new()), LimitsConfig::default());
+
+    let search = LogSearchParams {
+        time_range: hours(1),
+        pagination: Pagination {
+            offset: 0,
+            limit: 10_000_000,
+        },
+        ..LogSearchParams::default()
+    };
+    let err = store.search_logs(&search).await.unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("limit 10000000 exceeds the maximum of 10000"));
+    // The page probe asks for one more row, but the requested limit is checked
+    let page = LogSearchParams {
+        pagination: Pagination {
+            offset: 0,
+            limit: 10_000,
+        },
+        ..search
+    };
+    assert!(store.search_logs_page(&page).await.is_ok());
+
+    let query = MetricQueryParams {
+        metric_name: "cpu".to_string(),
+        time_range: hours(720),
+        aggregation: Aggregation::Avg,
+        interval_seconds: Some(1),
+        labels: Vec::new(),
+        group_by: Vec::new(),
+    };
+    assert!(matches!(
+        store.query_metrics(&query).await,
+        Err(Error::InvalidParameter(_))
+    ));
+}
@@ -0,0 +1,1027 @@
+//! In-memory storage backend
+//!
+//! Keeps logs and metrics in process memory and applies the same filter
+//! semantics as the ClickHouse queries. Used for local demos and unit tests;
+//! demos can seed it from a fixture file with `[storage] fixture`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::{Arc, RwLock, RwLockReadGuard},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    cursor,
+    error::{Error, Result},
+    filter::{attribute_text, AttributeScope},
+    histogram::{self, ExponentialHistogram},
+    pattern,
+    store::{
+        DatabaseStats, ErrorPattern, ErrorSummary, HistogramSeries, LabelKey, LabelQueryParams,
+        LabelValue, LogSearchParams, LogStore, LogVolumeBucket, MetricCatalogParams,
+        MetricDataPoint, MetricInfo, MetricQueryParams, MetricSeries, MetricStore, ServiceInfo,
+        SharedStore, Store, SummarySeries, SERVICE_RESOURCE_KEYS,
+    },
+    tenant::TenantScope,
+    types::{
+        Aggregation, LogEntry, LogSeverity, Metric, MetricType, QuantileValue, Temporality,
+        TimeRange,
+    },
+};
+
+/// Storage backend holding all data in memory
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStore {
+    logs: Arc<RwLock<Vec<LogEntry>>>,
+    metrics: Arc<RwLock<Vec<Metric>>>,
+    /// Tenant whose rows this view reads, see [`Self::with_tenant`]
+    tenant: Option<TenantScope>,
+}
+
+/// Contents of a fixture file, see [`MemoryStore::from_fixture`]
+#[derive(Deserialize)]
+struct Fixture {
+    #[serde(default)]
+    logs: Vec<LogEntry>,
+    #[serde(default)]
+    metrics: Vec<Metric>,
+}
+
+/// Rows of one kind visible through a store view
+struct Visible<'a, T> {
+    rows: RwLockReadGuard<'a, Vec<T>>,
+    tenant: Option<&'a TenantScope>,
+}
+
+impl<T: TenantRow> Visible<'_, T> {
+    fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.rows.iter().filter(move |row| {
+            self.tenant
+                .map_or(true, |scope| scope.owns(row.resource_attributes()))
+        })
+    }
+}
+
+/// A row carrying the resource attribute that names its tenant
+trait TenantRow {
+    fn resource_attributes(&self) -> &serde_json::Value;
+}
+
+impl TenantRow for LogEntry {
+    fn resource_attributes(&self) -> &serde_json::Value {
+        &self.resource_attributes
+    }
+}
+
+impl TenantRow for Metric {
+    fn resource_attributes(&self) -> &serde_json::Value {
+        &self.resource_attributes
+    }
+}
+
+impl MemoryStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a store holding the rows of a JSON fixture file.
+    ///
+    /// The file is an object with optional `logs` and `metrics` arrays of
+    /// [`LogEntry`] and [`Metric`] rows, so a saved `/v1/logs/search`
+    /// response is a valid fixture. Log IDs may be omitted.
+    pub fn from_fixture(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let fixture_error = |e: &dyn std::fmt::Display| {
+            Error::Config(format!("storage fixture {}: {e}", path.display()))
+        };
+        let text = std::fs::read_to_string(path).map_err(|e| fixture_error(&e))?;
+        let fixture: Fixture = serde_json::from_str(&text).map_err(|e| fixture_error(&e))?;
+
+        let store = Self::new();
+        store.insert_logs(fixture.logs);
+        store.insert_metrics(fixture.metrics);
+        Ok(store)
+    }
+
+    /// A view of the same data that only reads the rows of one tenant, as a
+    /// scoped ClickHouse client does. Inserts through the view are visible
+    /// to the store and all its views. Fails if this view is scoped to
+    /// another tenant already.
+    pub fn with_tenant(&self, scope: &TenantScope) -> Result<Self> {
+        if let Some(ref current) = self.tenant {
+            if current != scope {
+                return Err(Error::Forbidden(format!(
+                    "store is restricted to tenant {:?}",
+                    current.tenant()
+                )));
+            }
+        }
+        Ok(Self {
+            tenant: Some(scope.clone()),
+            ..self.clone()
+        })
+    }
+
+    /// Log entries visible through this view. Every read goes through here,
+    /// so a scoped view cannot return another tenant's rows.
+    fn logs(&self) -> Visible<'_, LogEntry> {
+        Visible {
+            rows: self.logs.read().expect("log store lock poisoned"),
+            tenant: self.tenant.as_ref(),
+        }
+    }
+
+    /// Metric data points visible through this view
+    fn metrics(&self) -> Visible<'_, Metric> {
+        Visible {
+            rows: self.metrics.read().expect("metric store lock poisoned"),
+            tenant: self.tenant.as_ref(),
+        }
+    }
+
+    /// Add log entries to the store. IDs are replaced with the stable ID
+    /// derived from each row, as ClickHouse returns them.
+    pub fn insert_logs(&self, logs: impl IntoIterator<Item = LogEntry>) {
+        self.logs
+            .write()
+            .expect("log store lock poisoned")
+            .extend(logs.into_iter().map(|mut entry| {
+                entry.id = cursor::log_id(&entry);
+                entry
+            }));
+    }
+
+    /// Add metric data points to the store
+    pub fn insert_metrics(&self, metrics: impl IntoIterator<Item = Metric>) {
+        self.metrics
+            .write()
+            .expect("metric store lock poisoned")
+            .extend(metrics);
+    }
+
+    /// Type of `name` if it is a histogram, exponential histogram or summary
+    fn distribution_type(&self, name: &str) -> Option<MetricType> {
+        self.metrics()
+            .iter()
+            .find(|m| {
+                m.name == name
+                    && matches!(
+                        m.metric_type,
+                        MetricType::Histogram
+                            | MetricType::ExponentialHistogram
+                            | MetricType::Summary
+                    )
+            })
+            .map(|m| m.metric_type)
+    }
+
+    /// Series of `metric_type` points matching the query, each sorted by
+    /// time. Reads one interval before the range so the first bucket has a
+    /// predecessor.
+    fn select_series(
+        &self,
+        params: &MetricQueryParams,
+        metric_type: MetricType,
+    ) -> Vec<Vec<Metric>> {
+        let interval_seconds = i64::from(params.interval_seconds.unwrap_or(60).max(1));
+        let scan_range = TimeRange {
+            start: params.time_range.start - chrono::Duration::seconds(interval_seconds),
+            end: params.time_range.end,
+        };
+
+        let metrics = self.metrics();
+        let matching: Vec<&Metric> = metrics
+            .iter()
+            .filter(|m| {
+                m.metric_type == metric_type
+                    && m.name == params.metric_name
+                    && scan_range.contains(m.timestamp)
+                    && params
+                        .labels
+                        .iter()
+                        .all(|f| f.matches(&m.resource_attributes, &m.attributes))
+            })
+            .collect();
+
+        let series = by_series(matching)
+            .into_iter()
+            .map(|points| points.into_iter().cloned().collect())
+            .collect();
+        drop(metrics);
+        series
+    }
+}
+
+/// Whether a log entry passes the search filters (mirrors the ClickHouse WHERE clause)
+fn matches_log(params: &LogSearchParams, log: &LogEntry) -> bool {
+    if !params.time_range.contains(log.timestamp) {
+        return false;
+    }
+    if let Some(min_severity) = params.min_severity {
+        if log.severity.to_severity_number() < min_severity.to_severity_number() {
+            return false;
+        }
+    }
+    if let Some(ref text) = params.text_query {
+        if !log.body.to_lowercase().contains(&text.to_lowercase()) {
+            return false;
+        }
+    }
+    if let Some(ref service) = params.service_name {
+        if log.service_name.as_deref().unwrap_or_default() != service {
+            return false;
+        }
+    }
+    if let Some(ref trace_id) = params.trace_id {
+        if !log
+            .trace_id
+            .as_deref()
+            .unwrap_or_default()
+            .eq_ignore_ascii_case(trace_id)
+        {
+            return false;
+        }
+    }
+    if let Some(ref span_id) = params.span_id {
+        if !log
+            .span_id
+            .as_deref()
+            .unwrap_or_default()
+            .eq_ignore_ascii_case(span_id)
+        {
+            return false;
+        }
+    }
+    let body = log.body.to_lowercase();
+    if !params
+        .body_terms
+        .iter()
+        .all(|t| body.contains(&t.to_lowercase()))
+    {
+        return false;
+    }
+    if params
+        .excluded_body_terms
+        .iter()
+        .any(|t| body.contains(&t.to_lowercase()))
+    {
+        return false;
+    }
+    params
+        .attribute_filters
+        .iter()
+        .all(|f| f.matches(&log.resource_attributes, &log.log_attributes))
+}
+
+/// Start of the bucket containing `timestamp`, aligned to the Unix epoch
+/// like ClickHouse `toStartOfInterval`
+fn bucket_start(timestamp: DateTime<Utc>, interval_seconds: i64) -> DateTime<Utc> {
+    let secs = timestamp.timestamp().div_euclid(interval_seconds) * interval_seconds;
+    DateTime::from_timestamp(secs, 0).unwrap_or_default()
+}
+
+/// Linearly interpolated quantile of `values`
+fn quantile(values: &mut [f64], level: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    #[allow(clippy::cast_precision_loss)]
+    let pos = level * (values.len() - 1) as f64;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let lower = pos.floor() as usize;
+    let upper = (lower + 1).min(values.len() - 1);
+    let frac = pos - pos.floor();
+    values[lower].mul_add(1.0 - frac, values[upper] * frac)
+}
+
+/// Aggregate the values of one bucket. Counter aggregations receive the
+/// per-point increases from [`counter_increases`].
+#[allow(clippy::cast_precision_loss)]
+fn aggregate(aggregation: Aggregation, values: &mut [f64], interval_seconds: i64) -> f64 {
+    match aggregation {
+        Aggregation::Avg => values.iter().sum::<f64>() / values.len() as f64,
+        Aggregation::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+        Aggregation::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        Aggregation::Sum | Aggregation::Increase => values.iter().sum(),
+        Aggregation::Count => values.len() as f64,
+        Aggregation::P50 => quantile(values, 0.5),
+        Aggregation::P90 => quantile(values, 0.9),
+        Aggregation::P99 => quantile(values, 0.99),
+        Aggregation::Rate => values.iter().sum::<f64>() / interval_seconds as f64,
+    }
+}
+
+/// Increase of a counter at each data point, oldest first. `points` must be
+/// one series sorted by time. Delta points are increases already; cumulative
+/// points are differenced against their predecessor, and a new start time or
+/// a drop in a monotonic sum is a reset, so the new value is the increase.
+/// The first cumulative point has no predecessor and contributes nothing.
+fn counter_increases(points: &[&Metric]) -> Vec<(DateTime<Utc>, f64)> {
+    let mut previous: Option<(&Metric, f64)> = None;
+    points
+        .iter()
+        .filter_map(|m| m.value.map(|value| (*m, value)))
+        .map(|(m, value)| {
+            let increase = match (m.temporality, previous) {
+                (Temporality::Delta, _) => value,
+                (Temporality::Cumulative, None) => 0.0,
+                (Temporality::Cumulative, Some((p, prev)))
+                    if m.is_monotonic && (restarted(p, m) || value < prev) =>
+                {
+                    value
+                }
+                (Temporality::Cumulative, Some((_, prev))) => value - prev,
+            };
+            previous = Some((m, value));
+            (m.timestamp, increase)
+        })
+        .collect()
+}
+
+/// Whether `current` starts a new series period after `previous`, as when the
+/// reporting process restarted
+fn restarted(previous: &Metric, current: &Metric) -> bool {
+    previous.start_time != current.start_time
+}
+
+#[async_trait]
+impl LogStore for MemoryStore {
+    async fn search_logs(&self, params: &LogSearchParams) -> Result<Vec<LogEntry>> {
+        params.validate()?;
+
+        let mut matched: Vec<LogEntry> = self
+            .logs()
+            .iter()
+            .filter(|l| matches_log(params, l))
+            .filter(|l| params.cursor.map_or(true, |c| c.precedes(l)))
+            .cloned()
+            .collect();
+        matched.sort_by(cursor::compare_desc);
+
+        let offset = usize::try_from(params.pagination.offset).unwrap_or(usize::MAX);
+        let limit = usize::try_from(params.pagination.limit).unwrap_or(usize::MAX);
+
+        Ok(matched.into_iter().skip(offset).take(limit).collect())
+    }
+
+    async fn count_logs(&self, time_range: &TimeRange) -> Result<u64> {
+        Ok(self
+            .logs()
+            .iter()
+            .filter(|l| time_range.contains(l.timestamp))
+            .count() as u64)
+    }
+
+    async fn get_log(&self, id: Uuid) -> Result<Option<LogEntry>> {
+        Ok(self.logs().iter().find(|l| l.id == id).cloned())
+    }
+
+    async fn count_logs_over_time(
+        &self,
+        params: &LogSearchParams,
+        interval_seconds: u32,
+    ) -> Result<Vec<LogVolumeBucket>> {
+        params.validate()?;
+        LogVolumeBucket::validate_interval(&params.time_range, interval_seconds)?;
+
+        let logs = self.logs();
+        Ok(LogVolumeBucket::fill(
+            &params.time_range,
+            interval_seconds,
+            logs.iter().filter(|l| matches_log(params, l)).map(|l| {
+                (
+                    bucket_start(l.timestamp, i64::from(interval_seconds)),
+                    l.severity,
+                    1,
+                )
+            }),
+        ))
+    }
+
+    async fn error_summary(&self, params: &LogSearchParams) -> Result<ErrorSummary> {
+        params.validate()?;
+
+        let logs = self.logs();
+        let mut summary = ErrorSummary::default();
+        let mut patterns: BTreeMap<String, ErrorPattern> = BTreeMap::new();
+        for log in logs.iter().filter(|l| matches_log(params, l)) {
+            let service = log.service_name.clone().unwrap_or_default();
+            summary.total += 1;
+            *summary.services.entry(service.clone()).or_default() += 1;
+
+            let template = pattern::mask(&log.body);
+            let entry = patterns
+                .entry(template.clone())
+                .or_insert_with(|| ErrorPattern {
+                    pattern: template,
+                    count: 0,
+                    samples: Vec::new(),
+                    first_seen: log.timestamp,
+                    last_seen: log.timestamp,
+                    services: BTreeMap::new(),
+                });
+            entry.count += 1;
+            entry.first_seen = entry.first_seen.min(log.timestamp);
+            entry.last_seen = entry.last_seen.max(log.timestamp);
+            if entry.samples.len() < ErrorPattern::MAX_SAMPLES && !entry.samples.contains(&log.body)
+            {
+                entry.samples.push(log.body.clone());
+            }
+            *entry.services.entry(service).or_default() += 1;
+        }
+        drop(logs);
+
+        // Templates are keyed in order, so ties stay sorted by template
+        summary.patterns = patterns.into_values().collect();
+        summary.patterns.sort_by_key(|p| std::cmp::Reverse(p.count));
+        summary
+            .patterns
+            .truncate(usize::try_from(params.pagination.limit).unwrap_or(usize::MAX));
+        Ok(summary)
+    }
+
+    async fn log_attribute_keys(&self, params: &LabelQueryParams) -> Result<Vec<LabelKey>> {
+        params.validate(None)?;
+        let logs = self.logs();
+        Ok(label_keys(
+            logs.iter()
+                .filter(|l| label_row(params, l.timestamp, l.service_name.as_deref()))
+                .map(|l| (&l.resource_attributes, &l.log_attributes)),
+            params.scope,
+        ))
+    }
+
+    async fn log_attribute_values(
+        &self,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>> {
+        params.validate(Some(key))?;
+        let logs = self.logs();
+        Ok(label_values(
+            logs.iter()
+                .filter(|l| label_row(params, l.timestamp, l.service_name.as_deref()))
+                .map(|l| (&l.resource_attributes, &l.log_attributes)),
+            key,
+            params,
+        ))
+    }
+}
+
+#[async_trait]
+impl MetricStore for MemoryStore {
+    async fn list_metric_names(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = self.metrics().iter().map(|m| m.name.clone()).collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    async fn query_metrics(&self, params: &MetricQueryParams) -> Result<Vec<MetricSeries>> {
+        params.validate()?;
+        match self.distribution_type(&params.metric_name) {
+            Some(MetricType::Summary) => {
+                return self
+                    .query_summary(params)
+                    .await?
+                    .into_iter()
+                    .map(|series| series.summarize(params.aggregation))
+                    .collect();
+            }
+            Some(_) => {
+                return self
+                    .query_histogram(params)
+                    .await?
+                    .into_iter()
+                    .map(|series| series.summarize(params.aggregation))
+                    .collect();
+            }
+            None => {}
+        }
+        let interval_seconds = i64::from(params.interval_seconds.unwrap_or(60).max(1));
+        let counter = params.aggregation.is_counter();
+
+        // Counters look back one interval so the first bucket has a predecessor
+        let lookback = if counter {
+            chrono::Duration::seconds(interval_seconds)
+        } else {
+            chrono::Duration::zero()
+        };
+        let scan_range = TimeRange {
+            start: params.time_range.start - lookback,
+            end: params.time_range.end,
+        };
+
+        let group_of = |metric: &Metric| group_values(&params.group_by, metric);
+
+        let metrics = self.metrics();
+        let matching: Vec<&Metric> = metrics
+            .iter()
+            .filter(|m| {
+                let type_matches = if counter {
+                    m.metric_type == MetricType::Sum
+                } else {
+                    matches!(m.metric_type, MetricType::Gauge | MetricType::Sum)
+                };
+                type_matches
+                    && m.name == params.metric_name
+                    && scan_range.contains(m.timestamp)
+                    && params
+                        .labels
+                        .iter()
+                        .all(|f| f.matches(&m.resource_attributes, &m.attributes))
+            })
+            .collect();
+
+        let samples: Vec<(Vec<String>, DateTime<Utc>, f64)> = if counter {
+            by_series(matching)
+                .into_iter()
+                .flat_map(|points| {
+                    let group = group_of(points[0]);
+                    counter_increases(&points)
+                        .into_iter()
+                        .map(move |(timestamp, increase)| (group.clone(), timestamp, increase))
+                })
+                .collect()
+        } else {
+            matching
+                .into_iter()
+                .filter_map(|m| m.value.map(|value| (group_of(m), m.timestamp, value)))
+                .collect()
+        };
+        drop(metrics);
+
+        let mut series: BTreeMap<Vec<String>, BTreeMap<DateTime<Utc>, Vec<f64>>> = BTreeMap::new();
+        for (group, timestamp, value) in samples {
+            if params.time_range.contains(timestamp) {
+                series
+                    .entry(group)
+                    .or_default()
+                    .entry(bucket_start(timestamp, interval_seconds))
+                    .or_default()
+                    .push(value);
+            }
+        }
+
+        Ok(series
+            .into_iter()
+            .map(|(group, buckets)| MetricSeries {
+                labels: params.group_by.iter().cloned().zip(group).collect(),
+                points: buckets
+                    .into_iter()
+                    .map(|(timestamp, mut values)| MetricDataPoint {
+                        timestamp,
+                        value: aggregate(params.aggregation, &mut values, interval_seconds),
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+
+    async fn query_histogram(&self, params: &MetricQueryParams) -> Result<Vec<HistogramSeries>> {
+        params.validate()?;
+        let interval_seconds = i64::from(params.interval_seconds.unwrap_or(60).max(1));
+        let labels = |group: Vec<String>| params.group_by.iter().cloned().zip(group).collect();
+
+        if self.distribution_type(&params.metric_name) == Some(MetricType::ExponentialHistogram) {
+            let mut groups: BTreeMap<Vec<String>, Vec<(DateTime<Utc>, ExponentialHistogram)>> =
+                BTreeMap::new();
+            for points in self.select_series(params, MetricType::ExponentialHistogram) {
+                let group = groups
+                    .entry(group_values(&params.group_by, &points[0]))
+                    .or_default();
+                let mut previous: Option<(&Metric, &ExponentialHistogram)> = None;
+                for m in &points {
+                    let Some(current) = m.exponential_histogram.as_ref() else {
+                        continue;
+                    };
+                    let increase = histogram::exponential_increase(
+                        m.temporality,
+                        previous.map(|(_, p)| p),
+                        current,
+                        previous.is_some_and(|(p, _)| restarted(p, m)),
+                    );
+                    previous = Some((m, current));
+                    if params.time_range.contains(m.timestamp) {
+                        group.push((bucket_start(m.timestamp, interval_seconds), increase));
+                    }
+                }
+            }
+            return Ok(groups
+                .into_iter()
+                .filter(|(_, samples)| !samples.is_empty())
+                .map(|(group, samples)| HistogramSeries::merge_exponential(labels(group), samples))
+                .collect());
+        }
+
+        let mut groups: BTreeMap<Vec<String>, Vec<HistogramSample>> = BTreeMap::new();
+        for points in self.select_series(params, MetricType::Histogram) {
+            let group = groups
+                .entry(group_values(&params.group_by, &points[0]))
+                .or_default();
+            let mut previous: Option<&Metric> = None;
+            for m in &points {
+                let reset = previous
+                    .is_some_and(|p| restarted(p, m) || p.explicit_bounds != m.explicit_bounds);
+                let increase = histogram::bucket_increase(
+                    m.temporality,
+                    previous.map(|p| p.bucket_counts.as_slice()),
+                    &m.bucket_counts,
+                    reset,
+                );
+                previous = Some(m);
+                if params.time_range.contains(m.timestamp) {
+                    group.push((
+                        bucket_start(m.timestamp, interval_seconds),
+                        m.explicit_bounds.clone(),
+                        increase,
+                    ));
+                }
+            }
+        }
+
+        Ok(groups
+            .into_iter()
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|(group, samples)| HistogramSeries::merge(labels(group), samples))
+            .collect())
+    }
+
+    async fn metric_catalog(&self, params: &MetricCatalogParams) -> Result<Vec<MetricInfo>> {
+        type Entry = (MetricInfo, BTreeSet<(String, String)>);
+        let metrics = self.metrics();
+        let mut catalog: BTreeMap<(&str, &str), Entry> = BTreeMap::new();
+        for m in metrics.iter().filter(|m| {
+            params.time_range.contains(m.timestamp)
+                && params
+                    .prefix
+                    .as_deref()
+                    .map_or(true, |prefix| m.name.starts_with(prefix))
+                && params
+                    .service_name
+                    .as_deref()
+                    .map_or(true, |service| m.service_name.as_deref() == Some(service))
+        }) {
+            let (info, series) = catalog
+                .entry((m.name.as_str(), m.metric_type.table_name()))
+                .or_insert_with(|| {
+                    let info = MetricInfo {
+                        name: m.name.clone(),
+                        metric_type: m.metric_type,
+                        unit: None,
+                        description: None,
+                        services: Vec::new(),
+                        series_count: 0,
+                        last_seen: m.timestamp,
+                    };
+                    (info, BTreeSet::new())
+                });
+            let non_empty = |s: &Option<String>| s.clone().filter(|s| !s.is_empty());
+            info.unit = info.unit.take().or_else(|| non_empty(&m.unit));
+            info.description = info
+                .description
+                .take()
+                .or_else(|| non_empty(&m.description));
+            if let Some(service) = non_empty(&m.service_name) {
+                if !info.services.contains(&service) {
+                    info.services.push(service);
+                }
+            }
+            info.last_seen = info.last_seen.max(m.timestamp);
+            series.insert((m.resource_attributes.to_string(), m.attributes.to_string()));
+        }
+
+        let catalog = catalog
+            .into_values()
+            .map(|(mut info, series)| {
+                info.services.sort();
+                info.series_count = series.len() as u64;
+                info
+            })
+            .collect();
+        drop(metrics);
+        Ok(catalog)
+    }
+
+    async fn query_summary(&self, params: &MetricQueryParams) -> Result<Vec<SummarySeries>> {
+        params.validate()?;
+        let interval_seconds = i64::from(params.interval_seconds.unwrap_or(60).max(1));
+
+        let mut groups: BTreeMap<Vec<String>, Vec<SummarySample>> = BTreeMap::new();
+        for points in self.select_series(params, MetricType::Summary) {
+            let group = groups
+                .entry(group_values(&params.group_by, &points[0]))
+                .or_default();
+            let mut previous: Option<&Metric> = None;
+            for m in &points {
+                let (count, sum) = histogram::summary_increase(
+                    previous.map(|p| (p.count.unwrap_or(0), p.sum.unwrap_or(0.0))),
+                    m.count.unwrap_or(0),
+                    m.sum.unwrap_or(0.0),
+                    previous.is_some_and(|p| restarted(p, m)),
+                );
+                previous = Some(m);
+                if params.time_range.contains(m.timestamp) {
+                    group.push((
+                        bucket_start(m.timestamp, interval_seconds),
+                        count,
+                        sum,
+                        m.quantile_values.clone(),
+                    ));
+                }
+            }
+        }
+
+        Ok(groups
+            .into_iter()
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|(group, samples)| {
+                SummarySeries::merge(
+                    params.group_by.iter().cloned().zip(group).collect(),
+                    samples,
+                )
+            })
+            .collect())
+    }
+
+    async fn metric_label_keys(
+        &self,
+        metric_name: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelKey>> {
+        params.validate(None)?;
+        let metrics = self.metrics();
+        Ok(label_keys(
+            metrics
+                .iter()
+                .filter(|m| {
+                    m.name == metric_name
+                        && label_row(params, m.timestamp, m.service_name.as_deref())
+                })
+                .map(|m| (&m.resource_attributes, &m.attributes)),
+            params.scope,
+        ))
+    }
+
+    async fn metric_label_values(
+        &self,
+        metric_name: &str,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>> {
+        params.validate(Some(key))?;
+        let metrics = self.metrics();
+        Ok(label_values(
+            metrics
+                .iter()
+                .filter(|m| {
+                    m.name == metric_name
+                        && label_row(params, m.timestamp, m.service_name.as_deref())
+                })
+                .map(|m| (&m.resource_attributes, &m.attributes)),
+            key,
+            params,
+        ))
+    }
+}
+
+/// Whether a row passes the time range and service filters of a label query
+fn label_row(params: &LabelQueryParams, timestamp: DateTime<Utc>, service: Option<&str>) -> bool {
+    params.time_range.contains(timestamp)
+        && params
+            .service_name
+            .as_deref()
+            .map_or(true, |s| service == Some(s))
+}
+
+/// Keys of the attribute maps selected by `scope`, given `(resource, attributes)`
+/// pairs, with distinct values and the number of rows carrying each key
+fn label_keys<'a>(
+    rows: impl Iterator<Item = (&'a serde_json::Value, &'a serde_json::Value)>,
+    scope: AttributeScope,
+) -> Vec<LabelKey> {
+    // Keyed by (key, is_resource) so log attributes sort before resource ones
+    let mut keys: BTreeMap<(String, bool), (BTreeSet<String>, u64)> = BTreeMap::new();
+    for (resource, attributes) in rows {
+        for (map_scope, map) in [
+            (AttributeScope::Log, attributes),
+            (AttributeScope::Resource, resource),
+        ] {
+            if scope != AttributeScope::Any && scope != map_scope {
+                continue;
+            }
+            for (key, value) in map.as_object().into_iter().flatten() {
+                let (values, count) = keys
+                    .entry((key.clone(), map_scope == AttributeScope::Resource))
+                    .or_default();
+                values.insert(attribute_text(Some(value)));
+                *count += 1;
+            }
+        }
+    }
+
+    keys.into_iter()
+        .map(|((key, resource), (values, count))| LabelKey {
+            key,
+            scope: if resource {
+                AttributeScope::Resource
+            } else {
+                AttributeScope::Log
+            },
+            cardinality: values.len() as u64,
+            count,
+        })
+        .collect()
+}
+
+/// Most frequent non-empty values of `key`, given `(resource, attributes)` pairs
+fn label_values<'a>(
+    rows: impl Iterator<Item = (&'a serde_json::Value, &'a serde_json::Value)>,
+    key: &str,
+    params: &LabelQueryParams,
+) -> Vec<LabelValue> {
+    let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+    for (resource, attributes) in rows {
+        let value = attribute_text(params.scope.lookup(key, resource, attributes));
+        if !value.is_empty() {
+            *counts.entry(value).or_default() += 1;
+        }
+    }
+
+    let mut values: Vec<LabelValue> = counts
+        .into_iter()
+        .map(|(value, count)| LabelValue { value, count })
+        .collect();
+    // Stable sort keeps ties in value order
+    values.sort_by_key(|v| std::cmp::Reverse(v.count));
+    values.truncate(usize::try_from(params.limit).unwrap_or(usize::MAX));
+    values
+}
+
+/// Time bucket, bucket bounds and bucket increases of one histogram point
+type HistogramSample = (DateTime<Utc>, Vec<f64>, Vec<u64>);
+
+/// Time bucket, count and sum increases and quantiles of one summary point
+type SummarySample = (DateTime<Utc>, u64, f64, Vec<QuantileValue>);
+
+/// Values of the `group_by` keys for a data point
+fn group_values(group_by: &[String], metric: &Metric) -> Vec<String> {
+    group_by
+        .iter()
+        .map(|key| {
+            attribute_text(AttributeScope::Any.lookup(
+                key,
+                &metric.resource_attributes,
+                &metric.attributes,
+            ))
+        })
+        .collect()
+}
+
+/// Split data points into series (distinct attribute sets), each sorted by time
+fn by_series(points: Vec<&Metric>) -> Vec<Vec<&Metric>> {
+    let mut series: BTreeMap<(String, String), Vec<&Metric>> = BTreeMap::new();
+    for metric in points {
+        series
+            .entry((
+                metric.resource_attributes.to_string(),
+                metric.attributes.to_string(),
+            ))
+            .or_default()
+            .push(metric);
+    }
+    series
+        .into_values()
+        .map(|mut points| {
+            points.sort_by_key(|m| m.timestamp);
+            points
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn health_check(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    async fn get_stats(&self) -> Result<DatabaseStats> {
+        let mut stats = DatabaseStats::default();
+
+        for log in self.logs().iter() {
+            stats.log_count += 1;
+            stats.log_bytes += serde_json::to_vec(log).map_or(0, |v| v.len() as u64);
+        }
+        for metric in self.metrics().iter() {
+            stats.metric_count += 1;
+            stats.metric_bytes += serde_json::to_vec(metric).map_or(0, |v| v.len() as u64);
+        }
+
+        Ok(stats)
+    }
+
+    async fn list_services(&self, time_range: &TimeRange) -> Result<Vec<ServiceInfo>> {
+        let mut services: BTreeMap<String, ServiceTally> = BTreeMap::new();
+
+        for log in self.logs().iter() {
+            if !time_range.contains(log.timestamp) {
+                continue;
+            }
+            let Some((info, _, values)) =
+                service_tally(&mut services, log.service_name.as_deref(), log.timestamp)
+            else {
+                continue;
+            };
+            info.log_count += 1;
+            if log.severity.to_severity_number() >= LogSeverity::Error.to_severity_number() {
+                info.error_count += 1;
+            }
+            count_resource_values(values, &log.resource_attributes, true);
+        }
+        for metric in self.metrics().iter() {
+            if !time_range.contains(metric.timestamp) {
+                continue;
+            }
+            let Some((_, names, values)) = service_tally(
+                &mut services,
+                metric.service_name.as_deref(),
+                metric.timestamp,
+            ) else {
+                continue;
+            };
+            names.insert(metric.name.clone());
+            count_resource_values(values, &metric.resource_attributes, false);
+        }
+
+        Ok(services
+            .into_values()
+            .map(|(mut info, names, values)| {
+                info.metric_count = names.len() as u64;
+                // Log occurrences decide first, as in the ClickHouse query
+                info.resource_attributes = values
+                    .into_iter()
+                    .filter_map(|(key, counts)| {
+                        let (value, _) = counts.into_iter().max_by_key(|(_, count)| *count)?;
+                        Some((key.to_string(), value))
+                    })
+                    .collect();
+                info.with_error_ratio()
+            })
+            .collect())
+    }
+
+    fn for_tenant(&self, scope: &TenantScope) -> Result<SharedStore> {
+        Ok(Arc::new(self.with_tenant(scope)?))
+    }
+}
+
+/// Service catalog entry, metric names, and per resource key the (log, metric)
+/// occurrences of each value
+type ServiceTally = (
+    ServiceInfo,
+    BTreeSet<String>,
+    BTreeMap<&'static str, BTreeMap<String, (u64, u64)>>,
+);
+
+/// Tally of a named service, widened to cover `timestamp`
+fn service_tally<'a>(
+    services: &'a mut BTreeMap<String, ServiceTally>,
+    service: Option<&str>,
+    timestamp: DateTime<Utc>,
+) -> Option<&'a mut ServiceTally> {
+    let name = service.filter(|s| !s.is_empty())?;
+    let tally = services.entry(name.to_string()).or_insert_with(|| {
+        let info = ServiceInfo::new(name.to_string(), timestamp);
+        (info, BTreeSet::new(), BTreeMap::new())
+    });
+    tally.0.seen(timestamp, timestamp);
+    Some(tally)
+}
+
+/// Count the non-empty values of the service catalog resource keys, as log
+/// or metric occurrences
+fn count_resource_values(
+    values: &mut BTreeMap<&'static str, BTreeMap<String, (u64, u64)>>,
+    resource: &serde_json::Value,
+    from_logs: bool,
+) {
+    for key in SERVICE_RESOURCE_KEYS {
+        let value = attribute_text(resource.get(key));
+        if value.is_empty() {
+            continue;
+        }
+        let (logs, metrics) = values.entry(key).or_default().entry(value).or_default();
+        if from_logs {
+            *logs += 1;
+        } else {
+            *metrics += 1;
+        }
+    }
+}
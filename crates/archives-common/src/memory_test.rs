@@ -0,0 +1,1018 @@
+//! Tests for memory module
+
+use futures::TryStreamExt;
+
+use crate::{
+    cursor::{self, LogCursor},
+    filter::{AttributeFilter, AttributeOp, AttributeScope},
+    histogram::ExponentialHistogram,
+    memory::MemoryStore,
+    store::{
+        LabelKey, LabelQueryParams, LabelValue, LogSearchParams, LogStore, LogVolumeBucket,
+        MetricCatalogParams, MetricQueryParams, MetricStore, Store,
+    },
+    test_support::{all_time, gauge, log, ts},
+    types::{
+        Aggregation, LogEntry, LogSeverity, Metric, MetricType, Pagination, QuantileValue,
+        Temporality, TimeRange,
+    },
+};
+
+fn sample_store() -> MemoryStore {
+    let store = MemoryStore::new();
+    store.insert_logs([
+        log(0, LogSeverity::Info, "api", "request handled"),
+        log(10, LogSeverity::Error, "api", "Connection refused"),
+        log(20, LogSeverity::Warn, "worker", "retrying connection"),
+        log(30, LogSeverity::Debug, "worker", "tick"),
+        log(40, LogSeverity::Fatal, "api", "out of memory"),
+    ]);
+    store
+}
+
+#[tokio::test]
+async fn test_search_logs_newest_first() {
+    let store = sample_store();
+    let params = LogSearchParams {
+        time_range: all_time(),
+        ..LogSearchParams::default()
+    };
+
+    let logs = store.search_logs(&params).await.unwrap();
+    assert_eq!(logs.len(), 5);
+    assert_eq!(logs[0].body, "out of memory");
+    assert_eq!(logs[4].body, "request handled");
+}
+
+#[tokio::test]
+async fn test_search_logs_time_range_end_exclusive() {
+    let store = sample_store();
+    let params = LogSearchParams {
+        time_range: TimeRange {
+            start: ts(10),
+            end: ts(30),
+        },
+        ..LogSearchParams::default()
+    };
+
+    let logs = store.search_logs(&params).await.unwrap();
+    let bodies: Vec<_> = logs.iter().map(|l| l.body.as_str()).collect();
+    assert_eq!(bodies, ["retrying connection", "Connection refused"]);
+}
+
+#[tokio::test]
+async fn test_search_logs_filters() {
+    let store = sample_store();
+
+    let params = LogSearchParams {
+        time_range: all_time(),
+        min_severity: Some(LogSeverity::Warn),
+        ..LogSearchParams::default()
+    };
+    assert_eq!(store.search_logs(&params).await.unwrap().len(), 3);
+
+    let params = LogSearchParams {
+        time_range: all_time(),
+        text_query: Some("CONNECTION".to_string()),
+        ..LogSearchParams::default()
+    };
+    assert_eq!(store.search_logs(&params).await.unwrap().len(), 2);
+
+    let params = LogSearchParams {
+        time_range: all_time(),
+        text_query: Some("connection".to_string()),
+        service_name: Some("worker".to_string()),
+        ..LogSearchParams::default()
+    };
+    let logs = store.search_logs(&params).await.unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].body, "retrying connection");
+}
+
+#[tokio::test]
+async fn test_search_logs_pagination() {
+    let store = sample_store();
+    let params = LogSearchParams {
+        time_range: all_time(),
+        pagination: Pagination {
+            offset: 1,
+            limit: 2,
+        },
+        ..LogSearchParams::default()
+    };
+
+    let logs = store.search_logs(&params).await.unwrap();
+    let bodies: Vec<_> = logs.iter().map(|l| l.body.as_str()).collect();
+    assert_eq!(bodies, ["tick", "retrying connection"]);
+}
+
+#[tokio::test]
+async fn test_stream_logs_matches_search() {
+    let store = sample_store();
+    let params = LogSearchParams {
+        time_range: all_time(),
+        min_severity: Some(LogSeverity::Warn),
+        ..LogSearchParams::default()
+    };
+
+    let streamed: Vec<LogEntry> = store.stream_logs(&params).try_collect().await.unwrap();
+    let bodies: Vec<_> = streamed.iter().map(|l| l.body.as_str()).collect();
+    assert_eq!(
+        bodies,
+        ["out of memory", "retrying connection", "Connection refused"]
+    );
+
+    // Invalid parameters end the stream with the error
+    let invalid = LogSearchParams {
+        pagination: Pagination {
+            offset: 1,
+            limit: 10,
+        },
+        cursor: Some(LogCursor::for_entry(&streamed[0])),
+        ..params
+    };
+    let result: Result<Vec<LogEntry>, _> = store.stream_logs(&invalid).try_collect().await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_count_logs() {
+    let store = sample_store();
+    assert_eq!(store.count_logs(&all_time()).await.unwrap(), 5);
+
+    let range = TimeRange {
+        start: ts(15),
+        end: ts(35),
+    };
+    assert_eq!(store.count_logs(&range).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn test_list_metric_names() {
+    let store = MemoryStore::new();
+    store.insert_metrics([
+        gauge("memory_bytes", 0, 1.0),
+        gauge("cpu_usage", 0, 1.0),
+        gauge("cpu_usage", 10, 1.0),
+    ]);
+
+    let names = store.list_metric_names().await.unwrap();
+    assert_eq!(names, ["cpu_usage", "memory_bytes"]);
+}
+
+#[tokio::test]
+async fn test_query_metrics_buckets_and_aggregation() {
+    let store = MemoryStore::new();
+    // Base timestamp is aligned to a 20s boundary
+    store.insert_metrics([
+        gauge("cpu_usage", 0, 1.0),
+        gauge("cpu_usage", 5, 3.0),
+        gauge("cpu_usage", 25, 10.0),
+        gauge("other", 5, 100.0),
+    ]);
+
+    let mut params = MetricQueryParams {
+        metric_name: "cpu_usage".to_string(),
+        time_range: all_time(),
+        aggregation: Aggregation::Avg,
+        interval_seconds: Some(20),
+        labels: Vec::new(),
+        group_by: Vec::new(),
+    };
+
+    let series = store.query_metrics(&params).await.unwrap();
+    assert_eq!(series.len(), 1);
+    assert!(series[0].labels.is_empty());
+    let points = &series[0].points;
+    assert_eq!(points.len(), 2);
+    assert_eq!(points[0].timestamp, ts(0));
+    assert!((points[0].value - 2.0).abs() < f64::EPSILON);
+    assert_eq!(points[1].timestamp, ts(20));
+    assert!((points[1].value - 10.0).abs() < f64::EPSILON);
+
+    params.aggregation = Aggregation::Count;
+    let points = &store.query_metrics(&params).await.unwrap()[0].points;
+    assert!((points[0].value - 2.0).abs() < f64::EPSILON);
+
+    params.aggregation = Aggregation::P50;
+    params.interval_seconds = Some(3600);
+    let points = &store.query_metrics(&params).await.unwrap()[0].points;
+    assert_eq!(points.len(), 1);
+    assert!((points[0].value - 3.0).abs() < f64::EPSILON);
+}
+
+#[tokio::test]
+async fn test_get_stats() {
+    let store = sample_store();
+    store.insert_metrics([gauge("cpu_usage", 0, 1.0)]);
+
+    let stats = store.get_stats().await.unwrap();
+    assert_eq!(stats.log_count, 5);
+    assert_eq!(stats.metric_count, 1);
+    assert!(stats.log_bytes > 0);
+    assert!(store.health_check().await.unwrap());
+}
+
+#[tokio::test]
+async fn test_from_fixture() {
+    let saved = sample_store()
+        .search_logs(&LogSearchParams {
+            time_range: all_time(),
+            ..LogSearchParams::default()
+        })
+        .await
+        .unwrap();
+    let mut logs = serde_json::to_value(&saved).unwrap();
+    // Hand-written fixtures leave the IDs out
+    logs[0].as_object_mut().unwrap().remove("id");
+    let fixture = serde_json::json!({
+        "logs": logs,
+        "next_cursor": null,
+        "metrics": [gauge("cpu_usage", 0, 1.0)]
+    });
+    let path = std::env::temp_dir().join(format!("archives-fixture-{}.json", std::process::id()));
+    std::fs::write(&path, fixture.to_string()).unwrap();
+
+    let store = MemoryStore::from_fixture(&path);
+    std::fs::remove_file(&path).unwrap();
+    let store = store.unwrap();
+    let stats = store.get_stats().await.unwrap();
+    assert_eq!((stats.log_count, stats.metric_count), (5, 1));
+    let loaded = store
+        .search_logs(&LogSearchParams {
+            time_range: all_time(),
+            ..LogSearchParams::default()
+        })
+        .await
+        .unwrap();
+    let ids = |logs: &[LogEntry]| logs.iter().map(|l| l.id).collect::<Vec<_>>();
+    assert_eq!(ids(&loaded), ids(&saved));
+
+    let err = MemoryStore::from_fixture(&path).unwrap_err();
+    assert!(err.to_string().contains("storage fixture"), "{err}");
+}
+
+#[tokio::test]
+async fn test_search_logs_attribute_filters() {
+    let store = sample_store();
+    let mut entry = log(50, LogSeverity::Error, "checkout", "payment failed");
+    entry.resource_attributes = serde_json::json!({"k8s.pod.name": "checkout-7f9"});
+    entry.log_attributes = serde_json::json!({"http.status_code": "502"});
+    store.insert_logs([entry]);
+
+    let params = LogSearchParams {
+        time_range: all_time(),
+        attribute_filters: vec![
+            "http.status_code>=500".parse().unwrap(),
+            "k8s.pod.name^=checkout".parse().unwrap(),
+        ],
+        ..LogSearchParams::default()
+    };
+    let logs = store.search_logs(&params).await.unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].body, "payment failed");
+
+    let params = LogSearchParams {
+        time_range: all_time(),
+        attribute_filters: vec![AttributeFilter::new(
+            "route",
+            AttributeOp::Regex("([".to_string()),
+        )],
+        ..LogSearchParams::default()
+    };
+    assert!(store
+        .search_logs(&params)
+        .await
+        .unwrap_err()
+        .to_string()
+        .contains("regex"));
+}
+
+#[tokio::test]
+async fn test_search_logs_cursor_pages() {
+    let store = sample_store();
+    // Same timestamp as an existing row, so the tie-break is exercised
+    store.insert_logs([log(20, LogSeverity::Info, "worker", "same second")]);
+
+    let mut params = LogSearchParams {
+        time_range: all_time(),
+        pagination: Pagination {
+            offset: 0,
+            limit: 2,
+        },
+        ..LogSearchParams::default()
+    };
+
+    let mut bodies = Vec::new();
+    let mut pages = 0;
+    loop {
+        let page = store.search_logs_page(&params).await.unwrap();
+        pages += 1;
+        bodies.extend(page.logs.into_iter().map(|l| l.body));
+        let Some(cursor) = page.next_cursor else {
+            assert!(!page.has_more);
+            break;
+        };
+        assert!(page.has_more);
+        params.cursor = Some(cursor.parse().unwrap());
+
+        // Rows arriving between pages must not shift the remaining ones
+        store.insert_logs([log(50, LogSeverity::Info, "api", "late arrival")]);
+    }
+
+    assert_eq!(pages, 3);
+    assert_eq!(bodies.len(), 6);
+    let all = store
+        .search_logs(&LogSearchParams {
+            time_range: TimeRange {
+                start: ts(0),
+                end: ts(45),
+            },
+            ..LogSearchParams::default()
+        })
+        .await
+        .unwrap();
+    let expected: Vec<_> = all.into_iter().map(|l| l.body).collect();
+    assert_eq!(bodies, expected);
+}
+
+#[tokio::test]
+async fn test_search_logs_cursor_with_offset_rejected() {
+    let store = sample_store();
+    let first = store
+        .search_logs(&LogSearchParams {
+            time_range: all_time(),
+            ..LogSearchParams::default()
+        })
+        .await
+        .unwrap();
+
+    let params = LogSearchParams {
+        time_range: all_time(),
+        pagination: Pagination {
+            offset: 1,
+            limit: 10,
+        },
+        cursor: Some(LogCursor::for_entry(&first[0])),
+        ..LogSearchParams::default()
+    };
+    assert!(store.search_logs(&params).await.is_err());
+}
+
+#[tokio::test]
+async fn test_get_log_by_stable_id() {
+    let store = sample_store();
+    let params = LogSearchParams {
+        time_range: all_time(),
+        ..LogSearchParams::default()
+    };
+
+    let first = store.search_logs(&params).await.unwrap();
+    let second = store.search_logs(&params).await.unwrap();
+    assert_eq!(first[0].id, second[0].id);
+    assert_eq!(first[0].id, cursor::log_id(&first[0]));
+
+    let found = store.get_log(first[2].id).await.unwrap().unwrap();
+    assert_eq!(found.body, first[2].body);
+    assert!(store.get_log(uuid::Uuid::nil()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_trace_and_span_filters() {
+    let store = MemoryStore::new();
+    let traced = |secs, span: &str, service, body| LogEntry {
+        trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+        span_id: Some(span.to_string()),
+        ..log(secs, LogSeverity::Info, service, body)
+    };
+    store.insert_logs([
+        traced(30, "00f067aa0ba902b7", "api", "responded"),
+        traced(10, "00f067aa0ba902b7", "api", "received"),
+        traced(20, "b7ad6b7169203331", "db", "query"),
+        log(15, LogSeverity::Info, "api", "unrelated"),
+    ]);
+
+    let params = LogSearchParams {
+        time_range: all_time(),
+        trace_id: Some("4BF92F3577B34DA6A3CE929D0E0E4736".to_string()),
+        span_id: Some("00f067aa0ba902b7".to_string()),
+        ..LogSearchParams::default()
+    };
+    assert_eq!(store.search_logs(&params).await.unwrap().len(), 2);
+
+    let logs = store
+        .get_trace_logs("4bf92f3577b34da6a3ce929d0e0e4736", &all_time(), 100)
+        .await
+        .unwrap();
+    let bodies: Vec<_> = logs.iter().map(|l| l.body.as_str()).collect();
+    assert_eq!(bodies, ["received", "query", "responded"]);
+
+    let logs = store
+        .get_trace_logs("4bf92f3577b34da6a3ce929d0e0e4736", &all_time(), 2)
+        .await
+        .unwrap();
+    let bodies: Vec<_> = logs.iter().map(|l| l.body.as_str()).collect();
+    assert_eq!(bodies, ["query", "responded"]);
+}
+
+#[tokio::test]
+async fn test_query_metrics_label_filters_and_group_by() {
+    let store = MemoryStore::new();
+    let labelled = |secs, value, region: &str, pod: &str| Metric {
+        resource_attributes: serde_json::json!({"service.name": "checkout"}),
+        attributes: serde_json::json!({"region": region, "pod": pod}),
+        ..gauge("requests", secs, value)
+    };
+    store.insert_metrics([
+        labelled(0, 1.0, "eu", "checkout-1"),
+        labelled(0, 3.0, "eu", "checkout-2"),
+        labelled(0, 10.0, "us", "checkout-1"),
+        labelled(0, 100.0, "us", "cart-1"),
+    ]);
+
+    let mut params = MetricQueryParams {
+        metric_name: "requests".to_string(),
+        time_range: all_time(),
+        aggregation: Aggregation::Sum,
+        interval_seconds: Some(60),
+        labels: vec!["pod=~^checkout-".parse().unwrap()],
+        group_by: vec!["region".to_string()],
+    };
+
+    let series = store.query_metrics(&params).await.unwrap();
+    assert_eq!(series.len(), 2);
+    assert_eq!(series[0].labels["region"], "eu");
+    assert!((series[0].points[0].value - 4.0).abs() < f64::EPSILON);
+    assert_eq!(series[1].labels["region"], "us");
+    assert!((series[1].points[0].value - 10.0).abs() < f64::EPSILON);
+
+    // Group keys fall back to resource attributes
+    params.labels = vec!["region!=eu".parse().unwrap()];
+    params.group_by = vec!["service.name".to_string(), "pod".to_string()];
+    let series = store.query_metrics(&params).await.unwrap();
+    assert_eq!(series.len(), 2);
+    assert_eq!(series[0].labels["service.name"], "checkout");
+    assert_eq!(series[0].labels["pod"], "cart-1");
+
+    params.labels = vec![AttributeFilter::new(
+        "pod",
+        AttributeOp::Regex("(".to_string()),
+    )];
+    assert!(store.query_metrics(&params).await.is_err());
+}
+
+fn counter(secs: i64, value: f64, temporality: Temporality, pod: &str) -> Metric {
+    Metric {
+        metric_type: MetricType::Sum,
+        is_monotonic: true,
+        temporality,
+        attributes: serde_json::json!({ "pod": pod }),
+        ..gauge("requests_total", secs, value)
+    }
+}
+
+fn counter_params(aggregation: Aggregation, start: i64) -> MetricQueryParams {
+    MetricQueryParams {
+        metric_name: "requests_total".to_string(),
+        time_range: TimeRange {
+            start: ts(start),
+            end: ts(3600),
+        },
+        aggregation,
+        interval_seconds: Some(60),
+        labels: Vec::new(),
+        group_by: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_counter_increase_handles_resets() {
+    let store = MemoryStore::new();
+    store.insert_metrics([
+        counter(0, 100.0, Temporality::Cumulative, "a"),
+        counter(30, 130.0, Temporality::Cumulative, "a"),
+        // Reset: the process restarted and counted 5 since
+        counter(70, 5.0, Temporality::Cumulative, "a"),
+        counter(90, 25.0, Temporality::Cumulative, "a"),
+        // A second series is differenced on its own
+        counter(10, 1000.0, Temporality::Cumulative, "b"),
+        counter(80, 1010.0, Temporality::Cumulative, "b"),
+    ]);
+
+    let series = store
+        .query_metrics(&counter_params(Aggregation::Increase, 0))
+        .await
+        .unwrap();
+    let values: Vec<f64> = series[0].points.iter().map(|p| p.value).collect();
+    assert_eq!(values, [30.0, 35.0]);
+
+    let series = store
+        .query_metrics(&counter_params(Aggregation::Rate, 0))
+        .await
+        .unwrap();
+    assert!((series[0].points[1].value - 35.0 / 60.0).abs() < 1e-9);
+
+    // The first bucket is differenced against the point before the range
+    let series = store
+        .query_metrics(&counter_params(Aggregation::Increase, 60))
+        .await
+        .unwrap();
+    assert_eq!(series[0].points.len(), 1);
+    assert!((series[0].points[0].value - 35.0).abs() < f64::EPSILON);
+}
+
+#[tokio::test]
+async fn test_counter_delta_and_non_monotonic() {
+    let store = MemoryStore::new();
+    store.insert_metrics([
+        counter(0, 4.0, Temporality::Delta, "a"),
+        counter(30, 6.0, Temporality::Delta, "a"),
+        Metric {
+            is_monotonic: false,
+            ..counter(0, 10.0, Temporality::Cumulative, "b")
+        },
+        Metric {
+            is_monotonic: false,
+            ..counter(30, 7.0, Temporality::Cumulative, "b")
+        },
+    ]);
+
+    let mut params = counter_params(Aggregation::Increase, 0);
+    params.group_by = vec!["pod".to_string()];
+    let series = store.query_metrics(&params).await.unwrap();
+    assert!((series[0].points[0].value - 10.0).abs() < f64::EPSILON);
+    // An up-down counter going down is a decrease, not a reset
+    assert!((series[1].points[0].value + 3.0).abs() < f64::EPSILON);
+
+    // Value aggregations read sums as-is
+    params.aggregation = Aggregation::Max;
+    params.group_by.clear();
+    let series = store.query_metrics(&params).await.unwrap();
+    assert!((series[0].points[0].value - 10.0).abs() < f64::EPSILON);
+    assert_eq!(store.list_metric_names().await.unwrap(), ["requests_total"]);
+}
+
+fn histogram(secs: i64, counts: [u64; 4], pod: &str) -> Metric {
+    Metric {
+        metric_type: MetricType::Histogram,
+        value: None,
+        temporality: Temporality::Cumulative,
+        bucket_counts: counts.to_vec(),
+        explicit_bounds: vec![0.1, 0.5, 1.0],
+        attributes: serde_json::json!({ "pod": pod }),
+        ..gauge("request_duration_seconds", secs, 0.0)
+    }
+}
+
+#[tokio::test]
+async fn test_histogram_quantiles_and_heatmap() {
+    let store = MemoryStore::new();
+    store.insert_metrics([
+        histogram(0, [0, 0, 0, 0], "a"),
+        histogram(50, [0, 10, 0, 0], "a"),
+        histogram(110, [0, 10, 10, 0], "a"),
+        histogram(0, [5, 0, 0, 0], "b"),
+        histogram(50, [5, 0, 0, 10], "b"),
+    ]);
+
+    let mut params = MetricQueryParams {
+        metric_name: "request_duration_seconds".to_string(),
+        ..counter_params(Aggregation::P50, 40)
+    };
+
+    // Both pods merge into one heatmap; the first points only set the baseline
+    let heatmap = store.query_histogram(&params).await.unwrap();
+    assert_eq!(heatmap.len(), 1);
+    assert_eq!(heatmap[0].bounds, [0.1, 0.5, 1.0]);
+    let counts: Vec<&[u64]> = heatmap[0]
+        .points
+        .iter()
+        .map(|p| p.counts.as_slice())
+        .collect();
+    assert_eq!(counts, [&[0, 10, 0, 10][..], &[0, 0, 10, 0][..]]);
+
+    let series = store.query_metrics(&params).await.unwrap();
+    let values: Vec<f64> = series[0].points.iter().map(|p| p.value).collect();
+    assert_eq!(values, [0.5, 0.75]);
+
+    params.aggregation = Aggregation::Count;
+    let series = store.query_metrics(&params).await.unwrap();
+    assert!((series[0].points[0].value - 20.0).abs() < 1e-9);
+
+    params.aggregation = Aggregation::Avg;
+    assert!(store.query_metrics(&params).await.is_err());
+
+    params.group_by = vec!["pod".to_string()];
+    assert_eq!(store.query_histogram(&params).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_exponential_histogram_quantiles() {
+    let point = |secs, counts: &[u64]| Metric {
+        metric_type: MetricType::ExponentialHistogram,
+        temporality: Temporality::Delta,
+        exponential_histogram: Some(ExponentialHistogram::from_dense(0, 0, 0, counts, 0, &[])),
+        ..histogram(secs, [0; 4], "a")
+    };
+    let store = MemoryStore::new();
+    store.insert_metrics([point(50, &[4]), point(60, &[0, 4])]);
+
+    let params = MetricQueryParams {
+        metric_name: "request_duration_seconds".to_string(),
+        ..counter_params(Aggregation::P50, 40)
+    };
+    let heatmap = store.query_histogram(&params).await.unwrap();
+    assert_eq!(heatmap[0].bounds, [0.0, 1.0, 2.0, 4.0]);
+    assert_eq!(heatmap[0].points[0].counts, [0, 0, 4, 4, 0]);
+
+    let series = store.query_metrics(&params).await.unwrap();
+    assert!((series[0].points[0].value - 2.0).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_summary_quantiles_and_counts() {
+    let point = |secs, count, sum, p99, pod: &str| Metric {
+        metric_type: MetricType::Summary,
+        count: Some(count),
+        sum: Some(sum),
+        quantile_values: vec![
+            QuantileValue {
+                quantile: 0.5,
+                value: 0.1,
+            },
+            QuantileValue {
+                quantile: 0.99,
+                value: p99,
+            },
+        ],
+        ..histogram(secs, [0; 4], pod)
+    };
+    let store = MemoryStore::new();
+    store.insert_metrics([
+        point(0, 100, 10.0, 0.5, "a"),
+        point(50, 110, 12.0, 0.7, "a"),
+        point(0, 10, 1.0, 0.9, "b"),
+        point(50, 20, 4.0, 0.9, "b"),
+    ]);
+
+    let mut params = MetricQueryParams {
+        metric_name: "request_duration_seconds".to_string(),
+        ..counter_params(Aggregation::P99, 40)
+    };
+    let series = store.query_metrics(&params).await.unwrap();
+    assert!((series[0].points[0].value - 0.8).abs() < 1e-9);
+
+    params.aggregation = Aggregation::Count;
+    let series = store.query_metrics(&params).await.unwrap();
+    assert!((series[0].points[0].value - 20.0).abs() < 1e-9);
+
+    params.aggregation = Aggregation::Avg;
+    let series = store.query_metrics(&params).await.unwrap();
+    assert!((series[0].points[0].value - 0.25).abs() < 1e-9);
+
+    params.aggregation = Aggregation::P90;
+    assert!(store.query_metrics(&params).await.is_err());
+
+    params.aggregation = Aggregation::Rate;
+    assert!(store.query_metrics(&params).await.is_err());
+}
+
+#[tokio::test]
+async fn test_new_start_time_is_a_reset() {
+    // Points of a series restarted at ts(40), without a drop in value
+    let started = |start, m| Metric {
+        start_time: Some(ts(start)),
+        ..m
+    };
+    let summary = |secs, count, sum| Metric {
+        metric_type: MetricType::Summary,
+        name: "rpc_duration_seconds".to_string(),
+        count: Some(count),
+        sum: Some(sum),
+        ..histogram(secs, [0; 4], "a")
+    };
+    let exponential = |secs, count| Metric {
+        name: "payload_bytes".to_string(),
+        metric_type: MetricType::ExponentialHistogram,
+        exponential_histogram: Some(ExponentialHistogram::from_dense(0, 0, 0, &[count], 0, &[])),
+        ..histogram(secs, [0; 4], "a")
+    };
+    let store = MemoryStore::new();
+    store.insert_metrics([
+        started(0, counter(0, 100.0, Temporality::Cumulative, "a")),
+        started(40, counter(50, 120.0, Temporality::Cumulative, "a")),
+        started(0, histogram(0, [0, 10, 0, 0], "a")),
+        started(40, histogram(50, [0, 12, 0, 0], "a")),
+        started(0, summary(0, 100, 10.0)),
+        started(40, summary(50, 120, 14.0)),
+        started(0, exponential(0, 4)),
+        started(40, exponential(50, 6)),
+    ]);
+
+    let series = store
+        .query_metrics(&counter_params(Aggregation::Increase, 40))
+        .await
+        .unwrap();
+    assert!((series[0].points[0].value - 120.0).abs() < f64::EPSILON);
+
+    let params = MetricQueryParams {
+        metric_name: "request_duration_seconds".to_string(),
+        ..counter_params(Aggregation::Count, 40)
+    };
+    let heatmap = store.query_histogram(&params).await.unwrap();
+    assert_eq!(heatmap[0].points[0].counts, [0, 12, 0, 0]);
+
+    let params = MetricQueryParams {
+        metric_name: "rpc_duration_seconds".to_string(),
+        ..counter_params(Aggregation::Count, 40)
+    };
+    let series = store.query_metrics(&params).await.unwrap();
+    assert!((series[0].points[0].value - 120.0).abs() < 1e-9);
+
+    let params = MetricQueryParams {
+        metric_name: "payload_bytes".to_string(),
+        ..counter_params(Aggregation::Count, 40)
+    };
+    let heatmap = store.query_histogram(&params).await.unwrap();
+    assert_eq!(heatmap[0].points[0].counts.iter().sum::<u64>(), 6);
+}
+
+#[tokio::test]
+async fn test_metric_catalog() {
+    let store = MemoryStore::new();
+    store.insert_metrics([
+        Metric {
+            unit: Some("s".to_string()),
+            service_name: Some("checkout".to_string()),
+            ..histogram(10, [0; 4], "a")
+        },
+        Metric {
+            service_name: Some("api".to_string()),
+            ..histogram(30, [0; 4], "b")
+        },
+        counter(20, 1.0, Temporality::Cumulative, "a"),
+        // Outside the catalog range
+        gauge("old_gauge", 7200, 1.0),
+    ]);
+
+    let mut params = MetricCatalogParams {
+        time_range: TimeRange {
+            start: ts(0),
+            end: ts(3600),
+        },
+        prefix: None,
+        service_name: None,
+    };
+    let catalog = store.metric_catalog(&params).await.unwrap();
+    let names: Vec<&str> = catalog.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["request_duration_seconds", "requests_total"]);
+    assert_eq!(catalog[0].metric_type, MetricType::Histogram);
+    assert_eq!(catalog[0].unit.as_deref(), Some("s"));
+    assert_eq!(catalog[0].services, ["api", "checkout"]);
+    assert_eq!(catalog[0].series_count, 2);
+    assert_eq!(catalog[0].last_seen, ts(30));
+
+    params.prefix = Some("requests_".to_string());
+    assert_eq!(store.metric_catalog(&params).await.unwrap().len(), 1);
+
+    params.prefix = None;
+    params.service_name = Some("checkout".to_string());
+    let catalog = store.metric_catalog(&params).await.unwrap();
+    assert_eq!(catalog.len(), 1);
+    assert_eq!(catalog[0].series_count, 1);
+}
+
+fn label_params() -> LabelQueryParams {
+    LabelQueryParams {
+        time_range: all_time(),
+        ..LabelQueryParams::default()
+    }
+}
+
+#[tokio::test]
+async fn test_log_attribute_keys_and_values() {
+    let store = MemoryStore::new();
+    let with_attributes = |secs, service, route: &str, region: &str| LogEntry {
+        resource_attributes: serde_json::json!({ "region": region }),
+        log_attributes: serde_json::json!({ "http.route": route }),
+        ..log(secs, LogSeverity::Info, service, "request")
+    };
+    store.insert_logs([
+        with_attributes(0, "api", "/orders", "eu"),
+        with_attributes(10, "api", "/orders", "eu"),
+        with_attributes(20, "api", "/users", "us"),
+        with_attributes(30, "worker", "", "us"),
+        // Outside the range
+        with_attributes(7200, "api", "/health", "eu"),
+    ]);
+
+    let mut params = label_params();
+    let keys = store.log_attribute_keys(&params).await.unwrap();
+    assert_eq!(
+        keys,
+        [
+            LabelKey {
+                key: "http.route".to_string(),
+                scope: AttributeScope::Log,
+                cardinality: 3,
+                count: 4,
+            },
+            LabelKey {
+                key: "region".to_string(),
+                scope: AttributeScope::Resource,
+                cardinality: 2,
+                count: 4,
+            },
+        ]
+    );
+
+    // Empty values are skipped, ties are ordered by value
+    let values = store
+        .log_attribute_values("http.route", &params)
+        .await
+        .unwrap();
+    let value = |value: &str, count| LabelValue {
+        value: value.to_string(),
+        count,
+    };
+    assert_eq!(values, [value("/orders", 2), value("/users", 1)]);
+
+    params.scope = AttributeScope::Resource;
+    assert_eq!(store.log_attribute_keys(&params).await.unwrap().len(), 1);
+    assert!(store
+        .log_attribute_values("http.route", &params)
+        .await
+        .unwrap()
+        .is_empty());
+
+    params.scope = AttributeScope::Any;
+    params.service_name = Some("worker".to_string());
+    params.limit = 1;
+    assert_eq!(
+        store.log_attribute_values("region", &params).await.unwrap(),
+        [value("us", 1)]
+    );
+
+    assert!(store.log_attribute_values("", &params).await.is_err());
+}
+
+#[tokio::test]
+async fn test_metric_label_keys_and_values() {
+    let store = MemoryStore::new();
+    store.insert_metrics([
+        counter(0, 1.0, Temporality::Cumulative, "a"),
+        counter(10, 2.0, Temporality::Cumulative, "a"),
+        counter(20, 1.0, Temporality::Cumulative, "b"),
+        Metric {
+            attributes: serde_json::json!({ "queue": "emails" }),
+            ..gauge("queue_depth", 0, 3.0)
+        },
+    ]);
+
+    let params = label_params();
+    let keys = store
+        .metric_label_keys("requests_total", &params)
+        .await
+        .unwrap();
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].key, "pod");
+    assert_eq!(keys[0].cardinality, 2);
+    assert_eq!(keys[0].count, 3);
+
+    let values = store
+        .metric_label_values("requests_total", "pod", &params)
+        .await
+        .unwrap();
+    let values: Vec<(&str, u64)> = values.iter().map(|v| (v.value.as_str(), v.count)).collect();
+    assert_eq!(values, [("a", 2), ("b", 1)]);
+
+    assert!(store
+        .metric_label_keys("missing", &params)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_list_services() {
+    let store = sample_store();
+    store.insert_logs([LogEntry {
+        resource_attributes: serde_json::json!({ "service.version": "1.4.2" }),
+        ..log(50, LogSeverity::Info, "api", "request handled")
+    }]);
+    store.insert_metrics([
+        Metric {
+            service_name: Some("api".to_string()),
+            resource_attributes: serde_json::json!({
+                "service.version": "1.4.1",
+                "deployment.environment": "prod",
+            }),
+            ..gauge("queue_depth", 60, 1.0)
+        },
+        Metric {
+            service_name: Some("scheduler".to_string()),
+            ..counter(5, 1.0, Temporality::Cumulative, "a")
+        },
+        // Outside the range
+        Metric {
+            service_name: Some("batch".to_string()),
+            ..gauge("queue_depth", 7200, 1.0)
+        },
+    ]);
+
+    let services = store.list_services(&all_time()).await.unwrap();
+    let names: Vec<&str> = services.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["api", "scheduler", "worker"]);
+
+    let api = &services[0];
+    assert_eq!(api.log_count, 4);
+    assert_eq!(api.error_count, 2);
+    assert!((api.error_ratio - 0.5).abs() < 1e-9);
+    assert_eq!(api.metric_count, 1);
+    assert_eq!((api.first_seen, api.last_seen), (ts(0), ts(60)));
+    // The log value wins over the metric one
+    assert_eq!(api.resource_attributes["service.version"], "1.4.2");
+    assert_eq!(api.resource_attributes["deployment.environment"], "prod");
+
+    let scheduler = &services[1];
+    assert_eq!(scheduler.log_count, 0);
+    assert!(scheduler.error_ratio.abs() < 1e-9);
+    assert_eq!(scheduler.metric_count, 1);
+}
+
+#[tokio::test]
+async fn test_count_logs_over_time() {
+    let store = sample_store();
+    let mut params = LogSearchParams {
+        time_range: TimeRange {
+            start: ts(0),
+            end: ts(120),
+        },
+        ..LogSearchParams::default()
+    };
+
+    // ts(0) is 20s into a minute, so the range spans three minute buckets
+    let buckets = store.count_logs_over_time(&params, 60).await.unwrap();
+    let totals: Vec<u64> = buckets.iter().map(|b| b.total).collect();
+    assert_eq!(totals, [4, 1, 0]);
+    assert_eq!(buckets[0].timestamp, ts(-20));
+    assert_eq!(buckets[0].counts.len(), 4);
+    assert_eq!(buckets[1].counts[&LogSeverity::Fatal], 1);
+    assert!(buckets[2].counts.is_empty());
+
+    // Search filters apply
+    params.min_severity = Some(LogSeverity::Warn);
+    let buckets = store.count_logs_over_time(&params, 60).await.unwrap();
+    let totals: Vec<u64> = buckets.iter().map(|b| b.total).collect();
+    assert_eq!(totals, [2, 1, 0]);
+
+    assert!(store.count_logs_over_time(&params, 0).await.is_err());
+    params.time_range.end = ts(3600 * 24 * 30);
+    assert!(store.count_logs_over_time(&params, 1).await.is_err());
+    assert!(LogVolumeBucket::validate_interval(&params.time_range, 3600).is_ok());
+}
+
+#[tokio::test]
+async fn test_error_summary() {
+    let store = MemoryStore::new();
+    store.insert_logs([
+        log(0, LogSeverity::Error, "api", "user 123 not found"),
+        log(10, LogSeverity::Error, "api", "user 456 not found"),
+        log(20, LogSeverity::Fatal, "worker", "user 123 not found"),
+        log(30, LogSeverity::Error, "worker", "queue full"),
+        log(40, LogSeverity::Warn, "api", "user 789 not found"),
+    ]);
+    let mut params = LogSearchParams {
+        time_range: all_time(),
+        min_severity: Some(LogSeverity::Error),
+        pagination: Pagination {
+            offset: 0,
+            limit: 1,
+        },
+        ..LogSearchParams::default()
+    };
+
+    let summary = store.error_summary(&params).await.unwrap();
+    // The total counts patterns past the limit too
+    assert_eq!(summary.total, 4);
+    assert_eq!(summary.services["api"], 2);
+    assert_eq!(summary.services["worker"], 2);
+    assert_eq!(summary.patterns.len(), 1);
+    let top = &summary.patterns[0];
+    assert_eq!(top.pattern, "user <NUM> not found");
+    assert_eq!(top.count, 3);
+    assert_eq!(top.samples, ["user 123 not found", "user 456 not found"]);
+    assert_eq!((top.first_seen, top.last_seen), (ts(0), ts(20)));
+    assert_eq!(top.services["worker"], 1);
+
+    params.service_name = Some("worker".to_string());
+    params.pagination.limit = 10;
+    let summary = store.error_summary(&params).await.unwrap();
+    assert_eq!(summary.total, 2);
+    let patterns: Vec<&str> = summary
+        .patterns
+        .iter()
+        .map(|p| p.pattern.as_str())
+        .collect();
+    assert_eq!(patterns, ["queue full", "user <NUM> not found"]);
+}
@@ -0,0 +1,221 @@
+//! Versioned schema migrations
+//!
+//! The OTEL exporter creates the `otel_*` tables; Archives adds what it needs
+//! on top (TTLs, materialized views) through the ordered SQL files in
+//! `crates/archives-common/migrations`, embedded in the binaries. Applied
+//! versions are recorded in `archives_migrations`, so [`migrate`] only runs
+//! pending migrations and running it again is a no-op. Statements are written
+//! to be idempotent (`IF NOT EXISTS`, `MODIFY`), so a migration interrupted
+//! halfway can simply be applied again.
+//!
+//! The API and MCP servers call [`check_version`] at startup and refuse to
+//! run against a database older than [`SCHEMA_VERSION`]. Migrations are
+//! applied with `archives admin migrate`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use xxhash_rust::xxh64::xxh64;
+
+use crate::{
+    clickhouse::ClickHouseClient,
+    error::{Error, Result},
+};
+
+/// Table recording applied migrations
+pub const MIGRATIONS_TABLE: &str = "archives_migrations";
+
+/// One schema change, applied as a whole
+#[derive(Debug)]
+pub struct Migration {
+    /// Position in [`MIGRATIONS`], starting at 1
+    pub version: u32,
+    /// File name without the version prefix
+    pub name: &'static str,
+    /// Statements separated by `;`
+    pub sql: &'static str,
+    /// Exporter tables the migration alters, which must exist first
+    pub requires: &'static [&'static str],
+}
+
+/// All migrations, in the order they are applied
+pub const MIGRATIONS: [Migration; 3] = [
+    Migration {
+        version: 1,
+        name: "retention_ttl",
+        sql: include_str!("../migrations/0001_retention_ttl.sql"),
+        requires: &[
+            "otel_logs",
+            "otel_metrics_gauge",
+            "otel_metrics_sum",
+            "otel_metrics_histogram",
+        ],
+    },
+    Migration {
+        version: 2,
+        name: "error_summary_mv",
+        sql: include_str!("../migrations/0002_error_summary_mv.sql"),
+        requires: &["otel_logs"],
+    },
+    Migration {
+        version: 3,
+        name: "metric_rollups",
+        sql: include_str!("../migrations/0003_metric_rollups.sql"),
+        requires: &["otel_metrics_gauge", "otel_metrics_sum"],
+    },
+];
+
+/// Schema version this build needs, the version of the last migration
+pub const SCHEMA_VERSION: u32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+impl Migration {
+    /// Hash of the SQL, recorded to notice migrations edited after applying
+    pub fn checksum(&self) -> u64 {
+        xxh64(self.sql.as_bytes(), 0)
+    }
+
+    /// The statements of the migration, without comments
+    pub fn statements(&self) -> Vec<String> {
+        split_statements(self.sql)
+    }
+}
+
+/// Split SQL into statements at `;`, dropping `--` comments. Semicolons and
+/// dashes inside quoted strings are kept.
+pub fn split_statements(sql: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), _) => {
+                current.push(c);
+                if c == '\\' {
+                    current.extend(chars.next());
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            (None, '\'' | '"' | '`') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (None, '-') if chars.peek() == Some(&'-') => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            (None, ';') => statements.push(std::mem::take(&mut current)),
+            (None, _) => current.push(c),
+        }
+    }
+    statements.push(current);
+    statements
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// A row of `archives_migrations`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AppliedMigration {
+    /// Migration version
+    pub version: u32,
+    /// Migration name
+    pub name: String,
+    /// [`Migration::checksum`] when it was applied
+    pub checksum: u64,
+    /// When it was applied
+    pub applied_at: DateTime<Utc>,
+}
+
+/// What [`migrate`] does, or did, to bring the database up to date
+#[derive(Debug, Serialize)]
+pub struct MigrationPlan {
+    /// Highest applied version before migrating
+    pub current_version: u32,
+    /// Version this build needs
+    pub target_version: u32,
+    /// Versions to apply, in order
+    pub pending: Vec<u32>,
+    /// Applied versions whose SQL has changed since
+    pub changed: Vec<u32>,
+}
+
+impl MigrationPlan {
+    /// Compare applied migrations with [`MIGRATIONS`]
+    pub fn new(applied: &[AppliedMigration]) -> Self {
+        let current_version = applied.iter().map(|m| m.version).max().unwrap_or(0);
+        let pending = MIGRATIONS
+            .iter()
+            .filter(|m| !applied.iter().any(|a| a.version == m.version))
+            .map(|m| m.version)
+            .collect();
+        let changed = applied
+            .iter()
+            .filter(|a| {
+                MIGRATIONS
+                    .iter()
+                    .any(|m| m.version == a.version && m.checksum() != a.checksum)
+            })
+            .map(|a| a.version)
+            .collect();
+        Self {
+            current_version,
+            target_version: SCHEMA_VERSION,
+            pending,
+            changed,
+        }
+    }
+
+    /// Pending migrations, in order
+    pub fn pending_migrations(&self) -> impl Iterator<Item = &'static Migration> + '_ {
+        MIGRATIONS
+            .iter()
+            .filter(|m| self.pending.contains(&m.version))
+    }
+
+    /// Whether nothing is left to apply
+    pub fn is_up_to_date(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Apply pending migrations in order, or with `dry_run` only report them.
+/// `client` must not be read-only, so it is built without
+/// [`ClickHouseClient::with_limits`].
+pub async fn migrate(client: &ClickHouseClient, dry_run: bool) -> Result<MigrationPlan> {
+    let plan = MigrationPlan::new(&client.applied_migrations().await?);
+    if dry_run || plan.is_up_to_date() {
+        return Ok(plan);
+    }
+
+    let tables = client.table_names().await?;
+    for migration in plan.pending_migrations() {
+        if let Some(missing) = migration
+            .requires
+            .iter()
+            .find(|t| !tables.iter().any(|name| name == *t))
+        {
+            return Err(Error::Schema(format!(
+                "migration {:04}_{} needs table {missing}, which the OTEL exporter has not \
+                 created yet; start the collector and retry",
+                migration.version, migration.name
+            )));
+        }
+        client.apply_migration(migration).await?;
+    }
+    Ok(plan)
+}
+
+/// Fail unless every migration this build needs has been applied
+pub async fn check_version(client: &ClickHouseClient) -> Result<()> {
+    let plan = MigrationPlan::new(&client.applied_migrations().await?);
+    match plan.pending.first() {
+        None => Ok(()),
+        Some(&first) => Err(Error::Schema(format!(
+            "database schema is at version {} but this build needs version {SCHEMA_VERSION} \
+             (missing {first} and later); run `archives admin migrate`",
+            plan.current_version
+        ))),
+    }
+}
@@ -0,0 +1,254 @@
+//! Tests for migrations module
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::Utc;
+use regex::Regex;
+
+use crate::{
+    fake_clickhouse::{push_string, FakeClickHouse, Received},
+    migrations::{
+        check_version, migrate, split_statements, AppliedMigration, MigrationPlan, MIGRATIONS,
+        MIGRATIONS_TABLE, SCHEMA_VERSION,
+    },
+    store::{LogSearchParams, LogStore},
+    test_support::ts,
+    types::{LogSeverity, TimeRange},
+};
+
+fn applied(version: u32, checksum: u64) -> AppliedMigration {
+    AppliedMigration {
+        version,
+        name: format!("migration_{version}"),
+        checksum,
+        applied_at: Utc::now(),
+    }
+}
+
+#[test]
+fn test_migrations_are_ordered() {
+    for (i, migration) in MIGRATIONS.iter().enumerate() {
+        assert_eq!(migration.version as usize, i + 1);
+        assert!(
+            !migration.statements().is_empty(),
+            "{} is empty",
+            migration.name
+        );
+    }
+    assert_eq!(SCHEMA_VERSION, MIGRATIONS.last().unwrap().version);
+}
+
+#[test]
+fn test_split_statements() {
+    let sql = "-- header; not a statement\n\
+               ALTER TABLE t MODIFY TTL ts + INTERVAL 1 DAY;\n\
+               \n\
+               SELECT 'a;b', \"c--d\" -- trailing; comment\n\
+               FROM t;\n\
+               SELECT 'it\\'s;'";
+    assert_eq!(
+        split_statements(sql),
+        vec![
+            "ALTER TABLE t MODIFY TTL ts + INTERVAL 1 DAY",
+            "SELECT 'a;b', \"c--d\" \nFROM t",
+            "SELECT 'it\\'s;'",
+        ]
+    );
+    assert!(split_statements("-- only a comment\n;\n").is_empty());
+}
+
+#[test]
+fn test_plan() {
+    let all: Vec<u32> = MIGRATIONS.iter().map(|m| m.version).collect();
+    let plan = MigrationPlan::new(&[]);
+    assert_eq!(plan.current_version, 0);
+    assert_eq!(plan.target_version, SCHEMA_VERSION);
+    assert_eq!(plan.pending, all);
+    assert_eq!(plan.pending_migrations().count(), MIGRATIONS.len());
+
+    let first = applied(1, MIGRATIONS[0].checksum());
+    let plan = MigrationPlan::new(std::slice::from_ref(&first));
+    assert_eq!(plan.current_version, 1);
+    assert_eq!(plan.pending, all[1..]);
+    assert!(plan.changed.is_empty());
+
+    // Up to date, with an edited migration reported
+    let mut applied_all: Vec<AppliedMigration> = MIGRATIONS
+        .iter()
+        .map(|m| applied(m.version, m.checksum()))
+        .collect();
+    applied_all[1].checksum = 0;
+    let plan = MigrationPlan::new(&applied_all);
+    assert!(plan.is_up_to_date());
+    assert_eq!(plan.current_version, SCHEMA_VERSION);
+    assert_eq!(plan.changed, vec![2]);
+}
+
+/// Tables and views of a fake database with the statements creating them,
+/// and the rows of `archives_migrations`
+#[derive(Default)]
+struct Database {
+    objects: BTreeMap<String, String>,
+    applied: Vec<(u32, String, u64)>,
+}
+
+/// The view `config/clickhouse/init.sql` created before migrations existed,
+/// with its own engine instead of writing to `error_summary`
+const BASELINE_ERROR_SUMMARY_MV: &str = "CREATE MATERIALIZED VIEW IF NOT EXISTS error_summary_mv \
+     ENGINE = SummingMergeTree() PARTITION BY toYYYYMMDD(Timestamp) \
+     ORDER BY (ServiceName, SeverityText, toStartOfHour(Timestamp)) \
+     AS SELECT ServiceName, SeverityText, toStartOfHour(Timestamp) as Hour, count() as Count \
+     FROM otel_logs WHERE SeverityNumber >= 17 GROUP BY ServiceName, SeverityText, Hour";
+
+/// A deployment set up by the exporter and the baseline `init.sql`
+fn baseline() -> Database {
+    let mut database = Database::default();
+    for table in [
+        "otel_logs",
+        "otel_metrics_gauge",
+        "otel_metrics_sum",
+        "otel_metrics_histogram",
+    ] {
+        database
+            .objects
+            .insert(table.to_string(), format!("CREATE TABLE {table}"));
+    }
+    database.objects.insert(
+        "error_summary_mv".to_string(),
+        BASELINE_ERROR_SUMMARY_MV.to_string(),
+    );
+    database
+}
+
+/// Apply DDL to `database` as the server would, `IF NOT EXISTS` keeping an
+/// existing object as it is, and answer the reads of [`migrate`] and of the
+/// error summary coverage; other reads get no rows
+fn execute(database: &Mutex<Database>, sql: &str) -> Result<Vec<u8>, String> {
+    let create = Regex::new(r"^CREATE (?:TABLE|MATERIALIZED VIEW) (IF NOT EXISTS )?(\w+)").unwrap();
+    let drop = Regex::new(r"^DROP (?:TABLE|VIEW) (IF EXISTS )?(\w+)").unwrap();
+    let alter = Regex::new(r"^ALTER TABLE (\w+)").unwrap();
+    let insert = Regex::new(r"^INSERT INTO \w+ .*VALUES \((\d+),\s*'(\w+)',\s*(\d+)\)").unwrap();
+
+    let mut database = database.lock().unwrap();
+    let mut rows = Vec::new();
+    if let Some(c) = create.captures(sql) {
+        match database.objects.get(&c[2]) {
+            Some(_) if c.get(1).is_some() => {}
+            Some(_) => return Err(format!("Table {} already exists", &c[2])),
+            None => {
+                database.objects.insert(c[2].to_string(), sql.to_string());
+            }
+        }
+    } else if let Some(c) = drop.captures(sql) {
+        if database.objects.remove(&c[2]).is_none() && c.get(1).is_none() {
+            return Err(format!("Table {} does not exist", &c[2]));
+        }
+    } else if let Some(c) = alter.captures(sql) {
+        if !database.objects.contains_key(&c[1]) {
+            return Err(format!("Table {} does not exist", &c[1]));
+        }
+    } else if let Some(c) = insert.captures(sql) {
+        let row = (
+            c[1].parse().unwrap(),
+            c[2].to_string(),
+            c[3].parse().unwrap(),
+        );
+        database.applied.push(row);
+    } else if sql.starts_with("SELECT name FROM system.tables") {
+        for name in database.objects.keys() {
+            push_string(&mut rows, name);
+        }
+    } else if sql.starts_with("SELECT create_table_query") {
+        if let Some(view) = database.objects.get("error_summary_mv") {
+            push_string(&mut rows, view);
+        }
+    } else if sql.starts_with("SELECT toUnixTimestamp(applied_at)") {
+        if database
+            .applied
+            .iter()
+            .any(|(_, name, _)| name == "error_summary_mv")
+        {
+            rows.extend_from_slice(&0_u32.to_le_bytes());
+        }
+    } else if sql.contains(&format!("FROM {MIGRATIONS_TABLE}")) {
+        for (version, name, checksum) in &database.applied {
+            rows.extend_from_slice(&version.to_le_bytes());
+            push_string(&mut rows, name);
+            rows.extend_from_slice(&checksum.to_le_bytes());
+            rows.extend_from_slice(&0_i64.to_le_bytes());
+        }
+    } else if !sql.starts_with("SELECT") {
+        return Err(format!("unexpected query: {sql}"));
+    }
+    Ok(rows)
+}
+
+#[tokio::test]
+async fn test_migrate_from_baseline_schema() {
+    let database = Arc::new(Mutex::new(baseline()));
+    let server = FakeClickHouse::start({
+        let database = database.clone();
+        move |sql| execute(&database, sql)
+    })
+    .await;
+    let client = server.client();
+    assert!(check_version(&client).await.is_err());
+
+    let plan = migrate(&client, false).await.unwrap();
+    assert_eq!(plan.pending.len(), MIGRATIONS.len());
+    let (objects, applied) = {
+        let database = database.lock().unwrap();
+        (database.objects.clone(), database.applied.len())
+    };
+    // The baseline view is replaced by one feeding error_summary
+    let view = &objects["error_summary_mv"];
+    assert!(view.contains(" TO error_summary AS"), "{view}");
+    assert!(objects.contains_key("error_summary"));
+    assert_eq!(applied, MIGRATIONS.len());
+
+    assert!(migrate(&client, false).await.unwrap().is_up_to_date());
+    check_version(&client).await.unwrap();
+}
+
+/// Whether an error summary of whole hours reads the hourly counts
+async fn reads_error_summary(server: &FakeClickHouse) -> bool {
+    server.take();
+    let errors = LogSearchParams {
+        time_range: TimeRange {
+            start: ts(0),
+            end: ts(4 * 3600),
+        },
+        min_severity: Some(LogSeverity::Error),
+        ..LogSearchParams::default()
+    };
+    server.client().error_summary(&errors).await.unwrap();
+    server
+        .take()
+        .iter()
+        .flat_map(Received::tables)
+        .any(|table| table == "error_summary")
+}
+
+#[tokio::test]
+async fn test_error_summary_read_once_view_fills_it() {
+    let database = Arc::new(Mutex::new(baseline()));
+    let server = FakeClickHouse::start({
+        let database = database.clone();
+        move |sql| execute(&database, sql)
+    })
+    .await;
+    assert!(!reads_error_summary(&server).await);
+
+    migrate(&server.client(), false).await.unwrap();
+    assert!(reads_error_summary(&server).await);
+
+    // Recorded as applied, but with the baseline view still in place
+    database.lock().unwrap().objects.insert(
+        "error_summary_mv".to_string(),
+        BASELINE_ERROR_SUMMARY_MV.to_string(),
+    );
+    assert!(!reads_error_summary(&server).await);
+}
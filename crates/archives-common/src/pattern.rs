@@ -0,0 +1,237 @@
+//! Log template mining
+//!
+//! Clusters log messages into templates in the style of Drain: variable
+//! tokens (numbers, UUIDs, IP addresses, hex IDs, quoted strings) are masked
+//! first, then messages with the same token count and leading token are
+//! merged when enough positions agree. Positions that differ within a cluster
+//! become `<*>`, so "user 123 not found" and "user 456 not found" share the
+//! template `user <NUM> not found`.
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use chrono::{DateTime, Utc};
+use regex::{NoExpand, Regex};
+use serde::Serialize;
+
+use crate::types::LogEntry;
+
+/// Placeholder for template positions that vary between messages
+pub const WILDCARD: &str = "<*>";
+
+/// A cluster of similar log messages
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogPattern {
+    /// Masked message with `<*>` where clustered messages differ
+    pub template: String,
+    /// Messages in the cluster
+    pub count: u64,
+    /// First distinct messages seen, unmasked
+    pub samples: Vec<String>,
+    /// Timestamp of the oldest message
+    pub first_seen: DateTime<Utc>,
+    /// Timestamp of the newest message
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Incremental template miner
+#[derive(Debug, Clone)]
+pub struct PatternMiner {
+    similarity: f64,
+    max_samples: usize,
+    /// Cluster indexes by (token count, leading token)
+    groups: HashMap<(usize, String), Vec<usize>>,
+    clusters: Vec<(Vec<String>, LogPattern)>,
+}
+
+impl Default for PatternMiner {
+    fn default() -> Self {
+        Self {
+            similarity: 0.5,
+            max_samples: 3,
+            groups: HashMap::new(),
+            clusters: Vec::new(),
+        }
+    }
+}
+
+impl PatternMiner {
+    /// Create a miner with the default similarity threshold (0.5)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fraction of token positions (0.0 to 1.0) that must match for a message
+    /// to join an existing cluster
+    #[must_use]
+    pub fn with_similarity(mut self, similarity: f64) -> Self {
+        self.similarity = similarity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Number of sample messages kept per cluster
+    #[must_use]
+    pub const fn with_max_samples(mut self, max_samples: usize) -> Self {
+        self.max_samples = max_samples;
+        self
+    }
+
+    /// Add the body of a log entry
+    pub fn add_log(&mut self, log: &LogEntry) {
+        self.add(&log.body, log.timestamp);
+    }
+
+    /// Add one message seen at `timestamp`
+    pub fn add(&mut self, message: &str, timestamp: DateTime<Utc>) {
+        let tokens: Vec<String> = mask(message)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        let leading = match tokens.first() {
+            Some(token) if !is_placeholder(token) => token.clone(),
+            _ => WILDCARD.to_string(),
+        };
+        let group = self.groups.entry((tokens.len(), leading)).or_default();
+
+        let best = group
+            .iter()
+            .map(|&i| (i, similarity(&self.clusters[i].0, &tokens)))
+            .filter(|&(_, score)| score >= self.similarity)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((i, _)) = best {
+            let (template, pattern) = &mut self.clusters[i];
+            for (slot, token) in template.iter_mut().zip(&tokens) {
+                if slot != token {
+                    *slot = WILDCARD.to_string();
+                }
+            }
+            pattern.template = template.join(" ");
+            pattern.count += 1;
+            pattern.first_seen = pattern.first_seen.min(timestamp);
+            pattern.last_seen = pattern.last_seen.max(timestamp);
+            if pattern.samples.len() < self.max_samples
+                && !pattern.samples.iter().any(|s| s == message)
+            {
+                pattern.samples.push(message.to_string());
+            }
+        } else {
+            group.push(self.clusters.len());
+            let pattern = LogPattern {
+                template: tokens.join(" "),
+                count: 1,
+                samples: if self.max_samples > 0 {
+                    vec![message.to_string()]
+                } else {
+                    Vec::new()
+                },
+                first_seen: timestamp,
+                last_seen: timestamp,
+            };
+            self.clusters.push((tokens, pattern));
+        }
+    }
+
+    /// Templates found so far, most frequent first
+    pub fn patterns(&self) -> Vec<LogPattern> {
+        let mut patterns: Vec<LogPattern> = self
+            .clusters
+            .iter()
+            .map(|(_, pattern)| pattern.clone())
+            .collect();
+        patterns.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.first_seen.cmp(&b.first_seen))
+        });
+        patterns
+    }
+}
+
+/// Cluster the bodies of `logs` with the default settings
+pub fn mine<'a>(logs: impl IntoIterator<Item = &'a LogEntry>) -> Vec<LogPattern> {
+    let mut miner = PatternMiner::new();
+    for log in logs {
+        miner.add_log(log);
+    }
+    miner.patterns()
+}
+
+/// Share of positions where the template and the tokens agree. Wildcards
+/// never count as agreement, so clusters do not absorb unrelated messages.
+#[allow(clippy::cast_precision_loss)]
+fn similarity(template: &[String], tokens: &[String]) -> f64 {
+    if template.is_empty() {
+        return 1.0;
+    }
+    let same = template
+        .iter()
+        .zip(tokens)
+        .filter(|(slot, token)| slot.as_str() != WILDCARD && slot == token)
+        .count();
+    same as f64 / template.len() as f64
+}
+
+fn is_placeholder(token: &str) -> bool {
+    token.starts_with('<') && token.ends_with('>')
+}
+
+/// Masks applied in order by [`mask`], as (regex, placeholder)
+///
+/// They avoid look-around so `ClickHouse` can apply the same ones in SQL.
+/// Classes are spelled out in ASCII, since `\d` and `\b` are Unicode-aware in
+/// Rust but not in RE2, so both backends produce the same templates; see
+/// [`re2_pattern`] for the SQL form.
+pub const MASKS: [(&str, &str); 5] = [
+    // Single quotes only outside words, so "don't" stays
+    (r#""(?:[^"\\]|\\.)*"|(?-u:\B)'[^'\n]*'(?-u:\B)"#, "<STR>"),
+    (
+        r"(?-u:\b)[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}(?-u:\b)",
+        "<UUID>",
+    ),
+    (
+        r"(?-u:\b)[0-9]{1,3}(?:\.[0-9]{1,3}){3}(?::[0-9]+)?(?-u:\b)",
+        "<IP>",
+    ),
+    (
+        r"(?-u:\b)[0-9]+(?:\.[0-9]+)?(?:ns|us|ms|s|m|h|d|b|kb|mb|gb|KB|MB|GB)?(?-u:\b)",
+        "<NUM>",
+    ),
+    // Hex IDs need a digit and a letter, so plain words stay
+    (
+        r"(?-u:\b)(?:0x[0-9a-fA-F]+|[0-9]+[a-fA-F][0-9a-fA-F]*|[a-fA-F]+[0-9][0-9a-fA-F]*)(?-u:\b)",
+        "<HEX>",
+    ),
+];
+
+/// A pattern of [`MASKS`] in RE2 syntax. RE2 has no Unicode flag, and its
+/// word boundaries are ASCII-only already.
+pub fn re2_pattern(pattern: &str) -> String {
+    pattern
+        .replace(r"(?-u:\b)", r"\b")
+        .replace(r"(?-u:\B)", r"\B")
+}
+
+/// Replace variable parts of a message with `<STR>`, `<UUID>`, `<IP>`,
+/// `<NUM>` and `<HEX>` placeholders
+pub fn mask(message: &str) -> String {
+    static COMPILED: OnceLock<Vec<(Regex, &str)>> = OnceLock::new();
+    let masks = COMPILED.get_or_init(|| {
+        MASKS
+            .iter()
+            .map(|&(pattern, placeholder)| {
+                (
+                    Regex::new(pattern).expect("valid mask pattern"),
+                    placeholder,
+                )
+            })
+            .collect()
+    });
+
+    let mut masked = message.to_string();
+    for (regex, placeholder) in masks {
+        masked = regex
+            .replace_all(&masked, NoExpand(placeholder))
+            .into_owned();
+    }
+    masked
+}
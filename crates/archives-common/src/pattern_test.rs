@@ -0,0 +1,91 @@
+//! Tests for pattern module
+
+use crate::{
+    pattern::{mask, PatternMiner},
+    test_support::ts,
+};
+
+#[test]
+fn test_mask_variable_tokens() {
+    assert_eq!(mask("user 123 not found"), "user <NUM> not found");
+    assert_eq!(
+        mask("request 550e8400-e29b-41d4-a716-446655440000 failed"),
+        "request <UUID> failed"
+    );
+    assert_eq!(
+        mask("connect to 10.0.12.5:5432 refused"),
+        "connect to <IP> refused"
+    );
+    assert_eq!(
+        mask("span 00f067aa0ba902b7 at 0x7ffd took 250ms"),
+        "span <HEX> at <HEX> took <NUM>"
+    );
+    assert_eq!(
+        mask(r#"key "orders/42" and 'x y' missing"#),
+        "key <STR> and <STR> missing"
+    );
+    // Words made of hex letters, contractions and identifiers stay
+    assert_eq!(
+        mask("don't decode deadbeef in v2"),
+        "don't decode deadbeef in v2"
+    );
+}
+
+#[test]
+fn test_mask_non_ascii_body() {
+    // Word boundaries and digits are ASCII, as in ClickHouse: non-ASCII
+    // letters end a word and non-ASCII digits stay
+    assert_eq!(
+        mask("Durée 250ms dépassée pour tâche٣ id=ß42 clé'v'"),
+        "Durée <NUM> dépassée pour tâche٣ id=ß<NUM> clé<STR>"
+    );
+}
+
+#[test]
+fn test_miner_clusters_similar_messages() {
+    let mut miner = PatternMiner::new();
+    miner.add("user 123 not found", ts(10));
+    miner.add("user 456 not found", ts(0));
+    miner.add("user 456 not found", ts(20));
+    miner.add("payment declined for order 9 by provider stripe", ts(5));
+    miner.add("payment declined for order 10 by provider adyen", ts(6));
+    miner.add("cache miss", ts(7));
+
+    let patterns = miner.patterns();
+    assert_eq!(patterns.len(), 3);
+
+    assert_eq!(patterns[0].template, "user <NUM> not found");
+    assert_eq!(patterns[0].count, 3);
+    assert_eq!(
+        patterns[0].samples,
+        ["user 123 not found", "user 456 not found"]
+    );
+    assert_eq!(patterns[0].first_seen, ts(0));
+    assert_eq!(patterns[0].last_seen, ts(20));
+
+    // Differing tokens become wildcards
+    assert_eq!(
+        patterns[1].template,
+        "payment declined for order <NUM> by provider <*>"
+    );
+    assert_eq!(patterns[1].count, 2);
+    assert_eq!(patterns[2].template, "cache miss");
+}
+
+#[test]
+fn test_miner_keeps_dissimilar_messages_apart() {
+    let mut miner = PatternMiner::new().with_max_samples(1);
+    miner.add("disk full on node a", ts(0));
+    miner.add("disk ok after retry b", ts(1));
+    miner.add("queue full on node b", ts(2));
+
+    let patterns = miner.patterns();
+    // Same length but a different leading token, or too few shared tokens
+    assert_eq!(patterns.len(), 3);
+    assert!(patterns.iter().all(|p| p.samples.len() == 1));
+
+    let mut strict = PatternMiner::new().with_similarity(1.0);
+    strict.add("disk full on node a", ts(0));
+    strict.add("disk full on node b", ts(1));
+    assert_eq!(strict.patterns().len(), 2);
+}
@@ -0,0 +1,217 @@
+//! Log query language
+//!
+//! One query string that works in the API, CLI and MCP tools:
+//!
+//! ```text
+//! service:checkout severity>=warn "payment timeout" attr.http.status_code=500 -body:healthcheck
+//! ```
+//!
+//! Terms are separated by whitespace and all must match:
+//!
+//! - `service:NAME` - exact service name
+//! - `severity>=LEVEL` (also `severity:LEVEL`, `severity>LEVEL`) - minimum severity
+//! - `attr.KEY<op>VALUE` - attribute filter on log or resource attributes, with the
+//!   operators of [`AttributeFilter`] (`=`, `!=`, `^=`, `=~`, `>`, `>=`, `<`, `<=`,
+//!   or a bare key for existence)
+//! - `resource.KEY<op>VALUE` - the same, restricted to resource attributes
+//! - `body:TEXT`, `"quoted phrase"` or a bare word - substring of the log body
+//!
+//! A leading `-` negates a body or attribute term.
+
+use std::str::FromStr;
+
+use crate::{
+    error::{Error, Result},
+    filter::{AttributeFilter, AttributeScope},
+    store::LogSearchParams,
+    types::LogSeverity,
+};
+
+/// A parsed log query
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogQuery {
+    /// Minimum severity to include
+    pub min_severity: Option<LogSeverity>,
+    /// Exact service name match
+    pub service_name: Option<String>,
+    /// Substrings that must appear in the body
+    pub body_terms: Vec<String>,
+    /// Substrings that must not appear in the body
+    pub excluded_body_terms: Vec<String>,
+    /// Attribute predicates
+    pub attribute_filters: Vec<AttributeFilter>,
+}
+
+impl LogQuery {
+    /// Parse a query string
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut query = Self::default();
+        for token in tokenize(input)? {
+            query.add_term(token)?;
+        }
+        Ok(query)
+    }
+
+    /// Merge the query into search parameters. Fields set by the query take
+    /// precedence over those already present.
+    pub fn apply(self, params: &mut LogSearchParams) {
+        if self.min_severity.is_some() {
+            params.min_severity = self.min_severity;
+        }
+        if self.service_name.is_some() {
+            params.service_name = self.service_name;
+        }
+        params.body_terms.extend(self.body_terms);
+        params.excluded_body_terms.extend(self.excluded_body_terms);
+        params.attribute_filters.extend(self.attribute_filters);
+    }
+
+    fn add_term(&mut self, token: Token) -> Result<()> {
+        if token.phrase {
+            self.add_body_term(token.text, token.negated);
+            return Ok(());
+        }
+
+        let text = token.text.as_str();
+        if let Some(service) = text.strip_prefix("service:") {
+            if token.negated {
+                return Err(unsupported_negation(text));
+            }
+            self.service_name = Some(service.to_string());
+        } else if let Some(rest) = text
+            .strip_prefix("severity")
+            .filter(|rest| rest.starts_with([':', '=', '>', '<']))
+        {
+            if token.negated {
+                return Err(unsupported_negation(text));
+            }
+            self.min_severity = Some(parse_severity(rest)?);
+        } else if let Some(body) = text.strip_prefix("body:") {
+            self.add_body_term(body.to_string(), token.negated);
+        } else if let Some(expr) = text.strip_prefix("attr.") {
+            self.add_attribute(expr, AttributeScope::Any, token.negated)?;
+        } else if let Some(expr) = text.strip_prefix("resource.") {
+            self.add_attribute(expr, AttributeScope::Resource, token.negated)?;
+        } else {
+            self.add_body_term(token.text, token.negated);
+        }
+        Ok(())
+    }
+
+    fn add_body_term(&mut self, text: String, negated: bool) {
+        if negated {
+            self.excluded_body_terms.push(text);
+        } else {
+            self.body_terms.push(text);
+        }
+    }
+
+    fn add_attribute(&mut self, expr: &str, scope: AttributeScope, negated: bool) -> Result<()> {
+        let mut filter = AttributeFilter::from_str(expr)?;
+        filter.scope = scope;
+        filter.negate = negated;
+        self.attribute_filters.push(filter);
+        Ok(())
+    }
+}
+
+impl FromStr for LogQuery {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+fn unsupported_negation(term: &str) -> Error {
+    Error::InvalidParameter(format!("negation is not supported for '{term}'"))
+}
+
+/// Parse the part after `severity`: `:LEVEL`, `=LEVEL`, `>=LEVEL` or `>LEVEL`
+fn parse_severity(rest: &str) -> Result<LogSeverity> {
+    const LEVELS: [LogSeverity; 6] = [
+        LogSeverity::Trace,
+        LogSeverity::Debug,
+        LogSeverity::Info,
+        LogSeverity::Warn,
+        LogSeverity::Error,
+        LogSeverity::Fatal,
+    ];
+
+    if let Some(level) = rest
+        .strip_prefix(">=")
+        .or_else(|| rest.strip_prefix(':'))
+        .or_else(|| rest.strip_prefix('='))
+    {
+        return level.parse();
+    }
+    if let Some(level) = rest.strip_prefix('>') {
+        let level: LogSeverity = level.parse()?;
+        let index = LEVELS.iter().position(|l| *l == level).unwrap_or_default();
+        return LEVELS
+            .get(index + 1)
+            .copied()
+            .ok_or_else(|| Error::InvalidParameter(format!("no severity above {level}")));
+    }
+    Err(Error::InvalidParameter(format!(
+        "invalid severity term: severity{rest}"
+    )))
+}
+
+/// A whitespace-separated query term
+#[derive(Debug)]
+struct Token {
+    text: String,
+    negated: bool,
+    /// The term was a quoted phrase and is matched against the body verbatim
+    phrase: bool,
+}
+
+/// Split on whitespace outside double quotes. Quotes may appear anywhere in a
+/// term (`attr.msg="a b"`); `\"` escapes a quote inside them.
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            break;
+        };
+
+        let negated = first == '-' && chars.clone().nth(1).is_some_and(|c| !c.is_whitespace());
+        if negated {
+            chars.next();
+        }
+        let phrase = chars.peek() == Some(&'"');
+
+        let mut text = String::new();
+        let mut in_quotes = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                '\\' if in_quotes => match chars.next() {
+                    Some(escaped) => text.push(escaped),
+                    None => break,
+                },
+                c if c.is_whitespace() && !in_quotes => break,
+                c => text.push(c),
+            }
+        }
+        if in_quotes {
+            return Err(Error::InvalidParameter(
+                "unterminated quote in query".to_string(),
+            ));
+        }
+
+        if !text.is_empty() {
+            tokens.push(Token {
+                text,
+                negated,
+                phrase,
+            });
+        }
+    }
+
+    Ok(tokens)
+}
@@ -0,0 +1,91 @@
+//! Tests for query module
+
+use crate::{
+    filter::{AttributeOp, AttributeScope},
+    query::LogQuery,
+    store::LogSearchParams,
+    types::LogSeverity,
+};
+
+#[test]
+fn test_parse_full_query() {
+    let query = LogQuery::parse(
+        r#"service:checkout severity>=warn "payment timeout" attr.http.status_code=500 -body:healthcheck"#,
+    )
+    .unwrap();
+
+    assert_eq!(query.service_name.as_deref(), Some("checkout"));
+    assert_eq!(query.min_severity, Some(LogSeverity::Warn));
+    assert_eq!(query.body_terms, ["payment timeout"]);
+    assert_eq!(query.excluded_body_terms, ["healthcheck"]);
+    assert_eq!(query.attribute_filters.len(), 1);
+
+    let filter = &query.attribute_filters[0];
+    assert_eq!(filter.key, "http.status_code");
+    assert_eq!(filter.op, AttributeOp::Eq("500".to_string()));
+    assert_eq!(filter.scope, AttributeScope::Any);
+    assert!(!filter.negate);
+}
+
+#[test]
+fn test_parse_bare_words_and_negation() {
+    let query = LogQuery::parse("connection  refused severity -retry -\"dry run\"").unwrap();
+    assert_eq!(query.body_terms, ["connection", "refused", "severity"]);
+    assert_eq!(query.excluded_body_terms, ["retry", "dry run"]);
+}
+
+#[test]
+fn test_parse_attribute_terms() {
+    let query = LogQuery::parse(r#"resource.k8s.pod.name^=checkout -attr.env=prod attr.msg="a b""#)
+        .unwrap();
+
+    let filters = &query.attribute_filters;
+    assert_eq!(filters.len(), 3);
+    assert_eq!(filters[0].scope, AttributeScope::Resource);
+    assert_eq!(filters[0].op, AttributeOp::Prefix("checkout".to_string()));
+    assert!(filters[1].negate);
+    assert_eq!(filters[2].op, AttributeOp::Eq("a b".to_string()));
+}
+
+#[test]
+fn test_parse_severity_forms() {
+    let severity = |q: &str| LogQuery::parse(q).unwrap().min_severity;
+    assert_eq!(severity("severity:error"), Some(LogSeverity::Error));
+    assert_eq!(severity("severity=INFO"), Some(LogSeverity::Info));
+    assert_eq!(severity("severity>warn"), Some(LogSeverity::Error));
+    assert_eq!(severity("severity>=warning"), Some(LogSeverity::Warn));
+
+    assert!(LogQuery::parse("severity>fatal").is_err());
+    assert!(LogQuery::parse("severity<error").is_err());
+    assert!(LogQuery::parse("severity:loud").is_err());
+}
+
+#[test]
+fn test_parse_errors() {
+    assert!(LogQuery::parse("\"unterminated").is_err());
+    assert!(LogQuery::parse("-service:checkout").is_err());
+    assert!(LogQuery::parse("attr.status>=abc").is_err());
+}
+
+#[test]
+fn test_parse_empty() {
+    assert_eq!(LogQuery::parse("   ").unwrap(), LogQuery::default());
+}
+
+#[test]
+fn test_apply_overrides_and_extends() {
+    let mut params = LogSearchParams {
+        service_name: Some("api".to_string()),
+        min_severity: Some(LogSeverity::Info),
+        body_terms: vec!["existing".to_string()],
+        ..LogSearchParams::default()
+    };
+
+    LogQuery::parse("service:checkout timeout")
+        .unwrap()
+        .apply(&mut params);
+
+    assert_eq!(params.service_name.as_deref(), Some("checkout"));
+    assert_eq!(params.min_severity, Some(LogSeverity::Info));
+    assert_eq!(params.body_terms, ["existing", "timeout"]);
+}
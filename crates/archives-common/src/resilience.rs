@@ -0,0 +1,367 @@
+//! Retries and circuit breaking for the ClickHouse backend
+//!
+//! [`ResilientStore`] wraps the ClickHouse client. Every call first takes one
+//! of `pool_size` permits, so no more queries run at once than the HTTP pool
+//! keeps connections for; a log stream holds its permit until it is dropped.
+//! Calls failing with [`Error::ClickHouseConnection`] (network errors,
+//! timeouts, overload) are retried up to `max_retries` times with exponential
+//! backoff and full jitter; all store calls are reads, so repeating them is
+//! safe. Other errors are returned at once.
+//!
+//! After `breaker_failure_threshold` consecutive failed calls the circuit
+//! breaker opens, and calls fail immediately with a connection error for
+//! `breaker_cooldown_secs`. Then a single trial call is let through: success
+//! closes the breaker, failure opens it for another cooldown.
+
+use std::{
+    collections::hash_map::RandomState,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{
+    cache::CacheStats,
+    config::ClickHouseConfig,
+    error::{Error, Result},
+    store::{
+        DatabaseStats, ErrorSummary, HistogramSeries, LabelKey, LabelQueryParams, LabelValue,
+        LogSearchParams, LogStore, LogStream, LogVolumeBucket, MetricCatalogParams, MetricInfo,
+        MetricQueryParams, MetricSeries, MetricStore, ServiceInfo, SharedStore, Store,
+        SummarySeries,
+    },
+    tenant::TenantScope,
+    types::{LogEntry, TimeRange},
+};
+
+/// Circuit breaker state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Calls pass through
+    Closed,
+    /// Calls fail fast until the cooldown has passed
+    Open,
+    /// One trial call is in flight; others fail fast
+    HalfOpen,
+}
+
+/// Circuit breaker state reported in `/health`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BreakerStatus {
+    /// Current state
+    pub state: BreakerState,
+    /// Failed calls since the last success
+    pub consecutive_failures: u32,
+    /// When the breaker last opened, while it is not closed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opened_at: Option<DateTime<Utc>>,
+}
+
+/// Consecutive failure counter deciding whether calls may run
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: BreakerState,
+    failures: u32,
+    /// When the current state was entered
+    since: Instant,
+    opened_at: Option<DateTime<Utc>>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            state: BreakerState::Closed,
+            failures: 0,
+            since: Instant::now(),
+            opened_at: None,
+        }
+    }
+
+    /// Let a call through, or fail fast while open. After the cooldown one
+    /// trial call is admitted; a trial that never reports back (a cancelled
+    /// request) is replaced after another cooldown.
+    pub(crate) fn admit(&mut self, now: Instant) -> Result<()> {
+        if self.state == BreakerState::Closed {
+            return Ok(());
+        }
+        let waited = now.saturating_duration_since(self.since);
+        if waited >= self.cooldown {
+            self.state = BreakerState::HalfOpen;
+            self.since = now;
+            return Ok(());
+        }
+        Err(Error::ClickHouseConnection(format!(
+            "circuit breaker open after {} consecutive failures, retrying in {}s",
+            self.failures,
+            self.cooldown.saturating_sub(waited).as_secs().max(1)
+        )))
+    }
+
+    /// Record the outcome of an admitted call
+    pub(crate) fn record(&mut self, success: bool, now: Instant) {
+        if success {
+            self.state = BreakerState::Closed;
+            self.failures = 0;
+            self.opened_at = None;
+            return;
+        }
+        self.failures = self.failures.saturating_add(1);
+        if self.state == BreakerState::HalfOpen || self.failures >= self.threshold {
+            if self.state == BreakerState::Closed {
+                warn!(
+                    failures = self.failures,
+                    "ClickHouse circuit breaker opened"
+                );
+            }
+            self.state = BreakerState::Open;
+            self.since = now;
+            self.opened_at = Some(Utc::now());
+        }
+    }
+
+    pub(crate) fn status(&self) -> BreakerStatus {
+        BreakerStatus {
+            state: self.state,
+            consecutive_failures: self.failures,
+            opened_at: self.opened_at,
+        }
+    }
+}
+
+/// Store wrapper bounding concurrency, retrying transient errors and
+/// failing fast while ClickHouse is down
+pub struct ResilientStore {
+    inner: SharedStore,
+    /// Shared with the tenant views of the store, as is the breaker
+    permits: Arc<Semaphore>,
+    max_retries: u32,
+    backoff: Duration,
+    breaker: Arc<Mutex<CircuitBreaker>>,
+}
+
+impl ResilientStore {
+    /// Wrap `inner` with the pool, retry and breaker settings of `config`
+    pub fn new(inner: SharedStore, config: &ClickHouseConfig) -> Self {
+        Self {
+            inner,
+            permits: Arc::new(Semaphore::new(config.pool_size.max(1) as usize)),
+            max_retries: config.max_retries,
+            backoff: Duration::from_millis(config.retry_backoff_ms),
+            breaker: Arc::new(Mutex::new(CircuitBreaker::new(
+                config.breaker_failure_threshold,
+                Duration::from_secs(config.breaker_cooldown_secs),
+            ))),
+        }
+    }
+
+    /// Run `call`, retrying connection errors, unless the breaker is open
+    pub(crate) async fn call<T, F, Fut>(&self, call: F) -> Result<T>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<T>> + Send,
+        T: Send,
+    {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| Error::Internal(e.to_string()))?;
+        self.breaker
+            .lock()
+            .expect("breaker lock poisoned")
+            .admit(Instant::now())?;
+
+        let mut attempt = 0;
+        let result = loop {
+            match call().await {
+                Err(e) if e.is_connection_error() && attempt < self.max_retries => {
+                    attempt += 1;
+                    debug!(attempt, error = %e, "Retrying ClickHouse query");
+                    tokio::time::sleep(self.backoff(attempt)).await;
+                }
+                result => break result,
+            }
+        };
+
+        // Any answer from ClickHouse, even a query error, shows it is up
+        let reachable = !matches!(&result, Err(e) if e.is_connection_error());
+        self.breaker
+            .lock()
+            .expect("breaker lock poisoned")
+            .record(reachable, Instant::now());
+        result
+    }
+
+    /// Random delay of up to `backoff * 2^(attempt - 1)`
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .backoff
+            .saturating_mul(1 << attempt.saturating_sub(1).min(10));
+        let ceiling_ms = u64::try_from(ceiling.as_millis()).unwrap_or(u64::MAX);
+        let random = RandomState::new().build_hasher().finish();
+        Duration::from_millis(random % ceiling_ms.saturating_add(1))
+    }
+}
+
+#[async_trait]
+impl LogStore for ResilientStore {
+    async fn search_logs(&self, params: &LogSearchParams) -> Result<Vec<LogEntry>> {
+        self.call(|| self.inner.search_logs(params)).await
+    }
+
+    async fn count_logs(&self, time_range: &TimeRange) -> Result<u64> {
+        self.call(|| self.inner.count_logs(time_range)).await
+    }
+
+    async fn get_log(&self, id: Uuid) -> Result<Option<LogEntry>> {
+        self.call(|| self.inner.get_log(id)).await
+    }
+
+    async fn count_logs_over_time(
+        &self,
+        params: &LogSearchParams,
+        interval_seconds: u32,
+    ) -> Result<Vec<LogVolumeBucket>> {
+        self.call(|| self.inner.count_logs_over_time(params, interval_seconds))
+            .await
+    }
+
+    async fn error_summary(&self, params: &LogSearchParams) -> Result<ErrorSummary> {
+        self.call(|| self.inner.error_summary(params)).await
+    }
+
+    async fn log_attribute_keys(&self, params: &LabelQueryParams) -> Result<Vec<LabelKey>> {
+        self.call(|| self.inner.log_attribute_keys(params)).await
+    }
+
+    async fn log_attribute_values(
+        &self,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>> {
+        self.call(|| self.inner.log_attribute_values(key, params))
+            .await
+    }
+
+    /// Holds a permit for the life of the stream and fails fast unless the
+    /// breaker admits it. The first item, or the end of an empty stream, is
+    /// recorded with the breaker. Streams are not retried, since rows may
+    /// already have been sent.
+    fn stream_logs<'a>(&'a self, params: &'a LogSearchParams) -> LogStream<'a> {
+        stream::once(async move {
+            let permit = Arc::clone(&self.permits)
+                .acquire_owned()
+                .await
+                .map_err(|e| Error::Internal(e.to_string()))?;
+            self.breaker
+                .lock()
+                .expect("breaker lock poisoned")
+                .admit(Instant::now())?;
+
+            let mut rows = self.inner.stream_logs(params);
+            let first = rows.next().await;
+            let reachable = !matches!(&first, Some(Err(e)) if e.is_connection_error());
+            self.breaker
+                .lock()
+                .expect("breaker lock poisoned")
+                .record(reachable, Instant::now());
+
+            // The permit is released once the stream is dropped
+            Ok::<_, Error>(stream::iter(first).chain(rows).map(move |row| {
+                let _permit = &permit;
+                row
+            }))
+        })
+        .try_flatten()
+        .boxed()
+    }
+}
+
+#[async_trait]
+impl MetricStore for ResilientStore {
+    async fn list_metric_names(&self) -> Result<Vec<String>> {
+        self.call(|| self.inner.list_metric_names()).await
+    }
+
+    async fn query_metrics(&self, params: &MetricQueryParams) -> Result<Vec<MetricSeries>> {
+        self.call(|| self.inner.query_metrics(params)).await
+    }
+
+    async fn query_histogram(&self, params: &MetricQueryParams) -> Result<Vec<HistogramSeries>> {
+        self.call(|| self.inner.query_histogram(params)).await
+    }
+
+    async fn metric_catalog(&self, params: &MetricCatalogParams) -> Result<Vec<MetricInfo>> {
+        self.call(|| self.inner.metric_catalog(params)).await
+    }
+
+    async fn query_summary(&self, params: &MetricQueryParams) -> Result<Vec<SummarySeries>> {
+        self.call(|| self.inner.query_summary(params)).await
+    }
+
+    async fn metric_label_keys(
+        &self,
+        metric_name: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelKey>> {
+        self.call(|| self.inner.metric_label_keys(metric_name, params))
+            .await
+    }
+
+    async fn metric_label_values(
+        &self,
+        metric_name: &str,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>> {
+        self.call(|| self.inner.metric_label_values(metric_name, key, params))
+            .await
+    }
+}
+
+#[async_trait]
+impl Store for ResilientStore {
+    async fn health_check(&self) -> Result<bool> {
+        self.call(|| self.inner.health_check()).await
+    }
+
+    async fn get_stats(&self) -> Result<DatabaseStats> {
+        self.call(|| self.inner.get_stats()).await
+    }
+
+    async fn list_services(&self, time_range: &TimeRange) -> Result<Vec<ServiceInfo>> {
+        self.call(|| self.inner.list_services(time_range)).await
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn breaker_status(&self) -> Option<BreakerStatus> {
+        Some(self.breaker.lock().expect("breaker lock poisoned").status())
+    }
+
+    fn for_tenant(&self, scope: &TenantScope) -> Result<SharedStore> {
+        Ok(Arc::new(Self {
+            inner: self.inner.for_tenant(scope)?,
+            permits: Arc::clone(&self.permits),
+            max_retries: self.max_retries,
+            backoff: self.backoff,
+            breaker: Arc::clone(&self.breaker),
+        }))
+    }
+}
@@ -0,0 +1,164 @@
+//! Tests for resilience module
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{StreamExt, TryStreamExt};
+
+use crate::{
+    clickhouse::ClickHouseClient,
+    config::ClickHouseConfig,
+    memory::MemoryStore,
+    resilience::{BreakerState, CircuitBreaker, ResilientStore},
+    store::{LogSearchParams, LogStore, Store},
+    test_support::{all_time, log},
+    types::{LogEntry, LogSeverity},
+    Error,
+};
+
+fn resilient(max_retries: u32, threshold: u32, cooldown_secs: u64) -> ResilientStore {
+    let config = ClickHouseConfig {
+        max_retries,
+        retry_backoff_ms: 1,
+        breaker_failure_threshold: threshold,
+        breaker_cooldown_secs: cooldown_secs,
+        ..ClickHouseConfig::default()
+    };
+    ResilientStore::new(Arc::new(MemoryStore::new()), &config)
+}
+
+#[tokio::test]
+async fn test_retries_connection_errors_only() {
+    let store = resilient(3, 5, 30);
+    let attempts = AtomicU32::new(0);
+
+    // Two transient failures, then success
+    let result = store
+        .call(|| async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(Error::ClickHouseConnection("refused".to_string())),
+                n => Ok(n),
+            }
+        })
+        .await;
+    assert_eq!(result.unwrap(), 2);
+
+    // Query errors are returned at once
+    attempts.store(0, Ordering::SeqCst);
+    let result: crate::Result<()> = store
+        .call(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(Error::ClickHouseQuery("syntax error".to_string()))
+        })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    assert_eq!(store.breaker_status().unwrap().state, BreakerState::Closed);
+}
+
+#[tokio::test]
+async fn test_breaker_fails_fast_while_open() {
+    let store = resilient(0, 2, 60);
+    let attempts = AtomicU32::new(0);
+    let failing = || async {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        Err::<(), _>(Error::ClickHouseConnection("refused".to_string()))
+    };
+
+    assert!(store.call(failing).await.is_err());
+    assert!(store.call(failing).await.is_err());
+    let status = store.breaker_status().unwrap();
+    assert_eq!(status.state, BreakerState::Open);
+    assert_eq!(status.consecutive_failures, 2);
+    assert!(status.opened_at.is_some());
+
+    // Open: the call is not attempted
+    let err = store.call(failing).await.unwrap_err();
+    assert!(err.is_connection_error());
+    assert!(err.to_string().contains("circuit breaker open"));
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_breaker_half_open_trial() {
+    let start = Instant::now();
+    let mut breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+    breaker.admit(start).unwrap();
+    breaker.record(false, start);
+    assert!(breaker.admit(start + Duration::from_secs(10)).is_err());
+
+    // After the cooldown one trial goes through and others still fail fast
+    let later = start + Duration::from_secs(30);
+    breaker.admit(later).unwrap();
+    assert_eq!(breaker.status().state, BreakerState::HalfOpen);
+    assert!(breaker.admit(later).is_err());
+
+    // A failed trial opens the breaker again, a successful one closes it
+    breaker.record(false, later);
+    assert_eq!(breaker.status().state, BreakerState::Open);
+    let retry = later + Duration::from_secs(30);
+    breaker.admit(retry).unwrap();
+    breaker.record(true, retry);
+    assert_eq!(breaker.status().state, BreakerState::Closed);
+    assert_eq!(breaker.status().consecutive_failures, 0);
+}
+
+#[tokio::test]
+async fn test_streams_hold_a_permit_and_feed_the_breaker() {
+    let memory = MemoryStore::new();
+    memory.insert_logs([
+        log(0, LogSeverity::Info, "api", "a"),
+        log(1, LogSeverity::Info, "api", "b"),
+    ]);
+    let config = ClickHouseConfig {
+        pool_size: 1,
+        ..ClickHouseConfig::default()
+    };
+    let store = ResilientStore::new(Arc::new(memory), &config);
+    let params = LogSearchParams {
+        time_range: all_time(),
+        ..LogSearchParams::default()
+    };
+
+    // An open stream holds the only permit until it is dropped
+    let mut rows = store.stream_logs(&params);
+    rows.next().await.unwrap().unwrap();
+    let all = all_time();
+    let waiting = tokio::time::timeout(Duration::from_millis(50), store.count_logs(&all));
+    assert!(waiting.await.is_err());
+    drop(rows);
+    assert_eq!(store.count_logs(&all_time()).await.unwrap(), 2);
+
+    // Streams that cannot reach ClickHouse open the breaker
+    let unused = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let config = ClickHouseConfig {
+        url: format!("http://{}", unused.local_addr().unwrap()),
+        max_retries: 0,
+        breaker_failure_threshold: 2,
+        breaker_cooldown_secs: 60,
+        ..ClickHouseConfig::default()
+    };
+    drop(unused);
+    let client = ClickHouseClient::new(&config).unwrap();
+    let store = ResilientStore::new(Arc::new(client), &config);
+    for _ in 0..2 {
+        let err = store
+            .stream_logs(&params)
+            .try_collect::<Vec<LogEntry>>()
+            .await
+            .unwrap_err();
+        assert!(err.is_connection_error(), "{err}");
+    }
+    assert_eq!(store.breaker_status().unwrap().state, BreakerState::Open);
+    let err = store
+        .stream_logs(&params)
+        .try_collect::<Vec<LogEntry>>()
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("circuit breaker open"), "{err}");
+}
@@ -0,0 +1,222 @@
+//! Retention enforcement
+//!
+//! Retention is a ClickHouse `TTL` on each exporter table, computed from
+//! [`RetentionConfig`]: one expression per table whose interval is a
+//! `multiIf` over the rules, so the first matching rule decides how long a row
+//! is kept and rows matching none get the default. [`reconcile`] compares the
+//! TTL each table has with the expected one and runs `ALTER TABLE ... MODIFY
+//! TTL` where they differ; it runs from `archives admin retention`, after
+//! `archives admin migrate`, which creates the rollup and error count tables
+//! without a TTL, and, with `enforce_on_startup`, when a server starts. Tables the exporter has not
+//! created yet are skipped. Metric rollups follow the metric retention, and the
+//! hourly error counts of `error_summary` the log retention.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    clickhouse::ClickHouseClient,
+    config::{RetentionConfig, RetentionRule},
+    error::Result,
+    rollup::Rollup,
+    types::MetricType,
+};
+
+/// Log table and its time column
+const LOG_TABLE: (&str, &str) = ("otel_logs", "Timestamp");
+
+/// Hourly error counts and their time column, kept as long as the logs
+const ERROR_SUMMARY_TABLE: (&str, &str) = ("error_summary", "Hour");
+
+/// Metric time column
+const METRIC_TIME_COLUMN: &str = "TimeUnix";
+
+/// Rollup time column
+const ROLLUP_TIME_COLUMN: &str = "Bucket";
+
+/// TTL of one table, expected and as applied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TableRetention {
+    /// Table name
+    pub table: String,
+    /// TTL expression computed from configuration
+    pub expected: String,
+    /// TTL expression of the table, `None` without a TTL
+    pub applied: Option<String>,
+    /// Whether `applied` matches `expected`
+    pub in_sync: bool,
+}
+
+/// Effective retention reported by `/v1/status`
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionStatus {
+    /// Days logs matching no rule are kept
+    pub log_retention_days: u32,
+    /// Days metrics matching no rule are kept
+    pub metrics_retention_days: u32,
+    /// Overrides, in evaluation order
+    pub rules: Vec<RetentionRule>,
+    /// TTL of each existing table; empty for backends without TTLs
+    pub tables: Vec<TableRetention>,
+}
+
+impl RetentionStatus {
+    /// Compare `config` with the TTLs of [`crate::store::DatabaseStats::table_ttls`]
+    pub fn new(config: &RetentionConfig, table_ttls: &BTreeMap<String, String>) -> Self {
+        Self {
+            log_retention_days: config.log_retention_days,
+            metrics_retention_days: config.metrics_retention_days,
+            rules: config.rules.clone(),
+            tables: compare(config, table_ttls),
+        }
+    }
+}
+
+/// Expected TTL of every table, log table first and error counts last
+pub fn expected_ttls(config: &RetentionConfig) -> Vec<(&'static str, String)> {
+    let mut ttls = vec![(
+        LOG_TABLE.0,
+        ttl_expression(LOG_TABLE.1, config.rules.iter(), config.log_retention_days),
+    )];
+    let metric_rules = config
+        .rules
+        .iter()
+        .filter(|r| r.min_severity.is_none() && r.max_severity.is_none());
+    let metric_ttl = ttl_expression(
+        METRIC_TIME_COLUMN,
+        metric_rules.clone(),
+        config.metrics_retention_days,
+    );
+    ttls.extend(
+        MetricType::ALL
+            .iter()
+            .map(|t| (t.table_name(), metric_ttl.clone())),
+    );
+    let rollup_ttl = ttl_expression(
+        ROLLUP_TIME_COLUMN,
+        metric_rules,
+        config.metrics_retention_days,
+    );
+    ttls.extend(
+        Rollup::ALL
+            .iter()
+            .map(|r| (r.table_name(), rollup_ttl.clone())),
+    );
+    ttls.push((
+        ERROR_SUMMARY_TABLE.0,
+        ttl_expression(
+            ERROR_SUMMARY_TABLE.1,
+            config.rules.iter(),
+            config.log_retention_days,
+        ),
+    ));
+    ttls
+}
+
+/// `TTL` expression keeping rows `default_days`, or the days of the first
+/// matching rule
+fn ttl_expression<'a>(
+    time_column: &str,
+    rules: impl Iterator<Item = &'a RetentionRule>,
+    default_days: u32,
+) -> String {
+    let mut default_days = default_days;
+    let mut branches = Vec::new();
+    for rule in rules {
+        if let Some(condition) = rule_condition(rule) {
+            branches.push(format!("{condition}, {}", rule.days));
+        } else {
+            // A rule matching everything ends the list
+            default_days = rule.days;
+            break;
+        }
+    }
+    let days = if branches.is_empty() {
+        default_days.to_string()
+    } else {
+        format!("multiIf({}, {default_days})", branches.join(", "))
+    };
+    format!("toDateTime({time_column}) + toIntervalDay({days})")
+}
+
+/// SQL condition of a rule, or `None` for a rule matching everything
+fn rule_condition(rule: &RetentionRule) -> Option<String> {
+    let mut conditions = Vec::new();
+    if let Some(service) = &rule.service {
+        conditions.push(format!("ServiceName = {}", string_literal(service)));
+    }
+    if let Some(min) = rule.min_severity {
+        conditions.push(format!("SeverityNumber >= {}", min.to_severity_number()));
+    }
+    if let Some(max) = rule.max_severity {
+        // Each severity covers four severity numbers
+        conditions.push(format!("SeverityNumber < {}", max.to_severity_number() + 4));
+    }
+    match conditions.len() {
+        0 => None,
+        1 => conditions.pop(),
+        _ => Some(
+            conditions
+                .iter()
+                .map(|c| format!("({c})"))
+                .collect::<Vec<_>>()
+                .join(" AND "),
+        ),
+    }
+}
+
+/// Quote `value` as a ClickHouse string literal
+pub(crate) fn string_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// The TTL expression in a `CREATE TABLE` engine clause, as reported in
+/// `system.tables.engine_full`
+pub fn ttl_clause(engine_full: &str) -> Option<String> {
+    let (_, rest) = engine_full.split_once(" TTL ")?;
+    let ttl = rest.split(" SETTINGS ").next().unwrap_or(rest);
+    Some(ttl.trim().to_string())
+}
+
+/// Compare expected TTLs with `table_ttls`, which maps each existing table to
+/// its TTL expression, empty without one. ClickHouse may reformat
+/// expressions, so whitespace is ignored.
+pub fn compare(
+    config: &RetentionConfig,
+    table_ttls: &BTreeMap<String, String>,
+) -> Vec<TableRetention> {
+    let normalize = |s: &str| s.split_whitespace().collect::<String>();
+    expected_ttls(config)
+        .into_iter()
+        .filter_map(|(table, expected)| {
+            let applied = table_ttls.get(table)?;
+            let applied = (!applied.is_empty()).then(|| applied.clone());
+            Some(TableRetention {
+                table: table.to_string(),
+                in_sync: applied.as_deref().map(normalize) == Some(normalize(&expected)),
+                expected,
+                applied,
+            })
+        })
+        .collect()
+}
+
+/// Bring the TTLs of existing tables in line with `config`, or with
+/// `dry_run` only report the differences. Returns the state found before
+/// altering. `client` must not be read-only.
+pub async fn reconcile(
+    client: &ClickHouseClient,
+    config: &RetentionConfig,
+    dry_run: bool,
+) -> Result<Vec<TableRetention>> {
+    let tables = compare(config, &client.table_ttls().await?);
+    if !dry_run {
+        for table in tables.iter().filter(|t| !t.in_sync) {
+            info!(table = %table.table, ttl = %table.expected, "Updating retention");
+            client.set_ttl(&table.table, &table.expected).await?;
+        }
+    }
+    Ok(tables)
+}
@@ -0,0 +1,159 @@
+//! Tests for retention module
+
+use std::collections::BTreeMap;
+
+use crate::{
+    config::{Config, RetentionConfig},
+    retention::{compare, expected_ttls, ttl_clause, RetentionStatus},
+};
+
+/// Retention section of a `config.toml`
+fn retention(toml: &str) -> RetentionConfig {
+    let config: Config = config::Config::builder()
+        .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+        .build()
+        .unwrap()
+        .try_deserialize()
+        .unwrap();
+    config.retention
+}
+
+#[test]
+fn test_default_ttls() {
+    let ttls = expected_ttls(&RetentionConfig::default());
+    assert_eq!(
+        ttls[0],
+        (
+            "otel_logs",
+            "toDateTime(Timestamp) + toIntervalDay(30)".to_string()
+        )
+    );
+    assert_eq!(ttls.len(), 9);
+    assert!(ttls[1..6]
+        .iter()
+        .all(|(_, ttl)| ttl == "toDateTime(TimeUnix) + toIntervalDay(90)"));
+    assert_eq!(
+        ttls[7],
+        (
+            "metrics_rollup_1m",
+            "toDateTime(Bucket) + toIntervalDay(90)".to_string()
+        )
+    );
+    assert_eq!(
+        ttls[8],
+        (
+            "error_summary",
+            "toDateTime(Hour) + toIntervalDay(30)".to_string()
+        )
+    );
+}
+
+#[test]
+fn test_rules() {
+    let config = retention(
+        r#"
+        [retention]
+        log_retention_days = 14
+
+        [[retention.rules]]
+        service = "payments"
+        days = 365
+
+        [[retention.rules]]
+        min_severity = "ERROR"
+        days = 90
+
+        [[retention.rules]]
+        service = "o'brien"
+        max_severity = "DEBUG"
+        days = 3
+        "#,
+    );
+    assert_eq!(config.rules.len(), 3);
+    assert!(!config.enforce_on_startup);
+
+    let ttls = expected_ttls(&config);
+    assert_eq!(
+        ttls[0].1,
+        "toDateTime(Timestamp) + toIntervalDay(multiIf(\
+         ServiceName = 'payments', 365, \
+         SeverityNumber >= 17, 90, \
+         (ServiceName = 'o\\'brien') AND (SeverityNumber < 9), 3, 14))"
+    );
+    // Error counts keep their hours as long as the logs
+    assert_eq!(
+        ttls[8].1,
+        ttls[0]
+            .1
+            .replace("toDateTime(Timestamp)", "toDateTime(Hour)")
+    );
+    // Only rules without a severity bound apply to metrics
+    assert_eq!(
+        ttls[1].1,
+        "toDateTime(TimeUnix) + toIntervalDay(multiIf(ServiceName = 'payments', 365, 90))"
+    );
+
+    // A rule matching everything replaces the default and ends the list
+    let config = retention(
+        r#"
+        [[retention.rules]]
+        days = 7
+
+        [[retention.rules]]
+        service = "payments"
+        days = 365
+        "#,
+    );
+    assert_eq!(
+        expected_ttls(&config)[0].1,
+        "toDateTime(Timestamp) + toIntervalDay(7)"
+    );
+}
+
+#[test]
+fn test_ttl_clause() {
+    assert_eq!(
+        ttl_clause(
+            "MergeTree PARTITION BY toDate(Timestamp) ORDER BY (ServiceName, Timestamp) \
+             TTL toDateTime(Timestamp) + toIntervalDay(3) SETTINGS index_granularity = 8192"
+        )
+        .as_deref(),
+        Some("toDateTime(Timestamp) + toIntervalDay(3)")
+    );
+    assert_eq!(
+        ttl_clause("MergeTree ORDER BY Timestamp TTL toDateTime(Timestamp) + toIntervalDay(3)")
+            .as_deref(),
+        Some("toDateTime(Timestamp) + toIntervalDay(3)")
+    );
+    assert_eq!(ttl_clause("MergeTree ORDER BY Timestamp"), None);
+}
+
+#[test]
+fn test_compare() {
+    let config = RetentionConfig::default();
+    let ttls = BTreeMap::from([
+        (
+            "otel_logs".to_string(),
+            "toDateTime(Timestamp) +  toIntervalDay(30)".to_string(),
+        ),
+        (
+            "otel_metrics_sum".to_string(),
+            "toDateTime(TimeUnix) + toIntervalDay(3)".to_string(),
+        ),
+        ("otel_metrics_gauge".to_string(), String::new()),
+    ]);
+
+    // Tables missing from the database are skipped
+    let tables = compare(&config, &ttls);
+    assert_eq!(tables.len(), 3);
+    assert!(tables[0].in_sync);
+    assert_eq!(tables[1].table, "otel_metrics_gauge");
+    assert_eq!(tables[1].applied, None);
+    assert!(!tables[1].in_sync);
+    assert!(!tables[2].in_sync);
+
+    // Backends without TTLs report the configuration only
+    let status = RetentionStatus::new(&config, &BTreeMap::new());
+    assert_eq!(status.log_retention_days, 30);
+    assert!(status.tables.is_empty());
+}
@@ -0,0 +1,216 @@
+//! Metric rollups
+//!
+//! Migration `0003_metric_rollups` creates `metrics_rollup_1m` and
+//! `metrics_rollup_1h`, `AggregatingMergeTree` tables holding per-series
+//! aggregate states of gauge and sum data points, fed by materialized views.
+//! `query_metrics` reads the coarsest rollup whose buckets line up with the
+//! requested interval and time range, and the raw tables otherwise. Counter
+//! aggregations always read raw points, since increases are computed between
+//! consecutive points of a series.
+//!
+//! The views only see points inserted after the migration; [`backfill`] adds
+//! the older ones, recording in [`BACKFILLS_TABLE`] how far each rollup has
+//! been filled from each source table.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    clickhouse::ClickHouseClient,
+    error::Result,
+    types::{Aggregation, TimeRange},
+};
+
+/// Raw tables the rollups are built from
+pub const SOURCE_TABLES: [&str; 2] = ["otel_metrics_gauge", "otel_metrics_sum"];
+
+/// Table recording the steps of [`backfill`]
+pub const BACKFILLS_TABLE: &str = "metrics_rollup_backfills";
+
+/// A rollup table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rollup {
+    /// One-minute buckets
+    Minute,
+    /// One-hour buckets
+    Hour,
+}
+
+impl Rollup {
+    /// All rollups, coarsest first
+    pub const ALL: [Self; 2] = [Self::Hour, Self::Minute];
+
+    /// ClickHouse table holding the rollup
+    pub const fn table_name(self) -> &'static str {
+        match self {
+            Self::Minute => "metrics_rollup_1m",
+            Self::Hour => "metrics_rollup_1h",
+        }
+    }
+
+    /// The rollup held in `table`
+    pub fn from_table_name(table: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.table_name() == table)
+    }
+
+    /// Bucket size in seconds
+    pub const fn seconds(self) -> u32 {
+        match self {
+            Self::Minute => 60,
+            Self::Hour => 3600,
+        }
+    }
+
+    /// ClickHouse function truncating a time to the bucket start
+    const fn bucket_function(self) -> &'static str {
+        match self {
+            Self::Minute => "toStartOfMinute",
+            Self::Hour => "toStartOfHour",
+        }
+    }
+
+    /// Whether buckets of `interval_seconds` are whole rollup buckets and
+    /// `time_range` starts and ends on bucket boundaries
+    fn fits(self, interval_seconds: u32, time_range: &TimeRange) -> bool {
+        let seconds = i64::from(self.seconds());
+        interval_seconds % self.seconds() == 0
+            && time_range.start.timestamp() % seconds == 0
+            && time_range.end.timestamp() % seconds == 0
+    }
+
+    /// `INSERT ... SELECT` aggregating the points of `source` older than
+    /// the bound placeholder, as the materialized views do for new points
+    pub(crate) fn backfill_query(self, source: &str) -> String {
+        format!(
+            "INSERT INTO {table} \
+             SELECT MetricName, ServiceName, Attributes, ResourceAttributes, \
+             {bucket}(TimeUnix) as Bucket, \
+             avgState(Value), minState(Value), maxState(Value), sumState(Value), \
+             countState(), quantilesState(0.5, 0.9, 0.99)(Value) \
+             FROM {source} \
+             WHERE TimeUnix < ? \
+             GROUP BY MetricName, ServiceName, Attributes, ResourceAttributes, Bucket",
+            table = self.table_name(),
+            bucket = self.bucket_function(),
+        )
+    }
+}
+
+/// Expression reading `aggregation` from the states of a rollup, or `None`
+/// if rollups cannot answer it
+pub fn merge_expression(aggregation: Aggregation) -> Option<&'static str> {
+    Some(match aggregation {
+        Aggregation::Avg => "avgMerge(ValueAvg)",
+        Aggregation::Min => "minMerge(ValueMin)",
+        Aggregation::Max => "maxMerge(ValueMax)",
+        Aggregation::Sum => "sumMerge(ValueSum)",
+        Aggregation::Count => "toFloat64(countMerge(ValueCount))",
+        Aggregation::P50 => "quantilesMerge(0.5, 0.9, 0.99)(ValueQuantiles)[1]",
+        Aggregation::P90 => "quantilesMerge(0.5, 0.9, 0.99)(ValueQuantiles)[2]",
+        Aggregation::P99 => "quantilesMerge(0.5, 0.9, 0.99)(ValueQuantiles)[3]",
+        Aggregation::Increase | Aggregation::Rate => return None,
+    })
+}
+
+/// The coarsest rollup that can answer a query, given the first bucket the
+/// rollups hold data for
+pub fn select(
+    aggregation: Aggregation,
+    interval_seconds: u32,
+    time_range: &TimeRange,
+    covered_from: Option<DateTime<Utc>>,
+) -> Option<Rollup> {
+    merge_expression(aggregation)?;
+    if !matches!(covered_from, Some(start) if start <= time_range.start) {
+        return None;
+    }
+    Rollup::ALL
+        .into_iter()
+        .find(|rollup| rollup.fits(interval_seconds, time_range))
+}
+
+/// One rollup filled from one source table by [`backfill`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BackfillStep {
+    /// Rollup filled
+    pub rollup: Rollup,
+    /// Raw table read
+    pub source: String,
+    /// Points before this time are added by the backfill; later ones are
+    /// covered by the materialized views
+    pub until: DateTime<Utc>,
+    /// Whether the points have been added
+    pub done: bool,
+}
+
+/// What [`backfill`] found to do
+#[derive(Debug, Serialize)]
+pub struct BackfillReport {
+    /// Points before this time are aggregated into the rollups
+    pub until: DateTime<Utc>,
+    /// Every step, finest rollup first, as found before backfilling
+    pub steps: Vec<BackfillStep>,
+    /// Whether nothing was left to add
+    pub up_to_date: bool,
+}
+
+/// Steps of a backfill: those `recorded` by earlier runs and the missing
+/// ones up to `until`. The minute rollup comes first, so a run stopped early
+/// has filled the finer one.
+pub fn plan(recorded: &[BackfillStep], until: DateTime<Utc>) -> Vec<BackfillStep> {
+    Rollup::ALL
+        .into_iter()
+        .rev()
+        .flat_map(|rollup| SOURCE_TABLES.map(|source| (rollup, source)))
+        .map(|(rollup, source)| {
+            recorded
+                .iter()
+                .find(|step| step.rollup == rollup && step.source == source)
+                .cloned()
+                .unwrap_or_else(|| BackfillStep {
+                    rollup,
+                    source: source.to_string(),
+                    until,
+                    done: false,
+                })
+        })
+        .collect()
+}
+
+/// Aggregate raw points older than the first rollup bucket into every
+/// rollup. `client` must not be read-only.
+///
+/// The steps are recorded with that bound before anything is inserted and
+/// marked done one at a time, so a run that failed halfway resumes with the
+/// same bound and skips the finished steps; only a step interrupted between
+/// its insert and its mark is added twice.
+pub async fn backfill(client: &ClickHouseClient, dry_run: bool) -> Result<BackfillReport> {
+    let recorded = client.rollup_backfills().await?;
+    let until = match recorded.first() {
+        Some(step) => step.until,
+        // Without rollup data yet, everything before the current minute
+        None => client.rollup_start().await?.unwrap_or_else(|| {
+            let now = Utc::now().timestamp();
+            DateTime::from_timestamp(now - now % 60, 0).unwrap_or_default()
+        }),
+    };
+    let steps = plan(&recorded, until);
+    let up_to_date = steps.iter().all(|step| step.done);
+    if !dry_run && !up_to_date {
+        let new: Vec<BackfillStep> = steps
+            .iter()
+            .filter(|step| !recorded.contains(step))
+            .cloned()
+            .collect();
+        client.record_backfills(&new).await?;
+        for step in steps.iter().filter(|step| !step.done) {
+            client.backfill_rollup(step).await?;
+        }
+    }
+    Ok(BackfillReport {
+        until,
+        steps,
+        up_to_date,
+    })
+}
@@ -0,0 +1,106 @@
+//! Tests for rollup module
+
+use chrono::{Duration, TimeZone, Utc};
+
+use crate::{
+    rollup::{merge_expression, plan, select, Rollup},
+    types::{Aggregation, TimeRange},
+};
+
+fn days(n: i64) -> TimeRange {
+    let end = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+    TimeRange {
+        start: end - Duration::days(n),
+        end,
+    }
+}
+
+#[test]
+fn test_select_coarsest_fitting_rollup() {
+    let range = days(30);
+    let covered = Some(range.start - Duration::days(1));
+
+    assert_eq!(
+        select(Aggregation::Avg, 3600, &range, covered),
+        Some(Rollup::Hour)
+    );
+    assert_eq!(
+        select(Aggregation::P99, 6 * 3600, &range, covered),
+        Some(Rollup::Hour)
+    );
+    assert_eq!(
+        select(Aggregation::Max, 300, &range, covered),
+        Some(Rollup::Minute)
+    );
+
+    // Intervals that are not whole buckets read raw points
+    assert_eq!(select(Aggregation::Avg, 90, &range, covered), None);
+    assert_eq!(select(Aggregation::Avg, 30, &range, covered), None);
+
+    // A range ending mid-hour can still use minute buckets
+    let mut partial = range;
+    partial.end -= Duration::minutes(30);
+    assert_eq!(
+        select(Aggregation::Avg, 3600, &partial, covered),
+        Some(Rollup::Minute)
+    );
+    partial.end -= Duration::seconds(1);
+    assert_eq!(select(Aggregation::Avg, 3600, &partial, covered), None);
+}
+
+#[test]
+fn test_select_falls_back_to_raw() {
+    let range = days(7);
+
+    // Counters need consecutive raw points
+    assert_eq!(merge_expression(Aggregation::Rate), None);
+    let covered = Some(range.start);
+    assert_eq!(select(Aggregation::Rate, 3600, &range, covered), None);
+    assert_eq!(select(Aggregation::Increase, 3600, &range, covered), None);
+
+    // Rollups without data for the whole range, or empty ones
+    let late = Some(range.start + Duration::hours(1));
+    assert_eq!(select(Aggregation::Avg, 3600, &range, late), None);
+    assert_eq!(select(Aggregation::Avg, 3600, &range, None), None);
+}
+
+#[test]
+fn test_backfill_query() {
+    let query = Rollup::Hour.backfill_query("otel_metrics_sum");
+    assert!(query.starts_with("INSERT INTO metrics_rollup_1h SELECT"));
+    assert!(query.contains("toStartOfHour(TimeUnix) as Bucket"));
+    assert!(query.contains("FROM otel_metrics_sum WHERE TimeUnix < ?"));
+}
+
+#[test]
+fn test_backfill_plan() {
+    let until = Utc.with_ymd_and_hms(2024, 3, 1, 10, 37, 0).unwrap();
+    let steps = plan(&[], until);
+    let order: Vec<_> = steps
+        .iter()
+        .map(|s| (s.rollup, s.source.as_str()))
+        .collect();
+    assert_eq!(
+        order,
+        [
+            (Rollup::Minute, "otel_metrics_gauge"),
+            (Rollup::Minute, "otel_metrics_sum"),
+            (Rollup::Hour, "otel_metrics_gauge"),
+            (Rollup::Hour, "otel_metrics_sum"),
+        ]
+    );
+    assert!(steps.iter().all(|s| s.until == until && !s.done));
+
+    // A rerun keeps the recorded bound and skips finished steps, though
+    // the first minute bucket has moved back since
+    let mut recorded = steps.clone();
+    recorded[0].done = true;
+    let rerun = plan(&recorded, until - Duration::days(30));
+    assert_eq!(rerun, recorded);
+    assert_eq!(rerun.iter().filter(|s| !s.done).count(), 3, "{rerun:?}");
+    assert_eq!(
+        Rollup::from_table_name("metrics_rollup_1h"),
+        Some(Rollup::Hour)
+    );
+    assert_eq!(Rollup::from_table_name("otel_metrics_sum"), None);
+}
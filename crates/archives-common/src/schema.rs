@@ -0,0 +1,206 @@
+//! Table layout detection
+//!
+//! Versions of the ClickHouse OTEL exporter differ in how they store
+//! attributes: `Map(LowCardinality(String), String)`, the `JSON` type, or a
+//! `String` holding a JSON object. They also add columns over time, such as
+//! `ObservedTimestamp`, and changed `SeverityNumber` from `Int32` to `UInt8`.
+//! [`LogSchema::detect`] reads the columns of `otel_logs` from
+//! `system.columns` once at startup, and the ClickHouse backend builds its
+//! queries from the result. Layouts it cannot read fail with
+//! [`Error::Schema`] instead of returning empty attributes.
+
+use serde::Serialize;
+
+use crate::{
+    error::{Error, Result},
+    types::MetricType,
+};
+
+/// Nesting depth up to which `JSON` attribute objects are flattened into
+/// dotted keys; `k8s.pod.name` has depth 3
+const JSON_FLATTEN_DEPTH: usize = 4;
+
+/// Columns every log table must have
+const REQUIRED_LOG_COLUMNS: [&str; 9] = [
+    "Timestamp",
+    "TraceId",
+    "SpanId",
+    "SeverityText",
+    "SeverityNumber",
+    "ServiceName",
+    "Body",
+    "ResourceAttributes",
+    "LogAttributes",
+];
+
+/// One row of `system.columns`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    /// Table the column belongs to
+    pub table: String,
+    /// Column name
+    pub name: String,
+    /// ClickHouse type, e.g. `Map(LowCardinality(String), String)`
+    pub type_name: String,
+}
+
+/// Storage type of an attribute column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeLayout {
+    /// `Map(String, String)`, optionally with `LowCardinality` keys
+    Map,
+    /// The `JSON` type, with dotted keys stored as nested paths
+    Json,
+    /// A `String` holding a flat JSON object
+    String,
+}
+
+impl AttributeLayout {
+    /// Layout of a column of the given type, or `None` if it is unsupported
+    pub fn from_type(type_name: &str) -> Option<Self> {
+        let type_name = unwrap_type(type_name);
+        if let Some(args) = type_name
+            .strip_prefix("Map(")
+            .and_then(|rest| rest.strip_suffix(')'))
+        {
+            let (key, value) = args.split_once(',')?;
+            return (unwrap_type(key.trim()) == "String" && unwrap_type(value.trim()) == "String")
+                .then_some(Self::Map);
+        }
+        if type_name == "JSON" || type_name.starts_with("JSON(") || type_name.starts_with("Object(")
+        {
+            return Some(Self::Json);
+        }
+        (type_name == "String").then_some(Self::String)
+    }
+
+    /// SQL expression reading `column` as a `Map(String, String)`
+    pub fn map_expression(self, column: &str) -> String {
+        match self {
+            Self::Map => column.to_string(),
+            Self::String => {
+                format!("CAST(JSONExtractKeysAndValues({column}, 'String'), 'Map(String, String)')")
+            }
+            Self::Json => {
+                // (key, raw JSON value) pairs, with objects replaced by their
+                // members under `key.member` one level per round
+                let mut entries = format!("JSONExtractKeysAndValuesRaw(toJSONString({column}))");
+                for _ in 1..JSON_FLATTEN_DEPTH {
+                    entries = format!(
+                        "arrayFlatten(arrayMap(e -> if(JSONType(e.2) = 'Object', \
+                         arrayMap(m -> (concat(e.1, '.', m.1), m.2), \
+                         JSONExtractKeysAndValuesRaw(e.2)), [e]), {entries}))"
+                    );
+                }
+                format!(
+                    "CAST(arrayMap(e -> (e.1, if(JSONType(e.2) = 'String', \
+                     JSONExtractString(e.2), e.2)), {entries}), 'Map(String, String)')"
+                )
+            }
+        }
+    }
+}
+
+/// Strip `LowCardinality(...)` and `Nullable(...)` wrappers from a type
+fn unwrap_type(type_name: &str) -> &str {
+    let mut type_name = type_name.trim();
+    for wrapper in ["LowCardinality(", "Nullable("] {
+        if let Some(inner) = type_name
+            .strip_prefix(wrapper)
+            .and_then(|rest| rest.strip_suffix(')'))
+        {
+            type_name = unwrap_type(inner);
+        }
+    }
+    type_name
+}
+
+/// Detected layout of the `otel_logs` table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogSchema {
+    /// Layout of `ResourceAttributes`
+    pub resource_attributes: AttributeLayout,
+    /// Layout of `LogAttributes`
+    pub log_attributes: AttributeLayout,
+    /// Whether `ObservedTimestamp` exists; without it `Timestamp` is used
+    pub observed_timestamp: bool,
+}
+
+impl Default for LogSchema {
+    /// The layout of current exporter versions without the JSON option
+    fn default() -> Self {
+        Self {
+            resource_attributes: AttributeLayout::Map,
+            log_attributes: AttributeLayout::Map,
+            observed_timestamp: true,
+        }
+    }
+}
+
+impl LogSchema {
+    /// Detect the layout of `otel_logs` from the columns of the database.
+    ///
+    /// Without an `otel_logs` table the default layout is assumed, since the
+    /// exporter creates its tables on first start. Metric tables must store
+    /// attributes as maps.
+    pub fn detect(columns: &[Column]) -> Result<Self> {
+        for metric_type in MetricType::ALL {
+            let table = metric_type.table_name();
+            for name in ["Attributes", "ResourceAttributes"] {
+                if let Some(column) = find(columns, table, name) {
+                    if AttributeLayout::from_type(&column.type_name) != Some(AttributeLayout::Map) {
+                        return Err(unsupported(column, "Map(String, String)"));
+                    }
+                }
+            }
+        }
+
+        if !columns.iter().any(|c| c.table == "otel_logs") {
+            return Ok(Self::default());
+        }
+        let missing: Vec<&str> = REQUIRED_LOG_COLUMNS
+            .into_iter()
+            .filter(|name| find(columns, "otel_logs", name).is_none())
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::Schema(format!(
+                "otel_logs has no {} column(s); is it an OTEL exporter table?",
+                missing.join(", ")
+            )));
+        }
+
+        let layout = |name: &str| {
+            let column = find(columns, "otel_logs", name).expect("checked above");
+            AttributeLayout::from_type(&column.type_name)
+                .ok_or_else(|| unsupported(column, "Map(String, String), JSON or String"))
+        };
+        Ok(Self {
+            resource_attributes: layout("ResourceAttributes")?,
+            log_attributes: layout("LogAttributes")?,
+            observed_timestamp: find(columns, "otel_logs", "ObservedTimestamp").is_some(),
+        })
+    }
+
+    /// SQL expression reading `ResourceAttributes` as a map
+    pub fn resource_map(&self) -> String {
+        self.resource_attributes
+            .map_expression("ResourceAttributes")
+    }
+
+    /// SQL expression reading `LogAttributes` as a map
+    pub fn log_map(&self) -> String {
+        self.log_attributes.map_expression("LogAttributes")
+    }
+}
+
+fn find<'a>(columns: &'a [Column], table: &str, name: &str) -> Option<&'a Column> {
+    columns.iter().find(|c| c.table == table && c.name == name)
+}
+
+fn unsupported(column: &Column, expected: &str) -> Error {
+    Error::Schema(format!(
+        "{}.{} has unsupported type {}; expected {expected}",
+        column.table, column.name, column.type_name
+    ))
+}
@@ -0,0 +1,110 @@
+//! Tests for schema module
+
+use crate::{
+    schema::{AttributeLayout, Column, LogSchema},
+    Error,
+};
+
+fn columns(table: &str, columns: &[(&str, &str)]) -> Vec<Column> {
+    columns
+        .iter()
+        .map(|(name, type_name)| Column {
+            table: table.to_string(),
+            name: (*name).to_string(),
+            type_name: (*type_name).to_string(),
+        })
+        .collect()
+}
+
+/// `otel_logs` columns with the given attribute type, without `ObservedTimestamp`
+fn log_table(attributes: &str) -> Vec<Column> {
+    columns(
+        "otel_logs",
+        &[
+            ("Timestamp", "DateTime64(9)"),
+            ("TraceId", "String"),
+            ("SpanId", "String"),
+            ("SeverityText", "LowCardinality(String)"),
+            ("SeverityNumber", "UInt8"),
+            ("ServiceName", "LowCardinality(String)"),
+            ("Body", "String"),
+            ("ResourceAttributes", attributes),
+            ("LogAttributes", attributes),
+        ],
+    )
+}
+
+#[test]
+fn test_attribute_layout_from_type() {
+    for map in [
+        "Map(String, String)",
+        "Map(LowCardinality(String), String)",
+        "Map(LowCardinality(String), LowCardinality(String))",
+    ] {
+        assert_eq!(AttributeLayout::from_type(map), Some(AttributeLayout::Map));
+    }
+    assert_eq!(
+        AttributeLayout::from_type("JSON"),
+        Some(AttributeLayout::Json)
+    );
+    assert_eq!(
+        AttributeLayout::from_type("JSON(max_dynamic_paths=1024)"),
+        Some(AttributeLayout::Json)
+    );
+    assert_eq!(
+        AttributeLayout::from_type("Nullable(String)"),
+        Some(AttributeLayout::String)
+    );
+    assert_eq!(AttributeLayout::from_type("Map(String, Int64)"), None);
+    assert_eq!(AttributeLayout::from_type("Array(String)"), None);
+}
+
+#[test]
+fn test_detect_log_layouts() {
+    let schema = LogSchema::detect(&log_table("Map(LowCardinality(String), String)")).unwrap();
+    assert_eq!(schema.log_attributes, AttributeLayout::Map);
+    assert!(!schema.observed_timestamp);
+    assert_eq!(schema.log_map(), "LogAttributes");
+
+    let mut json = log_table("JSON");
+    json.extend(columns(
+        "otel_logs",
+        &[("ObservedTimestamp", "DateTime64(9)")],
+    ));
+    let schema = LogSchema::detect(&json).unwrap();
+    assert_eq!(schema.resource_attributes, AttributeLayout::Json);
+    assert!(schema.observed_timestamp);
+    assert!(schema
+        .resource_map()
+        .starts_with("CAST(arrayMap(e -> (e.1,"));
+
+    let schema = LogSchema::detect(&log_table("String")).unwrap();
+    assert_eq!(
+        schema.log_map(),
+        "CAST(JSONExtractKeysAndValues(LogAttributes, 'String'), 'Map(String, String)')"
+    );
+
+    // No table yet: the exporter creates it on first start
+    assert_eq!(LogSchema::detect(&[]).unwrap(), LogSchema::default());
+}
+
+#[test]
+fn test_detect_rejects_unsupported_layouts() {
+    let err = LogSchema::detect(&log_table("Array(Tuple(String, String))")).unwrap_err();
+    assert!(matches!(err, Error::Schema(_)));
+    assert!(err.to_string().contains(
+        "otel_logs.ResourceAttributes has unsupported type Array(Tuple(String, String))"
+    ));
+
+    let mut missing = log_table("Map(String, String)");
+    missing.retain(|c| c.name != "Body");
+    let err = LogSchema::detect(&missing).unwrap_err();
+    assert!(err.to_string().contains("otel_logs has no Body column"));
+
+    let mut metrics = log_table("Map(String, String)");
+    metrics.extend(columns("otel_metrics_gauge", &[("Attributes", "JSON")]));
+    let err = LogSchema::detect(&metrics).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("otel_metrics_gauge.Attributes has unsupported type JSON"));
+}
@@ -0,0 +1,933 @@
+//! Storage abstraction for Archives
+//!
+//! Consumers query logs and metrics through the [`LogStore`] and [`MetricStore`]
+//! traits rather than a concrete client, so the API and MCP servers can run
+//! against ClickHouse in production and the in-memory backend in demos and tests.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use async_trait::async_trait;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    cache::{CacheStats, CachedStore},
+    clickhouse::ClickHouseClient,
+    config::Config,
+    cursor::LogCursor,
+    error::{Error, Result},
+    filter::{AttributeFilter, AttributeOp, AttributeScope},
+    histogram::{self, ExponentialHistogram},
+    limits::LimitedStore,
+    memory::MemoryStore,
+    migrations,
+    resilience::{BreakerStatus, ResilientStore},
+    retention,
+    tenant::{self, TenantScope},
+    types::{Aggregation, LogEntry, LogSeverity, MetricType, Pagination, QuantileValue, TimeRange},
+};
+
+/// Log entries produced one at a time by [`LogStore::stream_logs`]
+pub type LogStream<'a> = BoxStream<'a, Result<LogEntry>>;
+
+/// Read access to stored log entries
+#[async_trait]
+pub trait LogStore: Send + Sync {
+    /// Search logs with filters, newest first
+    async fn search_logs(&self, params: &LogSearchParams) -> Result<Vec<LogEntry>>;
+
+    /// Get log count for time range
+    async fn count_logs(&self, time_range: &TimeRange) -> Result<u64>;
+
+    /// Fetch a single log by its ID, or `None` if no row has it
+    async fn get_log(&self, id: Uuid) -> Result<Option<LogEntry>>;
+
+    /// Count logs matching the search filters per time bucket and severity,
+    /// oldest bucket first. Empty buckets are included; pagination and the
+    /// cursor are ignored.
+    async fn count_logs_over_time(
+        &self,
+        params: &LogSearchParams,
+        interval_seconds: u32,
+    ) -> Result<Vec<LogVolumeBucket>>;
+
+    /// Count matching logs per message template ([`crate::pattern::mask`]),
+    /// most frequent first. Counts are exact; `pagination.limit` caps the
+    /// number of patterns and the offset and cursor are ignored. No severity
+    /// filter is implied, so error summaries set `min_severity`.
+    async fn error_summary(&self, params: &LogSearchParams) -> Result<ErrorSummary>;
+
+    /// Log and resource attribute keys of matching entries with the number of
+    /// distinct values of each, ordered by key
+    async fn log_attribute_keys(&self, params: &LabelQueryParams) -> Result<Vec<LabelKey>>;
+
+    /// Most frequent values of one attribute key in matching entries
+    async fn log_attribute_values(
+        &self,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>>;
+
+    /// Search one page of logs. Fetches one row past the limit to detect
+    /// whether more rows follow, and returns a cursor for the next page.
+    async fn search_logs_page(&self, params: &LogSearchParams) -> Result<LogPage> {
+        let limit = params.pagination.limit;
+        let mut probe = params.clone();
+        probe.pagination.limit = limit.saturating_add(1);
+
+        let mut logs = self.search_logs(&probe).await?;
+        let has_more = logs.len() as u64 > limit;
+        logs.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+
+        let next_cursor = if has_more {
+            logs.last().map(|l| LogCursor::for_entry(l).encode())
+        } else {
+            None
+        };
+
+        Ok(LogPage {
+            logs,
+            next_cursor,
+            has_more,
+        })
+    }
+
+    /// Logs of one trace within the time range, oldest first. When the trace
+    /// has more than `limit` entries, the most recent `limit` are returned.
+    async fn get_trace_logs(
+        &self,
+        trace_id: &str,
+        time_range: &TimeRange,
+        limit: u64,
+    ) -> Result<Vec<LogEntry>> {
+        let params = LogSearchParams {
+            time_range: time_range.clone(),
+            trace_id: Some(trace_id.to_string()),
+            pagination: Pagination { offset: 0, limit },
+            ..LogSearchParams::default()
+        };
+
+        let mut logs = self.search_logs(&params).await?;
+        logs.reverse();
+        Ok(logs)
+    }
+
+    /// Search logs like [`LogStore::search_logs`], yielding entries as they
+    /// are read so that memory use does not grow with the result size. An
+    /// error ends the stream. The default implementation collects
+    /// [`LogStore::search_logs`], for backends that hold all rows in memory.
+    fn stream_logs<'a>(&'a self, params: &'a LogSearchParams) -> LogStream<'a> {
+        stream::once(self.search_logs(params))
+            .map_ok(|logs| stream::iter(logs.into_iter().map(Ok)))
+            .try_flatten()
+            .boxed()
+    }
+}
+
+/// Read access to stored metric data points
+#[async_trait]
+pub trait MetricStore: Send + Sync {
+    /// List available metric names
+    async fn list_metric_names(&self) -> Result<Vec<String>>;
+
+    /// Query metrics with aggregation, one series per distinct `group_by` value
+    async fn query_metrics(&self, params: &MetricQueryParams) -> Result<Vec<MetricSeries>>;
+
+    /// Merge histogram bucket counts per time bucket, one series per distinct
+    /// `group_by` value. The aggregation is ignored.
+    async fn query_histogram(&self, params: &MetricQueryParams) -> Result<Vec<HistogramSeries>>;
+
+    /// Metrics with data points in the time range, with type, unit,
+    /// description, emitting services and series counts, ordered by name
+    async fn metric_catalog(&self, params: &MetricCatalogParams) -> Result<Vec<MetricInfo>>;
+
+    /// Summary counts, sums and reported quantiles per time bucket, one series
+    /// per distinct `group_by` value. The aggregation is ignored.
+    async fn query_summary(&self, params: &MetricQueryParams) -> Result<Vec<SummarySeries>>;
+
+    /// Data point and resource attribute keys of one metric with the number
+    /// of distinct values of each, ordered by key
+    async fn metric_label_keys(
+        &self,
+        metric_name: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelKey>>;
+
+    /// Most frequent values of one label key of a metric
+    async fn metric_label_values(
+        &self,
+        metric_name: &str,
+        key: &str,
+        params: &LabelQueryParams,
+    ) -> Result<Vec<LabelValue>>;
+}
+
+/// A complete storage backend serving both logs and metrics
+#[async_trait]
+pub trait Store: LogStore + MetricStore {
+    /// Check if the backend is reachable
+    async fn health_check(&self) -> Result<bool>;
+
+    /// Get database statistics
+    async fn get_stats(&self) -> Result<DatabaseStats>;
+
+    /// Services with logs or metric data points in the time range, with log
+    /// volume, error counts, metric counts and common resource attributes,
+    /// ordered by name
+    async fn list_services(&self, time_range: &TimeRange) -> Result<Vec<ServiceInfo>>;
+
+    /// Hit and miss counters of the result cache, if the store has one
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+
+    /// State of the circuit breaker, if the store has one
+    fn breaker_status(&self) -> Option<BreakerStatus> {
+        None
+    }
+
+    /// This store restricted to the rows of one tenant, see [`crate::tenant`].
+    /// Fails by default, so a backend that cannot enforce the scope is never
+    /// queried without it.
+    fn for_tenant(&self, scope: &TenantScope) -> Result<SharedStore> {
+        Err(Error::Config(format!(
+            "storage backend cannot restrict queries to tenant {:?}",
+            scope.tenant()
+        )))
+    }
+}
+
+/// Shared handle to a storage backend
+pub type SharedStore = Arc<dyn Store>;
+
+/// Storage backend selection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// ClickHouse via the OTEL exporter schema
+    #[default]
+    ClickHouse,
+    /// Process-local store, seeded from `[storage] fixture` if set and
+    /// empty otherwise
+    Memory,
+}
+
+/// Create the storage backend selected in configuration
+///
+/// The query limits of `config.limits` are enforced. For ClickHouse the table
+/// layout is detected first, so this fails if ClickHouse is unreachable or
+/// its schema unsupported or not migrated to [`migrations::SCHEMA_VERSION`].
+/// ClickHouse calls are retried and guarded by a circuit breaker, and their
+/// results are cached when `config.cache` is enabled.
+/// With `retention.enforce_on_startup` table TTLs are reconciled first. The
+/// store is not scoped to a tenant; with `tenancy.enabled` callers query
+/// through [`Store::for_tenant`]. The memory backend fails if its
+/// `storage.fixture` cannot be read.
+pub async fn from_config(config: &Config) -> Result<SharedStore> {
+    tenant::validate(&config.tenancy)?;
+    let store: SharedStore = match config.storage.backend {
+        StorageBackend::ClickHouse => {
+            let client = ClickHouseClient::new(&config.clickhouse)?
+                .with_limits(&config.limits)
+                .detect_schema()
+                .await?;
+            migrations::check_version(&client).await?;
+            if config.retention.enforce_on_startup {
+                // The limited client is read-only
+                let writer = ClickHouseClient::new(&config.clickhouse)?;
+                retention::reconcile(&writer, &config.retention, false).await?;
+            }
+            let client: SharedStore = Arc::new(client);
+            let client: SharedStore = Arc::new(ResilientStore::new(client, &config.clickhouse));
+            if config.cache.enabled {
+                Arc::new(CachedStore::new(client, config.cache.clone()))
+            } else {
+                client
+            }
+        }
+        StorageBackend::Memory => Arc::new(
+            config
+                .storage
+                .fixture
+                .as_ref()
+                .map_or_else(|| Ok(MemoryStore::new()), MemoryStore::from_fixture)?,
+        ),
+    };
+    Ok(Arc::new(LimitedStore::new(store, config.limits.clone())))
+}
+
+/// Database statistics
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DatabaseStats {
+    /// Number of stored log rows
+    pub log_count: u64,
+    /// Bytes used by log storage
+    pub log_bytes: u64,
+    /// Number of stored metric rows
+    pub metric_count: u64,
+    /// Bytes used by metric storage
+    pub metric_bytes: u64,
+    /// TTL expression of each exporter table, empty without one; only
+    /// backends with TTLs report tables
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub table_ttls: BTreeMap<String, String>,
+}
+
+/// Resource attributes reported in the service catalog, with the most
+/// frequent value of each
+pub const SERVICE_RESOURCE_KEYS: [&str; 4] = [
+    "service.version",
+    "service.namespace",
+    "deployment.environment",
+    "deployment.environment.name",
+];
+
+/// One service in the service catalog
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceInfo {
+    /// Service name
+    pub name: String,
+    /// Log entries in the time range
+    pub log_count: u64,
+    /// Log entries at ERROR severity or above
+    pub error_count: u64,
+    /// `error_count / log_count`, 0 without logs
+    pub error_ratio: f64,
+    /// Distinct metrics emitted in the time range
+    pub metric_count: u64,
+    /// Timestamp of the oldest log entry or data point
+    pub first_seen: chrono::DateTime<chrono::Utc>,
+    /// Timestamp of the newest log entry or data point
+    pub last_seen: chrono::DateTime<chrono::Utc>,
+    /// Most frequent value of each [`SERVICE_RESOURCE_KEYS`] key the service
+    /// reports, taken from its logs and otherwise from its metrics
+    pub resource_attributes: BTreeMap<String, String>,
+}
+
+impl ServiceInfo {
+    /// An entry for a service first seen at `timestamp`
+    pub(crate) fn new(name: String, timestamp: chrono::DateTime<chrono::Utc>) -> Self {
+        Self {
+            name,
+            log_count: 0,
+            error_count: 0,
+            error_ratio: 0.0,
+            metric_count: 0,
+            first_seen: timestamp,
+            last_seen: timestamp,
+            resource_attributes: BTreeMap::new(),
+        }
+    }
+
+    /// Widen the seen range to cover `first..=last`
+    pub(crate) fn seen(
+        &mut self,
+        first: chrono::DateTime<chrono::Utc>,
+        last: chrono::DateTime<chrono::Utc>,
+    ) {
+        self.first_seen = self.first_seen.min(first);
+        self.last_seen = self.last_seen.max(last);
+    }
+
+    /// Set the error ratio from the counts
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn with_error_ratio(mut self) -> Self {
+        if self.log_count > 0 {
+            self.error_ratio = self.error_count as f64 / self.log_count as f64;
+        }
+        self
+    }
+}
+
+/// Parameters for log search
+#[derive(Debug, Clone)]
+pub struct LogSearchParams {
+    /// Time range to search
+    pub time_range: TimeRange,
+    /// Minimum severity to include
+    pub min_severity: Option<LogSeverity>,
+    /// Case-insensitive substring match on the body
+    pub text_query: Option<String>,
+    /// Exact service name match
+    pub service_name: Option<String>,
+    /// Trace ID (hex, case-insensitive)
+    pub trace_id: Option<String>,
+    /// Span ID (hex, case-insensitive)
+    pub span_id: Option<String>,
+    /// Case-insensitive substrings that must all appear in the body
+    pub body_terms: Vec<String>,
+    /// Case-insensitive substrings that must not appear in the body
+    pub excluded_body_terms: Vec<String>,
+    /// Predicates on log and resource attributes, all of which must match
+    pub attribute_filters: Vec<AttributeFilter>,
+    /// Offset and limit
+    pub pagination: Pagination,
+    /// Resume after this position instead of using an offset
+    pub cursor: Option<LogCursor>,
+}
+
+impl LogSearchParams {
+    /// Check that the parameters can be executed
+    pub fn validate(&self) -> Result<()> {
+        if self.cursor.is_some() && self.pagination.offset > 0 {
+            return Err(Error::InvalidParameter(
+                "cursor and offset cannot be combined".to_string(),
+            ));
+        }
+        for filter in &self.attribute_filters {
+            filter.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for LogSearchParams {
+    fn default() -> Self {
+        Self {
+            time_range: TimeRange::last_hours(1),
+            min_severity: None,
+            text_query: None,
+            service_name: None,
+            trace_id: None,
+            span_id: None,
+            body_terms: Vec::new(),
+            excluded_body_terms: Vec::new(),
+            attribute_filters: Vec::new(),
+            pagination: Pagination::default(),
+            cursor: None,
+        }
+    }
+}
+
+/// Logs sharing one message template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorPattern {
+    /// Message with variable parts masked
+    pub pattern: String,
+    /// Logs with this template
+    pub count: u64,
+    /// Up to [`ErrorPattern::MAX_SAMPLES`] distinct original messages
+    pub samples: Vec<String>,
+    /// Timestamp of the oldest log
+    pub first_seen: chrono::DateTime<chrono::Utc>,
+    /// Timestamp of the newest log
+    pub last_seen: chrono::DateTime<chrono::Utc>,
+    /// Logs per service name
+    pub services: BTreeMap<String, u64>,
+}
+
+impl ErrorPattern {
+    /// Samples kept per pattern
+    pub const MAX_SAMPLES: usize = 3;
+}
+
+/// Error counts over a time range
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ErrorSummary {
+    /// All matching logs, including those of patterns past the limit
+    pub total: u64,
+    /// Matching logs per service name
+    pub services: BTreeMap<String, u64>,
+    /// Most frequent templates first
+    pub patterns: Vec<ErrorPattern>,
+}
+
+/// Most buckets a log volume histogram may have
+pub const MAX_VOLUME_BUCKETS: i64 = 10_000;
+
+/// Log counts of one time bucket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogVolumeBucket {
+    /// Start of the bucket
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Logs in the bucket
+    pub total: u64,
+    /// Logs per severity; severities without logs are omitted
+    pub counts: BTreeMap<LogSeverity, u64>,
+}
+
+impl LogVolumeBucket {
+    /// Check that `interval_seconds` splits `time_range` into a drawable
+    /// number of buckets
+    pub fn validate_interval(time_range: &TimeRange, interval_seconds: u32) -> Result<()> {
+        if interval_seconds == 0 {
+            return Err(Error::InvalidParameter(
+                "interval_seconds must be at least 1".to_string(),
+            ));
+        }
+        let buckets =
+            (time_range.end - time_range.start).num_seconds() / i64::from(interval_seconds);
+        if buckets > MAX_VOLUME_BUCKETS {
+            return Err(Error::InvalidParameter(format!(
+                "interval_seconds {interval_seconds} gives {buckets} buckets, more than {MAX_VOLUME_BUCKETS}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Buckets of `interval_seconds` covering `time_range`, aligned to the
+    /// epoch like ClickHouse `toStartOfInterval`, with `(bucket start,
+    /// severity, count)` rows added up
+    pub(crate) fn fill(
+        time_range: &TimeRange,
+        interval_seconds: u32,
+        rows: impl IntoIterator<Item = (chrono::DateTime<chrono::Utc>, LogSeverity, u64)>,
+    ) -> Vec<Self> {
+        let interval = i64::from(interval_seconds.max(1));
+        let start = time_range.start.timestamp();
+        let mut buckets: BTreeMap<i64, Self> = BTreeMap::new();
+        let mut bucket = start - start.rem_euclid(interval);
+        while bucket < time_range.end.timestamp() || buckets.is_empty() {
+            buckets.insert(
+                bucket,
+                Self {
+                    timestamp: chrono::DateTime::from_timestamp(bucket, 0).unwrap_or_default(),
+                    total: 0,
+                    counts: BTreeMap::new(),
+                },
+            );
+            bucket += interval;
+        }
+
+        for (timestamp, severity, count) in rows {
+            if let Some(bucket) = buckets.get_mut(&timestamp.timestamp()) {
+                bucket.total += count;
+                *bucket.counts.entry(severity).or_default() += count;
+            }
+        }
+        buckets.into_values().collect()
+    }
+}
+
+/// One page of log search results
+#[derive(Debug, Clone, Serialize)]
+pub struct LogPage {
+    /// Matching entries, newest first
+    pub logs: Vec<LogEntry>,
+    /// Cursor for the next page, present when `has_more` is set
+    pub next_cursor: Option<String>,
+    /// Whether more entries follow this page
+    pub has_more: bool,
+}
+
+/// Filters for the metric catalog
+#[derive(Debug, Clone)]
+pub struct MetricCatalogParams {
+    /// Only count data points in this range as active
+    pub time_range: TimeRange,
+    /// Only metrics whose name starts with this prefix
+    pub prefix: Option<String>,
+    /// Only metrics emitted by this service
+    pub service_name: Option<String>,
+}
+
+/// Filters for label key and value discovery
+#[derive(Debug, Clone)]
+pub struct LabelQueryParams {
+    /// Only data points or log entries in this range
+    pub time_range: TimeRange,
+    /// Only data emitted by this service
+    pub service_name: Option<String>,
+    /// Attribute map(s) to read keys and values from
+    pub scope: AttributeScope,
+    /// Maximum number of values returned (keys are not limited)
+    pub limit: u64,
+}
+
+impl LabelQueryParams {
+    /// Check that the parameters can be executed, with the key when values
+    /// are requested
+    pub fn validate(&self, key: Option<&str>) -> Result<()> {
+        if key == Some("") {
+            return Err(Error::InvalidParameter(
+                "label key must not be empty".to_string(),
+            ));
+        }
+        if self.limit == 0 {
+            return Err(Error::InvalidParameter(
+                "limit must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for LabelQueryParams {
+    fn default() -> Self {
+        Self {
+            time_range: TimeRange::last_hours(24),
+            service_name: None,
+            scope: AttributeScope::Any,
+            limit: 20,
+        }
+    }
+}
+
+/// An attribute key seen in the time range
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LabelKey {
+    /// Attribute key
+    pub key: String,
+    /// Map the key was found in: `log` (data point attributes for metrics)
+    /// or `resource`
+    pub scope: AttributeScope,
+    /// Number of distinct values
+    pub cardinality: u64,
+    /// Number of rows carrying the key
+    pub count: u64,
+}
+
+/// One value of an attribute key and how often it occurs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LabelValue {
+    /// Value as stored in the attribute map
+    pub value: String,
+    /// Number of rows with this value
+    pub count: u64,
+}
+
+/// One metric in the catalog
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricInfo {
+    /// Metric name
+    pub name: String,
+    /// Metric type, which decides the supported aggregations
+    pub metric_type: MetricType,
+    /// Unit as reported by the source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// Description as reported by the source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Services emitting the metric, sorted
+    pub services: Vec<String>,
+    /// Distinct attribute sets seen in the time range
+    pub series_count: u64,
+    /// Timestamp of the newest data point
+    pub last_seen: chrono::DateTime<chrono::Utc>,
+}
+
+/// Merged histogram buckets over time
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HistogramSeries {
+    /// Values of the `group_by` keys for this series (empty when not grouped)
+    pub labels: BTreeMap<String, String>,
+    /// Bucket upper bounds; the last bucket is unbounded
+    pub bounds: Vec<f64>,
+    /// Bucket counts per time bucket, in time order
+    pub points: Vec<HistogramPoint>,
+}
+
+/// Histogram bucket counts observed during one time bucket
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramPoint {
+    /// Start of the time bucket
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Observations per histogram bucket, laid out by the series bounds
+    pub counts: Vec<u64>,
+}
+
+impl HistogramSeries {
+    /// Merge histogram samples of one group into a series. Every sample is
+    /// re-bucketed onto the layout with the most buckets, and samples that
+    /// share a time bucket are added up.
+    pub fn merge(
+        labels: BTreeMap<String, String>,
+        samples: Vec<(chrono::DateTime<chrono::Utc>, Vec<f64>, Vec<u64>)>,
+    ) -> Self {
+        let bounds = samples
+            .iter()
+            .map(|(_, bounds, _)| bounds)
+            .max_by_key(|bounds| bounds.len())
+            .cloned()
+            .unwrap_or_default();
+
+        let mut buckets: BTreeMap<chrono::DateTime<chrono::Utc>, Vec<u64>> = BTreeMap::new();
+        for (timestamp, sample_bounds, counts) in samples {
+            let target = buckets
+                .entry(timestamp)
+                .or_insert_with(|| vec![0; bounds.len() + 1]);
+            histogram::merge_into(&bounds, target, &sample_bounds, &counts);
+        }
+
+        Self {
+            labels,
+            bounds,
+            points: buckets
+                .into_iter()
+                .map(|(timestamp, counts)| HistogramPoint { timestamp, counts })
+                .collect(),
+        }
+    }
+
+    /// Merge exponential histogram samples of one group into a series. All
+    /// samples are merged at the lowest scale seen and laid out as explicit
+    /// buckets, so every time bucket shares one layout.
+    pub fn merge_exponential(
+        labels: BTreeMap<String, String>,
+        samples: Vec<(chrono::DateTime<chrono::Utc>, ExponentialHistogram)>,
+    ) -> Self {
+        let mut layout: Option<ExponentialHistogram> = None;
+        let mut buckets: BTreeMap<chrono::DateTime<chrono::Utc>, ExponentialHistogram> =
+            BTreeMap::new();
+        for (timestamp, sample) in samples {
+            match layout {
+                Some(ref mut layout) => layout.merge(&sample),
+                None => layout = Some(sample.clone()),
+            }
+            match buckets.get_mut(&timestamp) {
+                Some(bucket) => bucket.merge(&sample),
+                None => {
+                    buckets.insert(timestamp, sample);
+                }
+            }
+        }
+        let layout = layout.unwrap_or_default();
+
+        Self {
+            labels,
+            bounds: layout.explicit_bounds(),
+            points: buckets
+                .into_iter()
+                .map(|(timestamp, merged)| HistogramPoint {
+                    timestamp,
+                    counts: merged.explicit_counts(&layout),
+                })
+                .collect(),
+        }
+    }
+
+    /// Reduce each time bucket to a single value: a quantile for p50/p90/p99,
+    /// or the number of observations for count
+    pub fn summarize(self, aggregation: Aggregation) -> Result<MetricSeries> {
+        let level = match aggregation {
+            Aggregation::P50 => Some(0.5),
+            Aggregation::P90 => Some(0.9),
+            Aggregation::P99 => Some(0.99),
+            Aggregation::Count => None,
+            other => {
+                return Err(Error::InvalidParameter(format!(
+                "aggregation {other} is not supported for histograms, use p50, p90, p99 or count"
+            )))
+            }
+        };
+
+        let bounds = self.bounds;
+        let points = self
+            .points
+            .into_iter()
+            .filter_map(|point| {
+                #[allow(clippy::cast_precision_loss)]
+                let value = match level {
+                    Some(level) => histogram::quantile(&bounds, &point.counts, level)?,
+                    None => point.counts.iter().sum::<u64>() as f64,
+                };
+                Some(MetricDataPoint {
+                    timestamp: point.timestamp,
+                    value,
+                })
+            })
+            .collect();
+
+        Ok(MetricSeries {
+            labels: self.labels,
+            points,
+        })
+    }
+}
+
+/// Summary metric values over time
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SummarySeries {
+    /// Values of the `group_by` keys for this series (empty when not grouped)
+    pub labels: BTreeMap<String, String>,
+    /// Values per time bucket, in time order
+    pub points: Vec<SummaryPoint>,
+}
+
+/// Summary values observed during one time bucket
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryPoint {
+    /// Start of the time bucket
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Observations during the bucket
+    pub count: u64,
+    /// Sum of the observations during the bucket
+    pub sum: f64,
+    /// Reported quantiles, averaged over the data points in the bucket
+    pub quantiles: Vec<QuantileValue>,
+}
+
+impl SummarySeries {
+    /// Merge summary samples of one group into a series. Count and sum
+    /// increases are added up. Quantiles of different data points cannot be
+    /// combined exactly, so each level is averaged over the bucket.
+    pub fn merge(
+        labels: BTreeMap<String, String>,
+        samples: Vec<(chrono::DateTime<chrono::Utc>, u64, f64, Vec<QuantileValue>)>,
+    ) -> Self {
+        // Per bucket: count, sum and (level, sum of values, number of values)
+        type Bucket = (u64, f64, Vec<(f64, f64, u32)>);
+        let mut buckets: BTreeMap<chrono::DateTime<chrono::Utc>, Bucket> = BTreeMap::new();
+        for (timestamp, count, sum, quantiles) in samples {
+            let bucket = buckets.entry(timestamp).or_default();
+            bucket.0 += count;
+            bucket.1 += sum;
+            for q in quantiles {
+                match bucket
+                    .2
+                    .iter_mut()
+                    .find(|(level, _, _)| (level - q.quantile).abs() < 1e-9)
+                {
+                    Some(entry) => {
+                        entry.1 += q.value;
+                        entry.2 += 1;
+                    }
+                    None => bucket.2.push((q.quantile, q.value, 1)),
+                }
+            }
+        }
+
+        Self {
+            labels,
+            points: buckets
+                .into_iter()
+                .map(|(timestamp, (count, sum, mut levels))| {
+                    levels.sort_by(|a, b| a.0.total_cmp(&b.0));
+                    SummaryPoint {
+                        timestamp,
+                        count,
+                        sum,
+                        quantiles: levels
+                            .into_iter()
+                            .map(|(quantile, total, n)| QuantileValue {
+                                quantile,
+                                value: total / f64::from(n),
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Reduce each time bucket to a single value: a reported quantile for
+    /// p50/p90/p99, the count or sum of observations, or their mean for avg
+    pub fn summarize(self, aggregation: Aggregation) -> Result<MetricSeries> {
+        let level = match aggregation {
+            Aggregation::P50 => Some(0.5),
+            Aggregation::P90 => Some(0.9),
+            Aggregation::P99 => Some(0.99),
+            Aggregation::Count | Aggregation::Sum | Aggregation::Avg => None,
+            other => {
+                return Err(Error::InvalidParameter(format!(
+                    "aggregation {other} is not supported for summaries, \
+                     use p50, p90, p99, count, sum or avg"
+                )))
+            }
+        };
+
+        let total = self.points.len();
+        #[allow(clippy::cast_precision_loss)]
+        let points: Vec<MetricDataPoint> = self
+            .points
+            .into_iter()
+            .filter_map(|point| {
+                let value = match (level, aggregation) {
+                    (Some(level), _) => {
+                        point
+                            .quantiles
+                            .iter()
+                            .find(|q| (q.quantile - level).abs() < 1e-9)?
+                            .value
+                    }
+                    (None, Aggregation::Count) => point.count as f64,
+                    (None, Aggregation::Sum) => point.sum,
+                    (None, _) if point.count == 0 => return None,
+                    (None, _) => point.sum / point.count as f64,
+                };
+                Some(MetricDataPoint {
+                    timestamp: point.timestamp,
+                    value,
+                })
+            })
+            .collect();
+
+        if let Some(level) = level {
+            if points.is_empty() && total > 0 {
+                return Err(Error::InvalidParameter(format!(
+                    "summary does not report the {level} quantile"
+                )));
+            }
+        }
+
+        Ok(MetricSeries {
+            labels: self.labels,
+            points,
+        })
+    }
+}
+
+/// Parameters for metric query
+#[derive(Debug, Clone)]
+pub struct MetricQueryParams {
+    /// Metric to query
+    pub metric_name: String,
+    /// Time range to query
+    pub time_range: TimeRange,
+    /// Aggregation applied per bucket
+    pub aggregation: Aggregation,
+    /// Bucket size in seconds (default: 60)
+    pub interval_seconds: Option<u32>,
+    /// Label matchers on data point attributes (`log` scope) and resource
+    /// attributes, all of which must match
+    pub labels: Vec<AttributeFilter>,
+    /// Label keys to split the result by; each key is read from the data point
+    /// attributes, falling back to resource attributes
+    pub group_by: Vec<String>,
+}
+
+impl MetricQueryParams {
+    /// Check that the parameters can be executed
+    pub fn validate(&self) -> Result<()> {
+        for filter in &self.labels {
+            filter.validate()?;
+        }
+        if self.group_by.iter().any(String::is_empty) {
+            return Err(Error::InvalidParameter(
+                "group_by keys must not be empty".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Equality matchers for `label -> value` pairs
+pub fn label_matchers(labels: impl IntoIterator<Item = (String, String)>) -> Vec<AttributeFilter> {
+    labels
+        .into_iter()
+        .map(|(key, value)| AttributeFilter::new(key, AttributeOp::Eq(value)))
+        .collect()
+}
+
+/// One aggregated time series
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetricSeries {
+    /// Values of the `group_by` keys for this series (empty when not grouped)
+    pub labels: BTreeMap<String, String>,
+    /// Data points in time order
+    pub points: Vec<MetricDataPoint>,
+}
+
+/// A single metric data point in a time series
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricDataPoint {
+    /// Start of the bucket
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Aggregated value
+    pub value: f64,
+}
@@ -0,0 +1,216 @@
+//! Tenant isolation
+//!
+//! With `[tenancy]` enabled, every API request and MCP session resolves to
+//! one tenant, from its API key or, when trusted, the tenant header, and
+//! queries run against a store scoped with [`Store::for_tenant`]. A tenant
+//! owns the log and metric rows whose resource attribute
+//! [`TenancyConfig::attribute`] holds its ID.
+//!
+//! ClickHouse enforces the predicate through the `additional_table_filters`
+//! setting, which applies it to every read of the listed tables whatever
+//! the query looks like, so no query path can return another tenant's rows.
+//! The memory backend filters its rows where they are read.
+//!
+//! [`Store::for_tenant`]: crate::store::Store::for_tenant
+
+use serde::Serialize;
+
+use crate::{
+    config::TenancyConfig,
+    error::{Error, Result},
+    filter::attribute_text,
+    retention::string_literal,
+};
+
+/// Longest tenant ID or attribute key accepted
+pub const MAX_TENANT_LEN: usize = 128;
+
+/// Exporter and rollup tables holding tenant rows
+pub const TENANT_TABLES: [&str; 8] = [
+    "otel_logs",
+    "otel_metrics_gauge",
+    "otel_metrics_sum",
+    "otel_metrics_histogram",
+    "otel_metrics_exponential_histogram",
+    "otel_metrics_summary",
+    "metrics_rollup_1m",
+    "metrics_rollup_1h",
+];
+
+/// Tables aggregated across tenants, which scoped queries must not read
+pub const SHARED_TABLES: [&str; 1] = ["error_summary"];
+
+/// The tenant a store is restricted to
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct TenantScope {
+    attribute: String,
+    tenant: String,
+}
+
+impl TenantScope {
+    /// Scope to rows whose resource attribute `attribute` equals `tenant`.
+    /// Both are limited to ASCII letters, digits, `.`, `_` and `-`, so they
+    /// can be embedded in queries and cache keys as they are.
+    pub fn new(attribute: &str, tenant: &str) -> Result<Self> {
+        if !valid_name(attribute) {
+            return Err(Error::Config(format!(
+                "invalid tenancy.attribute {attribute:?}"
+            )));
+        }
+        if !valid_name(tenant) {
+            return Err(Error::InvalidParameter(format!(
+                "invalid tenant ID {tenant:?}"
+            )));
+        }
+        Ok(Self {
+            attribute: attribute.to_string(),
+            tenant: tenant.to_string(),
+        })
+    }
+
+    /// Resource attribute holding the tenant ID
+    pub fn attribute(&self) -> &str {
+        &self.attribute
+    }
+
+    /// Tenant ID
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    /// Whether a row with these resource attributes belongs to the tenant
+    pub fn owns(&self, resource_attributes: &serde_json::Value) -> bool {
+        attribute_text(resource_attributes.get(&self.attribute)) == self.tenant
+    }
+
+    /// Value of the ClickHouse `additional_table_filters` setting: a
+    /// `Map(String, String)` literal with the tenant predicate for each of
+    /// [`TENANT_TABLES`] and a filter dropping every row of
+    /// [`SHARED_TABLES`]. `log_resource_map` reads `otel_logs`
+    /// resource attributes as a map; metric tables always store one.
+    pub fn table_filters(&self, log_resource_map: &str) -> String {
+        let predicate = |map: &str| {
+            format!(
+                "{map}[{}] = {}",
+                string_literal(&self.attribute),
+                string_literal(&self.tenant)
+            )
+        };
+        let filters: Vec<String> = TENANT_TABLES
+            .iter()
+            .map(|&table| {
+                let map = if table == "otel_logs" {
+                    log_resource_map
+                } else {
+                    "ResourceAttributes"
+                };
+                (table, predicate(map))
+            })
+            .chain(SHARED_TABLES.iter().map(|&table| (table, "0".to_string())))
+            .map(|(table, filter)| {
+                format!("{}: {}", string_literal(table), string_literal(&filter))
+            })
+            .collect();
+        format!("{{{}}}", filters.join(", "))
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_TENANT_LEN
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+}
+
+/// Credentials presented by an API request or MCP session
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Credentials<'a> {
+    /// Value of the `Authorization` header
+    pub authorization: Option<&'a str>,
+    /// Value of the `X-API-Key` header
+    pub api_key: Option<&'a str>,
+    /// Value of the configured tenant header
+    pub tenant: Option<&'a str>,
+}
+
+impl Credentials<'_> {
+    /// API key from `Authorization: Bearer <key>`, else `X-API-Key`
+    fn key(&self) -> Option<&str> {
+        self.authorization
+            .and_then(|value| {
+                let (scheme, token) = value.trim().split_once(' ')?;
+                scheme
+                    .eq_ignore_ascii_case("bearer")
+                    .then_some(token.trim())
+            })
+            .or(self.api_key)
+            .filter(|key| !key.is_empty())
+    }
+}
+
+/// Tenant a request is restricted to, or `None` with tenancy disabled.
+///
+/// A known API key selects its tenant; a tenant header naming another tenant
+/// is then refused. Without a key the tenant header is only accepted with
+/// `trust_header`.
+pub fn resolve(config: &TenancyConfig, credentials: &Credentials) -> Result<Option<TenantScope>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    let tenant = match credentials.key() {
+        Some(key) => {
+            let granted = config
+                .api_keys
+                .iter()
+                .find(|api_key| constant_time_eq(api_key.key.as_bytes(), key.as_bytes()))
+                .ok_or_else(|| Error::Unauthorized("unknown API key".to_string()))?;
+            if let Some(requested) = credentials.tenant {
+                if requested != granted.tenant {
+                    return Err(Error::Forbidden(format!(
+                        "API key does not grant access to tenant {requested:?}"
+                    )));
+                }
+            }
+            granted.tenant.as_str()
+        }
+        None => match credentials.tenant {
+            Some(requested) if config.trust_header => requested,
+            _ => {
+                return Err(Error::Unauthorized(
+                    "an API key is required to select a tenant".to_string(),
+                ))
+            }
+        },
+    };
+    TenantScope::new(&config.attribute, tenant).map(Some)
+}
+
+/// Compare keys without returning early on the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Check the tenancy configuration when a server starts
+pub fn validate(config: &TenancyConfig) -> Result<()> {
+    if !config.enabled {
+        return Ok(());
+    }
+    if config.api_keys.is_empty() && !config.trust_header {
+        return Err(Error::Config(
+            "tenancy is enabled without api_keys or trust_header, so no request can be served"
+                .to_string(),
+        ));
+    }
+    for api_key in &config.api_keys {
+        if api_key.key.is_empty() {
+            return Err(Error::Config(format!(
+                "empty API key for tenant {:?}",
+                api_key.tenant
+            )));
+        }
+        TenantScope::new(&config.attribute, &api_key.tenant)
+            .map_err(|e| Error::Config(e.to_string()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,645 @@
+//! Tests for tenant module
+
+use std::{collections::BTreeSet, sync::Arc};
+
+use futures::TryStreamExt;
+
+use crate::{
+    cache::CachedStore,
+    config::{CacheConfig, ClickHouseConfig, LimitsConfig, TenancyConfig, TenantApiKey},
+    error::Error,
+    fake_clickhouse::{push_string, FakeClickHouse, Received},
+    limits::LimitedStore,
+    memory::MemoryStore,
+    migrations::MIGRATIONS_TABLE,
+    resilience::ResilientStore,
+    rollup::BACKFILLS_TABLE,
+    store::{
+        LabelQueryParams, LogSearchParams, LogStore, MetricCatalogParams, MetricQueryParams,
+        SharedStore, Store,
+    },
+    tenant::{resolve, validate, Credentials, TenantScope, SHARED_TABLES, TENANT_TABLES},
+    test_support::{self, all_time, gauge, ts},
+    types::{
+        Aggregation, LogEntry, LogSeverity, Metric, MetricType, QuantileValue, Temporality,
+        TimeRange,
+    },
+};
+
+const TENANTS: [&str; 2] = ["acme", "globex"];
+
+fn resource(tenant: &str) -> serde_json::Value {
+    serde_json::json!({ "tenant.id": tenant, "deployment.environment": tenant })
+}
+
+fn log(tenant: &str, secs: i64, service: &str) -> LogEntry {
+    LogEntry {
+        trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+        resource_attributes: resource(tenant),
+        log_attributes: serde_json::json!({ "owner": tenant }),
+        ..test_support::log(
+            secs,
+            LogSeverity::Error,
+            service,
+            &format!("{tenant} failed at {secs}"),
+        )
+    }
+}
+
+fn metric(tenant: &str, name: &str, secs: i64, value: f64) -> Metric {
+    Metric {
+        resource_attributes: resource(tenant),
+        attributes: serde_json::json!({ "owner": tenant }),
+        service_name: Some("shop".to_string()),
+        ..gauge(name, secs, value)
+    }
+}
+
+/// Each tenant has three logs and five data points under the same service,
+/// trace and metric names; globex has one more metric and service. Rows
+/// without the tenant attribute belong to nobody.
+fn shared_memory() -> Arc<MemoryStore> {
+    let store = MemoryStore::new();
+    for (i, tenant) in TENANTS.into_iter().enumerate() {
+        let value = if i == 0 { 1.0 } else { 100.0 };
+        store.insert_logs([
+            log(tenant, 10, "shop"),
+            log(tenant, 20, "shop"),
+            log(tenant, 30, &format!("{tenant}-api")),
+        ]);
+        store.insert_metrics([
+            metric(tenant, "cpu", 10, value),
+            metric(tenant, "cpu", 70, value),
+            Metric {
+                metric_type: MetricType::Histogram,
+                temporality: Temporality::Delta,
+                value: None,
+                bucket_counts: vec![1, 2],
+                explicit_bounds: vec![0.5],
+                ..metric(tenant, "latency", 10, 0.0)
+            },
+            Metric {
+                metric_type: MetricType::Summary,
+                value: None,
+                count: Some(4),
+                sum: Some(value),
+                quantile_values: vec![QuantileValue {
+                    quantile: 0.5,
+                    value,
+                }],
+                ..metric(tenant, "rpc", 10, 0.0)
+            },
+        ]);
+    }
+    store.insert_metrics([metric("globex", "globex_only", 10, 5.0)]);
+    store.insert_logs([LogEntry {
+        resource_attributes: serde_json::json!({}),
+        ..log("untagged", 15, "shop")
+    }]);
+    Arc::new(store)
+}
+
+/// The store stack `from_config` builds for ClickHouse, over `memory`
+fn store_stack(memory: Arc<MemoryStore>) -> SharedStore {
+    let store: SharedStore = Arc::new(ResilientStore::new(memory, &ClickHouseConfig::default()));
+    let store: SharedStore = Arc::new(CachedStore::new(store, CacheConfig::default()));
+    Arc::new(LimitedStore::new(store, LimitsConfig::default()))
+}
+
+fn scope(tenant: &str) -> TenantScope {
+    TenantScope::new("tenant.id", tenant).unwrap()
+}
+
+fn owned(tenant: &str, resource_attributes: &serde_json::Value) -> bool {
+    resource_attributes["tenant.id"] == tenant
+}
+
+/// Every log query and the service catalog through a tenant view return
+/// the tenant's rows only
+async fn assert_logs_isolated(store: &dyn Store, tenant: &str, other_log: uuid::Uuid) {
+    let search = LogSearchParams {
+        time_range: all_time(),
+        ..LogSearchParams::default()
+    };
+    let logs = store.search_logs(&search).await.unwrap();
+    assert_eq!(logs.len(), 3, "{tenant}");
+    assert!(logs.iter().all(|l| owned(tenant, &l.resource_attributes)));
+
+    let page = store.search_logs_page(&search).await.unwrap();
+    assert_eq!(page.logs.len(), 3);
+    let streamed: Vec<LogEntry> = store.stream_logs(&search).try_collect().await.unwrap();
+    assert_eq!(streamed.len(), 3);
+    assert!(streamed
+        .iter()
+        .all(|l| owned(tenant, &l.resource_attributes)));
+
+    assert_eq!(store.count_logs(&all_time()).await.unwrap(), 3);
+    assert!(store.get_log(other_log).await.unwrap().is_none());
+    assert!(store.get_log(logs[0].id).await.unwrap().is_some());
+
+    let trace = store
+        .get_trace_logs("4bf92f3577b34da6a3ce929d0e0e4736", &all_time(), 100)
+        .await
+        .unwrap();
+    assert_eq!(trace.len(), 3);
+
+    let volume = store.count_logs_over_time(&search, 60).await.unwrap();
+    assert_eq!(volume.iter().map(|b| b.total).sum::<u64>(), 3);
+
+    let summary = store
+        .error_summary(&LogSearchParams {
+            min_severity: Some(LogSeverity::Error),
+            ..search.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(summary.total, 3);
+    assert!(summary
+        .patterns
+        .iter()
+        .flat_map(|p| &p.samples)
+        .all(|sample| sample.starts_with(tenant)));
+
+    let labels = LabelQueryParams {
+        time_range: all_time(),
+        ..LabelQueryParams::default()
+    };
+    let values = store
+        .log_attribute_values("tenant.id", &labels)
+        .await
+        .unwrap();
+    assert_eq!(values.len(), 1);
+    assert_eq!(values[0].value, tenant);
+    let keys = store.log_attribute_keys(&labels).await.unwrap();
+    assert!(keys.iter().all(|k| k.cardinality == 1));
+
+    let services = store.list_services(&all_time()).await.unwrap();
+    let names: Vec<&str> = services.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, [format!("{tenant}-api").as_str(), "shop"]);
+    assert_eq!(services[1].log_count, 2);
+    assert_eq!(
+        services[1].resource_attributes["deployment.environment"],
+        tenant
+    );
+}
+
+/// Every metric query through a tenant view reads the tenant's points only
+async fn assert_metrics_isolated(store: &dyn Store, tenant: &str) {
+    let names = store.list_metric_names().await.unwrap();
+    assert_eq!(
+        names.contains(&"globex_only".to_string()),
+        tenant == "globex"
+    );
+
+    let expected = if tenant == "acme" { 1.0 } else { 100.0 };
+    let query = |name: &str, aggregation| MetricQueryParams {
+        metric_name: name.to_string(),
+        time_range: all_time(),
+        aggregation,
+        interval_seconds: Some(60),
+        labels: Vec::new(),
+        group_by: vec!["tenant.id".to_string()],
+    };
+    let series = store
+        .query_metrics(&query("cpu", Aggregation::Avg))
+        .await
+        .unwrap();
+    assert_eq!(series.len(), 1);
+    assert_eq!(series[0].labels["tenant.id"], tenant);
+    assert!(series[0]
+        .points
+        .iter()
+        .all(|p| (p.value - expected).abs() < 1e-9));
+
+    let heatmap = store
+        .query_histogram(&query("latency", Aggregation::P50))
+        .await
+        .unwrap();
+    let observations: u64 = heatmap
+        .iter()
+        .flat_map(|s| &s.points)
+        .flat_map(|p| &p.counts)
+        .sum();
+    assert_eq!(observations, 3);
+
+    let summaries = store
+        .query_summary(&query("rpc", Aggregation::P50))
+        .await
+        .unwrap();
+    assert_eq!(summaries.len(), 1);
+    assert!(!summaries[0].points.is_empty());
+    assert!(summaries[0]
+        .points
+        .iter()
+        .flat_map(|p| &p.quantiles)
+        .all(|q| (q.value - expected).abs() < 1e-9));
+
+    let catalog = store
+        .metric_catalog(&MetricCatalogParams {
+            time_range: all_time(),
+            prefix: None,
+            service_name: None,
+        })
+        .await
+        .unwrap();
+    assert!(catalog.iter().all(|m| m.series_count == 1));
+    assert_eq!(catalog.len(), if tenant == "globex" { 4 } else { 3 });
+
+    let labels = LabelQueryParams {
+        time_range: all_time(),
+        ..LabelQueryParams::default()
+    };
+    let values = store
+        .metric_label_values("cpu", "owner", &labels)
+        .await
+        .unwrap();
+    assert_eq!(values.len(), 1);
+    assert_eq!(values[0].value, tenant);
+    let keys = store.metric_label_keys("cpu", &labels).await.unwrap();
+    assert!(keys.iter().all(|k| k.cardinality == 1));
+
+    let stats = store.get_stats().await.unwrap();
+    assert_eq!(stats.log_count, 3);
+    let points = if tenant == "globex" { 5 } else { 4 };
+    assert_eq!(stats.metric_count, points);
+}
+
+#[tokio::test]
+async fn test_tenant_views_do_not_leak() {
+    let memory = shared_memory();
+    let all = memory
+        .search_logs(&LogSearchParams {
+            time_range: all_time(),
+            ..LogSearchParams::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(all.len(), 7);
+    let id_of = |tenant: &str| {
+        all.iter()
+            .find(|l| owned(tenant, &l.resource_attributes))
+            .unwrap()
+            .id
+    };
+
+    // Directly on the backend, then through the full wrapper stack twice, so
+    // the second round is answered from the shared cache
+    for tenant in TENANTS {
+        let other = TENANTS.into_iter().find(|&t| t != tenant).unwrap();
+        let view = memory.for_tenant(&scope(tenant)).unwrap();
+        assert_logs_isolated(view.as_ref(), tenant, id_of(other)).await;
+        assert_metrics_isolated(view.as_ref(), tenant).await;
+    }
+    let stack = store_stack(memory.clone());
+    for _ in 0..2 {
+        for tenant in TENANTS {
+            let other = TENANTS.into_iter().find(|&t| t != tenant).unwrap();
+            let view = stack.for_tenant(&scope(tenant)).unwrap();
+            assert_logs_isolated(view.as_ref(), tenant, id_of(other)).await;
+            assert_metrics_isolated(view.as_ref(), tenant).await;
+        }
+    }
+    let stats = stack.cache_stats().unwrap();
+    assert!(stats.hits > 0);
+
+    // A tenant without rows sees nothing at all
+    let view = stack.for_tenant(&scope("initech")).unwrap();
+    assert_eq!(view.count_logs(&all_time()).await.unwrap(), 0);
+    assert!(view.list_metric_names().await.unwrap().is_empty());
+    assert!(view.list_services(&all_time()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_scoped_view_cannot_switch_tenant() {
+    let memory = shared_memory();
+    let acme = memory.with_tenant(&scope("acme")).unwrap();
+    assert!(acme.with_tenant(&scope("acme")).is_ok());
+    assert!(matches!(
+        acme.for_tenant(&scope("globex")),
+        Err(Error::Forbidden(_))
+    ));
+
+    let view = store_stack(memory).for_tenant(&scope("acme")).unwrap();
+    assert!(matches!(
+        view.for_tenant(&scope("globex")),
+        Err(Error::Forbidden(_))
+    ));
+}
+
+/// `RowBinary` rows for a query: table sizes, a rollup start and an
+/// `error_summary_mv` filling `error_summary` so both are read, and zero for
+/// counts; no rows otherwise
+fn answer(sql: &str) -> Vec<u8> {
+    let mut rows = Vec::new();
+    if sql.contains("FROM system.parts") {
+        for table in ["otel_logs", "otel_metrics_gauge", "metrics_rollup_1m"] {
+            push_string(&mut rows, table);
+            rows.extend_from_slice(&10_u64.to_le_bytes());
+            rows.extend_from_slice(&1000_u64.to_le_bytes());
+        }
+    } else if sql.contains("toUnixTimestamp(min(Bucket))") {
+        rows.extend_from_slice(&1_u32.to_le_bytes());
+    } else if sql.starts_with("SELECT create_table_query") {
+        push_string(
+            &mut rows,
+            "CREATE MATERIALIZED VIEW default.error_summary_mv TO default.error_summary \
+             (`ServiceName` String, `Count` UInt64) AS SELECT ServiceName, count() AS Count \
+             FROM default.otel_logs",
+        );
+    } else if sql.contains("toUnixTimestamp(applied_at)") {
+        rows.extend_from_slice(&0_u32.to_le_bytes());
+    } else if sql.starts_with("SELECT count()") {
+        rows.extend_from_slice(&0_u64.to_le_bytes());
+    } else if sql.starts_with("SELECT 1") {
+        rows.push(1);
+    }
+    rows
+}
+
+/// Call every store method, covering log search, pages, streams, traces,
+/// labels, raw and rollup metric queries, error summaries and stats
+async fn call_every_method(store: &dyn Store) {
+    let search = LogSearchParams {
+        time_range: all_time(),
+        ..LogSearchParams::default()
+    };
+    store.search_logs(&search).await.unwrap();
+    let page = store.search_logs_page(&search).await.unwrap();
+    assert!(page.logs.is_empty());
+    let streamed: Vec<LogEntry> = store.stream_logs(&search).try_collect().await.unwrap();
+    assert!(streamed.is_empty());
+    store.count_logs(&all_time()).await.unwrap();
+    store.get_log(uuid::Uuid::nil()).await.unwrap();
+    store
+        .get_trace_logs("4bf92f3577b34da6a3ce929d0e0e4736", &all_time(), 100)
+        .await
+        .unwrap();
+    store.count_logs_over_time(&search, 60).await.unwrap();
+    // Whole hours, which unscoped clients count from error_summary
+    let errors = LogSearchParams {
+        time_range: TimeRange {
+            start: ts(0),
+            end: ts(4 * 3600),
+        },
+        min_severity: Some(LogSeverity::Error),
+        ..LogSearchParams::default()
+    };
+    store.error_summary(&errors).await.unwrap();
+
+    let labels = LabelQueryParams {
+        time_range: all_time(),
+        ..LabelQueryParams::default()
+    };
+    store.log_attribute_keys(&labels).await.unwrap();
+    store.log_attribute_values("owner", &labels).await.unwrap();
+
+    store.list_metric_names().await.unwrap();
+    // ts(2800) starts an hour, so the first two read the hour and minute rollups
+    let hours = TimeRange {
+        start: ts(2800),
+        end: ts(2800 + 2 * 3600),
+    };
+    for (aggregation, interval_seconds) in [
+        (Aggregation::Avg, 3600),
+        (Aggregation::P99, 60),
+        (Aggregation::Max, 90),
+        (Aggregation::Rate, 60),
+    ] {
+        let params = MetricQueryParams {
+            metric_name: "cpu".to_string(),
+            time_range: hours.clone(),
+            aggregation,
+            interval_seconds: Some(interval_seconds),
+            labels: Vec::new(),
+            group_by: vec!["owner".to_string()],
+        };
+        store.query_metrics(&params).await.unwrap();
+        store.query_histogram(&params).await.unwrap();
+        store.query_summary(&params).await.unwrap();
+    }
+    store
+        .metric_catalog(&MetricCatalogParams {
+            time_range: all_time(),
+            prefix: None,
+            service_name: None,
+        })
+        .await
+        .unwrap();
+    store.metric_label_keys("cpu", &labels).await.unwrap();
+    store
+        .metric_label_values("cpu", "owner", &labels)
+        .await
+        .unwrap();
+
+    assert!(store.health_check().await.unwrap());
+    let stats = store.get_stats().await.unwrap();
+    assert!(stats.table_ttls.is_empty());
+    store.list_services(&all_time()).await.unwrap();
+}
+
+/// Every query carries the tenant filter and reads only tables it applies
+/// to; returns the tables read
+fn assert_scoped(received: &[Received], filters: &str) -> BTreeSet<String> {
+    assert!(!received.is_empty());
+    let mut read = BTreeSet::new();
+    for request in received {
+        assert_eq!(
+            request.option("additional_table_filters"),
+            Some(filters),
+            "{}",
+            request.sql
+        );
+        for table in request.tables() {
+            assert!(
+                TENANT_TABLES.contains(&table.as_str())
+                    || table.starts_with("system.")
+                    || table == BACKFILLS_TABLE,
+                "{table} read by {}",
+                request.sql
+            );
+            read.insert(table);
+        }
+    }
+    read
+}
+
+#[tokio::test]
+async fn test_clickhouse_queries_carry_tenant_filter() {
+    let server = FakeClickHouse::start(|sql| Ok(answer(sql))).await;
+    let acme = scope("acme");
+    let filters = acme.table_filters("ResourceAttributes");
+
+    // Unscoped, the error summary reads the shared hourly counts
+    call_every_method(&server.client()).await;
+    let received = server.take();
+    assert!(received
+        .iter()
+        .all(|r| r.option("additional_table_filters").is_none()));
+    let read: BTreeSet<String> = received.iter().flat_map(Received::tables).collect();
+    assert!(read.contains(SHARED_TABLES[0]));
+    assert!(read.contains(MIGRATIONS_TABLE));
+
+    // Scoped directly and through the wrapper stack of `from_config`
+    let client = server.client().with_tenant(&acme).unwrap();
+    call_every_method(&client).await;
+    let read = assert_scoped(&server.take(), &filters);
+    for table in TENANT_TABLES {
+        assert!(read.contains(table), "{table} not read");
+    }
+
+    let store: SharedStore = Arc::new(server.client());
+    let store: SharedStore = Arc::new(ResilientStore::new(store, &ClickHouseConfig::default()));
+    let store: SharedStore = Arc::new(CachedStore::new(store, CacheConfig::default()));
+    let store = LimitedStore::new(store, LimitsConfig::default());
+    let view = store.for_tenant(&acme).unwrap();
+    call_every_method(view.as_ref()).await;
+    assert_scoped(&server.take(), &filters);
+}
+
+#[test]
+fn test_scope_validation() {
+    assert!(TenantScope::new("tenant.id", "acme-eu_1.prod").is_ok());
+    for tenant in ["", "acme'", "a b", "acme\\", "ü", &"a".repeat(129)] {
+        assert!(
+            matches!(
+                TenantScope::new("tenant.id", tenant),
+                Err(Error::InvalidParameter(_))
+            ),
+            "{tenant:?}"
+        );
+    }
+    assert!(matches!(
+        TenantScope::new("tenant']", "acme"),
+        Err(Error::Config(_))
+    ));
+}
+
+#[test]
+fn test_table_filters() {
+    let filters = scope("acme").table_filters("ResourceAttributes");
+    assert!(filters.starts_with(
+        "{'otel_logs': 'ResourceAttributes[\\'tenant.id\\'] = \\'acme\\'', \
+         'otel_metrics_gauge': 'ResourceAttributes[\\'tenant.id\\'] = \\'acme\\'', "
+    ));
+    for table in TENANT_TABLES {
+        assert!(filters.contains(&format!("'{table}': ")), "{table}");
+    }
+    assert!(filters.ends_with(", 'error_summary': '0'}"));
+
+    // String layouts read the attribute through a JSON expression
+    let filters = scope("acme").table_filters(
+        "CAST(JSONExtractKeysAndValues(ResourceAttributes, 'String'), 'Map(String, String)')",
+    );
+    assert!(filters.starts_with(
+        "{'otel_logs': 'CAST(JSONExtractKeysAndValues(ResourceAttributes, \\'String\\'), \
+         \\'Map(String, String)\\')[\\'tenant.id\\'] = \\'acme\\'', "
+    ));
+}
+
+fn tenancy() -> TenancyConfig {
+    TenancyConfig {
+        enabled: true,
+        api_keys: vec![
+            TenantApiKey {
+                key: "acme-key".to_string(),
+                tenant: "acme".to_string(),
+            },
+            TenantApiKey {
+                key: "globex-key".to_string(),
+                tenant: "globex".to_string(),
+            },
+        ],
+        ..TenancyConfig::default()
+    }
+}
+
+fn tenant_of(config: &TenancyConfig, credentials: Credentials) -> Option<String> {
+    resolve(config, &credentials)
+        .unwrap()
+        .map(|scope| scope.tenant().to_string())
+}
+
+#[test]
+fn test_resolve() {
+    let config = tenancy();
+
+    // Disabled tenancy ignores credentials
+    assert_eq!(
+        tenant_of(&TenancyConfig::default(), Credentials::default()),
+        None
+    );
+
+    let bearer = Credentials {
+        authorization: Some("Bearer globex-key"),
+        ..Credentials::default()
+    };
+    assert_eq!(tenant_of(&config, bearer).as_deref(), Some("globex"));
+    let header_key = Credentials {
+        api_key: Some("acme-key"),
+        tenant: Some("acme"),
+        ..Credentials::default()
+    };
+    assert_eq!(tenant_of(&config, header_key).as_deref(), Some("acme"));
+
+    // A key never reaches another tenant's data
+    let other = Credentials {
+        tenant: Some("globex"),
+        ..header_key
+    };
+    assert!(matches!(resolve(&config, &other), Err(Error::Forbidden(_))));
+    for credentials in [
+        Credentials::default(),
+        Credentials {
+            authorization: Some("Basic acme-key"),
+            ..Credentials::default()
+        },
+        Credentials {
+            api_key: Some("acme-key2"),
+            ..Credentials::default()
+        },
+        Credentials {
+            tenant: Some("acme"),
+            ..Credentials::default()
+        },
+    ] {
+        assert!(
+            matches!(resolve(&config, &credentials), Err(Error::Unauthorized(_))),
+            "{credentials:?}"
+        );
+    }
+
+    // Behind a trusted gateway the header alone selects the tenant
+    let trusted = TenancyConfig {
+        trust_header: true,
+        ..tenancy()
+    };
+    let header = Credentials {
+        tenant: Some("initech"),
+        ..Credentials::default()
+    };
+    assert_eq!(tenant_of(&trusted, header).as_deref(), Some("initech"));
+    let injected = Credentials {
+        tenant: Some("x' OR 1 = 1"),
+        ..Credentials::default()
+    };
+    assert!(matches!(
+        resolve(&trusted, &injected),
+        Err(Error::InvalidParameter(_))
+    ));
+}
+
+#[test]
+fn test_validate() {
+    assert!(validate(&TenancyConfig::default()).is_ok());
+    assert!(validate(&tenancy()).is_ok());
+
+    let unreachable = TenancyConfig {
+        enabled: true,
+        ..TenancyConfig::default()
+    };
+    assert!(matches!(validate(&unreachable), Err(Error::Config(_))));
+
+    let mut config = tenancy();
+    config.api_keys[1].tenant = "globex corp".to_string();
+    assert!(matches!(validate(&config), Err(Error::Config(_))));
+}
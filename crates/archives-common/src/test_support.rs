@@ -0,0 +1,61 @@
+//! Fixtures shared by the tests of this crate and of the servers
+//!
+//! Built with `cfg(test)` and, for other crates, the `test-support` feature.
+
+use chrono::{DateTime, Utc};
+
+use crate::types::{LogEntry, LogSeverity, Metric, MetricType, Temporality, TimeRange};
+
+/// `secs` seconds after a fixed instant in November 2023
+pub fn ts(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+}
+
+/// The hour starting at [`ts(0)`](ts)
+pub fn all_time() -> TimeRange {
+    TimeRange {
+        start: ts(0),
+        end: ts(3600),
+    }
+}
+
+/// Log of `service` at [`ts(secs)`](ts) without attributes or trace context
+pub fn log(secs: i64, severity: LogSeverity, service: &str, body: &str) -> LogEntry {
+    LogEntry {
+        id: uuid::Uuid::new_v4(),
+        timestamp: ts(secs),
+        observed_timestamp: ts(secs),
+        trace_id: None,
+        span_id: None,
+        severity,
+        severity_text: severity.to_string(),
+        body: body.to_string(),
+        resource_attributes: serde_json::json!({}),
+        log_attributes: serde_json::json!({}),
+        service_name: Some(service.to_string()),
+    }
+}
+
+/// Gauge data point at [`ts(secs)`](ts) without attributes or service
+pub fn gauge(name: &str, secs: i64, value: f64) -> Metric {
+    Metric {
+        name: name.to_string(),
+        description: None,
+        unit: None,
+        metric_type: MetricType::Gauge,
+        timestamp: ts(secs),
+        value: Some(value),
+        is_monotonic: false,
+        temporality: Temporality::Cumulative,
+        start_time: None,
+        bucket_counts: Vec::new(),
+        explicit_bounds: Vec::new(),
+        exponential_histogram: None,
+        quantile_values: Vec::new(),
+        count: None,
+        sum: None,
+        resource_attributes: serde_json::json!({}),
+        attributes: serde_json::json!({}),
+        service_name: None,
+    }
+}
@@ -6,8 +6,11 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-/// Log severity levels matching OpenTelemetry specification
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+use crate::histogram::ExponentialHistogram;
+
+/// Log severity levels matching OpenTelemetry specification, ordered from
+/// least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum LogSeverity {
     Trace,
@@ -45,6 +48,25 @@ impl LogSeverity {
     }
 }
 
+impl std::str::FromStr for LogSeverity {
+    type Err = crate::Error;
+
+    /// Parse a severity name, case-insensitively
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "TRACE" => Ok(Self::Trace),
+            "DEBUG" => Ok(Self::Debug),
+            "INFO" => Ok(Self::Info),
+            "WARN" | "WARNING" => Ok(Self::Warn),
+            "ERROR" => Ok(Self::Error),
+            "FATAL" => Ok(Self::Fatal),
+            _ => Err(crate::Error::InvalidParameter(format!(
+                "unknown severity: {s}"
+            ))),
+        }
+    }
+}
+
 impl std::fmt::Display for LogSeverity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -61,7 +83,8 @@ impl std::fmt::Display for LogSeverity {
 /// A log entry from ClickHouse otel_logs table
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
-    /// Unique identifier
+    /// Stable identifier derived from the row, see [`crate::cursor::log_id`]
+    #[serde(default)]
     pub id: Uuid,
 
     /// Timestamp of the log entry
@@ -111,6 +134,39 @@ pub enum MetricType {
     Summary,
 }
 
+impl MetricType {
+    /// Every metric type, in table order
+    pub const ALL: [Self; 5] = [
+        Self::Gauge,
+        Self::Sum,
+        Self::Histogram,
+        Self::ExponentialHistogram,
+        Self::Summary,
+    ];
+
+    /// ClickHouse table holding data points of this type
+    pub const fn table_name(self) -> &'static str {
+        match self {
+            Self::Gauge => "otel_metrics_gauge",
+            Self::Sum => "otel_metrics_sum",
+            Self::Histogram => "otel_metrics_histogram",
+            Self::ExponentialHistogram => "otel_metrics_exponential_histogram",
+            Self::Summary => "otel_metrics_summary",
+        }
+    }
+
+    /// Aggregations `query_metrics` supports for this type
+    pub const fn aggregations(self) -> &'static [Aggregation] {
+        use Aggregation::{Avg, Count, Increase, Max, Min, Rate, Sum, P50, P90, P99};
+        match self {
+            Self::Gauge => &[Avg, Min, Max, Sum, Count, P50, P90, P99],
+            Self::Sum => &[Avg, Min, Max, Sum, Count, P50, P90, P99, Rate, Increase],
+            Self::Histogram | Self::ExponentialHistogram => &[P50, P90, P99, Count],
+            Self::Summary => &[P50, P90, P99, Count, Sum, Avg],
+        }
+    }
+}
+
 impl std::fmt::Display for MetricType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -123,6 +179,17 @@ impl std::fmt::Display for MetricType {
     }
 }
 
+/// Aggregation temporality of sum metrics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Temporality {
+    /// Each value is the change since the previous data point
+    Delta,
+    /// Each value is the total since the series start time
+    #[default]
+    Cumulative,
+}
+
 /// A metric data point from ClickHouse otel_metrics tables
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metric {
@@ -147,6 +214,43 @@ pub struct Metric {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub value: Option<f64>,
 
+    /// Whether a sum only increases, apart from resets (counters)
+    #[serde(default)]
+    pub is_monotonic: bool,
+
+    /// How sum and histogram values accumulate between data points
+    #[serde(default)]
+    pub temporality: Temporality,
+
+    /// Start of the period a cumulative value counts from; a new start time
+    /// marks a reset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<DateTime<Utc>>,
+
+    /// Histogram bucket counts; one more than `explicit_bounds`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bucket_counts: Vec<u64>,
+
+    /// Histogram bucket upper bounds, ascending
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub explicit_bounds: Vec<f64>,
+
+    /// Exponential histogram buckets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exponential_histogram: Option<ExponentialHistogram>,
+
+    /// Summary quantiles as reported by the source
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quantile_values: Vec<QuantileValue>,
+
+    /// Number of observations (for histograms and summaries)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+
+    /// Sum of observations (for histograms and summaries)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sum: Option<f64>,
+
     /// Resource attributes
     #[serde(default)]
     pub resource_attributes: serde_json::Value,
@@ -160,6 +264,15 @@ pub struct Metric {
     pub service_name: Option<String>,
 }
 
+/// A pre-computed quantile of a summary metric
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct QuantileValue {
+    /// Quantile level, 0 to 1
+    pub quantile: f64,
+    /// Observed value at the quantile
+    pub value: f64,
+}
+
 /// Time range for queries
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimeRange {
@@ -184,6 +297,11 @@ impl TimeRange {
         let start = end - chrono::Duration::hours(hours);
         Self { start, end }
     }
+
+    /// Whether `timestamp` falls in the range
+    pub fn contains(&self, timestamp: DateTime<Utc>) -> bool {
+        timestamp >= self.start && timestamp < self.end
+    }
 }
 
 /// Pagination parameters
@@ -223,6 +341,17 @@ pub enum Aggregation {
     P50,
     P90,
     P99,
+    /// Per-second increase of a counter, adjusted for resets
+    Rate,
+    /// Total increase of a counter over each bucket, adjusted for resets
+    Increase,
+}
+
+impl Aggregation {
+    /// Whether the aggregation works on counter increases rather than raw values
+    pub const fn is_counter(self) -> bool {
+        matches!(self, Self::Rate | Self::Increase)
+    }
 }
 
 impl std::fmt::Display for Aggregation {
@@ -236,6 +365,8 @@ impl std::fmt::Display for Aggregation {
             Aggregation::P50 => write!(f, "p50"),
             Aggregation::P90 => write!(f, "p90"),
             Aggregation::P99 => write!(f, "p99"),
+            Aggregation::Rate => write!(f, "rate"),
+            Aggregation::Increase => write!(f, "increase"),
         }
     }
 }
@@ -104,6 +104,8 @@ fn test_aggregation_display() {
     assert_eq!(format!("{}", Aggregation::P50), "p50");
     assert_eq!(format!("{}", Aggregation::P90), "p90");
     assert_eq!(format!("{}", Aggregation::P99), "p99");
+    assert_eq!(format!("{}", Aggregation::Rate), "rate");
+    assert_eq!(format!("{}", Aggregation::Increase), "increase");
 }
 
 #[test]
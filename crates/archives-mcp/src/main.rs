@@ -9,7 +9,7 @@ use std::{net::SocketAddr, sync::Arc};
 
 use tracing::info;
 
-use archives_common::{clickhouse::ClickHouseClient, Config};
+use archives_common::{store, Config};
 
 use server::McpServer;
 
@@ -31,22 +31,23 @@ async fn main() -> anyhow::Result<()> {
     let config = Config::load_or_default();
     info!(
         clickhouse_url = %config.clickhouse.url,
+        storage_backend = ?config.storage.backend,
         mcp_port = config.mcp.port,
         "Configuration loaded"
     );
 
-    // Create ClickHouse client
-    let clickhouse = ClickHouseClient::new(&config.clickhouse)?;
+    // Create storage backend
+    let store = store::from_config(&config).await?;
 
-    // Check connectivity
-    match clickhouse.health_check().await {
-        Ok(true) => info!("ClickHouse connection established"),
-        Ok(false) => tracing::error!("ClickHouse health check returned false"),
-        Err(e) => tracing::error!(error = %e, "ClickHouse connection failed - continuing anyway"),
+    // Check connectivity; the check is already retried, so give up here
+    match store.health_check().await {
+        Ok(true) => info!("Storage connection established"),
+        Ok(false) => anyhow::bail!("storage health check returned false"),
+        Err(e) => return Err(anyhow::anyhow!(e).context("storage connection failed")),
     }
 
     // Create and run MCP server
-    let server = McpServer::new(clickhouse, config.clone());
+    let server = McpServer::new(store, config.clone());
 
     let addr = SocketAddr::new(
         config.mcp.host.parse().unwrap_or([0, 0, 0, 0].into()),
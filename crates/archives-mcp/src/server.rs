@@ -1,11 +1,16 @@
 //! MCP Server implementation
 
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use axum::{
     extract::State,
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
@@ -13,21 +18,24 @@ use serde::{Deserialize, Serialize};
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing::{error, info};
 
-use archives_common::{clickhouse::ClickHouseClient, Config};
+use archives_common::{
+    tenant::{self, Credentials, TenantScope},
+    Config, Error, Result, SharedStore,
+};
 
 use crate::tools::{self, McpTool, ToolRegistry};
 
 /// MCP Server state
 pub struct McpServer {
-    clickhouse: ClickHouseClient,
+    store: SharedStore,
     config: Config,
     tools: ToolRegistry,
 }
 
 impl McpServer {
-    pub fn new(clickhouse: ClickHouseClient, config: Config) -> Self {
+    pub fn new(store: SharedStore, config: Config) -> Self {
         Self {
-            clickhouse,
+            store,
             config,
             tools: tools::create_tool_registry(),
         }
@@ -35,9 +43,10 @@ impl McpServer {
 
     pub async fn run(self, addr: SocketAddr) -> anyhow::Result<()> {
         let state = Arc::new(AppState {
-            clickhouse: self.clickhouse,
+            store: self.store,
             config: self.config,
             tools: self.tools,
+            sessions: Sessions::default(),
         });
 
         let app = Router::new()
@@ -59,9 +68,99 @@ impl McpServer {
 }
 
 struct AppState {
-    clickhouse: ClickHouseClient,
+    store: SharedStore,
     config: Config,
     tools: ToolRegistry,
+    sessions: Sessions,
+}
+
+impl AppState {
+    /// Store a tool call runs against, scoped to the tenant of the request,
+    /// and the session it belongs to. With tenancy disabled the whole store
+    /// is used and no session is kept.
+    fn scoped_store(&self, headers: &HeaderMap) -> Result<(SharedStore, Option<String>)> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let credentials = Credentials {
+            authorization: header(header::AUTHORIZATION.as_str()),
+            api_key: header("x-api-key"),
+            tenant: header(&self.config.tenancy.header),
+        };
+        let Some(scope) = tenant::resolve(&self.config.tenancy, &credentials)? else {
+            return Ok((Arc::clone(&self.store), None));
+        };
+        let session = self.sessions.bind(header(SESSION_HEADER), &scope)?;
+        Ok((self.store.for_tenant(&scope)?, Some(session)))
+    }
+}
+
+/// Header carrying the session ID, as in the MCP Streamable HTTP transport
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Sessions idle for longer are forgotten
+const SESSION_IDLE: Duration = Duration::from_secs(3600);
+
+/// Tenant each MCP session is bound to. A session takes the tenant of the
+/// request that started it, and every later request in it must present
+/// credentials for the same tenant, so an agent's session never mixes data
+/// of two tenants.
+#[derive(Default)]
+struct Sessions {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+struct Session {
+    tenant: TenantScope,
+    last_seen: Instant,
+}
+
+impl Sessions {
+    /// Check a request with tenant `scope` against session `id`, or start a
+    /// new session without one; returns the session ID
+    fn bind(&self, id: Option<&str>, scope: &TenantScope) -> Result<String> {
+        let now = Instant::now();
+        id.map_or_else(
+            || Ok(self.start(scope, now)),
+            |id| self.touch(id, scope, now).map(|()| id.to_string()),
+        )
+    }
+
+    /// Start a session for `scope`, dropping idle ones
+    fn start(&self, scope: &TenantScope, now: Instant) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let session = Session {
+            tenant: scope.clone(),
+            last_seen: now,
+        };
+        let mut sessions = self.sessions.lock().expect("session lock poisoned");
+        sessions.retain(|_, session| now.duration_since(session.last_seen) < SESSION_IDLE);
+        sessions.insert(id.clone(), session);
+        drop(sessions);
+        id
+    }
+
+    /// Keep session `id` alive if it belongs to `scope`
+    fn touch(&self, id: &str, scope: &TenantScope, now: Instant) -> Result<()> {
+        let owned = self
+            .sessions
+            .lock()
+            .expect("session lock poisoned")
+            .get_mut(id)
+            .filter(|session| now.duration_since(session.last_seen) < SESSION_IDLE)
+            .map(|session| {
+                let owned = session.tenant == *scope;
+                if owned {
+                    session.last_seen = now;
+                }
+                owned
+            });
+        match owned {
+            None => Err(Error::NotFound(format!("session {id}"))),
+            Some(false) => Err(Error::Forbidden(format!(
+                "session {id} is bound to another tenant"
+            ))),
+            Some(true) => Ok(()),
+        }
+    }
 }
 
 async fn shutdown_signal() {
@@ -76,16 +175,15 @@ async fn shutdown_signal() {
 // ============================================================================
 
 async fn health_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.clickhouse.health_check().await {
-        Ok(true) => (
-            StatusCode::OK,
-            Json(serde_json::json!({"status": "healthy"})),
-        ),
-        _ => (
-            StatusCode::SERVICE_UNAVAILABLE,
-            Json(serde_json::json!({"status": "unhealthy"})),
-        ),
+    let (status, health) = match state.store.health_check().await {
+        Ok(true) => (StatusCode::OK, "healthy"),
+        _ => (StatusCode::SERVICE_UNAVAILABLE, "unhealthy"),
+    };
+    let mut body = serde_json::json!({"status": health});
+    if let Some(breaker) = state.store.breaker_status() {
+        body["circuit_breaker"] = serde_json::json!(breaker);
     }
+    (status, Json(body))
 }
 
 async fn ping_handler() -> impl IntoResponse {
@@ -103,11 +201,32 @@ async fn list_tools_handler(State(state): State<Arc<AppState>>) -> impl IntoResp
 /// Main MCP endpoint for tool invocation
 async fn mcp_handler(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(request): Json<McpRequest>,
-) -> impl IntoResponse {
+) -> Response {
     info!(tool = %request.tool, "MCP tool invocation");
 
-    match tools::execute_tool(&state.clickhouse, &request.tool, request.params).await {
+    let (store, session) = match state.scoped_store(&headers) {
+        Ok(scoped) => scoped,
+        Err(e) => {
+            let status = match e {
+                Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+                Error::Forbidden(_) => StatusCode::FORBIDDEN,
+                Error::NotFound(_) => StatusCode::NOT_FOUND,
+                Error::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            let body = McpResponse {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            };
+            return (status, Json(body)).into_response();
+        }
+    };
+
+    let result = tools::execute_tool(store.as_ref(), &request.tool, request.params).await;
+    let mut response = match result {
         Ok(result) => (
             StatusCode::OK,
             Json(McpResponse {
@@ -128,6 +247,11 @@ async fn mcp_handler(
             )
         }
     }
+    .into_response();
+    if let Some(id) = session.and_then(|id| HeaderValue::from_str(&id).ok()) {
+        response.headers_mut().insert(SESSION_HEADER, id);
+    }
+    response
 }
 
 #[derive(Debug, Deserialize)]
@@ -145,3 +269,31 @@ struct McpResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_bound_to_one_tenant() {
+        let sessions = Sessions::default();
+        let acme = TenantScope::new("tenant.id", "acme").unwrap();
+        let globex = TenantScope::new("tenant.id", "globex").unwrap();
+
+        let id = sessions.bind(None, &acme).unwrap();
+        assert_eq!(sessions.bind(Some(&id), &acme).unwrap(), id);
+        assert!(matches!(
+            sessions.bind(Some(&id), &globex),
+            Err(Error::Forbidden(_))
+        ));
+        assert!(matches!(
+            sessions.bind(Some("unknown"), &acme),
+            Err(Error::NotFound(_))
+        ));
+
+        // Each new session gets its own ID
+        let other = sessions.bind(None, &globex).unwrap();
+        assert_ne!(other, id);
+        assert_eq!(sessions.bind(Some(&other), &globex).unwrap(), other);
+    }
+}
@@ -2,12 +2,18 @@
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use archives_common::{
-    clickhouse::{ClickHouseClient, LogSearchParams},
+    cursor,
+    filter::{AttributeFilter, AttributeScope},
+    query::LogQuery,
+    store::{
+        LabelQueryParams, LogSearchParams, MetricCatalogParams, MetricDataPoint, MetricInfo,
+        MetricQueryParams,
+    },
     types::{Aggregation, LogSeverity, Pagination, TimeRange},
-    Error, Result,
+    Error, Result, Store,
 };
 
 /// MCP Tool definition
@@ -46,9 +52,25 @@ impl ToolRegistry {
 /// Create the default tool registry with all available tools
 pub fn create_tool_registry() -> ToolRegistry {
     let mut registry = ToolRegistry::new();
+    for tool in [
+        search_logs_tool(),
+        tail_logs_tool(),
+        get_log_tool(),
+        get_trace_logs_tool(),
+        get_error_summary_tool(),
+        list_services_tool(),
+        describe_metric_tool(),
+        list_labels_tool(),
+        query_metrics_tool(),
+        get_system_health_tool(),
+    ] {
+        registry.register(tool);
+    }
+    registry
+}
 
-    // search_logs tool
-    registry.register(McpTool {
+fn search_logs_tool() -> McpTool {
+    McpTool {
         name: "search_logs".to_string(),
         description: "Search logs with time range, severity filter, and text query. Returns matching log entries.".to_string(),
         input_schema: serde_json::json!({
@@ -58,6 +80,10 @@ pub fn create_tool_registry() -> ToolRegistry {
                     "type": "string",
                     "description": "Text to search for in log messages"
                 },
+                "q": {
+                    "type": "string",
+                    "description": "Log query language, alternative to separate params, e.g. 'service:checkout severity>=warn \"payment timeout\" attr.http.status_code=500 -body:healthcheck'"
+                },
                 "hours": {
                     "type": "integer",
                     "description": "Number of hours to search back (default: 1)",
@@ -72,17 +98,57 @@ pub fn create_tool_registry() -> ToolRegistry {
                     "type": "string",
                     "description": "Filter by service name"
                 },
+                "trace_id": {
+                    "type": "string",
+                    "description": "Filter by trace ID (hex)"
+                },
+                "span_id": {
+                    "type": "string",
+                    "description": "Filter by span ID (hex)"
+                },
+                "attributes": {
+                    "type": "array",
+                    "description": "Filters on log/resource attributes, all of which must match",
+                    "items": {
+                        "type": "object",
+                        "required": ["key", "op"],
+                        "properties": {
+                            "key": {
+                                "type": "string",
+                                "description": "Attribute key, e.g. http.status_code or k8s.pod.name"
+                            },
+                            "op": {
+                                "type": "string",
+                                "enum": ["eq", "ne", "exists", "prefix", "regex", "gt", "gte", "lt", "lte"]
+                            },
+                            "value": {
+                                "description": "Operand (omit for exists; number for gt/gte/lt/lte)"
+                            },
+                            "scope": {
+                                "type": "string",
+                                "enum": ["any", "log", "resource"],
+                                "description": "Attribute map to read (default: any)",
+                                "default": "any"
+                            }
+                        }
+                    }
+                },
                 "limit": {
                     "type": "integer",
                     "description": "Maximum number of results (default: 50)",
                     "default": 50
+                },
+                "cursor": {
+                    "type": "string",
+                    "description": "next_cursor from a previous call, to fetch the following page"
                 }
             }
         }),
-    });
+    }
+}
 
-    // tail_logs tool
-    registry.register(McpTool {
+fn tail_logs_tool() -> McpTool {
+    McpTool {
         name: "tail_logs".to_string(),
         description:
             "Get the most recent log entries. Useful for seeing what's happening right now."
@@ -106,12 +172,57 @@ pub fn create_tool_registry() -> ToolRegistry {
                 }
             }
         }),
-    });
+    }
+}
 
-    // get_error_summary tool
-    registry.register(McpTool {
+fn get_log_tool() -> McpTool {
+    McpTool {
+        name: "get_log".to_string(),
+        description: "Get a single log entry with all attributes by the id returned from search_logs or tail_logs.".to_string(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "id": {
+                    "type": "string",
+                    "description": "Log id"
+                }
+            },
+            "required": ["id"]
+        }),
+    }
+}
+
+fn get_trace_logs_tool() -> McpTool {
+    McpTool {
+        name: "get_trace_logs".to_string(),
+        description: "Get all logs emitted during one distributed trace, oldest first, to follow a request across services.".to_string(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "trace_id": {
+                    "type": "string",
+                    "description": "Trace ID (hex)"
+                },
+                "hours": {
+                    "type": "integer",
+                    "description": "Number of hours to search back (default: 24)",
+                    "default": 24
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of logs (default: 200)",
+                    "default": 200
+                }
+            },
+            "required": ["trace_id"]
+        }),
+    }
+}
+
+fn get_error_summary_tool() -> McpTool {
+    McpTool {
         name: "get_error_summary".to_string(),
-        description: "Get a summary of errors in the system. Groups errors by message pattern and shows counts.".to_string(),
+        description: "Get a summary of errors in the system. Groups error messages into templates with variable parts (numbers, IDs, IPs, quoted strings) masked, and shows exact counts, a breakdown per service, samples and first/last seen.".to_string(),
         input_schema: serde_json::json!({
             "type": "object",
             "properties": {
@@ -124,13 +235,107 @@ pub fn create_tool_registry() -> ToolRegistry {
                     "type": "integer",
                     "description": "Maximum number of error patterns to return (default: 10)",
                     "default": 10
+                },
+                "service": {
+                    "type": "string",
+                    "description": "Only count errors of this service"
+                }
+            }
+        }),
+    }
+}
+
+fn list_services_tool() -> McpTool {
+    McpTool {
+        name: "list_services".to_string(),
+        description: "List services seen in logs and metrics with log volume, error count and ratio, metric count, first/last seen and version/environment. Use to discover service names for the service filters."
+            .to_string(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "hours": {
+                    "type": "integer",
+                    "description": "Only consider data from the last N hours (default: 24)",
+                    "default": 24
                 }
             }
         }),
-    });
+    }
+}
 
-    // query_metrics tool
-    registry.register(McpTool {
+fn describe_metric_tool() -> McpTool {
+    McpTool {
+        name: "describe_metric".to_string(),
+        description: "Describe a metric (type, unit, description, services, series count, supported aggregations), or list metrics by prefix or service. Use before query_metrics."
+            .to_string(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "metric_name": {
+                    "type": "string",
+                    "description": "Exact metric name to describe; omit to list metrics"
+                },
+                "prefix": {
+                    "type": "string",
+                    "description": "Only list metrics whose name starts with this prefix"
+                },
+                "service": {
+                    "type": "string",
+                    "description": "Only list metrics emitted by this service"
+                },
+                "hours": {
+                    "type": "integer",
+                    "description": "Only consider data points from the last N hours (default: 24)",
+                    "default": 24
+                }
+            }
+        }),
+    }
+}
+
+fn list_labels_tool() -> McpTool {
+    McpTool {
+        name: "list_labels".to_string(),
+        description: "List the label keys of a metric (or log attribute keys when metric_name is omitted) with their cardinality, or the most frequent values of one key. Use to build label filters and group_by keys."
+            .to_string(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "metric_name": {
+                    "type": "string",
+                    "description": "Metric whose labels to list; omit to list log attributes"
+                },
+                "key": {
+                    "type": "string",
+                    "description": "Return the most frequent values of this key instead of listing keys"
+                },
+                "scope": {
+                    "type": "string",
+                    "enum": ["any", "log", "resource"],
+                    "description": "Attribute map to read: log (log or data point attributes), resource, or any (default; log attributes, falling back to resource)",
+                    "default": "any"
+                },
+                "service": {
+                    "type": "string",
+                    "description": "Only consider data from this service"
+                },
+                "hours": {
+                    "type": "integer",
+                    "description": "Only consider data from the last N hours (default: 24)",
+                    "default": 24
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of values (default: 20)",
+                    "default": 20
+                }
+            }
+        }),
+    }
+}
+
+fn query_metrics_tool() -> McpTool {
+    McpTool {
         name: "query_metrics".to_string(),
         description: "Query metrics with aggregation over time. Returns time series data."
             .to_string(),
@@ -149,44 +354,54 @@ pub fn create_tool_registry() -> ToolRegistry {
                 },
                 "aggregation": {
                     "type": "string",
-                    "enum": ["avg", "min", "max", "sum", "count", "p50", "p90", "p99"],
-                    "description": "Aggregation function (default: avg)",
+                    "enum": ["avg", "min", "max", "sum", "count", "p50", "p90", "p99", "rate", "increase"],
+                    "description": "Aggregation function (default: avg). rate (per second) and increase apply to counters and handle resets. Histogram metrics support p50, p90, p99 and count; summaries support p50, p90, p99, count, sum and avg",
                     "default": "avg"
                 },
                 "interval_seconds": {
                     "type": "integer",
                     "description": "Time bucket size in seconds (default: 60)",
                     "default": 60
+                },
+                "labels": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Label matchers, all of which must match, e.g. [\"region=us-east-1\", \"pod=~checkout-.*\", \"env!=dev\"]"
+                },
+                "group_by": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Label keys to split the result into one series per value, e.g. [\"service.name\"]"
                 }
             }
         }),
-    });
+    }
+}
 
-    // get_system_health tool
-    registry.register(McpTool {
+fn get_system_health_tool() -> McpTool {
+    McpTool {
         name: "get_system_health".to_string(),
         description: "Get overall system health summary including error rates, log volume, and storage usage.".to_string(),
         input_schema: serde_json::json!({
             "type": "object",
             "properties": {}
         }),
-    });
-
-    registry
+    }
 }
 
 /// Execute a tool by name
-pub async fn execute_tool(
-    clickhouse: &ClickHouseClient,
-    tool_name: &str,
-    params: Value,
-) -> Result<Value> {
+pub async fn execute_tool(store: &dyn Store, tool_name: &str, params: Value) -> Result<Value> {
     match tool_name {
-        "search_logs" => execute_search_logs(clickhouse, params).await,
-        "tail_logs" => execute_tail_logs(clickhouse, params).await,
-        "get_error_summary" => execute_get_error_summary(clickhouse, params).await,
-        "query_metrics" => execute_query_metrics(clickhouse, params).await,
-        "get_system_health" => execute_get_system_health(clickhouse, params).await,
+        "search_logs" => execute_search_logs(store, params).await,
+        "tail_logs" => execute_tail_logs(store, params).await,
+        "get_log" => execute_get_log(store, params).await,
+        "get_trace_logs" => execute_get_trace_logs(store, params).await,
+        "get_error_summary" => execute_get_error_summary(store, params).await,
+        "list_services" => execute_list_services(store, params).await,
+        "describe_metric" => execute_describe_metric(store, params).await,
+        "list_labels" => execute_list_labels(store, params).await,
+        "query_metrics" => execute_query_metrics(store, params).await,
+        "get_system_health" => execute_get_system_health(store, params).await,
         _ => Err(Error::NotFound(format!("Tool not found: {}", tool_name))),
     }
 }
@@ -198,13 +413,19 @@ pub async fn execute_tool(
 #[derive(Debug, Deserialize)]
 struct SearchLogsParams {
     query: Option<String>,
+    q: Option<String>,
     hours: Option<i64>,
     min_severity: Option<String>,
     service: Option<String>,
+    trace_id: Option<String>,
+    span_id: Option<String>,
+    #[serde(default)]
+    attributes: Vec<AttributeFilter>,
     limit: Option<u64>,
+    cursor: Option<String>,
 }
 
-async fn execute_search_logs(clickhouse: &ClickHouseClient, params: Value) -> Result<Value> {
+async fn execute_search_logs(store: &dyn Store, params: Value) -> Result<Value> {
     let p: SearchLogsParams = serde_json::from_value(params)?;
 
     let hours = p.hours.unwrap_or(1);
@@ -222,21 +443,32 @@ async fn execute_search_logs(clickhouse: &ClickHouseClient, params: Value) -> Re
             _ => None,
         });
 
-    let search_params = LogSearchParams {
+    let mut search_params = LogSearchParams {
         time_range: TimeRange::last_hours(hours),
         min_severity,
         text_query: p.query,
         service_name: p.service,
+        trace_id: p.trace_id,
+        span_id: p.span_id,
+        body_terms: Vec::new(),
+        excluded_body_terms: Vec::new(),
+        attribute_filters: p.attributes,
         pagination: Pagination { offset: 0, limit },
+        cursor: p.cursor.as_deref().map(str::parse).transpose()?,
     };
+    if let Some(ref q) = p.q {
+        LogQuery::parse(q)?.apply(&mut search_params);
+    }
 
-    let logs = clickhouse.search_logs(&search_params).await?;
+    let page = store.search_logs_page(&search_params).await?;
+    let logs = page.logs;
 
     // Format for LLM consumption
     let formatted: Vec<Value> = logs
         .iter()
         .map(|log| {
             serde_json::json!({
+                "id": log.id,
                 "timestamp": log.timestamp.to_rfc3339(),
                 "severity": log.severity.to_string(),
                 "service": log.service_name,
@@ -248,7 +480,9 @@ async fn execute_search_logs(clickhouse: &ClickHouseClient, params: Value) -> Re
 
     Ok(serde_json::json!({
         "count": formatted.len(),
-        "logs": formatted
+        "logs": formatted,
+        "has_more": page.has_more,
+        "next_cursor": page.next_cursor
     }))
 }
 
@@ -259,7 +493,7 @@ struct TailLogsParams {
     service: Option<String>,
 }
 
-async fn execute_tail_logs(clickhouse: &ClickHouseClient, params: Value) -> Result<Value> {
+async fn execute_tail_logs(store: &dyn Store, params: Value) -> Result<Value> {
     let p: TailLogsParams = serde_json::from_value(params)?;
 
     let count = p.count.unwrap_or(20);
@@ -281,18 +515,25 @@ async fn execute_tail_logs(clickhouse: &ClickHouseClient, params: Value) -> Resu
         min_severity,
         text_query: None,
         service_name: p.service,
+        trace_id: None,
+        span_id: None,
+        body_terms: Vec::new(),
+        excluded_body_terms: Vec::new(),
+        attribute_filters: Vec::new(),
         pagination: Pagination {
             offset: 0,
             limit: count,
         },
+        cursor: None,
     };
 
-    let logs = clickhouse.search_logs(&search_params).await?;
+    let logs = store.search_logs(&search_params).await?;
 
     let formatted: Vec<Value> = logs
         .iter()
         .map(|log| {
             serde_json::json!({
+                "id": log.id,
                 "timestamp": log.timestamp.to_rfc3339(),
                 "severity": log.severity.to_string(),
                 "service": log.service_name,
@@ -307,66 +548,240 @@ async fn execute_tail_logs(clickhouse: &ClickHouseClient, params: Value) -> Resu
     }))
 }
 
+#[derive(Debug, Deserialize)]
+struct GetLogParams {
+    id: String,
+}
+
+async fn execute_get_log(store: &dyn Store, params: Value) -> Result<Value> {
+    let p: GetLogParams = serde_json::from_value(params)?;
+    let id = cursor::parse_log_id(&p.id)?;
+
+    let log = store
+        .get_log(id)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("log {id}")))?;
+
+    Ok(serde_json::to_value(log)?)
+}
+
+#[derive(Debug, Deserialize)]
+struct GetTraceLogsParams {
+    trace_id: String,
+    hours: Option<i64>,
+    limit: Option<u64>,
+}
+
+async fn execute_get_trace_logs(store: &dyn Store, params: Value) -> Result<Value> {
+    let p: GetTraceLogsParams = serde_json::from_value(params)?;
+
+    let time_range = TimeRange::last_hours(p.hours.unwrap_or(24));
+    let logs = store
+        .get_trace_logs(&p.trace_id, &time_range, p.limit.unwrap_or(200))
+        .await?;
+
+    let services: BTreeSet<&str> = logs
+        .iter()
+        .filter_map(|log| log.service_name.as_deref())
+        .collect();
+
+    let formatted: Vec<Value> = logs
+        .iter()
+        .map(|log| {
+            serde_json::json!({
+                "id": log.id,
+                "timestamp": log.timestamp.to_rfc3339(),
+                "severity": log.severity.to_string(),
+                "service": log.service_name,
+                "span_id": log.span_id,
+                "message": log.body,
+            })
+        })
+        .collect();
+
+    Ok(serde_json::json!({
+        "trace_id": p.trace_id,
+        "count": formatted.len(),
+        "services": services,
+        "logs": formatted
+    }))
+}
+
 #[derive(Debug, Deserialize)]
 struct ErrorSummaryParams {
     hours: Option<i64>,
     limit: Option<u64>,
+    service: Option<String>,
 }
 
-async fn execute_get_error_summary(clickhouse: &ClickHouseClient, params: Value) -> Result<Value> {
+async fn execute_get_error_summary(store: &dyn Store, params: Value) -> Result<Value> {
     let p: ErrorSummaryParams = serde_json::from_value(params)?;
 
     let hours = p.hours.unwrap_or(24);
     let limit = p.limit.unwrap_or(10);
 
-    // Get errors from the time range
+    // Errors are counted per template by the store, so totals stay exact
     let search_params = LogSearchParams {
         time_range: TimeRange::last_hours(hours),
         min_severity: Some(LogSeverity::Error),
-        text_query: None,
-        service_name: None,
-        pagination: Pagination {
-            offset: 0,
-            limit: 1000, // Get more logs for aggregation
-        },
+        service_name: p.service,
+        pagination: Pagination { offset: 0, limit },
+        ..LogSearchParams::default()
     };
 
-    let logs = clickhouse.search_logs(&search_params).await?;
+    let summary = store.error_summary(&search_params).await?;
 
-    // Group by message pattern (first 100 chars)
-    let mut error_counts: HashMap<String, (u64, String)> = HashMap::new();
-    for log in &logs {
-        let pattern = if log.body.len() > 100 {
-            format!("{}...", &log.body[..100])
-        } else {
-            log.body.clone()
-        };
-        let entry = error_counts
-            .entry(pattern.clone())
-            .or_insert((0, log.body.clone()));
-        entry.0 += 1;
-    }
+    let patterns: Vec<Value> = summary
+        .patterns
+        .into_iter()
+        .map(|p| {
+            serde_json::json!({
+                "pattern": p.pattern,
+                "count": p.count,
+                "example": p.samples.first(),
+                "samples": p.samples,
+                "services": p.services,
+                "first_seen": p.first_seen.to_rfc3339(),
+                "last_seen": p.last_seen.to_rfc3339()
+            })
+        })
+        .collect();
+
+    Ok(serde_json::json!({
+        "total_errors": summary.total,
+        "time_range_hours": hours,
+        "services": summary.services,
+        "top_patterns": patterns
+    }))
+}
 
-    // Sort by count and take top N
-    let mut sorted: Vec<_> = error_counts.into_iter().collect();
-    sorted.sort_by(|a, b| b.1 .0.cmp(&a.1 .0));
-    sorted.truncate(limit as usize);
+#[derive(Debug, Deserialize)]
+struct ListServicesParams {
+    hours: Option<i64>,
+}
+
+async fn execute_list_services(store: &dyn Store, params: Value) -> Result<Value> {
+    let p: ListServicesParams = serde_json::from_value(params)?;
+    let hours = p.hours.unwrap_or(24);
 
-    let patterns: Vec<Value> = sorted
+    let services: Vec<Value> = store
+        .list_services(&TimeRange::last_hours(hours))
+        .await?
         .into_iter()
-        .map(|(pattern, (count, example))| {
+        .map(|s| {
             serde_json::json!({
-                "pattern": pattern,
-                "count": count,
-                "example": example
+                "name": s.name,
+                "log_count": s.log_count,
+                "error_count": s.error_count,
+                "error_ratio": (s.error_ratio * 10_000.0).round() / 10_000.0,
+                "metric_count": s.metric_count,
+                "first_seen": s.first_seen.to_rfc3339(),
+                "last_seen": s.last_seen.to_rfc3339(),
+                "resource_attributes": s.resource_attributes,
             })
         })
         .collect();
 
     Ok(serde_json::json!({
-        "total_errors": logs.len(),
         "time_range_hours": hours,
-        "top_patterns": patterns
+        "count": services.len(),
+        "services": services
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct DescribeMetricParams {
+    metric_name: Option<String>,
+    prefix: Option<String>,
+    service: Option<String>,
+    hours: Option<i64>,
+}
+
+async fn execute_describe_metric(store: &dyn Store, params: Value) -> Result<Value> {
+    let p: DescribeMetricParams = serde_json::from_value(params)?;
+
+    let catalog = store
+        .metric_catalog(&MetricCatalogParams {
+            time_range: TimeRange::last_hours(p.hours.unwrap_or(24)),
+            prefix: p.metric_name.clone().or(p.prefix),
+            service_name: p.service,
+        })
+        .await?;
+
+    let describe = |info: &MetricInfo| {
+        let aggregations: Vec<String> = info
+            .metric_type
+            .aggregations()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        serde_json::json!({
+            "name": info.name,
+            "type": info.metric_type.to_string(),
+            "unit": info.unit,
+            "description": info.description,
+            "services": info.services,
+            "series_count": info.series_count,
+            "last_seen": info.last_seen.to_rfc3339(),
+            "aggregations": aggregations,
+        })
+    };
+
+    if let Some(name) = p.metric_name {
+        let info = catalog
+            .iter()
+            .find(|info| info.name == name)
+            .ok_or_else(|| Error::NotFound(format!("Metric not found: {}", name)))?;
+        return Ok(describe(info));
+    }
+
+    let metrics: Vec<Value> = catalog.iter().map(describe).collect();
+    Ok(serde_json::json!({
+        "count": metrics.len(),
+        "metrics": metrics
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct ListLabelsParams {
+    metric_name: Option<String>,
+    key: Option<String>,
+    #[serde(default)]
+    scope: AttributeScope,
+    service: Option<String>,
+    hours: Option<i64>,
+    limit: Option<u64>,
+}
+
+async fn execute_list_labels(store: &dyn Store, params: Value) -> Result<Value> {
+    let p: ListLabelsParams = serde_json::from_value(params)?;
+
+    let params = LabelQueryParams {
+        time_range: TimeRange::last_hours(p.hours.unwrap_or(24)),
+        service_name: p.service,
+        scope: p.scope,
+        limit: p.limit.unwrap_or(20),
+    };
+
+    if let Some(key) = p.key {
+        let values = match p.metric_name {
+            Some(ref name) => store.metric_label_values(name, &key, &params).await?,
+            None => store.log_attribute_values(&key, &params).await?,
+        };
+        return Ok(serde_json::json!({
+            "key": key,
+            "count": values.len(),
+            "values": values
+        }));
+    }
+
+    let keys = match p.metric_name {
+        Some(ref name) => store.metric_label_keys(name, &params).await?,
+        None => store.log_attribute_keys(&params).await?,
+    };
+    Ok(serde_json::json!({
+        "count": keys.len(),
+        "keys": keys
     }))
 }
 
@@ -376,9 +791,13 @@ struct QueryMetricsParams {
     hours: Option<i64>,
     aggregation: Option<String>,
     interval_seconds: Option<u32>,
+    #[serde(default)]
+    labels: Vec<String>,
+    #[serde(default)]
+    group_by: Vec<String>,
 }
 
-async fn execute_query_metrics(clickhouse: &ClickHouseClient, params: Value) -> Result<Value> {
+async fn execute_query_metrics(store: &dyn Store, params: Value) -> Result<Value> {
     let p: QueryMetricsParams = serde_json::from_value(params)?;
 
     let hours = p.hours.unwrap_or(1);
@@ -395,26 +814,63 @@ async fn execute_query_metrics(clickhouse: &ClickHouseClient, params: Value) ->
             "p50" => Some(Aggregation::P50),
             "p90" => Some(Aggregation::P90),
             "p99" => Some(Aggregation::P99),
+            "rate" => Some(Aggregation::Rate),
+            "increase" => Some(Aggregation::Increase),
             _ => None,
         })
         .unwrap_or(Aggregation::Avg);
 
-    let query_params = archives_common::clickhouse::MetricQueryParams {
+    let labels = p
+        .labels
+        .iter()
+        .map(|l| l.parse::<AttributeFilter>())
+        .collect::<Result<Vec<_>>>()?;
+
+    let query_params = MetricQueryParams {
         metric_name: p.metric_name.clone(),
         time_range: TimeRange::last_hours(hours),
         aggregation,
         interval_seconds: Some(interval),
-        labels: None,
+        labels,
+        group_by: p.group_by.clone(),
+    };
+
+    let series = store.query_metrics(&query_params).await?;
+
+    let format_points = |points: &[MetricDataPoint]| -> Vec<Value> {
+        points
+            .iter()
+            .map(|p| {
+                serde_json::json!({
+                    "timestamp": p.timestamp.to_rfc3339(),
+                    "value": p.value
+                })
+            })
+            .collect()
     };
 
-    let data = clickhouse.query_metrics(&query_params).await?;
+    if p.group_by.is_empty() {
+        let points = series
+            .first()
+            .map(|s| format_points(&s.points))
+            .unwrap_or_default();
+
+        return Ok(serde_json::json!({
+            "metric_name": p.metric_name,
+            "aggregation": aggregation.to_string(),
+            "interval_seconds": interval,
+            "data_points": points.len(),
+            "data": points
+        }));
+    }
 
-    let points: Vec<Value> = data
+    let formatted: Vec<Value> = series
         .iter()
-        .map(|p| {
+        .map(|s| {
             serde_json::json!({
-                "timestamp": p.timestamp.to_rfc3339(),
-                "value": p.value
+                "labels": s.labels,
+                "data_points": s.points.len(),
+                "data": format_points(&s.points)
             })
         })
         .collect();
@@ -423,14 +879,15 @@ async fn execute_query_metrics(clickhouse: &ClickHouseClient, params: Value) ->
         "metric_name": p.metric_name,
         "aggregation": aggregation.to_string(),
         "interval_seconds": interval,
-        "data_points": points.len(),
-        "data": points
+        "group_by": p.group_by,
+        "series_count": formatted.len(),
+        "series": formatted
     }))
 }
 
-async fn execute_get_system_health(clickhouse: &ClickHouseClient, _params: Value) -> Result<Value> {
+async fn execute_get_system_health(store: &dyn Store, _params: Value) -> Result<Value> {
     // Get database stats
-    let stats = clickhouse.get_stats().await?;
+    let stats = store.get_stats().await?;
 
     // Get recent error count
     let error_params = LogSearchParams {
@@ -438,18 +895,24 @@ async fn execute_get_system_health(clickhouse: &ClickHouseClient, _params: Value
         min_severity: Some(LogSeverity::Error),
         text_query: None,
         service_name: None,
+        trace_id: None,
+        span_id: None,
+        body_terms: Vec::new(),
+        excluded_body_terms: Vec::new(),
+        attribute_filters: Vec::new(),
         pagination: Pagination {
             offset: 0,
             limit: 1,
         },
+        cursor: None,
     };
-    let recent_errors = clickhouse
+    let recent_errors = store
         .count_logs(&error_params.time_range)
         .await
         .unwrap_or(0);
 
     // Get total log count for last hour
-    let total_logs = clickhouse
+    let total_logs = store
         .count_logs(&TimeRange::last_hours(1))
         .await
         .unwrap_or(0);
@@ -490,6 +953,13 @@ fn format_bytes(bytes: u64) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use archives_common::{
+        memory::MemoryStore,
+        tenant::TenantScope,
+        test_support::{gauge, log},
+        types::{LogEntry, Metric, MetricType},
+        LogStore,
+    };
 
     #[test]
     fn test_tool_registry_new() {
@@ -531,12 +1001,17 @@ mod tests {
         let tools = registry.list();
 
         // Should have 5 tools
-        assert_eq!(tools.len(), 5);
+        assert_eq!(tools.len(), 10);
 
         // Check all expected tools exist
         assert!(registry.get("search_logs").is_some());
         assert!(registry.get("tail_logs").is_some());
+        assert!(registry.get("get_log").is_some());
+        assert!(registry.get("get_trace_logs").is_some());
         assert!(registry.get("get_error_summary").is_some());
+        assert!(registry.get("list_services").is_some());
+        assert!(registry.get("describe_metric").is_some());
+        assert!(registry.get("list_labels").is_some());
         assert!(registry.get("query_metrics").is_some());
         assert!(registry.get("get_system_health").is_some());
     }
@@ -552,6 +1027,347 @@ mod tests {
         assert_eq!(format_bytes(1024 * 1024 * 1024 * 2), "2.00 GB");
     }
 
+    fn memory_store() -> MemoryStore {
+        let now = chrono::Utc::now();
+        let store = MemoryStore::new();
+        store.insert_logs([
+            LogEntry {
+                timestamp: now - chrono::Duration::minutes(5),
+                observed_timestamp: now,
+                trace_id: Some("abc123".to_string()),
+                ..log(0, LogSeverity::Error, "api", "Connection refused")
+            },
+            LogEntry {
+                timestamp: now - chrono::Duration::minutes(1),
+                observed_timestamp: now,
+                ..log(0, LogSeverity::Info, "api", "Request handled")
+            },
+        ]);
+        store
+    }
+
+    #[tokio::test]
+    async fn test_execute_search_logs_with_memory_store() {
+        let store = memory_store();
+
+        let result = execute_tool(&store, "search_logs", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(result["count"], 2);
+
+        let result = execute_tool(
+            &store,
+            "search_logs",
+            serde_json::json!({"min_severity": "ERROR"}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["count"], 1);
+        assert_eq!(result["logs"][0]["message"], "Connection refused");
+        assert_eq!(result["logs"][0]["trace_id"], "abc123");
+    }
+
+    #[tokio::test]
+    async fn test_tools_on_tenant_view() {
+        let params = LogSearchParams {
+            time_range: TimeRange::last_hours(1),
+            ..LogSearchParams::default()
+        };
+        let logs = memory_store().search_logs(&params).await.unwrap();
+        let store = MemoryStore::new();
+        store.insert_logs(
+            logs.into_iter()
+                .zip(["acme", "globex"])
+                .map(|(log, tenant)| LogEntry {
+                    resource_attributes: serde_json::json!({ "tenant.id": tenant }),
+                    ..log
+                }),
+        );
+        let acme = store
+            .with_tenant(&TenantScope::new("tenant.id", "acme").unwrap())
+            .unwrap();
+
+        let result = execute_tool(&acme, "search_logs", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(result["count"], 1);
+        let result = execute_tool(&acme, "get_system_health", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(result["storage"]["log_count"], 1);
+        assert_eq!(result["last_hour"]["total_logs"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_search_logs_with_query_language() {
+        let store = memory_store();
+
+        let result = execute_tool(
+            &store,
+            "search_logs",
+            serde_json::json!({"q": "service:api -body:refused"}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["count"], 1);
+        assert_eq!(result["logs"][0]["message"], "Request handled");
+
+        let err = execute_tool(
+            &store,
+            "search_logs",
+            serde_json::json!({"q": "severity>=loud"}),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown severity"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_get_log_by_search_id() {
+        let store = memory_store();
+
+        let search = execute_tool(
+            &store,
+            "search_logs",
+            serde_json::json!({"query": "refused", "hours": 1}),
+        )
+        .await
+        .unwrap();
+        let id = search["logs"][0]["id"].clone();
+
+        let result = execute_tool(&store, "get_log", serde_json::json!({ "id": id }))
+            .await
+            .unwrap();
+        assert_eq!(result["body"], "Connection refused");
+        assert_eq!(result["id"], id);
+
+        let err = execute_tool(
+            &store,
+            "get_log",
+            serde_json::json!({"id": uuid::Uuid::nil().to_string()}),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.is_not_found());
+    }
+
+    #[tokio::test]
+    async fn test_execute_get_trace_logs() {
+        let store = memory_store();
+
+        let result = execute_tool(
+            &store,
+            "get_trace_logs",
+            serde_json::json!({"trace_id": "ABC123"}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["count"], 1);
+        assert_eq!(result["services"], serde_json::json!(["api"]));
+        assert_eq!(result["logs"][0]["message"], "Connection refused");
+
+        let err = execute_tool(&store, "get_trace_logs", serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("trace_id"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_query_metrics_grouped() {
+        let now = chrono::Utc::now();
+        let store = MemoryStore::new();
+        store.insert_metrics(["eu", "eu", "us", "ap"].into_iter().map(|region| Metric {
+            timestamp: now - chrono::Duration::minutes(5),
+            attributes: serde_json::json!({"region": region}),
+            ..gauge("queue_depth", 0, 2.0)
+        }));
+
+        let result = execute_tool(
+            &store,
+            "query_metrics",
+            serde_json::json!({
+                "metric_name": "queue_depth",
+                "aggregation": "sum",
+                "interval_seconds": 3600,
+                "labels": ["region!=ap"],
+                "group_by": ["region"]
+            }),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result["series_count"], 2);
+        assert_eq!(result["series"][0]["labels"]["region"], "eu");
+        assert_eq!(result["series"][1]["labels"]["region"], "us");
+
+        let result = execute_tool(
+            &store,
+            "query_metrics",
+            serde_json::json!({"metric_name": "queue_depth", "labels": ["region=eu"]}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["data_points"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_get_error_summary_clusters_messages() {
+        let now = chrono::Utc::now();
+        let store = memory_store();
+        store.insert_logs(
+            ["user 123 not found", "user 456 not found"].map(|body| LogEntry {
+                id: uuid::Uuid::new_v4(),
+                timestamp: now - chrono::Duration::minutes(2),
+                observed_timestamp: now,
+                trace_id: None,
+                span_id: None,
+                severity: LogSeverity::Error,
+                severity_text: "ERROR".to_string(),
+                body: body.to_string(),
+                resource_attributes: serde_json::json!({}),
+                log_attributes: serde_json::json!({}),
+                service_name: Some("api".to_string()),
+            }),
+        );
+
+        let result = execute_tool(&store, "get_error_summary", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(result["total_errors"], 3);
+        let top = &result["top_patterns"][0];
+        assert_eq!(top["pattern"], "user <NUM> not found");
+        assert_eq!(top["count"], 2);
+        assert_eq!(top["samples"].as_array().unwrap().len(), 2);
+        assert_eq!(top["services"]["api"], 2);
+        assert_eq!(result["services"]["api"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_execute_list_services() {
+        let store = memory_store();
+
+        let result = execute_tool(&store, "list_services", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(result["count"], 1);
+        let api = &result["services"][0];
+        assert_eq!(api["name"], "api");
+        assert_eq!(api["log_count"], 2);
+        assert_eq!(api["error_count"], 1);
+        assert_eq!(api["error_ratio"], 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_execute_describe_metric() {
+        let now = chrono::Utc::now();
+        let store = MemoryStore::new();
+        store.insert_metrics(["a", "b"].into_iter().map(|pod| Metric {
+            description: Some("Handled requests".to_string()),
+            unit: Some("1".to_string()),
+            metric_type: MetricType::Sum,
+            timestamp: now - chrono::Duration::minutes(5),
+            is_monotonic: true,
+            attributes: serde_json::json!({ "pod": pod }),
+            service_name: Some("api".to_string()),
+            ..gauge("http_requests_total", 0, 10.0)
+        }));
+
+        let result = execute_tool(
+            &store,
+            "describe_metric",
+            serde_json::json!({"metric_name": "http_requests_total"}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["type"], "sum");
+        assert_eq!(result["series_count"], 2);
+        assert_eq!(result["services"], serde_json::json!(["api"]));
+        assert!(result["aggregations"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!("rate")));
+
+        let result = execute_tool(
+            &store,
+            "describe_metric",
+            serde_json::json!({"prefix": "http_"}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["count"], 1);
+
+        let missing = execute_tool(
+            &store,
+            "describe_metric",
+            serde_json::json!({"metric_name": "http_requests"}),
+        )
+        .await;
+        assert!(missing.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_execute_list_labels() {
+        let now = chrono::Utc::now();
+        let store = memory_store();
+        store.insert_metrics(["a", "a", "b"].into_iter().map(|pod| Metric {
+            timestamp: now - chrono::Duration::minutes(5),
+            resource_attributes: serde_json::json!({ "service.name": "worker" }),
+            attributes: serde_json::json!({ "pod": pod }),
+            service_name: Some("worker".to_string()),
+            ..gauge("queue_depth", 0, 1.0)
+        }));
+
+        let result = execute_tool(
+            &store,
+            "list_labels",
+            serde_json::json!({"metric_name": "queue_depth"}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["count"], 2);
+        assert_eq!(result["keys"][0]["key"], "pod");
+        assert_eq!(result["keys"][0]["scope"], "log");
+        assert_eq!(result["keys"][0]["cardinality"], 2);
+
+        let result = execute_tool(
+            &store,
+            "list_labels",
+            serde_json::json!({"metric_name": "queue_depth", "key": "pod", "limit": 1}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            result["values"],
+            serde_json::json!([{"value": "a", "count": 2}])
+        );
+
+        // Without a metric name the log attributes are listed
+        let result = execute_tool(&store, "list_labels", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(result["count"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_execute_get_system_health_with_memory_store() {
+        let store = memory_store();
+
+        let result = execute_tool(&store, "get_system_health", Value::Null)
+            .await
+            .unwrap();
+        assert_eq!(result["storage"]["log_count"], 2);
+        assert_eq!(result["last_hour"]["total_logs"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_execute_unknown_tool() {
+        let store = MemoryStore::new();
+        let err = execute_tool(&store, "nonexistent", Value::Null)
+            .await
+            .unwrap_err();
+        assert!(err.is_not_found());
+    }
+
     #[test]
     fn test_mcp_tool_serialization() {
         let tool = McpTool {
@@ -142,8 +142,9 @@ _ops_dev() {
 
     cd "$ARCHIVES_ROOT"
 
-    # Ensure infrastructure is running
-    docker compose up -d clickhouse otel-collector
+    # Ensure infrastructure is running and the schema is current
+    docker compose up -d --wait clickhouse otel-collector
+    cargo run -q -p archives-cli -- admin migrate || return 1
 
     case "$service" in
         api)